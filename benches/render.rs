@@ -0,0 +1,100 @@
+//! Expanding placeholders and rendering menus with thousands of entries,
+//! compared with the previous approach of repeated `replace` calls and
+//! collecting each formatted line before joining them
+
+use colored::Colorize;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+#[path = "../src/menu.rs"]
+mod menu;
+// Only `expand` is benchmarked
+#[allow(dead_code)]
+#[path = "../src/template.rs"]
+mod template;
+
+/// Replacing each placeholder in turn, cloning the command every time
+fn replace_each(command: &str, args: &[String], prev: Option<&str>) -> String {
+    let mut command = prev.map_or_else(|| command.to_owned(), |p| command.replace("{prev}", p));
+    for (index, arg) in args.iter().enumerate() {
+        command = command.replace(&format!("{{{}}}", index), arg);
+    }
+    command
+}
+
+/// Formatting each entry into its own string, then joining them
+fn collect_join(keys: &[String], tags: &[String]) -> String {
+    keys.iter()
+        .map(|key| {
+            let mut entry = key.green().bold().to_string();
+            entry = format!("{}: {}", entry, "description".magenta());
+            format!("{}  {}", entry, tags.join(" ").dimmed())
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn expand(c: &mut Criterion) {
+    let mut group = c.benchmark_group("expand");
+
+    for count in &[1_usize, 10, 100] {
+        let args = (0..*count)
+            .map(|i| format!("item-{}", i))
+            .collect::<Vec<_>>();
+        let command = (0..*count)
+            .map(|i| format!("echo {{{}}} | awk '{{ print $1 }}'", i))
+            .collect::<Vec<_>>()
+            .join(" && ")
+            + " {prev}";
+
+        group.bench_with_input(BenchmarkId::new("replace_each", count), &args, |b, args| {
+            b.iter(|| replace_each(black_box(&command), args, Some("prev")));
+        });
+        group.bench_with_input(BenchmarkId::new("template", count), &args, |b, args| {
+            b.iter(|| template::expand(black_box(&command), args, Some("prev")).into_owned());
+        });
+    }
+
+    group.finish();
+}
+
+fn render(c: &mut Criterion) {
+    // Styled as in a terminal, even when the output is redirected
+    colored::control::set_override(true);
+    let mut group = c.benchmark_group("menu");
+    let tags = vec![String::from("git"), String::from("work")];
+    // Those of the default theme
+    let styles = menu::Styles {
+        key:         "1;32",
+        description: "35",
+        dimmed:      "2",
+    };
+
+    for count in &[100_usize, 1_000, 10_000] {
+        let keys = (0..*count)
+            .map(|i| format!("action-{}", i))
+            .collect::<Vec<_>>();
+
+        group.bench_with_input(BenchmarkId::new("collect_join", count), &keys, |b, keys| {
+            b.iter(|| collect_join(black_box(keys), &tags));
+        });
+        group.bench_with_input(BenchmarkId::new("render", count), &keys, |b, keys| {
+            b.iter(|| {
+                menu::render(
+                    black_box(keys).iter().map(|key| menu::Entry {
+                        key,
+                        description: Some("description"),
+                        reason: None,
+                        tags: &tags,
+                        group: None,
+                    }),
+                    styles,
+                )
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, expand, render);
+criterion_main!(benches);
@@ -1,10 +1,12 @@
+use crate::{crypt::Scheme, preview::Builtin, starter};
+use anyhow::{anyhow, Result};
 use clap::{crate_authors, crate_name, crate_version, App, AppSettings, Arg, ArgMatches};
 use once_cell::sync::Lazy;
-use std::env;
+use std::{env, iter, path::Path};
 
 pub(crate) static NO_COLOR: Lazy<bool> = Lazy::new(|| env::var_os("NO_COLOR").is_some());
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct Handler {
     matches: ArgMatches,
 }
@@ -46,6 +48,364 @@ impl<'a> Handler {
                     .required(false)
                     .about("Use skim binary instead of skim library"),
             )
+            .arg(
+                Arg::new("tui")
+                    .long("tui")
+                    .short('t')
+                    .takes_value(false)
+                    .required(false)
+                    .about("Navigate the menu as a tree in a full-screen interface"),
+            )
+            .arg(
+                Arg::new("plain")
+                    .long("plain")
+                    .short('p')
+                    .takes_value(false)
+                    .required(false)
+                    .conflicts_with_all(&["fzf", "skim", "tui"])
+                    .about("Display numbered lists without styling instead of a fuzzy finder"),
+            )
+            .arg(
+                Arg::new("frontend-protocol")
+                    .long("frontend-protocol")
+                    .takes_value(false)
+                    .required(false)
+                    .conflicts_with_all(&["plain", "fzf", "skim", "tui"])
+                    .about("Be driven by another interface with JSON lines on stdin/stdout"),
+            )
+            .arg(
+                Arg::new("strict")
+                    .long("strict")
+                    .takes_value(false)
+                    .required(false)
+                    .global(true)
+                    .about("Fail on problems of the configuration instead of warning about them"),
+            )
+            .arg(
+                Arg::new("record")
+                    .long("record")
+                    .takes_value(true)
+                    .value_name("file")
+                    .required(false)
+                    .global(true)
+                    .about(
+                        "Record the selections, inputs, and commands of the session into a file",
+                    ),
+            )
+            .arg(
+                Arg::new("replay")
+                    .long("replay")
+                    .takes_value(true)
+                    .value_name("file")
+                    .required(false)
+                    .global(true)
+                    .conflicts_with("record")
+                    .about("Replay a session recorded with --record, confirming each step"),
+            )
+            .arg(
+                Arg::new("explain")
+                    .long("explain")
+                    .required(false)
+                    .global(true)
+                    .about("Print how each command is assembled from its template before it runs"),
+            )
+            .arg(
+                Arg::new("sandbox")
+                    .long("sandbox")
+                    .required(false)
+                    .global(true)
+                    .about("Run the commands under bwrap or firejail, or confirm each of them"),
+            )
+            .arg(
+                Arg::new("sandbox-policy")
+                    .long("sandbox-policy")
+                    .takes_value(true)
+                    .value_name("file")
+                    .required(false)
+                    .global(true)
+                    .about("What the commands may do in the sandbox (implies --sandbox)"),
+            )
+            .subcommand(
+                App::new("run")
+                    .about("Run the action at a dotted path of the menu, even if it is hidden")
+                    .arg(
+                        Arg::new("path")
+                            .required(true)
+                            .about("Path of the action (e.g., git.log)"),
+                    )
+                    .arg(Arg::new("parameters").multiple_values(true).about(
+                        "Values of the widgets, as name=value (or index=value). A widget given \
+                         {pick} is displayed",
+                    )),
+            )
+            .subcommand(
+                App::new("last")
+                    .about("Run the last command again, edit it before running it, or copy it"),
+            )
+            .subcommand(
+                App::new("encrypt")
+                    .about("Encrypt a section of the configuration, printing what replaces it")
+                    .arg(
+                        Arg::new("age")
+                            .long("age")
+                            .takes_value(true)
+                            .multiple_occurrences(true)
+                            .required_unless_present("gpg")
+                            .about("Encrypt with age for this recipient (public key)"),
+                    )
+                    .arg(
+                        Arg::new("gpg")
+                            .long("gpg")
+                            .takes_value(true)
+                            .multiple_occurrences(true)
+                            .conflicts_with("age")
+                            .about("Encrypt with GPG for this recipient (key ID or email)"),
+                    )
+                    .arg(
+                        Arg::new("file")
+                            .about("YAML of the section (read from the standard input if absent)"),
+                    ),
+            )
+            .subcommand(
+                App::new("decrypt")
+                    .about("Print the YAML of an encrypted section of the configuration")
+                    .arg(Arg::new("file").about(
+                        "Encrypted section, or its armored text (read from the standard input if \
+                         absent)",
+                    )),
+            )
+            .subcommand(
+                App::new("doctor")
+                    .about("Check that the binaries used by the configuration are installed"),
+            )
+            .subcommand(
+                App::new("bench")
+                    .about("Measure the time taken by the configuration and widget commands")
+                    .arg(
+                        Arg::new("path")
+                            .about("Only measure the action at this dotted path (e.g., git.log)"),
+                    )
+                    .arg(
+                        Arg::new("runs")
+                            .long("runs")
+                            .short('n')
+                            .takes_value(true)
+                            .default_value("5")
+                            .about("Number of times everything is measured"),
+                    ),
+            )
+            .subcommand(
+                App::new("cache")
+                    .about("Manage the memoized configuration")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        App::new("rebuild").about("Parse the configuration again and memoize it"),
+                    ),
+            )
+            .subcommand(
+                App::new("jobs")
+                    .about("List the exclusive commands running, or follow one of them")
+                    .subcommand(
+                        App::new("attach")
+                            .about("Follow the output of a running command until it exits")
+                            .arg(
+                                Arg::new("path")
+                                    .required(true)
+                                    .about("Dotted path of the command, as listed by `jaime jobs`"),
+                            )
+                            .arg(Arg::new("kill").long("kill").about(
+                                "Interrupt the command on ctrl-c instead of detaching from it",
+                            )),
+                    ),
+            )
+            .subcommand(
+                App::new("history")
+                    .about("List the commands ran, or show one of them with its output")
+                    .subcommand(
+                        App::new("show")
+                            .about("Show a command of the history, with its output if stored")
+                            .arg(
+                                Arg::new("id")
+                                    .required(true)
+                                    .about("ID of the command, as listed by `jaime history`"),
+                            ),
+                    )
+                    .subcommand(
+                        App::new("rerun")
+                            .about(
+                                "Run a command of the history again, possibly with some of its \
+                                 values changed",
+                            )
+                            .arg(Arg::new("id").about(
+                                "ID of the command, as listed by `jaime history` (selected if \
+                                 absent)",
+                            )),
+                    ),
+            )
+            .subcommand(
+                App::new("runs")
+                    .about("Browse the directories of the past runs, and their artifacts")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(App::new("ls").about("List the past runs, the most recent first"))
+                    .subcommand(
+                        App::new("open")
+                            .about("Open the directory of a run (the last one by default)")
+                            .arg(Arg::new("id").about("Run to open, as listed by `jaime runs ls`")),
+                    ),
+            )
+            .subcommand(
+                App::new("tags")
+                    .about("Select among the actions grouped by their tags")
+                    .arg(Arg::new("tag").about("Only show the actions with this tag")),
+            )
+            .subcommand(
+                App::new("diff")
+                    .about("Print the differences between two configurations, entry by entry")
+                    .arg(
+                        Arg::new("old")
+                            .required(true)
+                            .about("Configuration to compare from"),
+                    )
+                    .arg(Arg::new("new").about(
+                        "Configuration to compare to (the one in use, with its overlays, if \
+                         absent)",
+                    )),
+            )
+            .subcommand(
+                App::new("grep")
+                    .about("Search the commands, previews, and descriptions of the menu")
+                    .arg(
+                        Arg::new("pattern")
+                            .required(true)
+                            .about("Regular expression to search for"),
+                    )
+                    .arg(
+                        Arg::new("ignore-case")
+                            .long("ignore-case")
+                            .short('i')
+                            .about("Ignore the case of the letters"),
+                    )
+                    .arg(
+                        Arg::new("fixed-strings")
+                            .long("fixed-strings")
+                            .short('F')
+                            .about("Search for the pattern as is, not as a regular expression"),
+                    ),
+            )
+            .subcommand(
+                App::new("mv")
+                    .about("Move an action to another path of the menu, keeping the comments")
+                    .arg(
+                        Arg::new("old")
+                            .required(true)
+                            .about("Dotted path of the action (e.g., git.log)"),
+                    )
+                    .arg(
+                        Arg::new("new")
+                            .required(true)
+                            .about("Dotted path to move it to (e.g., git.history.log)"),
+                    ),
+            )
+            .subcommand(
+                App::new("tree")
+                    .about("Print the menu as a tree, with the descriptions and commands")
+                    .arg(Arg::new("path").about("Only print the menu at this dotted path"))
+                    .arg(
+                        Arg::new("depth")
+                            .long("depth")
+                            .short('d')
+                            .takes_value(true)
+                            .about("Number of levels of the menu printed"),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .long("json")
+                            .about("Print the tree as JSON"),
+                    ),
+            )
+            .subcommand(
+                App::new("docs")
+                    .about("Print the menu as a Markdown document, or a man page")
+                    .arg(
+                        Arg::new("man")
+                            .long("man")
+                            .about("Render a man page (roff) instead of Markdown"),
+                    ),
+            )
+            .subcommand(
+                App::new("init-config")
+                    .about("Add starter menus for common tools to the configuration")
+                    .arg(
+                        Arg::new("topics")
+                            .multiple_values(true)
+                            .possible_values(starter::NAMES)
+                            .about("Menus to add (all of them if absent)"),
+                    )
+                    .arg(
+                        Arg::new("print")
+                            .long("print")
+                            .about("Print the menus instead of adding them"),
+                    ),
+            )
+            .subcommand(
+                App::new("nvim-server")
+                    .about("Answer the msgpack-RPC requests of a Neovim plugin on stdin/stdout"),
+            )
+            .subcommand(
+                App::new("serve")
+                    .about("Serve the menu on a local web page, running the commands here")
+                    .arg(
+                        Arg::new("listen")
+                            .long("listen")
+                            .takes_value(true)
+                            .default_value("127.0.0.1:7878")
+                            .about("Address to listen on"),
+                    )
+                    .arg(
+                        Arg::new("systemd")
+                            .long("systemd")
+                            .conflicts_with("listen")
+                            .about("Listen on the socket passed by systemd (socket activation)"),
+                    )
+                    .arg(
+                        Arg::new("allow-remote")
+                            .long("allow-remote")
+                            .about("Listen on an address other machines can reach"),
+                    ),
+            )
+            .subcommand(
+                App::new("install-service")
+                    .about("Write systemd user units starting `jaime serve` on demand")
+                    .arg(
+                        Arg::new("listen")
+                            .long("listen")
+                            .takes_value(true)
+                            .default_value("127.0.0.1:7878")
+                            .about("Address systemd listens on"),
+                    )
+                    .arg(
+                        Arg::new("allow-remote")
+                            .long("allow-remote")
+                            .about("Listen on an address other machines can reach"),
+                    )
+                    .arg(
+                        Arg::new("print")
+                            .long("print")
+                            .about("Print the units instead of writing them"),
+                    ),
+            )
+            .subcommand(
+                App::new("preview")
+                    .about("Run a builtin previewer (used internally by the selectors)")
+                    .setting(AppSettings::Hidden)
+                    .arg(
+                        Arg::new("kind")
+                            .required(true)
+                            .possible_values(Builtin::NAMES)
+                            .about("Previewer to run"),
+                    )
+                    .arg(Arg::new("item").required(true).about("Item to preview")),
+            )
     }
 
     pub(crate) fn parse() -> Handler {
@@ -54,6 +414,16 @@ impl<'a> Handler {
         }
     }
 
+    /// The options of `args`, given as they would be on the command line
+    /// (e.g., `--plain`), for a menu ran from a program
+    pub(crate) fn from_args(args: &[&str]) -> Result<Handler> {
+        Ok(Handler {
+            matches: Handler::build()
+                .try_get_matches_from(iter::once("jaime").chain(args.iter().copied()))
+                .map_err(|e| anyhow!("{}", e))?,
+        })
+    }
+
     /// Get the raw matches.
     #[allow(unused)]
     pub(crate) fn matches(&'a self) -> &'a ArgMatches {
@@ -75,4 +445,306 @@ impl<'a> Handler {
     pub(crate) fn skim(&'a self) -> bool {
         self.matches.is_present("skim")
     }
+
+    pub(crate) fn tui(&'a self) -> bool {
+        self.matches.is_present("tui")
+    }
+
+    pub(crate) fn plain(&'a self) -> bool {
+        self.matches.is_present("plain")
+    }
+
+    /// Whether the menus are driven through `--frontend-protocol`
+    pub(crate) fn frontend_protocol(&'a self) -> bool {
+        self.matches.is_present("frontend-protocol")
+    }
+
+    pub(crate) fn strict(&'a self) -> bool {
+        self.matches.is_present("strict")
+    }
+
+    /// The file given to `--record`
+    pub(crate) fn record(&'a self) -> Option<&'a Path> {
+        self.matches.value_of_os("record").map(Path::new)
+    }
+
+    /// Whether to print how the commands are assembled before they run
+    pub(crate) fn explain(&'a self) -> bool {
+        self.matches.is_present("explain")
+    }
+
+    /// Whether the commands run in the sandbox
+    pub(crate) fn sandbox(&'a self) -> bool {
+        self.matches.is_present("sandbox") || self.matches.is_present("sandbox-policy")
+    }
+
+    /// The file given to `--sandbox-policy`
+    pub(crate) fn sandbox_policy(&'a self) -> Option<&'a Path> {
+        self.matches.value_of_os("sandbox-policy").map(Path::new)
+    }
+
+    /// The file given to `--replay`
+    pub(crate) fn replay(&'a self) -> Option<&'a Path> {
+        self.matches.value_of_os("replay").map(Path::new)
+    }
+
+    /// The path given to `jaime run`
+    pub(crate) fn run_path(&'a self) -> Option<&'a str> {
+        self.matches
+            .subcommand_matches("run")
+            .and_then(|m| m.value_of("path"))
+    }
+
+    /// The parameters given to `jaime run`
+    pub(crate) fn run_parameters(&'a self) -> Vec<&'a str> {
+        self.matches
+            .subcommand_matches("run")
+            .and_then(|m| m.values_of("parameters"))
+            .map(Iterator::collect)
+            .unwrap_or_default()
+    }
+
+    /// Whether ran as `jaime last`
+    pub(crate) fn last(&'a self) -> bool {
+        self.matches.subcommand_matches("last").is_some()
+    }
+
+    /// The scheme, recipients, and file given to `jaime encrypt`, if ran as
+    /// such
+    pub(crate) fn encrypt(&'a self) -> Option<(Scheme, Vec<&'a str>, Option<&'a str>)> {
+        self.matches.subcommand_matches("encrypt").map(|m| {
+            let (scheme, recipients) = match m.values_of("age") {
+                Some(age) => (Scheme::Age, age),
+                None => (Scheme::Gpg, m.values_of("gpg").unwrap_or_default()),
+            };
+            (scheme, recipients.collect(), m.value_of("file"))
+        })
+    }
+
+    /// Whether ran as `jaime decrypt`
+    pub(crate) fn decrypt(&'a self) -> bool {
+        self.matches.subcommand_matches("decrypt").is_some()
+    }
+
+    /// The file given to `jaime decrypt`
+    pub(crate) fn decrypt_file(&'a self) -> Option<&'a str> {
+        self.matches
+            .subcommand_matches("decrypt")
+            .and_then(|m| m.value_of("file"))
+    }
+
+    /// Whether ran as `jaime doctor`
+    pub(crate) fn doctor(&'a self) -> bool {
+        self.matches.subcommand_matches("doctor").is_some()
+    }
+
+    /// The path and number of runs given to `jaime bench`, if ran as such
+    pub(crate) fn bench(&'a self) -> Option<Result<(Option<&'a str>, usize)>> {
+        self.matches.subcommand_matches("bench").map(|m| {
+            let runs = m.value_of("runs").unwrap_or("5");
+            let runs = runs
+                .parse::<usize>()
+                .map_err(|_| anyhow!("invalid number of runs: {}", runs))?;
+            Ok((m.value_of("path"), runs))
+        })
+    }
+
+    /// Whether ran as `jaime cache rebuild`
+    pub(crate) fn cache_rebuild(&'a self) -> bool {
+        self.matches
+            .subcommand_matches("cache")
+            .and_then(|m| m.subcommand_matches("rebuild"))
+            .is_some()
+    }
+
+    /// Whether ran as `jaime jobs`
+    pub(crate) fn jobs(&'a self) -> bool {
+        self.matches.subcommand_matches("jobs").is_some()
+    }
+
+    /// The path given to `jaime jobs attach` and whether ctrl-c kills the
+    /// command, if ran as such
+    pub(crate) fn jobs_attach(&'a self) -> Option<(&'a str, bool)> {
+        self.matches
+            .subcommand_matches("jobs")
+            .and_then(|m| m.subcommand_matches("attach"))
+            .and_then(|m| Some((m.value_of("path")?, m.is_present("kill"))))
+    }
+
+    /// Whether ran as `jaime history`
+    pub(crate) fn history(&'a self) -> bool {
+        self.matches.subcommand_matches("history").is_some()
+    }
+
+    /// The ID given to `jaime history show`, if ran as such
+    pub(crate) fn history_show(&'a self) -> Option<Result<usize>> {
+        self.matches
+            .subcommand_matches("history")
+            .and_then(|m| m.subcommand_matches("show"))
+            .map(|m| {
+                let id = m.value_of("id").unwrap_or_default();
+                id.parse::<usize>()
+                    .map_err(|_| anyhow!("invalid ID: {}", id))
+            })
+    }
+
+    /// Whether ran as `jaime history rerun`
+    pub(crate) fn history_rerun(&'a self) -> bool {
+        self.matches
+            .subcommand_matches("history")
+            .and_then(|m| m.subcommand_matches("rerun"))
+            .is_some()
+    }
+
+    /// The ID given to `jaime history rerun`
+    pub(crate) fn rerun_id(&'a self) -> Option<Result<usize>> {
+        self.matches
+            .subcommand_matches("history")
+            .and_then(|m| m.subcommand_matches("rerun"))
+            .and_then(|m| m.value_of("id"))
+            .map(|id| {
+                id.parse::<usize>()
+                    .map_err(|_| anyhow!("invalid ID: {}", id))
+            })
+    }
+
+    /// Whether ran as `jaime runs ls`
+    pub(crate) fn runs_ls(&'a self) -> bool {
+        self.matches
+            .subcommand_matches("runs")
+            .and_then(|m| m.subcommand_matches("ls"))
+            .is_some()
+    }
+
+    /// Whether ran as `jaime runs open`
+    pub(crate) fn runs_open(&'a self) -> bool {
+        self.matches
+            .subcommand_matches("runs")
+            .and_then(|m| m.subcommand_matches("open"))
+            .is_some()
+    }
+
+    /// The run given to `jaime runs open`
+    pub(crate) fn run_id(&'a self) -> Option<&'a str> {
+        self.matches
+            .subcommand_matches("runs")
+            .and_then(|m| m.subcommand_matches("open"))
+            .and_then(|m| m.value_of("id"))
+    }
+
+    /// Whether ran as `jaime tags`
+    pub(crate) fn tags(&'a self) -> bool {
+        self.matches.subcommand_matches("tags").is_some()
+    }
+
+    /// The tag given to `jaime tags`
+    pub(crate) fn tag(&'a self) -> Option<&'a str> {
+        self.matches
+            .subcommand_matches("tags")
+            .and_then(|m| m.value_of("tag"))
+    }
+
+    /// The configurations given to `jaime diff`, if ran as such
+    pub(crate) fn diff(&'a self) -> Option<(&'a str, Option<&'a str>)> {
+        self.matches
+            .subcommand_matches("diff")
+            .and_then(|m| Some((m.value_of("old")?, m.value_of("new"))))
+    }
+
+    /// The pattern given to `jaime grep`, and whether to ignore the case and
+    /// to search for it as is, if ran as such
+    pub(crate) fn grep(&'a self) -> Option<(&'a str, bool, bool)> {
+        self.matches.subcommand_matches("grep").and_then(|m| {
+            Some((
+                m.value_of("pattern")?,
+                m.is_present("ignore-case"),
+                m.is_present("fixed-strings"),
+            ))
+        })
+    }
+
+    /// The old and new paths given to `jaime mv`, if ran as such
+    pub(crate) fn mv(&'a self) -> Option<(&'a str, &'a str)> {
+        self.matches
+            .subcommand_matches("mv")
+            .and_then(|m| Some((m.value_of("old")?, m.value_of("new")?)))
+    }
+
+    /// The path given to `jaime tree` and whether to print JSON, if ran as
+    /// such
+    pub(crate) fn tree(&'a self) -> Option<(Option<&'a str>, bool)> {
+        self.matches
+            .subcommand_matches("tree")
+            .map(|m| (m.value_of("path"), m.is_present("json")))
+    }
+
+    /// The depth given to `jaime tree`
+    pub(crate) fn tree_depth(&'a self) -> Result<Option<usize>> {
+        self.matches
+            .subcommand_matches("tree")
+            .and_then(|m| m.value_of("depth"))
+            .map(|depth| {
+                depth
+                    .parse::<usize>()
+                    .map_err(|_| anyhow!("invalid depth: {}", depth))
+            })
+            .transpose()
+    }
+
+    /// Whether to render a man page, if ran as `jaime docs`
+    pub(crate) fn docs(&'a self) -> Option<bool> {
+        self.matches
+            .subcommand_matches("docs")
+            .map(|m| m.is_present("man"))
+    }
+
+    /// The topics given to `jaime init-config` and whether to print them, if
+    /// ran as such
+    pub(crate) fn init_config(&'a self) -> Option<(Vec<&'a str>, bool)> {
+        self.matches.subcommand_matches("init-config").map(|m| {
+            (
+                m.values_of("topics").unwrap_or_default().collect(),
+                m.is_present("print"),
+            )
+        })
+    }
+
+    /// Whether ran as `jaime nvim-server`
+    pub(crate) fn nvim_server(&'a self) -> bool {
+        self.matches.subcommand_matches("nvim-server").is_some()
+    }
+
+    /// The address given to `jaime serve`, whether the socket is passed by
+    /// systemd instead, and whether other machines may reach it, if ran as
+    /// such
+    pub(crate) fn serve(&'a self) -> Option<(&'a str, bool, bool)> {
+        self.matches.subcommand_matches("serve").and_then(|m| {
+            Some((
+                m.value_of("listen")?,
+                m.is_present("systemd"),
+                m.is_present("allow-remote"),
+            ))
+        })
+    }
+
+    /// The address, whether other machines may reach it, and whether to print
+    /// the units, if ran as `jaime install-service`
+    pub(crate) fn install_service(&'a self) -> Option<(&'a str, bool, bool)> {
+        self.matches
+            .subcommand_matches("install-service")
+            .and_then(|m| {
+                Some((
+                    m.value_of("listen")?,
+                    m.is_present("allow-remote"),
+                    m.is_present("print"),
+                ))
+            })
+    }
+
+    /// The builtin previewer and item to preview, if ran as `jaime preview`
+    pub(crate) fn preview(&'a self) -> Option<(&'a str, &'a str)> {
+        self.matches
+            .subcommand_matches("preview")
+            .and_then(|m| Some((m.value_of("kind")?, m.value_of("item")?)))
+    }
 }
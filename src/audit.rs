@@ -0,0 +1,136 @@
+//! Audit log of the commands ran, for shared and operations environments.
+//!
+//! Unlike the [`history`](crate::history), which is a convenience that can be
+//! cleared, the log is opt-in, only ever appended to, and records who ran what
+//! from where. The commands are redacted by the `redact` rules of the
+//! configuration before they are written
+
+use crate::{runner::Context, user};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    env,
+    fs::OpenOptions,
+    io::Write,
+    ops::Range,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Replaces what the `redact` rules match
+const REDACTED: &str = "***";
+
+/// Settings of the audit log, which is only written when they are given
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct Audit {
+    /// File the log is appended to, `audit.jsonl` in the cache directory by
+    /// default
+    pub(crate) file:   Option<String>,
+    /// Regular expressions whose matches are redacted from the commands. When
+    /// a rule has groups, only the groups are redacted
+    #[serde(default)]
+    pub(crate) redact: Vec<String>,
+}
+
+/// A command that was ran
+#[derive(Serialize, Debug)]
+struct Entry {
+    /// UNIX timestamp of when the command was started
+    time:    u64,
+    user:    String,
+    cwd:     String,
+    /// Dotted path of the action in the menu
+    path:    String,
+    /// The command, redacted
+    command: String,
+}
+
+impl Audit {
+    fn file(&self, context: &Context) -> PathBuf {
+        match &self.file {
+            Some(file) => match (file.strip_prefix("~/"), dirs::home_dir()) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(file),
+            },
+            None => context.cache_directory.join("audit.jsonl"),
+        }
+    }
+
+    /// The compiled `redact` rules, or the first one that is invalid
+    pub(crate) fn rules(&self) -> Result<Vec<Regex>> {
+        self.redact
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                Regex::new(rule).map_err(|e| anyhow!("audit.redact.{}: invalid rule: {}", index, e))
+            })
+            .collect()
+    }
+
+    /// `command` with what the `redact` rules match replaced by `***`
+    pub(crate) fn redact(&self, command: &str) -> Result<String> {
+        let mut command = command.to_owned();
+        for rule in self.rules()? {
+            let mut ranges = Vec::new();
+            for captures in rule.captures_iter(&command) {
+                if captures.len() == 1 {
+                    ranges.extend(captures.get(0).map(|m| m.range()));
+                } else {
+                    ranges.extend(captures.iter().skip(1).flatten().map(|m| m.range()));
+                }
+            }
+            // Nested groups overlap
+            ranges.sort_by_key(|range| range.start);
+            let mut merged: Vec<Range<usize>> = Vec::new();
+            for range in ranges {
+                match merged.last_mut() {
+                    Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                    _ => merged.push(range),
+                }
+            }
+            // From the end, so that the ranges before stay valid
+            for range in merged.into_iter().rev() {
+                command.replace_range(range, REDACTED);
+            }
+        }
+
+        Ok(command)
+    }
+}
+
+/// Append a command to the audit log, when it is enabled. Nothing is written
+/// if the command can't be redacted
+pub(crate) fn record(
+    context: &Context,
+    audit: Option<&Audit>,
+    path: &[String],
+    command: &str,
+) -> Result<()> {
+    let audit = match audit {
+        Some(audit) => audit,
+        None => return Ok(()),
+    };
+
+    let entry = Entry {
+        time:    SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        user:    user::name().to_owned(),
+        cwd:     env::current_dir()
+            .map(|d| d.display().to_string())
+            .unwrap_or_default(),
+        path:    path.join("."),
+        command: audit.redact(command)?,
+    };
+
+    let file = audit.file(context);
+    let mut log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&file)
+        .context(format!("unable to open the audit log: {}", file.display()))?;
+    writeln!(log, "{}", serde_json::to_string(&entry)?)?;
+
+    Ok(())
+}
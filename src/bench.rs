@@ -0,0 +1,331 @@
+//! `jaime bench`: measure where the time goes before a menu shows up, in order
+//! to find out why a menu bound to a hotkey feels slow
+
+use crate::{
+    cache,
+    exec::run_shell_command_for_output,
+    preview,
+    runner::{Action, Config, Context, Widget, WidgetConfig, FZF_BIN, SKIM_BIN},
+    theme,
+};
+use anyhow::{Context as AnyhowContext, Result};
+use colored::Colorize;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{
+    fs::{self, File},
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// Placeholders replaced by the items selected by earlier widgets
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(\d+|prev)\}").unwrap());
+
+/// Number of items of a widget whose preview is measured
+const PREVIEWED: usize = 10;
+
+/// One line of the report
+#[derive(Debug)]
+struct Row {
+    name:    String,
+    timings: Vec<Duration>,
+    note:    String,
+}
+
+impl Row {
+    fn new(name: impl Into<String>) -> Self {
+        Self {
+            name:    name.into(),
+            timings: Vec::new(),
+            note:    String::new(),
+        }
+    }
+
+    fn note(mut self, note: impl Into<String>) -> Self {
+        self.note = note.into();
+        self
+    }
+
+    /// Run `f`, recording how long it took
+    fn time<T>(&mut self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let start = Instant::now();
+        let value = f()?;
+        self.timings.push(start.elapsed());
+        Ok(value)
+    }
+
+    /// Minimum, mean, and maximum of the timings
+    fn stats(&self) -> Option<(Duration, Duration, Duration)> {
+        let min = *self.timings.iter().min()?;
+        let max = *self.timings.iter().max()?;
+        #[allow(clippy::cast_possible_truncation)]
+        let mean = self.timings.iter().sum::<Duration>() / self.timings.len() as u32;
+        Some((min, mean, max))
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// The widgets of `action` and its sub-actions, along with a name locating them
+fn widgets<'a>(action: &'a Action, path: &str, found: &mut Vec<(String, &'a Widget)>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match action {
+        Action::Command { widgets, .. } =>
+            for (index, WidgetConfig { widget, .. }) in widgets.iter().flatten().enumerate() {
+                found.push((format!("{}[{}]", path, index), widget.displayed()));
+            },
+        Action::Select { options, .. } =>
+            for (key, action) in options {
+                widgets(action, &join(key), found);
+            },
+        Action::Chain { steps, .. } =>
+            for (index, step) in steps.iter().enumerate() {
+                widgets(step, &format!("{}/{}", path, index), found);
+            },
+        Action::Menu { .. } | Action::Systemd { .. } => {},
+    }
+}
+
+/// The shell command a widget runs to list its items
+fn widget_command(widget: &Widget) -> Option<String> {
+    match widget {
+        Widget::FromCommand { command, .. } | Widget::JsonPick { command, .. } =>
+            Some(command.clone()),
+        Widget::FlagPicker { command } => Some(format!("{} --help 2>&1", command)),
+        _ => None,
+    }
+}
+
+/// Whether the output of a preview is cached for `item`, computing the name of
+/// the cache file the same way as the script wrapping the preview
+fn is_cached(context: &Context, shell: &str, prefix: &Path, item: &str) -> Result<bool> {
+    let checksum = run_shell_command_for_output(
+        context,
+        &format!(
+            "printf '%s' {} | cksum | tr -d ' \\t'",
+            shlex::try_quote(item).context("unable to quote item")?
+        ),
+        shell,
+        &[],
+    )?;
+    let mut path = prefix.as_os_str().to_owned();
+    path.push(format!("-{}", checksum.trim()));
+
+    Ok(fs::metadata(path).map_or(false, |m| m.len() > 0))
+}
+
+/// Measure the preview of the first items of a widget, `runs` times. When the
+/// preview is cached, the items whose output was already cached are counted
+fn bench_preview(
+    context: &Context,
+    shell: &str,
+    name: &str,
+    preview: &str,
+    cache: Option<Duration>,
+    items: &[&str],
+    runs: usize,
+) -> Result<Row> {
+    let mut row = Row::new(format!("{} preview", name));
+    let prefix = preview::cache_prefix(context, &preview::resolve(context, preview, None)?)?;
+    let preview = preview::resolve(context, preview, cache)?;
+    let mut hits = 0_usize;
+
+    for _ in 0..runs {
+        for item in items {
+            let command = preview.replace(
+                "{}",
+                &shlex::try_quote(item).context("unable to quote item")?,
+            );
+            if cache.is_some() && is_cached(context, shell, &prefix, item)? {
+                hits += 1;
+            }
+            row.time(|| run_shell_command_for_output(context, &command, shell, &[]))?;
+        }
+    }
+
+    let total = runs * items.len();
+    Ok(if cache.is_some() {
+        #[allow(clippy::cast_precision_loss)]
+        let rate = if total == 0 {
+            0.0
+        } else {
+            hits as f64 * 100.0 / total as f64
+        };
+        row.note(format!("{}/{} cache hits ({:.0}%)", hits, total, rate))
+    } else {
+        row.note("not cached")
+    })
+}
+
+/// Measure parsing the configuration, the commands of the widgets of the
+/// action at `path` (or of every action), their previews, and starting the
+/// external selectors, `runs` times each. A table of the timings is printed
+pub(crate) fn run(
+    context: &Context,
+    config: &Config,
+    path: Option<&str>,
+    runs: usize,
+) -> Result<()> {
+    let runs = runs.max(1);
+    let shell = config.shell();
+    let mut rows = Vec::new();
+
+    let mut row = Row::new("config parse");
+    for _ in 0..runs {
+        row.time(|| {
+            let file = File::open(&context.config_path).context("Couldn't read config file")?;
+            Ok(serde_yaml::from_reader::<_, Config>(file)?)
+        })?;
+    }
+    rows.push(row);
+
+    let mut row = Row::new("config load (memoized)");
+    for _ in 0..runs {
+        row.time(|| cache::load(&context.config_path, &context.cache_directory))?;
+    }
+    rows.push(row);
+
+    let mut found = Vec::new();
+    if let Some(path) = path {
+        let (keys, action) = config.find(path)?;
+        widgets(action, &keys.join("."), &mut found);
+    } else {
+        for (key, action) in &config.options {
+            widgets(action, key, &mut found);
+        }
+    }
+
+    for (name, widget) in found {
+        let command = if let Some(command) = widget_command(widget) {
+            command
+        } else {
+            rows.push(Row::new(name).note("builtin widget, not measured"));
+            continue;
+        };
+        if PLACEHOLDER.is_match(&command) {
+            rows.push(Row::new(name).note("depends on earlier selections, skipped"));
+            continue;
+        }
+
+        let mut row = Row::new(name.clone());
+        let mut output = String::new();
+        let mut failure = None;
+        for _ in 0..runs {
+            match row.time(|| run_shell_command_for_output(context, &command, &shell, &[])) {
+                Ok(o) => output = o,
+                Err(e) => {
+                    failure = Some(e);
+                    break;
+                },
+            }
+        }
+        if let Some(e) = failure {
+            rows.push(row.note(format!("failed: {}", e)));
+            continue;
+        }
+        let items = output
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        rows.push(row.note(format!("{} items", items.len())));
+
+        // Preview scripts are given the values of the widgets before
+        if let Widget::FromCommand {
+            preview: Some(preview),
+            preview_script: None,
+            preview_cache,
+            ..
+        } = widget
+        {
+            if PLACEHOLDER.is_match(preview) {
+                rows.push(
+                    Row::new(format!("{} preview", name))
+                        .note("depends on earlier selections, skipped"),
+                );
+            } else {
+                rows.push(
+                    bench_preview(
+                        context,
+                        &shell,
+                        &name,
+                        preview,
+                        preview_cache.map(Duration::from_secs),
+                        &items[..items.len().min(PREVIEWED)],
+                        runs,
+                    )
+                    .unwrap_or_else(|e| {
+                        Row::new(format!("{} preview", name)).note(format!("failed: {}", e))
+                    }),
+                );
+            }
+        }
+    }
+
+    for binary in &[FZF_BIN, SKIM_BIN] {
+        let mut row = Row::new(format!("{} spawn", binary));
+        if which::which(binary).is_err() {
+            rows.push(row.note("not installed"));
+            continue;
+        }
+        for _ in 0..runs {
+            row.time(|| {
+                Ok(Command::new(binary)
+                    .arg("--filter=")
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()?)
+            })?;
+        }
+        rows.push(row);
+    }
+
+    let width = rows
+        .iter()
+        .map(|row| row.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    println!(
+        "{}",
+        format!(
+            "{:<width$}  {:>5}  {:>9}  {:>9}  {:>9}  {}",
+            "",
+            "runs",
+            "min",
+            "mean",
+            "max",
+            "",
+            width = width
+        )
+        .bold()
+    );
+    for row in &rows {
+        let (min, mean, max) = row.stats().map_or_else(
+            || (String::from("-"), String::from("-"), String::from("-")),
+            |(min, mean, max)| (millis(min), millis(mean), millis(max)),
+        );
+        println!(
+            "{:<width$}  {:>5}  {:>9}  {:>9}  {:>9}  {}",
+            row.name,
+            row.timings.len(),
+            min,
+            mean,
+            max,
+            theme::get().dimmed.paint(&row.note),
+            width = width
+        );
+    }
+
+    Ok(())
+}
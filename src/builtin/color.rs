@@ -0,0 +1,64 @@
+//! Color picker, listing the named CSS colors along with a block of each
+//! color, and substituting the selected one in the chosen format
+
+use super::Item;
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+/// One color per line: its name and its hex code, separated by a tab
+const COLORS: &str = include_str!("colors.tsv");
+
+/// How the selected color is substituted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    /// `#rrggbb`
+    Hex,
+    /// `rgb(r, g, b)`
+    Rgb,
+    /// The CSS name of the color
+    Name,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Hex
+    }
+}
+
+/// The red, green, and blue components of a `#rrggbb` code
+fn components(hex: &str) -> Result<(u8, u8, u8)> {
+    let component = |range| {
+        hex.get(range)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| anyhow!("invalid color: {}", hex))
+    };
+
+    Ok((component(1..3)?, component(3..5)?, component(5..7)?))
+}
+
+/// List the named colors, each one's value being in `format`
+pub(crate) fn list(format: Format) -> Result<Vec<Item>> {
+    COLORS
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(name, hex)| {
+            let (r, g, b) = components(hex)?;
+            let value = match format {
+                Format::Hex => hex.to_owned(),
+                Format::Rgb => format!("rgb({}, {}, {})", r, g, b),
+                Format::Name => name.to_owned(),
+            };
+            let detail = match format {
+                Format::Name => hex,
+                Format::Hex | Format::Rgb => name,
+            };
+
+            Ok(Item::new(
+                value,
+                Some(format!("{} {}", "    ".on_truecolor(r, g, b), detail)),
+            ))
+        })
+        .collect()
+}
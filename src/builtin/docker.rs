@@ -0,0 +1,241 @@
+//! Docker container, image, and volume pickers.
+//!
+//! The Docker socket is used when the `docker` feature is enabled and the
+//! socket is accessible, otherwise the `docker` CLI is used
+
+use super::Item;
+use anyhow::Result;
+use colored::Colorize;
+
+/// The kind of Docker object listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Container,
+    Image,
+    Volume,
+}
+
+impl Kind {
+    fn cli_name(self) -> &'static str {
+        match self {
+            Self::Container => "container",
+            Self::Image => "image",
+            Self::Volume => "volume",
+        }
+    }
+}
+
+/// A row of the listing, before it is rendered
+struct Row {
+    name:    String,
+    columns: Vec<String>,
+    state:   Option<String>,
+}
+
+impl Row {
+    fn into_item(self) -> Item {
+        let mut columns = self.columns;
+        if let Some(state) = self.state {
+            let colored = match state.as_str() {
+                "running" => state.green().to_string(),
+                "paused" | "restarting" => state.yellow().to_string(),
+                "exited" | "dead" => state.red().to_string(),
+                _ => state,
+            };
+            columns.insert(1, colored);
+        }
+
+        Item::new(self.name, Some(columns.join("  ")))
+    }
+}
+
+/// List the Docker objects of the given kind. Stopped containers are only
+/// listed when `all` is set
+pub(crate) fn list(kind: Kind, all: bool) -> Result<Vec<Item>> {
+    #[cfg(feature = "docker")]
+    {
+        if let Ok(rows) = socket::list(kind, all) {
+            return Ok(rows.into_iter().map(Row::into_item).collect());
+        }
+    }
+
+    Ok(cli::list(kind, all)?
+        .into_iter()
+        .map(Row::into_item)
+        .collect())
+}
+
+/// Print the inspection of a Docker object as JSON
+pub(crate) fn preview(kind: Kind, name: &str) -> Result<()> {
+    #[cfg(feature = "docker")]
+    {
+        if let Ok(value) = socket::inspect(kind, name) {
+            return crate::preview::print_json(&value);
+        }
+    }
+
+    crate::preview::print_json(&cli::inspect(kind, name)?)
+}
+
+mod cli {
+    use super::{Kind, Row};
+    use anyhow::{anyhow, Context as AnyhowContext, Result};
+    use std::process::Command;
+
+    fn docker(args: &[&str]) -> Result<String> {
+        let output = Command::new("docker")
+            .args(args)
+            .output()
+            .context("failed to run docker")?;
+
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)?)
+        } else {
+            Err(anyhow!(
+                "docker failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    pub(super) fn list(kind: Kind, all: bool) -> Result<Vec<Row>> {
+        let output = match kind {
+            Kind::Container => {
+                let format = "{{.Names}}\t{{.State}}\t{{.Image}}\t{{.Status}}";
+                let mut args = vec!["ps", "--format", format];
+                if all {
+                    args.push("--all");
+                }
+                docker(&args)?
+            },
+            Kind::Image => docker(&[
+                "images",
+                "--format",
+                "{{.Repository}}:{{.Tag}}\t{{.ID}}\t{{.Size}}\t{{.CreatedSince}}",
+            ])?,
+            Kind::Volume => docker(&["volume", "ls", "--format", "{{.Name}}\t{{.Driver}}"])?,
+        };
+
+        Ok(output
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let mut fields = line.split('\t').map(ToOwned::to_owned);
+                let name = fields.next().unwrap_or_default();
+                let state = (kind == Kind::Container).then(|| fields.next()).flatten();
+
+                Row {
+                    name,
+                    columns: fields.collect(),
+                    state,
+                }
+            })
+            .collect())
+    }
+
+    pub(super) fn inspect(kind: Kind, name: &str) -> Result<serde_json::Value> {
+        Ok(serde_json::from_str(&docker(&[
+            kind.cli_name(),
+            "inspect",
+            name,
+        ])?)?)
+    }
+}
+
+#[cfg(feature = "docker")]
+mod socket {
+    use super::{Kind, Row};
+    use crate::builtin::human_size;
+    use anyhow::Result;
+    use bollard::{
+        container::ListContainersOptions, image::ListImagesOptions, volume::ListVolumesOptions,
+        Docker,
+    };
+    use std::{convert::TryFrom, future::Future};
+
+    /// Run a request against the Docker socket
+    fn block_on<F, T>(f: impl FnOnce(Docker) -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T, bollard::errors::Error>>,
+    {
+        let docker = Docker::connect_with_local_defaults()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        Ok(runtime.block_on(f(docker))?)
+    }
+
+    pub(super) fn list(kind: Kind, all: bool) -> Result<Vec<Row>> {
+        Ok(match kind {
+            Kind::Container => block_on(|docker| async move {
+                docker
+                    .list_containers(Some(ListContainersOptions::<String> {
+                        all,
+                        ..ListContainersOptions::default()
+                    }))
+                    .await
+            })?
+            .into_iter()
+            .map(|c| Row {
+                name:    c
+                    .names
+                    .and_then(|names| names.into_iter().next())
+                    .map_or_else(String::new, |n| n.trim_start_matches('/').to_owned()),
+                columns: vec![c.image.unwrap_or_default(), c.status.unwrap_or_default()],
+                state:   c.state,
+            })
+            .collect(),
+            Kind::Image => block_on(|docker| async move {
+                docker
+                    .list_images(Some(ListImagesOptions::<String>::default()))
+                    .await
+            })?
+            .into_iter()
+            .flat_map(|image| {
+                let id = image
+                    .id
+                    .trim_start_matches("sha256:")
+                    .chars()
+                    .take(12)
+                    .collect::<String>();
+                let size = human_size(u64::try_from(image.size).unwrap_or_default());
+
+                image.repo_tags.into_iter().map(move |tag| Row {
+                    name:    tag,
+                    columns: vec![id.clone(), size.clone()],
+                    state:   None,
+                })
+            })
+            .collect(),
+            Kind::Volume => block_on(|docker| async move {
+                docker
+                    .list_volumes(None::<ListVolumesOptions<String>>)
+                    .await
+            })?
+            .volumes
+            .unwrap_or_default()
+            .into_iter()
+            .map(|volume| Row {
+                name:    volume.name,
+                columns: vec![volume.driver],
+                state:   None,
+            })
+            .collect(),
+        })
+    }
+
+    pub(super) fn inspect(kind: Kind, name: &str) -> Result<serde_json::Value> {
+        Ok(match kind {
+            Kind::Container => serde_json::to_value(block_on(|docker| async move {
+                docker.inspect_container(name, None).await
+            })?)?,
+            Kind::Image => serde_json::to_value(block_on(|docker| async move {
+                docker.inspect_image(name).await
+            })?)?,
+            Kind::Volume => serde_json::to_value(block_on(|docker| async move {
+                docker.inspect_volume(name).await
+            })?)?,
+        })
+    }
+}
@@ -0,0 +1,21 @@
+//! Emoji picker, listing a bundled dataset of emoji along with their name and
+//! some keywords to search them by
+
+use super::Item;
+
+/// One emoji per line: the emoji, its name, and optionally keywords, separated
+/// by tabs
+const EMOJI: &str = include_str!("emoji.tsv");
+
+/// List the bundled emoji
+pub(crate) fn list() -> Vec<Item> {
+    EMOJI
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let emoji = fields.next()?;
+            let detail = fields.collect::<Vec<_>>().join("  ");
+            Some(Item::new(emoji, Some(detail)))
+        })
+        .collect()
+}
@@ -0,0 +1,149 @@
+//! Environment variable browser
+
+use super::Item;
+use crate::preview::Builtin;
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// Variables whose names match one of these patterns have their value masked
+pub(crate) const DEFAULT_MASK: &[&str] = &[
+    "*TOKEN*",
+    "*SECRET*",
+    "*PASSWORD*",
+    "*PASSWD*",
+    "*API_KEY*",
+    "*PRIVATE*",
+    "*CREDENTIAL*",
+];
+
+/// Environment variable that passes the mask patterns to the previewer,
+/// separated by commas
+const MASK_VAR: &str = "JAIME_ENV_MASK";
+
+/// Shown in place of a masked value
+const MASKED: &str = "********";
+
+/// What is substituted into the command when a variable is selected
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Substitute {
+    /// The name of the variable
+    Name,
+    /// The value of the variable
+    Value,
+}
+
+impl Default for Substitute {
+    fn default() -> Self {
+        Self::Name
+    }
+}
+
+/// Whether `name` matches the glob `pattern`, where `*` matches any number of
+/// characters. Case is ignored
+fn matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.to_uppercase();
+    let name = name.to_uppercase();
+    let parts = pattern.split('*').collect::<Vec<_>>();
+
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !name.starts_with(first) || !name[first.len()..].ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+fn is_masked(mask: &[String], name: &str) -> bool {
+    mask.iter().any(|pattern| matches(pattern, name))
+}
+
+/// The patterns given to the widget, or the default ones
+pub(crate) fn mask_or_default(mask: Option<&[String]>) -> Vec<String> {
+    mask.map_or_else(
+        || DEFAULT_MASK.iter().map(|&p| p.to_owned()).collect(),
+        <[String]>::to_vec,
+    )
+}
+
+/// List the environment variables, sorted by name, masking the values of the
+/// ones that match `mask`
+pub(crate) fn list(mask: &[String]) -> Vec<Item> {
+    let mut vars = env::vars_os()
+        .map(|(name, value)| {
+            (
+                name.to_string_lossy().into_owned(),
+                value.to_string_lossy().into_owned(),
+            )
+        })
+        .filter(|(name, _)| name != MASK_VAR)
+        .collect::<Vec<_>>();
+    vars.sort();
+
+    vars.into_iter()
+        .map(|(name, value)| {
+            let detail = if is_masked(mask, &name) {
+                MASKED.to_owned()
+            } else {
+                value.lines().next().unwrap_or_default().to_owned()
+            };
+            Item::new(name, Some(detail))
+        })
+        .collect()
+}
+
+/// The command previewing the variables, given the patterns of `mask` in its
+/// environment only, so they don't reach the commands ran afterwards
+pub(crate) fn preview_command(mask: &[String]) -> Result<String> {
+    Ok(format!(
+        "env {}={} {}",
+        MASK_VAR,
+        shlex::try_quote(&mask.join(",")).context("unable to quote the mask")?,
+        Builtin::EnvVar.command("{}")?
+    ))
+}
+
+/// The value of the selected variable
+pub(crate) fn value(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| anyhow!("{} is not set or is not valid unicode", name))
+}
+
+/// Print the value of a variable, one entry per line for lists of paths
+pub(crate) fn preview(name: &str) -> Result<()> {
+    let mask = env::var(MASK_VAR).map_or_else(
+        |_| mask_or_default(None),
+        |m| m.split(',').map(ToOwned::to_owned).collect(),
+    );
+
+    println!("{}", name.green().bold());
+
+    if is_masked(&mask, name) {
+        println!("{}", MASKED);
+        return Ok(());
+    }
+
+    let value = value(name)?;
+    if name.ends_with("PATH") && value.contains(':') {
+        for path in value.split(':') {
+            println!("{}", path);
+        }
+    } else {
+        println!("{}", value);
+    }
+
+    Ok(())
+}
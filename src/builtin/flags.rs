@@ -0,0 +1,162 @@
+//! Flags of a command, parsed from its `--help` output or man page
+
+use super::Item;
+
+/// A flag found in the help of a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Flag {
+    /// The flag, preferring the long form (e.g., `--all` over `-a`)
+    pub(crate) name:        String,
+    /// All forms of the flag, as shown to the user
+    pub(crate) forms:       Vec<String>,
+    /// Name of the argument the flag takes, if any (e.g., `WHEN`)
+    pub(crate) arg:         Option<String>,
+    /// Whether the argument may be left out
+    pub(crate) optional:    bool,
+    /// Separator between the flag and its argument (`=` or a space)
+    pub(crate) separator:   char,
+    pub(crate) description: String,
+}
+
+impl Flag {
+    pub(crate) fn to_item(&self) -> Item {
+        let forms = match &self.arg {
+            Some(arg) => format!("{} {}", self.forms.join(", "), arg),
+            None => self.forms.join(", "),
+        };
+
+        Item::new(
+            self.name.clone(),
+            Some(format!("{}  {}", forms, self.description)),
+        )
+    }
+
+    /// The flag followed by its argument
+    pub(crate) fn with_arg(&self, arg: &str) -> String {
+        if arg.is_empty() {
+            self.name.clone()
+        } else {
+            let arg = shlex::try_quote(arg).map_or_else(|_| arg.to_owned(), Into::into);
+            format!("{}{}{}", self.name, self.separator, arg)
+        }
+    }
+}
+
+/// The name of the man page documenting `command` (e.g., `git-commit` for
+/// `git commit`)
+pub(crate) fn man_page(command: &str) -> String {
+    command.split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+/// The argument following the name of a flag (e.g., `=WHEN`, `[=WHEN]`,
+/// ` <file>`, or ` COLS`), returned with the rest of the line
+fn take_arg(rest: &str) -> (Option<&str>, &str) {
+    let s = if rest.starts_with('=') || rest.starts_with('[') || rest.starts_with('<') {
+        rest
+    } else if rest.starts_with(' ') && !rest.starts_with("  ") {
+        &rest[1..]
+    } else {
+        return (None, rest);
+    };
+
+    let mut depth = 0_i32;
+    let end = s
+        .char_indices()
+        .find(|&(_, c)| {
+            match c {
+                '[' | '<' | '(' => depth += 1,
+                ']' | '>' | ')' => depth -= 1,
+                _ => {},
+            }
+            depth <= 0 && (c.is_whitespace() || c == ',' || c == '|')
+        })
+        .map_or(s.len(), |(idx, _)| idx);
+    let token = &s[..end];
+
+    // A bare word after a space is only an argument when it looks like one
+    // (e.g., `COLS`), otherwise it is the start of the description
+    let bare = !token.starts_with(&['=', '[', '<'][..]);
+    if token.is_empty()
+        || (bare
+            && !token
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || "_-.:".contains(c)))
+    {
+        return (None, rest);
+    }
+
+    (Some(token), &s[end..])
+}
+
+fn parse_line(line: &str) -> Option<Flag> {
+    let line = line.trim_start();
+    if !line.starts_with('-') || line.starts_with("---") {
+        return None;
+    }
+
+    let mut forms = Vec::new();
+    let mut arg = None;
+    let mut optional = false;
+    let mut separator = ' ';
+    let mut rest = line;
+
+    loop {
+        let end = rest
+            .find(&['=', ' ', '\t', '[', '<', ',', '|'][..])
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        name.trim_start_matches('-')
+            .chars()
+            .next()
+            .filter(char::is_ascii_alphanumeric)?;
+        forms.push(name.to_owned());
+
+        let (token, after) = take_arg(&rest[end..]);
+        if let Some(token) = token {
+            optional = token.starts_with('[');
+            if token.trim_start_matches('[').starts_with('=') {
+                separator = '=';
+            }
+            arg = Some(
+                token
+                    .trim_matches(|c| matches!(c, '[' | ']' | '=' | '<' | '>'))
+                    .to_owned(),
+            );
+        }
+        rest = after;
+
+        // Further forms of the flag are separated by a comma or a pipe
+        match rest.strip_prefix(&[',', '|'][..]).map(str::trim_start) {
+            Some(next) if next.starts_with('-') => rest = next,
+            _ => break,
+        }
+    }
+
+    let name = forms
+        .iter()
+        .find(|f| f.starts_with("--"))
+        .or_else(|| forms.first())?
+        .clone();
+
+    Some(Flag {
+        name,
+        forms,
+        arg,
+        optional,
+        separator,
+        description: rest.trim().to_owned(),
+    })
+}
+
+/// Parse the flags documented in the help of a command
+pub(crate) fn parse(help: &str) -> Vec<Flag> {
+    let mut flags: Vec<Flag> = Vec::new();
+
+    for flag in help.lines().filter_map(parse_line) {
+        if !flags.iter().any(|f| f.name == flag.name) {
+            flags.push(flag);
+        }
+    }
+
+    flags
+}
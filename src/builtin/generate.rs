@@ -0,0 +1,69 @@
+//! Values generated locally, for menus such as "generate a UUID and copy it"
+//! that would otherwise need external tools
+
+use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Symbols a password is made of, along with letters and digits
+const SYMBOLS: &[u8] = b"!#$%&*+-.:=?@^_~";
+
+/// Words of the placeholder text
+const LOREM: &str = "lorem ipsum dolor sit amet consectetur adipiscing elit sed do eiusmod tempor \
+                     incididunt ut labore et dolore magna aliqua ut enim ad minim veniam quis \
+                     nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat \
+                     duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore \
+                     eu fugiat nulla pariatur";
+
+/// What is generated
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Kind {
+    /// A random (version 4) UUID
+    Uuid,
+    /// Letters and digits, `length` long (20 by default)
+    Password,
+    /// Hexadecimal digits, `length` long (32 by default)
+    Hex,
+    /// Placeholder text, `length` words long (8 by default)
+    Lorem,
+}
+
+/// Generate a value of `kind`, `length` long when it has a length. Passwords
+/// have no symbols unless `symbols` is set
+pub(crate) fn generate(kind: Kind, length: Option<usize>, symbols: bool) -> String {
+    let mut rng = rand::thread_rng();
+
+    match kind {
+        Kind::Uuid => Uuid::new_v4().to_string(),
+        Kind::Password => {
+            let length = length.unwrap_or(20);
+            if symbols {
+                let chars = (b'a'..=b'z')
+                    .chain(b'A'..=b'Z')
+                    .chain(b'0'..=b'9')
+                    .chain(SYMBOLS.iter().copied())
+                    .collect::<Vec<_>>();
+                (0..length)
+                    .filter_map(|_| chars.choose(&mut rng).map(|c| char::from(*c)))
+                    .collect()
+            } else {
+                (&mut rng)
+                    .sample_iter(Alphanumeric)
+                    .take(length)
+                    .map(char::from)
+                    .collect()
+            }
+        },
+        Kind::Hex => (0..length.unwrap_or(32))
+            .filter_map(|_| char::from_digit(rng.gen_range(0..16), 16))
+            .collect(),
+        Kind::Lorem => {
+            let words = LOREM.split_whitespace().collect::<Vec<_>>();
+            (0..length.unwrap_or(8))
+                .filter_map(|_| words.choose(&mut rng).copied())
+                .collect::<Vec<_>>()
+                .join(" ")
+        },
+    }
+}
@@ -0,0 +1,277 @@
+//! Git branch, tag, file, stash, and commit pickers, implemented with `git2` so
+//! they behave the same across shells and platforms
+
+use super::{relative_time, Item};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use git2::{BranchType, Commit, Diff, DiffFormat, DiffOptions, Repository, Sort, StatusOptions};
+
+/// Number of commits listed when no limit is given
+const DEFAULT_MAX_COMMITS: usize = 500;
+
+/// The kind of Git object listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Branch,
+    Tag,
+    File,
+    Stash,
+    Commit,
+}
+
+fn open() -> Result<Repository> {
+    Repository::discover(".").map_err(|_| anyhow!("not inside a git repository"))
+}
+
+fn short_id(commit: &Commit<'_>) -> Result<String> {
+    Ok(commit
+        .as_object()
+        .short_id()?
+        .as_str()
+        .unwrap_or_default()
+        .to_owned())
+}
+
+/// Author and relative date of a commit
+fn signature(commit: &Commit<'_>) -> String {
+    format!(
+        "{} {}",
+        commit.author().name().unwrap_or("unknown"),
+        relative_time(commit.time().seconds())
+    )
+}
+
+/// List the Git objects of the given kind. `max` limits the number of commits
+pub(crate) fn list(kind: Kind, max: Option<usize>) -> Result<Vec<Item>> {
+    let mut repo = open()?;
+
+    match kind {
+        Kind::Branch => branches(&repo),
+        Kind::Tag => tags(&repo),
+        Kind::File => files(&repo),
+        Kind::Stash => stashes(&mut repo),
+        Kind::Commit => commits(&repo, max.unwrap_or(DEFAULT_MAX_COMMITS)),
+    }
+}
+
+fn branches(repo: &Repository) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    for branch in repo.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let name = match branch.name()? {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        let commit = branch.get().peel_to_commit()?;
+
+        let upstream = match branch.upstream() {
+            Ok(upstream) => match (branch.get().target(), upstream.get().target()) {
+                (Some(local), Some(remote)) => {
+                    let (ahead, behind) = repo.graph_ahead_behind(local, remote)?;
+                    match (ahead, behind) {
+                        (0, 0) => "up to date".green().to_string(),
+                        (a, 0) => format!("ahead {}", a).yellow().to_string(),
+                        (0, b) => format!("behind {}", b).yellow().to_string(),
+                        (a, b) => format!("ahead {} behind {}", a, b).red().to_string(),
+                    }
+                },
+                _ => String::from("no upstream"),
+            },
+            Err(_) => String::from("no upstream"),
+        };
+
+        let head = if branch.is_head() { "* " } else { "" };
+        items.push(Item::new(
+            name,
+            Some(format!("{}{}  {}", head, upstream, signature(&commit))),
+        ));
+    }
+
+    Ok(items)
+}
+
+fn tags(repo: &Repository) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    for name in repo.tag_names(None)?.iter().flatten() {
+        let commit = repo
+            .revparse_single(&format!("refs/tags/{}", name))?
+            .peel_to_commit()?;
+
+        items.push(Item::new(
+            name,
+            Some(format!(
+                "{}  {}  {}",
+                short_id(&commit)?,
+                commit.summary().unwrap_or_default(),
+                signature(&commit)
+            )),
+        ));
+    }
+
+    items.reverse();
+    Ok(items)
+}
+
+fn files(repo: &Repository) -> Result<Vec<Item>> {
+    let statuses = repo.statuses(Some(
+        StatusOptions::new()
+            .include_untracked(true)
+            .recurse_untracked_dirs(true),
+    ))?;
+
+    Ok(statuses
+        .iter()
+        .filter_map(|entry| {
+            let status = entry.status();
+            let label = if status.is_conflicted() {
+                "conflicted".red()
+            } else if status.is_wt_new() {
+                "untracked".yellow()
+            } else if status.is_index_new() {
+                "added".green()
+            } else if status.is_wt_deleted() || status.is_index_deleted() {
+                "deleted".red()
+            } else if status.is_wt_renamed() || status.is_index_renamed() {
+                "renamed".blue()
+            } else if status.is_wt_modified() || status.is_index_modified() {
+                "modified".blue()
+            } else {
+                return None;
+            };
+
+            let staged = if status.is_index_new()
+                || status.is_index_modified()
+                || status.is_index_deleted()
+                || status.is_index_renamed()
+            {
+                " (staged)"
+            } else {
+                ""
+            };
+
+            entry
+                .path()
+                .map(|path| Item::new(path, Some(format!("{}{}", label, staged))))
+        })
+        .collect())
+}
+
+fn stashes(repo: &mut Repository) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    repo.stash_foreach(|index, message, _| {
+        items.push(Item::new(
+            format!("stash@{{{}}}", index),
+            Some(message.to_owned()),
+        ));
+        true
+    })?;
+
+    Ok(items)
+}
+
+fn commits(repo: &Repository, max: usize) -> Result<Vec<Item>> {
+    let mut walk = repo.revwalk()?;
+    walk.push_head()?;
+    walk.set_sorting(Sort::TIME)?;
+
+    walk.take(max)
+        .map(|oid| {
+            let commit = repo.find_commit(oid?)?;
+            Ok(Item::new(
+                short_id(&commit)?,
+                Some(format!(
+                    "{}  {}",
+                    commit.summary().unwrap_or_default(),
+                    signature(&commit)
+                )),
+            ))
+        })
+        .collect()
+}
+
+/// Print a diff as a colored patch
+fn print_diff(diff: &Diff<'_>) -> Result<()> {
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        let content = String::from_utf8_lossy(line.content());
+        match line.origin() {
+            '+' => print!("{}", format!("+{}", content).green()),
+            '-' => print!("{}", format!("-{}", content).red()),
+            ' ' => print!(" {}", content),
+            'H' => print!("{}", content.cyan()),
+            'F' => print!("{}", content.bold()),
+            _ => print!("{}", content),
+        }
+        true
+    })?;
+
+    Ok(())
+}
+
+/// Print the details of a commit followed by its patch
+fn print_commit(repo: &Repository, commit: &Commit<'_>) -> Result<()> {
+    println!("{} {}", "commit".yellow(), commit.id().to_string().yellow());
+    println!(
+        "Author: {} <{}>",
+        commit.author().name().unwrap_or("unknown"),
+        commit.author().email().unwrap_or_default()
+    );
+    println!("Date:   {}\n", relative_time(commit.time().seconds()));
+    for line in commit.message().unwrap_or_default().lines() {
+        println!("    {}", line);
+    }
+    println!();
+
+    let parent = commit.parent(0).ok().map(|p| p.tree()).transpose()?;
+    print_diff(&repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?)
+}
+
+/// Print the preview of a Git object
+pub(crate) fn preview(kind: Kind, name: &str) -> Result<()> {
+    let repo = open()?;
+
+    match kind {
+        Kind::Branch => {
+            let branch = repo.find_branch(name, BranchType::Local)?;
+            let mut walk = repo.revwalk()?;
+            walk.push(
+                branch
+                    .get()
+                    .target()
+                    .ok_or_else(|| anyhow!("branch has no target"))?,
+            )?;
+
+            for oid in walk.take(50) {
+                let commit = repo.find_commit(oid?)?;
+                println!(
+                    "{} {} {}",
+                    short_id(&commit)?.yellow(),
+                    commit.summary().unwrap_or_default(),
+                    signature(&commit).dimmed()
+                );
+            }
+
+            Ok(())
+        },
+        Kind::Tag | Kind::Stash | Kind::Commit => {
+            let commit = repo.revparse_single(name)?.peel_to_commit()?;
+            print_commit(&repo, &commit)
+        },
+        Kind::File => {
+            let head = repo.head().ok().map(|h| h.peel_to_tree()).transpose()?;
+            let diff = repo.diff_tree_to_workdir_with_index(
+                head.as_ref(),
+                Some(
+                    DiffOptions::new()
+                        .pathspec(name)
+                        .include_untracked(true)
+                        .recurse_untracked_dirs(true)
+                        .show_untracked_content(true),
+                ),
+            )?;
+            print_diff(&diff)
+        },
+    }
+}
@@ -0,0 +1,110 @@
+//! Values picked out of the JSON (or YAML) output of a command, without
+//! depending on `jq`
+
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde_json::Value;
+
+/// Parse the output of a command as JSON, falling back to YAML
+pub(crate) fn parse(output: &str) -> Result<Value> {
+    serde_json::from_str(output)
+        .or_else(|_| serde_yaml::from_str(output).context("output is neither valid JSON nor YAML"))
+}
+
+/// A step of a jq-like path
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+    /// `.name` or `["name"]`
+    Key(String),
+    /// `[N]`
+    Index(usize),
+    /// `[]`, every element of an array or value of an object
+    Each,
+}
+
+/// Parse a jq-like path such as `.items[].metadata.name`
+fn steps(path: &str) -> Result<Vec<Step>> {
+    let mut steps = Vec::new();
+    let mut rest = path.trim();
+
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('[') {
+            let end = r
+                .find(']')
+                .ok_or_else(|| anyhow!("unclosed `[` in {}", path))?;
+            let inner = r[..end].trim();
+            steps.push(if inner.is_empty() {
+                Step::Each
+            } else if let Ok(index) = inner.parse() {
+                Step::Index(index)
+            } else {
+                Step::Key(inner.trim_matches('"').to_owned())
+            });
+            rest = &r[end + 1..];
+        } else if let Some(r) = rest.strip_prefix('.') {
+            let end = r.find(&['.', '['][..]).unwrap_or(r.len());
+            if end > 0 {
+                steps.push(Step::Key(r[..end].trim_matches('"').to_owned()));
+            }
+            rest = &r[end..];
+        } else {
+            return Err(anyhow!("invalid path: {}", path));
+        }
+    }
+
+    Ok(steps)
+}
+
+/// Select the values at `path`, either a JSON pointer (`/items/0`) or a
+/// jq-like path (`.items[].name`). An array (or object) found at the end of the
+/// path is expanded into its elements
+pub(crate) fn pick<'a>(value: &'a Value, path: Option<&str>) -> Result<Vec<&'a Value>> {
+    let mut values = match path {
+        Some(pointer) if pointer.starts_with('/') => vec![value
+            .pointer(pointer)
+            .ok_or_else(|| anyhow!("nothing found at {}", pointer))?],
+        Some(path) => {
+            let mut values = vec![value];
+            for step in steps(path)? {
+                values = values
+                    .into_iter()
+                    .flat_map(|value| -> Vec<&Value> {
+                        match (&step, value) {
+                            (Step::Key(key), Value::Object(map)) =>
+                                map.get(key).into_iter().collect(),
+                            (Step::Index(index), Value::Array(array)) =>
+                                array.get(*index).into_iter().collect(),
+                            (Step::Each, Value::Array(array)) => array.iter().collect(),
+                            (Step::Each, Value::Object(map)) => map.values().collect(),
+                            _ => Vec::new(),
+                        }
+                    })
+                    .collect();
+            }
+            values
+        },
+        None => vec![value],
+    };
+
+    if values.len() == 1 {
+        match values[0] {
+            Value::Array(array) => values = array.iter().collect(),
+            Value::Object(map) => values = map.values().collect(),
+            _ => {},
+        }
+    }
+
+    if values.is_empty() {
+        Err(anyhow!("nothing found at {}", path.unwrap_or(".")))
+    } else {
+        Ok(values)
+    }
+}
+
+/// The raw value substituted into a command: strings without their quotes,
+/// anything else as compact JSON
+pub(crate) fn raw(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,216 @@
+//! Kubernetes context and namespace pickers, read directly from the kubeconfig
+
+use super::Item;
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use colored::Colorize;
+use serde::Deserialize;
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+#[derive(Deserialize, Debug, Default)]
+struct KubeConfig {
+    #[serde(rename = "current-context")]
+    current_context: Option<String>,
+    #[serde(default)]
+    contexts:        Vec<NamedContext>,
+    #[serde(default)]
+    clusters:        Vec<NamedCluster>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NamedContext {
+    name:    String,
+    context: ContextInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContextInfo {
+    cluster:   String,
+    user:      Option<String>,
+    namespace: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NamedCluster {
+    name:    String,
+    cluster: ClusterInfo,
+}
+
+#[derive(Deserialize, Debug)]
+struct ClusterInfo {
+    server: Option<String>,
+}
+
+/// The kubeconfig files in use: `$KUBECONFIG` or `~/.kube/config`
+fn kubeconfig_paths() -> Vec<PathBuf> {
+    match env::var_os("KUBECONFIG") {
+        Some(paths) => env::split_paths(&paths)
+            .filter(|p| !p.as_os_str().is_empty())
+            .collect(),
+        None => dirs::home_dir()
+            .map(|home| home.join(".kube").join("config"))
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// Load and merge the kubeconfig files. As with `kubectl`, the first file to
+/// set the current context or define an entry wins
+fn load() -> Result<KubeConfig> {
+    let mut merged = KubeConfig::default();
+    let mut found = false;
+
+    for path in kubeconfig_paths().into_iter().filter(|p| p.is_file()) {
+        let file = fs::File::open(&path).context(format!("unable to read: {}", path.display()))?;
+        let config: KubeConfig = serde_yaml::from_reader(file)
+            .context(format!("invalid kubeconfig: {}", path.display()))?;
+        found = true;
+
+        if merged.current_context.is_none() {
+            merged.current_context = config.current_context;
+        }
+        for ctx in config.contexts {
+            if !merged.contexts.iter().any(|c| c.name == ctx.name) {
+                merged.contexts.push(ctx);
+            }
+        }
+        for cluster in config.clusters {
+            if !merged.clusters.iter().any(|c| c.name == cluster.name) {
+                merged.clusters.push(cluster);
+            }
+        }
+    }
+
+    if found {
+        Ok(merged)
+    } else {
+        Err(anyhow!("no kubeconfig found"))
+    }
+}
+
+/// List the contexts, the current one first
+pub(crate) fn contexts() -> Result<Vec<Item>> {
+    let config = load()?;
+    let current = config.current_context.as_deref();
+
+    let mut items = config
+        .contexts
+        .iter()
+        .map(|ctx| {
+            let mut detail = format!("cluster: {}", ctx.context.cluster);
+            if let Some(ns) = &ctx.context.namespace {
+                detail.push_str(&format!(" namespace: {}", ns));
+            }
+            if Some(ctx.name.as_str()) == current {
+                detail.push_str(" (current)");
+            }
+            (
+                Some(ctx.name.as_str()) != current,
+                Item::new(&ctx.name, Some(detail)),
+            )
+        })
+        .collect::<Vec<_>>();
+    items.sort_by_key(|(not_current, _)| *not_current);
+
+    Ok(items.into_iter().map(|(_, item)| item).collect())
+}
+
+/// List the namespaces referenced by the kubeconfig, along with the namespaces
+/// of the cluster when `kubectl` is available
+pub(crate) fn namespaces(context: Option<&str>) -> Result<Vec<Item>> {
+    let config = load()?;
+    let context = context.or(config.current_context.as_deref());
+
+    let mut namespaces = config
+        .contexts
+        .iter()
+        .filter_map(|ctx| ctx.context.namespace.clone())
+        .collect::<BTreeSet<_>>();
+    namespaces.insert(String::from("default"));
+
+    if which::which("kubectl").is_ok() {
+        let mut kubectl = Command::new("kubectl");
+        if let Some(context) = context {
+            kubectl.arg("--context").arg(context);
+        }
+        if let Ok(output) = kubectl
+            .args(&["get", "namespaces", "-o", "name", "--request-timeout=2s"])
+            .stderr(Stdio::null())
+            .output()
+        {
+            namespaces.extend(
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .map(|ns| ns.trim_start_matches("namespace/").to_owned()),
+            );
+        }
+    }
+
+    let active = config
+        .contexts
+        .iter()
+        .find(|ctx| Some(ctx.name.as_str()) == context)
+        .and_then(|ctx| ctx.context.namespace.as_deref())
+        .unwrap_or("default");
+
+    Ok(namespaces
+        .into_iter()
+        .map(|ns| {
+            let detail = (ns == active).then(|| String::from("(active)"));
+            Item::new(ns, detail)
+        })
+        .collect())
+}
+
+/// Print the cluster, server, user, and namespace of a context
+pub(crate) fn preview_context(name: &str) -> Result<()> {
+    let config = load()?;
+    let ctx = config
+        .contexts
+        .iter()
+        .find(|ctx| ctx.name == name)
+        .ok_or_else(|| anyhow!("unknown context: {}", name))?;
+    let server = config
+        .clusters
+        .iter()
+        .find(|c| c.name == ctx.context.cluster)
+        .and_then(|c| c.cluster.server.as_deref())
+        .unwrap_or("unknown");
+
+    println!("{} {}", "context:".green().bold(), ctx.name);
+    println!("{} {}", "cluster:".green().bold(), ctx.context.cluster);
+    println!("{} {}", "server:".green().bold(), server);
+    println!(
+        "{} {}",
+        "user:".green().bold(),
+        ctx.context.user.as_deref().unwrap_or("none")
+    );
+    println!(
+        "{} {}",
+        "namespace:".green().bold(),
+        ctx.context.namespace.as_deref().unwrap_or("default")
+    );
+
+    Ok(())
+}
+
+/// Print the contexts that use a namespace
+pub(crate) fn preview_namespace(name: &str) -> Result<()> {
+    let config = load()?;
+
+    println!("{} {}", "namespace:".green().bold(), name);
+    println!("{}", "contexts:".green().bold());
+    for ctx in config
+        .contexts
+        .iter()
+        .filter(|ctx| ctx.context.namespace.as_deref().unwrap_or("default") == name)
+    {
+        println!("  {} ({})", ctx.name, ctx.context.cluster);
+    }
+
+    Ok(())
+}
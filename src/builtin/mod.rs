@@ -0,0 +1,109 @@
+//! Widgets that list their items from within `jaime` instead of running an
+//! external command
+
+pub(crate) mod color;
+pub(crate) mod docker;
+pub(crate) mod emoji;
+pub(crate) mod env;
+pub(crate) mod flags;
+pub(crate) mod generate;
+pub(crate) mod git;
+pub(crate) mod json;
+pub(crate) mod kube;
+pub(crate) mod process;
+pub(crate) mod systemd;
+
+use crate::theme;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// An item listed by a builtin widget
+#[derive(Debug, Clone)]
+pub(crate) struct Item {
+    /// The value substituted into the command when the item is selected. It
+    /// is always the first (tab separated) field of the rendered line
+    pub(crate) value:  String,
+    /// Extra information displayed next to the value
+    pub(crate) detail: Option<String>,
+}
+
+impl Item {
+    pub(crate) fn new<S: Into<String>>(value: S, detail: Option<String>) -> Self {
+        Self {
+            value: value.into(),
+            detail,
+        }
+    }
+}
+
+/// Render the items into the lines given to a selector, with the details
+/// aligned in a column
+pub(crate) fn render(items: &[Item]) -> String {
+    let width = items
+        .iter()
+        .map(|item| item.value.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    items
+        .iter()
+        .map(|item| match &item.detail {
+            Some(detail) => format!(
+                "{:width$}\t{}",
+                item.value,
+                theme::get().dimmed.paint(detail),
+                width = width
+            ),
+            None => item.value.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Get the value back from a selected line
+pub(crate) fn value_of(selected: &str) -> String {
+    selected
+        .split('\t')
+        .next()
+        .unwrap_or_default()
+        .trim_end()
+        .to_owned()
+}
+
+/// Format a size in bytes for humans
+pub(crate) fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "kB", "MB", "GB", "TB"];
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{:.1}{}", size, UNITS[unit])
+}
+
+/// Describe a UNIX timestamp relative to now (e.g., `3 days ago`)
+pub(crate) fn relative_time(secs: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let elapsed = now.saturating_sub(secs.max(0).unsigned_abs());
+
+    let (amount, unit) = match elapsed {
+        s if s < 60 => return String::from("just now"),
+        s if s < 3600 => (s / 60, "minute"),
+        s if s < 86_400 => (s / 3600, "hour"),
+        s if s < 86_400 * 30 => (s / 86_400, "day"),
+        s if s < 86_400 * 365 => (s / (86_400 * 30), "month"),
+        s => (s / (86_400 * 365), "year"),
+    };
+
+    format!(
+        "{} {}{} ago",
+        amount,
+        unit,
+        if amount == 1 { "" } else { "s" }
+    )
+}
@@ -0,0 +1,112 @@
+//! Process picker, listing the running processes with `sysinfo` instead of
+//! parsing the output of `ps`
+
+use super::{human_size, relative_time, Item};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use std::{convert::TryFrom, process, thread};
+use sysinfo::{Pid, Process, System, MINIMUM_CPU_UPDATE_INTERVAL};
+
+/// The command line of a process, or its name if the command line is not
+/// readable
+fn command_line(process: &Process) -> String {
+    if process.cmd().is_empty() {
+        process.name().to_owned()
+    } else {
+        process.cmd().join(" ")
+    }
+}
+
+/// List the running processes, sorted by CPU usage. When `filter` is given,
+/// only the processes whose name or command line contain it (ignoring case)
+/// are listed
+pub(crate) fn list(filter: Option<&str>) -> Result<Vec<Item>> {
+    // The CPU usage is computed from the difference between two refreshes
+    let mut system = System::new();
+    system.refresh_processes();
+    thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+    system.refresh_processes();
+
+    let filter = filter.map(str::to_lowercase);
+    let own = Pid::from_u32(process::id());
+
+    let mut processes = system
+        .processes()
+        .values()
+        .filter(|p| p.pid() != own)
+        .filter(|p| {
+            filter.as_ref().map_or(true, |filter| {
+                p.name().to_lowercase().contains(filter)
+                    || command_line(p).to_lowercase().contains(filter)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if processes.is_empty() {
+        return Err(anyhow!("no matching processes"));
+    }
+
+    processes.sort_by(|a, b| b.cpu_usage().total_cmp(&a.cpu_usage()));
+
+    Ok(processes
+        .into_iter()
+        .map(|p| {
+            Item::new(
+                p.pid().to_string(),
+                Some(format!(
+                    "{:>5.1}%  {:>8}  {}",
+                    p.cpu_usage(),
+                    human_size(p.memory()),
+                    command_line(p)
+                )),
+            )
+        })
+        .collect())
+}
+
+/// Print the details of a process
+pub(crate) fn preview(pid: &str) -> Result<()> {
+    let pid = pid
+        .parse::<Pid>()
+        .map_err(|_| anyhow!("invalid pid: {}", pid))?;
+
+    let mut system = System::new();
+    system.refresh_processes();
+    let process = system
+        .process(pid)
+        .ok_or_else(|| anyhow!("no process with pid {}", pid))?;
+
+    let field = |name: &str, value: &dyn std::fmt::Display| {
+        println!("{} {}", format!("{}:", name).green().bold(), value);
+    };
+
+    field("pid", &pid);
+    field("name", &process.name());
+    field(
+        "parent",
+        &process
+            .parent()
+            .map_or_else(|| String::from("none"), |p| p.to_string()),
+    );
+    field("status", &process.status());
+    field(
+        "started",
+        &relative_time(i64::try_from(process.start_time()).unwrap_or_default()),
+    );
+    field("memory", &human_size(process.memory()));
+    field(
+        "exe",
+        &process
+            .exe()
+            .map_or_else(|| String::from("unknown"), |p| p.display().to_string()),
+    );
+    field(
+        "cwd",
+        &process
+            .cwd()
+            .map_or_else(|| String::from("unknown"), |p| p.display().to_string()),
+    );
+    println!("\n{}", command_line(process));
+
+    Ok(())
+}
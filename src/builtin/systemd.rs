@@ -0,0 +1,175 @@
+//! systemd unit picker, along with a builtin menu to control the units
+
+use super::Item;
+use crate::{
+    runner::{Action, Meta, Options, Output, Widget, WidgetConfig},
+    secrets::Secrets,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use colored::Colorize;
+use serde::Deserialize;
+use std::process::Command;
+
+/// A unit as listed by `systemctl list-units --json=short`
+#[derive(Debug, Deserialize)]
+struct Unit {
+    #[serde(rename = "unit")]
+    name:        String,
+    active:      String,
+    sub:         String,
+    #[serde(default)]
+    description: String,
+}
+
+impl Unit {
+    fn into_item(self) -> Item {
+        let state = format!("{} ({})", self.active, self.sub);
+        let state = match self.active.as_str() {
+            "active" => state.green(),
+            "failed" => state.red(),
+            "activating" | "deactivating" | "reloading" => state.yellow(),
+            _ => state.normal(),
+        };
+
+        Item::new(self.name, Some(format!("{}  {}", state, self.description)))
+    }
+}
+
+fn systemctl(user: bool, args: &[&str]) -> Result<String> {
+    let mut systemctl = Command::new("systemctl");
+    if user {
+        systemctl.arg("--user");
+    }
+
+    let output = systemctl
+        .args(args)
+        .output()
+        .context("failed to run systemctl")?;
+
+    if output.status.success() {
+        Ok(String::from_utf8(output.stdout)?)
+    } else {
+        Err(anyhow!(
+            "systemctl failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// List the units of the system (or user) manager. Inactive units are only
+/// listed when `all` is set
+pub(crate) fn units(user: bool, all: bool) -> Result<Vec<Item>> {
+    let mut args = vec!["list-units", "--no-pager", "--json=short"];
+    if all {
+        args.push("--all");
+    }
+
+    // `--output=` only changes the format of the journal, while `--json=` isn't
+    // understood by older versions (systemd 252 rejects it), which fall back to
+    // the plain listing
+    let json = systemctl(user, &args)
+        .ok()
+        .and_then(|out| serde_json::from_str::<Vec<Unit>>(&out).ok());
+
+    let units = if let Some(units) = json {
+        units
+    } else {
+        args[2] = "--plain";
+        args.push("--no-legend");
+
+        systemctl(user, &args)?
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let name = fields.next()?.to_owned();
+                let _load = fields.next()?;
+                let active = fields.next()?.to_owned();
+                let sub = fields.next()?.to_owned();
+
+                Some(Unit {
+                    name,
+                    active,
+                    sub,
+                    description: fields.collect::<Vec<_>>().join(" "),
+                })
+            })
+            .collect()
+    };
+
+    Ok(units.into_iter().map(Unit::into_item).collect())
+}
+
+/// Print the status of a unit
+pub(crate) fn preview(user: bool, unit: &str) -> Result<()> {
+    let mut systemctl = Command::new("systemctl");
+    if user {
+        systemctl.arg("--user");
+    }
+
+    // The status is still printed for units that are not running, which exit
+    // with a non-zero status
+    let output = systemctl
+        .args(&["status", "--no-pager", "--lines=20", unit])
+        .env("SYSTEMD_COLORS", "1")
+        .output()
+        .context("failed to run systemctl")?;
+
+    print!("{}", String::from_utf8_lossy(&output.stdout));
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+    Ok(())
+}
+
+/// The builtin menu to start, stop, restart, and show the status of a unit
+pub(crate) fn actions(user: bool) -> Action {
+    let systemctl = if user {
+        "systemctl --user"
+    } else {
+        "systemctl"
+    };
+
+    let command = |verb: &str, description: &str, all: bool, output: Output| {
+        (verb.into(), Action::Command {
+            meta: Meta {
+                description: Some(description.to_owned()),
+                ..Meta::default()
+            },
+            command: format!("{} {} {{0}}", systemctl, verb),
+            interpreter: None,
+            strict_shell: None,
+            login_shell: None,
+            tty: None,
+            widgets: Some(vec![WidgetConfig {
+                widget:           Widget::SystemdUnit { user, all },
+                map:              None,
+                optional:         false,
+                name:             None,
+                depends:          Vec::new(),
+                when:             None,
+                selector:         None,
+                selector_options: None,
+            }]),
+            output,
+            capture: false,
+            notify: false,
+            timeout: None,
+            exclusive: false,
+            capture_output: false,
+            secrets: Secrets::new(),
+            summary: None,
+            computed: Vec::new(),
+        })
+    };
+
+    Action::Select {
+        meta:    Meta::default(),
+        options: vec![
+            command("start", "Start a unit", true, Output::Terminal),
+            command("stop", "Stop a unit", false, Output::Terminal),
+            command("restart", "Restart a unit", false, Output::Terminal),
+            command("status", "Show the status of a unit", true, Output::Pager),
+        ]
+        .into_iter()
+        .collect::<Options>(),
+    }
+}
@@ -0,0 +1,247 @@
+//! The parsed configuration, memoized in a binary format under the cache
+//! directory.
+//!
+//! Parsing a large YAML configuration on every hotkey press adds noticeable
+//! latency, while decoding it back (as `MessagePack`) is much faster. The
+//! memoized copy is used as long as the files keep the same paths, sizes, and
+//! modification times, or the same contents when only the modification times
+//! changed, and was written by the same version of Jaime.
+//!
+//! The configuration can be tweaked for a machine by the overlays next to it,
+//! `config.<hostname>.yml` and then `config.local.yml`, which are merged over
+//! it in that order. A configuration with encrypted sections is decrypted on
+//! every start instead of being memoized
+
+use crate::{crypt, doctor, error::JaimeError, runner::Config, yaml};
+use anyhow::{Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use sysinfo::System;
+
+/// Name of the file holding the memoized configuration
+const FILE_NAME: &str = "config.msgpack";
+
+/// A parsed configuration
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Loaded {
+    pub(crate) config:         Config,
+    /// Dotted paths of the fields of the file that were ignored
+    pub(crate) unknown_fields: Vec<String>,
+    /// Encrypted sections that were left out, with the reason they couldn't be
+    /// decrypted
+    pub(crate) locked:         Vec<String>,
+    /// Problems of the configuration found without looking at the system,
+    /// reported on every start
+    pub(crate) problems:       Vec<String>,
+}
+
+/// What is known of a file without reading it
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Stamp {
+    path:     PathBuf,
+    len:      u64,
+    /// Modification time, in nanoseconds since the epoch
+    modified: u128,
+}
+
+/// What the memoized configuration was parsed from
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct Key {
+    version: String,
+    /// The configuration file, followed by the overlays found next to it
+    files:   Vec<Stamp>,
+    /// Hash of the contents of the files
+    hash:    u64,
+}
+
+impl Key {
+    /// Whether the files didn't change, judging from their metadata alone
+    fn same_file(&self, other: &Self) -> bool {
+        self.version == other.version && self.files == other.files
+    }
+
+    /// Whether the files have the same contents, even if they were touched
+    fn same_contents(&self, other: &Self) -> bool {
+        self.version == other.version
+            && self.files.len() == other.files.len()
+            && self
+                .files
+                .iter()
+                .zip(&other.files)
+                .all(|(a, b)| a.path == b.path)
+            && self.hash == other.hash
+    }
+}
+
+/// The overlays of the configuration at `path` that exist, the one taking
+/// precedence last
+pub(crate) fn overlays(path: &Path) -> Vec<PathBuf> {
+    let stem = path
+        .file_stem()
+        .map_or_else(|| "config".into(), |s| s.to_string_lossy());
+    let names = System::host_name()
+        .map(|host| format!("{}.{}.yml", stem, host))
+        .into_iter()
+        .chain(Some(format!("{}.local.yml", stem)));
+
+    names
+        .map(|name| path.with_file_name(name))
+        .filter(|overlay| overlay.is_file())
+        .collect()
+}
+
+/// The key of the files, without the hash of their contents
+fn stat(files: &[PathBuf]) -> Result<Key> {
+    let files = files
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path)
+                .context(format!("Couldn't read config file: {}", path.display()))?;
+            Ok(Stamp {
+                path:     path.clone(),
+                len:      metadata.len(),
+                modified: metadata
+                    .modified()?
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos()),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Key {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        files,
+        hash: 0,
+    })
+}
+
+/// Parse the configuration from the texts of `files`, merging the overlays
+/// over the first one and decrypting its encrypted sections. Returns whether
+/// it had encrypted sections as well
+fn parse(files: &[PathBuf], texts: &[String]) -> Result<(Loaded, bool)> {
+    let (base, overlays) = texts.split_first().context("no configuration file")?;
+    yaml::check_duplicate_keys(base)?;
+
+    let mut merged: Value = serde_yaml::from_str(base).map_err(JaimeError::from)?;
+    for (file, text) in files.iter().skip(1).zip(overlays) {
+        if text.trim().is_empty() {
+            continue;
+        }
+        let invalid = || format!("invalid overlay: {}", file.display());
+        yaml::check_duplicate_keys(text).with_context(invalid)?;
+        let overlay = serde_yaml::from_str(text)
+            .map_err(JaimeError::from)
+            .with_context(invalid)?;
+        yaml::merge(&mut merged, overlay);
+    }
+
+    let mut locked = Vec::new();
+    let encrypted = crypt::decrypt_sections(&mut merged, &crypt::identity(&files[0]), &mut locked)?;
+
+    // Parsed from the text when nothing was merged nor decrypted, so that
+    // errors point at a line
+    let config = if overlays.is_empty() && !encrypted {
+        serde_yaml::from_str(base)
+    } else {
+        serde_yaml::from_value(merged.clone())
+    }
+    .map_err(JaimeError::from)?;
+
+    let unknown_fields = yaml::unknown_fields(&merged, &config);
+    Ok((
+        Loaded {
+            problems: doctor::problems(&config, &unknown_fields),
+            unknown_fields,
+            locked,
+            config,
+        },
+        encrypted,
+    ))
+}
+
+/// The path of the memoized configuration
+pub(crate) fn path(cache_directory: &Path) -> PathBuf {
+    cache_directory.join(FILE_NAME)
+}
+
+fn read(memo: &Path) -> Option<(Key, Loaded)> {
+    rmp_serde::from_slice(&fs::read(memo).ok()?).ok()
+}
+
+/// Memoize `config`, writing to a temporary file first so that a concurrent
+/// start never reads half of it
+fn write(memo: &Path, key: &Key, loaded: &Loaded) -> Result<()> {
+    let tmp = memo.with_extension(format!("msgpack.{}", std::process::id()));
+    fs::write(&tmp, rmp_serde::to_vec_named(&(key, loaded))?)?;
+    fs::rename(&tmp, memo)?;
+
+    Ok(())
+}
+
+/// Parse the configuration at `path` along with its overlays, or decode it
+/// from the cache directory when none of them changed since it was last parsed
+pub(crate) fn load(path: &Path, cache_directory: &Path) -> Result<Loaded> {
+    let memo = self::path(cache_directory);
+    let mut files = vec![path.to_owned()];
+    files.extend(overlays(path));
+    let mut key = stat(&files)?;
+
+    let memoized = match read(&memo) {
+        Some((found, loaded)) if found.same_file(&key) => return Ok(loaded),
+        memoized => memoized,
+    };
+
+    let texts = files
+        .iter()
+        .map(|file| {
+            fs::read_to_string(file)
+                .context(format!("Couldn't read config file: {}", file.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut hasher = DefaultHasher::new();
+    texts.hash(&mut hasher);
+    key.hash = hasher.finish();
+
+    let (loaded, encrypted) = match memoized {
+        Some((found, loaded)) if found.same_contents(&key) => (loaded, false),
+        _ => parse(&files, &texts)?,
+    };
+
+    // Decrypted sections are never written to the disk. Failing to memoize
+    // only makes the next start slower
+    if !encrypted {
+        let _ = write(&memo, &key, &loaded);
+    }
+
+    Ok(loaded)
+}
+
+/// Parse the configuration at `path` by itself, without its overlays nor the
+/// memoized copy
+pub(crate) fn parse_file(path: &Path) -> Result<Config> {
+    let text = fs::read_to_string(path)
+        .context(format!("Couldn't read config file: {}", path.display()))?;
+    let (loaded, _) = parse(&[path.to_owned()], &[text])?;
+
+    Ok(loaded.config)
+}
+
+/// Discard the memoized configuration, then parse it again
+pub(crate) fn rebuild(path: &Path, cache_directory: &Path) -> Result<Loaded> {
+    let memo = self::path(cache_directory);
+    match fs::remove_file(&memo) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound =>
+            return Err(e).context(format!("unable to remove: {}", memo.display())),
+        _ => {},
+    }
+
+    load(path, cache_directory)
+}
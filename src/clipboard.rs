@@ -0,0 +1,116 @@
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use std::{
+    env,
+    fs::OpenOptions,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+/// The way text is copied to the system clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Clipboard {
+    /// `pbcopy` on macOS
+    Pbcopy,
+    /// `wl-copy` on Wayland
+    WlCopy,
+    /// `xclip` on X11
+    Xclip,
+    /// `xsel` on X11
+    Xsel,
+    /// OSC-52 escape sequence written to the terminal
+    Osc52,
+    /// OSC-52 wrapped in a tmux passthrough sequence
+    Osc52Tmux,
+}
+
+impl Clipboard {
+    /// Detect which clipboard should be used based on the environment.
+    ///
+    /// Remote sessions (SSH) always use OSC-52, as a local clipboard binary
+    /// would copy to the clipboard of the remote machine
+    pub(crate) fn detect() -> Self {
+        let remote = env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some();
+
+        if !remote {
+            if cfg!(target_os = "macos") && which::which("pbcopy").is_ok() {
+                return Self::Pbcopy;
+            }
+            if env::var_os("WAYLAND_DISPLAY").is_some() && which::which("wl-copy").is_ok() {
+                return Self::WlCopy;
+            }
+            if env::var_os("DISPLAY").is_some() {
+                if which::which("xclip").is_ok() {
+                    return Self::Xclip;
+                }
+                if which::which("xsel").is_ok() {
+                    return Self::Xsel;
+                }
+            }
+        }
+
+        if env::var_os("TMUX").is_some() {
+            Self::Osc52Tmux
+        } else {
+            Self::Osc52
+        }
+    }
+
+    /// Copy `text` to the clipboard
+    pub(crate) fn copy(self, text: &str) -> Result<()> {
+        let (bin, args): (&str, &[&str]) = match self {
+            Self::Pbcopy => ("pbcopy", &[]),
+            Self::WlCopy => ("wl-copy", &[]),
+            Self::Xclip => ("xclip", &["-selection", "clipboard"]),
+            Self::Xsel => ("xsel", &["--clipboard", "--input"]),
+            Self::Osc52 | Self::Osc52Tmux => return write_terminal(&self.osc52(text)),
+        };
+
+        let mut child = Command::new(bin)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .context(format!("failed to spawn {}", bin))?;
+
+        child
+            .stdin
+            .as_mut()
+            .context("failed to open stdin")?
+            .write_all(text.as_bytes())?;
+
+        if child.wait()?.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("{} exited with a non-zero status", bin))
+        }
+    }
+
+    /// Build the OSC-52 sequence, wrapping it for tmux if needed.
+    ///
+    /// tmux requires every `ESC` within a passthrough sequence to be doubled
+    fn osc52(self, text: &str) -> String {
+        let seq = format!("\x1b]52;c;{}\x07", base64::encode(text));
+
+        if self == Self::Osc52Tmux {
+            format!("\x1bPtmux;{}\x1b\\", seq.replace('\x1b', "\x1b\x1b"))
+        } else {
+            seq
+        }
+    }
+}
+
+/// Write an escape sequence to the controlling terminal. Standard output is
+/// often captured (e.g., by the shell key-bindings), so `/dev/tty` is preferred
+fn write_terminal(seq: &str) -> Result<()> {
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(seq.as_bytes())?,
+        Err(_) => io::stderr().write_all(seq.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+/// Copy `text` to the clipboard that best fits the current environment
+pub(crate) fn copy(text: &str) -> Result<()> {
+    Clipboard::detect().copy(text)
+}
@@ -0,0 +1,209 @@
+//! Conditions of the widgets (e.g., `when: "{0} == 'ssh'"`), telling whether a
+//! widget is displayed given the values of the widgets displayed before it.
+//!
+//! A condition compares values with `==`, `!=`, and `=~` (a regular expression
+//! match), and combines the comparisons with `&&`, `||`, `!`, and parentheses.
+//! A value alone is true when it isn't empty. Values are placeholders (`{0}`,
+//! `{prev}`), quoted strings, or words
+
+use crate::template;
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use regex::Regex;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Value(String),
+    Equal,
+    NotEqual,
+    Matches,
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Value(value) => write!(f, "'{}'", value),
+            Token::Equal => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            Token::Matches => write!(f, "=~"),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Not => write!(f, "!"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+}
+
+/// Split `condition` into tokens, replacing the placeholders with the value
+/// `resolve` gives them
+fn tokenize(condition: &str, resolve: impl Fn(&str) -> Option<String>) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = condition.trim_start();
+
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '=' if rest.starts_with("==") => (Token::Equal, 2),
+            '=' if rest.starts_with("=~") => (Token::Matches, 2),
+            '!' if rest.starts_with("!=") => (Token::NotEqual, 2),
+            '!' => (Token::Not, 1),
+            '&' if rest.starts_with("&&") => (Token::And, 2),
+            '|' if rest.starts_with("||") => (Token::Or, 2),
+            '\'' | '"' => {
+                let end = rest[1..]
+                    .find(c)
+                    .ok_or_else(|| anyhow!("unterminated string: {}", rest))?;
+                (Token::Value(rest[1..=end].to_owned()), end + 2)
+            },
+            '{' => {
+                let end = rest
+                    .find('}')
+                    .ok_or_else(|| anyhow!("unterminated placeholder: {}", rest))?;
+                let placeholder = &rest[..=end];
+                let value = resolve(placeholder)
+                    .ok_or_else(|| anyhow!("{} has no value to be replaced with", placeholder))?;
+                (Token::Value(value), end + 1)
+            },
+            _ => {
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || "()=!&|'\"{".contains(c))
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(anyhow!("unexpected: {}", rest));
+                }
+                (Token::Value(rest[..end].to_owned()), end)
+            },
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+
+    Ok(tokens)
+}
+
+/// Evaluates the tokens while reading them, from the lowest precedence (`||`)
+/// to the highest (`!`)
+#[derive(Debug)]
+struct Parser {
+    tokens:   Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<bool> {
+        let mut value = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let right = self.and()?;
+            value = value || right;
+        }
+        Ok(value)
+    }
+
+    fn and(&mut self) -> Result<bool> {
+        let mut value = self.not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            let right = self.not()?;
+            value = value && right;
+        }
+        Ok(value)
+    }
+
+    fn not(&mut self) -> Result<bool> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(!self.not()?)
+            },
+            Some(Token::Open) => {
+                self.next();
+                let value = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(value),
+                    Some(token) => Err(anyhow!("expected ), found {}", token)),
+                    None => Err(anyhow!("expected ) at the end")),
+                }
+            },
+            _ => self.comparison(),
+        }
+    }
+
+    fn comparison(&mut self) -> Result<bool> {
+        let left = self.value()?;
+        match self.peek() {
+            Some(Token::Equal) => {
+                self.next();
+                Ok(left == self.value()?)
+            },
+            Some(Token::NotEqual) => {
+                self.next();
+                Ok(left != self.value()?)
+            },
+            Some(Token::Matches) => {
+                self.next();
+                let pattern = self.value()?;
+                Ok(Regex::new(&pattern)
+                    .context(format!("invalid regular expression: {}", pattern))?
+                    .is_match(&left))
+            },
+            _ => Ok(!left.is_empty()),
+        }
+    }
+
+    fn value(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Value(value)) => Ok(value),
+            Some(token) => Err(anyhow!("expected a value, found {}", token)),
+            None => Err(anyhow!("expected a value at the end")),
+        }
+    }
+}
+
+fn parse(tokens: Vec<Token>) -> Result<bool> {
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+    };
+    let value = parser.or()?;
+    if let Some(token) = parser.peek() {
+        return Err(anyhow!("unexpected {}", token));
+    }
+
+    Ok(value)
+}
+
+/// Whether `condition` holds, with `{N}` replaced by `args[N]` and `{prev}`
+/// by `prev`
+pub(crate) fn evaluate<S: AsRef<str>>(
+    condition: &str,
+    args: &[S],
+    prev: Option<&str>,
+) -> Result<bool> {
+    parse(tokenize(condition, |placeholder| {
+        let value = template::expand(placeholder, args, prev);
+        (value != placeholder).then(|| value.into_owned())
+    })?)
+}
+
+/// Check the syntax of `condition`, whatever the values of its placeholders
+pub(crate) fn check(condition: &str) -> Result<()> {
+    parse(tokenize(condition, |_| Some(String::new()))?).map(|_| ())
+}
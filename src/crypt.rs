@@ -0,0 +1,234 @@
+//! Sections of the configuration encrypted with `age` or GPG, so that a
+//! configuration holding sensitive commands can be kept in a public dotfiles
+//! repository.
+//!
+//! Any mapping or item of the configuration can be replaced by a mapping with
+//! the single key [`KEY`], whose value is the armored encryption of the YAML
+//! of the section. It is decrypted in memory when the configuration is loaded,
+//! and the section is left out when it can't be (e.g., on a machine without
+//! the key)
+
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde_yaml::Value;
+use std::{
+    env,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Key of the mappings holding an encrypted section
+pub(crate) const KEY: &str = "$encrypted";
+
+/// Header of the armored files written by `age`
+const AGE_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+/// Header of the armored messages written by GPG
+const GPG_HEADER: &str = "-----BEGIN PGP MESSAGE-----";
+
+/// Who can decrypt a section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Scheme {
+    /// Recipients are `age` public keys, decrypted with an identity file
+    Age,
+    /// Recipients are GPG key IDs or emails, decrypted by the GPG agent
+    Gpg,
+}
+
+/// The identity `age` decrypts with: `$JAIME_AGE_IDENTITY`, or `age.key` next
+/// to the configuration
+pub(crate) fn identity(config_path: &Path) -> PathBuf {
+    env::var_os("JAIME_AGE_IDENTITY")
+        .map_or_else(|| config_path.with_file_name("age.key"), PathBuf::from)
+}
+
+/// Run `command` with `input` on its standard input, returning its output
+fn pipe(mut command: Command, input: &str) -> Result<String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context(format!("unable to run {}", program))?;
+    child
+        .stdin
+        .take()
+        .context("no input to write")?
+        .write_all(input.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Encrypt `text` for `recipients`, armored
+pub(crate) fn encrypt(text: &str, scheme: Scheme, recipients: &[&str]) -> Result<String> {
+    let mut command = match scheme {
+        Scheme::Age => {
+            let mut command = Command::new("age");
+            command.args(&["--encrypt", "--armor"]);
+            command
+        },
+        Scheme::Gpg => {
+            let mut command = Command::new("gpg");
+            command.args(&["--encrypt", "--armor", "--batch", "--yes"]);
+            command
+        },
+    };
+    for recipient in recipients {
+        command.arg("--recipient").arg(recipient);
+    }
+
+    pipe(command, text)
+}
+
+/// Decrypt an armored section, telling `age` from GPG by its header
+pub(crate) fn decrypt(armored: &str, identity: &Path) -> Result<String> {
+    let armored = armored.trim();
+    let command = if armored.starts_with(AGE_HEADER) {
+        if !identity.is_file() {
+            return Err(anyhow!("no age identity at {}", identity.display()));
+        }
+        let mut command = Command::new("age");
+        command.arg("--decrypt").arg("--identity").arg(identity);
+        command
+    } else if armored.starts_with(GPG_HEADER) {
+        let mut command = Command::new("gpg");
+        command.args(&["--decrypt", "--quiet", "--batch"]);
+        command
+    } else {
+        return Err(anyhow!("not an armored age or GPG message"));
+    };
+
+    pipe(command, &format!("{}\n", armored))
+}
+
+/// The armored text of `value` when it is an encrypted section
+pub(crate) fn armored(value: &Value) -> Option<&str> {
+    match value {
+        Value::Mapping(mapping) if mapping.len() == 1 =>
+            mapping.get(&Value::from(KEY)).and_then(Value::as_str),
+        _ => None,
+    }
+}
+
+/// Decrypt `value`, found at `path`, when it is an encrypted section, then the
+/// encrypted sections it has. Those that can't be decrypted are removed and
+/// described in `locked`. Returns whether an encrypted section was found, or
+/// why `value` itself can't be decrypted
+fn decrypt_value(
+    value: &mut Value,
+    identity: &Path,
+    path: &str,
+    locked: &mut Vec<String>,
+) -> Result<bool, String> {
+    let mut found = false;
+    if let Some(armored) = armored(value) {
+        let text = decrypt(armored, identity).map_err(|e| format!("{}: {}", path, e))?;
+        *value = serde_yaml::from_str(&text)
+            .map_err(|e| format!("{}: decrypted to invalid YAML: {}", path, e))?;
+        found = true;
+    }
+
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    let mut decrypt_or_remove =
+        |value: &mut Value, path: String| match decrypt_value(value, identity, &path, locked) {
+            Ok(f) => {
+                found |= f;
+                true
+            },
+            Err(reason) => {
+                locked.push(reason);
+                found = true;
+                false
+            },
+        };
+
+    match value {
+        Value::Mapping(mapping) => {
+            let mut removed = Vec::new();
+            for (key, value) in mapping.iter_mut() {
+                let name = key
+                    .as_str()
+                    .map_or_else(|| format!("{:?}", key), ToOwned::to_owned);
+                if !decrypt_or_remove(value, join(&name)) {
+                    removed.push(key.clone());
+                }
+            }
+            for key in removed {
+                mapping.remove(&key);
+            }
+        },
+        Value::Sequence(sequence) => {
+            let mut index = 0;
+            sequence.retain_mut(|value| {
+                let kept = decrypt_or_remove(value, join(&index.to_string()));
+                index += 1;
+                kept
+            });
+        },
+        _ => {},
+    }
+
+    Ok(found)
+}
+
+/// Decrypt the encrypted sections of the configuration `value` in place. The
+/// sections that can't be decrypted are left out, and described in `locked`
+/// along with the reason. Returns whether the configuration has encrypted
+/// sections
+pub(crate) fn decrypt_sections(
+    value: &mut Value,
+    identity: &Path,
+    locked: &mut Vec<String>,
+) -> Result<bool> {
+    decrypt_value(value, identity, "", locked)
+        .map_err(|reason| anyhow!("unable to decrypt the configuration: {}", reason))
+}
+
+/// The YAML of an encrypted section holding `text`, to paste in place of the
+/// section in the configuration. `text` has to be valid YAML
+pub(crate) fn encrypt_section(text: &str, scheme: Scheme, recipients: &[&str]) -> Result<String> {
+    serde_yaml::from_str::<Value>(text).context("the section to encrypt isn't valid YAML")?;
+    let armored = encrypt(text, scheme, recipients)?;
+
+    let indented = armored
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("  {}", line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!("{}: |\n{}\n", KEY, indented))
+}
+
+/// The YAML of a section, given as an encrypted section or as its armored text
+/// alone
+pub(crate) fn decrypt_section(text: &str, identity: &Path) -> Result<String> {
+    match serde_yaml::from_str::<Value>(text)
+        .ok()
+        .as_ref()
+        .and_then(armored)
+    {
+        Some(armored) => decrypt(armored, identity),
+        None => decrypt(text, identity),
+    }
+}
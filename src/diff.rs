@@ -0,0 +1,197 @@
+//! `jaime diff`: the differences between two configurations in terms of their
+//! menus rather than their lines: the actions added, removed, or moved to
+//! another path, and the fields changed in the others, so that an update of a
+//! shared configuration can be reviewed
+
+use crate::{
+    cache,
+    runner::{Action, Config, Options},
+    theme,
+};
+use anyhow::Result;
+use indexmap::IndexMap;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::{fmt::Write as _, path::Path};
+
+/// Fields whose old and new values are printed, instead of only their names
+const SHOWN: &[&str] = &["type", "description", "command", "path", "shell"];
+
+/// The actions of `options` and those below them, by their dotted path, in
+/// the order of the configuration
+fn flatten<'a>(options: &'a Options, parent: &str, found: &mut IndexMap<String, &'a Action>) {
+    for (key, action) in options {
+        let path = if parent.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", parent, key)
+        };
+        if let Action::Select { options, .. } = action {
+            found.insert(path.clone(), action);
+            flatten(options, &path, found);
+        } else {
+            found.insert(path, action);
+        }
+    }
+}
+
+/// Whether `path` is below the menu at `menu`
+fn is_below(path: &str, menu: &str) -> bool {
+    path.strip_prefix(menu)
+        .map_or(false, |rest| rest.starts_with('.'))
+}
+
+/// The fields of `value` as a mapping, without those in `left_out`
+fn fields(value: &impl Serialize, left_out: &[&str]) -> Mapping {
+    let mut fields = match serde_yaml::to_value(value) {
+        Ok(Value::Mapping(fields)) => fields,
+        _ => Mapping::new(),
+    };
+    for name in left_out {
+        fields.remove(&Value::from(*name));
+    }
+    fields
+}
+
+/// A value of a field on a single line
+fn inline(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::from("(none)"),
+        Some(Value::String(text)) => text.split_whitespace().collect::<Vec<_>>().join(" "),
+        Some(value) => serde_yaml::to_string(value)
+            .map(|text| text.trim_start_matches("---").trim().to_owned())
+            .unwrap_or_default(),
+    }
+}
+
+/// The lines describing how the fields of `old` changed in `new`
+fn changes(old: &Mapping, new: &Mapping) -> Vec<String> {
+    let theme = theme::get();
+    let mut names = old.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    names.extend(
+        new.iter()
+            .map(|(name, _)| name)
+            .filter(|n| !old.contains_key(n)),
+    );
+
+    let mut lines = Vec::new();
+    let mut others = Vec::new();
+    for name in names {
+        let (before, after) = (old.get(name), new.get(name));
+        if before == after {
+            continue;
+        }
+        let name = inline(Some(name));
+        if SHOWN.contains(&name.as_str()) {
+            lines.push(format!(
+                "    {}: {}\n    {}  {}",
+                name,
+                theme.error.paint(&format!("- {}", inline(before))),
+                " ".repeat(name.chars().count()),
+                theme.success.paint(&format!("+ {}", inline(after)))
+            ));
+        } else {
+            others.push(name);
+        }
+    }
+    if !others.is_empty() {
+        lines.push(format!("    also changed: {}", others.join(", ")));
+    }
+
+    lines
+}
+
+/// The differences from the menu of `old` to that of `new`, one per line,
+/// empty if there are none
+fn diff(old: &Config, new: &Config) -> Vec<String> {
+    let theme = theme::get();
+    let mut before = IndexMap::new();
+    let mut after = IndexMap::new();
+    flatten(&old.options, "", &mut before);
+    flatten(&new.options, "", &mut after);
+
+    // Only the outermost of the menus removed or added is reported
+    let outermost = |from: &IndexMap<String, &Action>, to: &IndexMap<String, &Action>| {
+        let mut found: Vec<String> = Vec::new();
+        for path in from.keys().filter(|path| !to.contains_key(*path)) {
+            if !found.iter().any(|menu| is_below(path, menu)) {
+                found.push(path.clone());
+            }
+        }
+        found
+    };
+    let mut removed = outermost(&before, &after);
+    let mut added = outermost(&after, &before);
+
+    // An action removed from a path and added as is at another was moved
+    let mut moved = Vec::new();
+    removed.retain(|from| {
+        let action = serde_yaml::to_value(before[from]).ok();
+        let to = added
+            .iter()
+            .position(|to| action.is_some() && serde_yaml::to_value(after[to]).ok() == action);
+        match to {
+            Some(index) => {
+                moved.push((from.clone(), added.remove(index)));
+                false
+            },
+            None => true,
+        }
+    });
+
+    let mut lines = Vec::new();
+    for (from, to) in moved {
+        lines.push(format!(
+            "{} {} (moved to {})",
+            theme.warning.paint("→"),
+            from,
+            to
+        ));
+    }
+    for path in removed {
+        lines.push(format!("{} {}", theme.error.paint("-"), path));
+    }
+    for path in added {
+        let action = after[&path];
+        let mut line = format!("{} {}", theme.success.paint("+"), path);
+        if let Some(description) = action.description() {
+            let _ = write!(line, "  {}", theme.description.paint(description));
+        }
+        lines.push(line);
+    }
+    for (path, action) in &before {
+        if let Some(other) = after.get(path) {
+            let changed = changes(&fields(action, &["options"]), &fields(other, &["options"]));
+            if !changed.is_empty() {
+                lines.push(format!("{} {}", theme.warning.paint("~"), path));
+                lines.extend(changed);
+            }
+        }
+    }
+
+    let changed = changes(&fields(old, &["options"]), &fields(new, &["options"]));
+    if !changed.is_empty() {
+        lines.push(format!("{} settings", theme.warning.paint("~")));
+        lines.extend(changed);
+    }
+
+    lines
+}
+
+/// Print the differences from the configuration at `old` to the one at `new`,
+/// or to `current` (the configuration in use, along with its overlays)
+pub(crate) fn run(old: &str, new: Option<&str>, current: &Config) -> Result<()> {
+    let old = cache::parse_file(Path::new(old))?;
+    let lines = match new {
+        Some(new) => diff(&old, &cache::parse_file(Path::new(new))?),
+        None => diff(&old, current),
+    };
+
+    if lines.is_empty() {
+        println!("No differences");
+    } else {
+        println!("{}", lines.join("\n"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,260 @@
+//! `jaime docs`: the menu tree written out as a Markdown document or a man
+//! page, so that the actions of a shared configuration can be read without
+//! running Jaime, as the runbook of a team
+
+use crate::{
+    doctor,
+    runner::{Action, Config, Map, Options, WidgetConfig},
+};
+use std::fmt::Write as _;
+
+/// Title of the document when the configuration has no description
+const TITLE: &str = "Jaime menu";
+
+/// What is documented of an action
+#[derive(Debug, Default)]
+struct Section<'a> {
+    /// Dotted path of the action
+    path:        String,
+    /// Number of menus above the action
+    depth:       usize,
+    kind:        &'static str,
+    description: Option<&'a str>,
+    /// Notes on how the action is shown or where it leads
+    notes:       Vec<String>,
+    /// Types (and names) of the widgets of a command
+    widgets:     Vec<String>,
+    /// Shell commands ran by the action, with their placeholders
+    commands:    Vec<&'a str>,
+    /// Binaries from `requires` and those guessed from the commands
+    binaries:    Vec<String>,
+}
+
+impl<'a> Section<'a> {
+    fn new(path: String, depth: usize, action: &'a Action) -> Self {
+        let meta = action.meta();
+        let mut section = Self {
+            path,
+            depth,
+            kind: action.kind(),
+            description: action.description(),
+            ..Self::default()
+        };
+
+        if meta.hidden {
+            section
+                .notes
+                .push(String::from("Hidden from the menus, ran with `jaime run`"));
+        }
+        if meta.disabled {
+            section.notes.push(format!(
+                "Disabled: {}",
+                meta.reason.as_deref().unwrap_or("no reason given")
+            ));
+        }
+        if !meta.allow_users.is_empty() || !meta.allow_groups.is_empty() {
+            let allowed = meta
+                .allow_users
+                .iter()
+                .cloned()
+                .chain(meta.allow_groups.iter().map(|g| format!("@{}", g)))
+                .collect::<Vec<_>>();
+            section
+                .notes
+                .push(format!("Restricted to {}", allowed.join(", ")));
+        }
+        if !meta.tags.is_empty() {
+            section
+                .notes
+                .push(format!("Tags: {}", meta.tags.join(", ")));
+        }
+
+        section.binaries.extend(meta.requires.iter().cloned());
+        section.action(action);
+        let mut seen = Vec::new();
+        section.binaries.retain(|binary| {
+            let new = !seen.contains(binary);
+            seen.push(binary.clone());
+            new
+        });
+
+        section
+    }
+
+    /// Gather the commands and widgets of `action`, which is a step of a chain
+    /// or the documented action itself
+    fn action(&mut self, action: &'a Action) {
+        match action {
+            Action::Command {
+                command,
+                widgets,
+                computed,
+                ..
+            } => {
+                for WidgetConfig {
+                    widget, name, map, ..
+                } in widgets.iter().flatten()
+                {
+                    self.widgets.push(match name {
+                        Some(name) => format!("{} ({})", widget.kind(), name),
+                        None => widget.kind().to_owned(),
+                    });
+                    if let Some(command) = widget.command() {
+                        self.binaries.extend(doctor::binaries(command));
+                    }
+                    if let Some(Map::Command(command)) = map {
+                        self.binaries.extend(doctor::binaries(command));
+                    }
+                }
+                for command in computed.iter().chain(Some(command)) {
+                    self.binaries.extend(doctor::binaries(command));
+                    self.commands.push(command);
+                }
+            },
+            Action::Chain { steps, .. } =>
+                for step in steps {
+                    self.action(step);
+                },
+            Action::Menu { path, .. } => self.notes.push(format!("Opens {}", path)),
+            Action::Systemd { user, .. } => {
+                self.notes.push(format!(
+                    "Starts, stops, restarts, or shows the status of a {} systemd unit",
+                    if *user { "user" } else { "system" }
+                ));
+                self.binaries.push(String::from("systemctl"));
+            },
+            Action::Select { .. } => {},
+        }
+    }
+}
+
+/// The sections of `options` and the actions below them, in the order of the
+/// configuration
+fn sections<'a>(options: &'a Options, parent: &str, depth: usize, found: &mut Vec<Section<'a>>) {
+    for (key, action) in options {
+        let path = if parent.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", parent, key)
+        };
+        found.push(Section::new(path.clone(), depth, action));
+        if let Action::Select { options, .. } = action {
+            sections(options, &path, depth + 1, found);
+        }
+    }
+}
+
+fn markdown(config: &Config, sections: &[Section<'_>]) -> String {
+    let mut text = format!("# {}\n", config.description.as_deref().unwrap_or(TITLE));
+
+    for section in sections {
+        let _ = write!(
+            text,
+            "\n{} `{}`\n\n*{}*",
+            "#".repeat((section.depth + 2).min(6)),
+            section.path,
+            section.kind
+        );
+        if let Some(description) = section.description {
+            let _ = write!(text, ": {}", description);
+        }
+        text.push('\n');
+
+        if !section.notes.is_empty() {
+            text.push('\n');
+            for note in &section.notes {
+                let _ = writeln!(text, "- {}", note);
+            }
+        }
+        if !section.widgets.is_empty() {
+            let _ = writeln!(text, "\nWidgets: {}", section.widgets.join(", "));
+        }
+        for command in &section.commands {
+            let _ = writeln!(text, "\n```sh\n{}\n```", command.trim_end());
+        }
+        if !section.binaries.is_empty() {
+            let binaries = section
+                .binaries
+                .iter()
+                .map(|binary| format!("`{}`", binary))
+                .collect::<Vec<_>>();
+            let _ = writeln!(text, "\nRequires: {}", binaries.join(", "));
+        }
+    }
+
+    text
+}
+
+/// `text` escaped for roff, so that its backslashes and the dots or quotes
+/// starting its lines aren't taken as requests
+fn roff(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .lines()
+        .map(|line| {
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{}", line)
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn man(config: &Config, sections: &[Section<'_>]) -> String {
+    let mut text = format!(
+        ".TH JAIME-MENU 7 \"\" \"jaime {}\" \"Jaime menu\"\n.SH NAME\njaime-menu \\- {}\n.SH \
+         ACTIONS\n",
+        env!("CARGO_PKG_VERSION"),
+        roff(config.description.as_deref().unwrap_or(TITLE))
+    );
+
+    for section in sections {
+        let _ = write!(
+            text,
+            ".SS {}\n\\fI{}\\fR",
+            roff(&section.path),
+            section.kind
+        );
+        if let Some(description) = section.description {
+            let _ = write!(text, ": {}", roff(description));
+        }
+        text.push('\n');
+
+        for note in &section.notes {
+            let _ = writeln!(text, ".br\n{}", roff(note));
+        }
+        if !section.widgets.is_empty() {
+            let _ = writeln!(text, ".PP\nWidgets: {}", roff(&section.widgets.join(", ")));
+        }
+        for command in &section.commands {
+            let _ = writeln!(
+                text,
+                ".PP\n.RS 4\n.nf\n{}\n.fi\n.RE",
+                roff(command.trim_end())
+            );
+        }
+        if !section.binaries.is_empty() {
+            let _ = writeln!(
+                text,
+                ".PP\nRequires: \\fB{}\\fR",
+                roff(&section.binaries.join(", "))
+            );
+        }
+    }
+
+    text
+}
+
+/// The document of the menu of `config`, as a man page if `as_man`, and as
+/// Markdown otherwise
+pub(crate) fn render(config: &Config, as_man: bool) -> String {
+    let mut found = Vec::new();
+    sections(&config.options, "", 0, &mut found);
+
+    if as_man {
+        man(config, &found)
+    } else {
+        markdown(config, &found)
+    }
+}
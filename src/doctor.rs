@@ -0,0 +1,498 @@
+//! `jaime doctor`: check that the binaries the configuration relies on are
+//! installed and that its menus are valid, before they fail when used. The
+//! same checks are made on every start, as warnings or, in strict mode, errors
+
+use crate::{
+    audit::Audit,
+    condition, embed,
+    graph::Graph,
+    listing, palette,
+    policy::Policy,
+    runner::{Action, Config, Map, OnFailure, Widget, WidgetConfig, CANCEL_KEY, FZF_BIN, SKIM_BIN},
+    template,
+    theme::{self, Theme},
+};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::{
+    collections::BTreeMap,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Words starting a command that are handled by the shell itself
+const SHELL_WORDS: &[&str] = &[
+    ".", ":", "[", "[[", "alias", "break", "builtin", "case", "cd", "continue", "declare", "do",
+    "done", "echo", "elif", "else", "esac", "eval", "exit", "export", "false", "fi", "for",
+    "function", "if", "in", "let", "local", "print", "printf", "pwd", "read", "readonly", "return",
+    "set", "shift", "source", "test", "then", "trap", "true", "type", "typeset", "unset", "until",
+    "wait", "while",
+];
+
+/// Commands that run the command given as their arguments
+const WRAPPERS: &[&str] = &[
+    "command", "env", "exec", "nice", "nohup", "sudo", "time", "xargs",
+];
+
+/// Placeholders replaced by the items selected with widgets, or by the output
+/// of a previous step, along with those blocks depend on (`{{#if 0}}`)
+static PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{(?:\{#(?:if|each) )?(\d+|prev|query|key)\}").unwrap());
+
+/// The named placeholders of the commands and the computed values, which the
+/// widgets don't have
+const NAMED: &[&str] = &["query", "key"];
+
+/// How long `<binary> --version` may take
+const VERSION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Whether `word` looks like the name or path of a binary, rather than a
+/// placeholder, a variable, or part of a quoted string
+fn is_binary_name(word: &str) -> bool {
+    !word.is_empty()
+        && !SHELL_WORDS.contains(&word)
+        && word
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | '+'))
+}
+
+/// The parts of a shell command between the operators separating pipeline
+/// stages, list elements, and substitutions, ignoring the ones that are quoted
+fn segments(command: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (index, c) in command.char_indices() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('"') | None, '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|' | ';' | '&' | '\n' | '(' | ')' | '`') => {
+                segments.push(&command[start..index]);
+                start = index + c.len_utf8();
+            },
+            _ => {},
+        }
+    }
+    segments.push(&command[start..]);
+
+    segments
+}
+
+/// The binaries a shell command starts. This is a heuristic: the first word of
+/// every pipeline stage, list element, and substitution is taken, skipping
+/// variable assignments and wrappers such as `sudo`
+pub(crate) fn binaries(command: &str) -> Vec<String> {
+    segments(command)
+        .into_iter()
+        .filter_map(|segment| {
+            let words = shlex::split(segment)
+                .unwrap_or_else(|| segment.split_whitespace().map(ToOwned::to_owned).collect());
+
+            words.into_iter().find(|word| {
+                !(word.contains('=') || word.starts_with('-') || WRAPPERS.contains(&word.as_str()))
+            })
+        })
+        .filter(|word| is_binary_name(word))
+        .collect()
+}
+
+/// What was found while walking the configuration
+#[derive(Debug, Default)]
+struct Report {
+    /// Binaries, along with the paths of the actions using them
+    binaries:      BTreeMap<String, Vec<String>>,
+    /// Invalid parts of the configuration
+    problems:      Vec<String>,
+    /// Leave out the actions that are disabled, on purpose or because a
+    /// binary they require is missing
+    skip_disabled: bool,
+}
+
+/// Whether running `action` as a step of a chain captures its output
+fn captures(action: &Action) -> bool {
+    match action {
+        Action::Command { capture, .. } => *capture,
+        Action::Chain { steps, .. } => steps.iter().any(captures),
+        _ => false,
+    }
+}
+
+impl Report {
+    fn uses(&mut self, path: &str, binaries: impl IntoIterator<Item = String>) {
+        for binary in binaries {
+            let users = self.binaries.entry(binary).or_default();
+            if !users.iter().any(|p| p == path) {
+                users.push(path.to_owned());
+            }
+        }
+    }
+
+    /// Check that the placeholders of `text` have a value, when `selected`
+    /// tells whether the widget at an index is displayed before it, `prev`
+    /// whether a previous step captured its output, and `named` the named
+    /// placeholders it has. Its blocks are checked too
+    fn placeholders(
+        &mut self,
+        path: &str,
+        text: &str,
+        selected: impl Fn(usize) -> bool,
+        prev: bool,
+        named: &[&str],
+    ) {
+        if let Err(e) = template::check(text) {
+            self.problems
+                .push(format!("{}: invalid block: {}", path, e));
+        }
+        for placeholder in PLACEHOLDER.captures_iter(text) {
+            let name = &placeholder[1];
+            let resolved = match name.parse::<usize>() {
+                Ok(index) => selected(index),
+                Err(_) if name == "prev" => prev,
+                Err(_) => named.contains(&name),
+            };
+            if !resolved {
+                self.problems.push(format!(
+                    "{}: {{{}}} has no value to be replaced with",
+                    path, name
+                ));
+            }
+        }
+    }
+
+    /// Check a widget, displayed after the widgets at the indices of `before`
+    fn widget(
+        &mut self,
+        path: &str,
+        before: &[usize],
+        WidgetConfig {
+            widget, map, when, ..
+        }: &WidgetConfig,
+        prev: bool,
+    ) {
+        match widget.displayed() {
+            Widget::FromCommand {
+                command,
+                preview,
+                preview_script,
+                ..
+            } => {
+                self.uses(path, binaries(command));
+                self.placeholders(path, command, |index| before.contains(&index), prev, &[]);
+                if preview.is_some() && preview_script.is_some() {
+                    self.problems.push(format!(
+                        "{}: preview is ignored, as preview_script is given",
+                        path
+                    ));
+                } else if let Some(preview) = preview {
+                    self.uses(path, binaries(preview));
+                    // `{N}` are the fields of the item there, replaced by the
+                    // selector
+                    self.placeholders(path, preview, |_| true, prev, &[]);
+                }
+            },
+            Widget::JsonPick { command, .. } | Widget::FlagPicker { command } =>
+                self.uses(path, binaries(command)),
+            Widget::SystemdUnit { .. } => self.uses(path, vec![String::from("systemctl")]),
+            #[cfg(not(feature = "docker"))]
+            Widget::DockerContainer { .. } | Widget::DockerImage | Widget::DockerVolume =>
+                self.uses(path, vec![String::from("docker")]),
+            // Only a program building its menu with the library registers them
+            Widget::Source { source } if !embed::has_source(source) => self.problems.push(format!(
+                "{}: no item source is registered as {}",
+                path, source
+            )),
+            _ => {},
+        }
+
+        if let Some(Map::Command(command)) = map {
+            self.uses(path, binaries(command));
+        }
+        if let Some(when) = when {
+            self.placeholders(path, when, |index| before.contains(&index), prev, &[]);
+            if let Err(e) = condition::check(when) {
+                self.problems
+                    .push(format!("{}: invalid condition `{}`: {}", path, when, e));
+            }
+        }
+    }
+
+    /// Walk `action`, where `prev` tells whether a previous step of a chain
+    /// captured its output
+    fn action(&mut self, config: &Config, path: &str, action: &Action, prev: bool) {
+        if self.skip_disabled && action.meta().disabled_reason().is_some() {
+            return;
+        }
+        self.uses(path, action.meta().requires.iter().cloned());
+        if let OnFailure::Run(target) = &action.meta().on_failure {
+            if let Err(e) = config.find(target) {
+                self.problems.push(format!("{}: on_failure: {}", path, e));
+            }
+        }
+
+        match action {
+            Action::Command {
+                command,
+                widgets,
+                computed,
+                ..
+            } => {
+                self.uses(path, binaries(command));
+                let widgets = widgets.as_deref().unwrap_or_default();
+                self.placeholders(
+                    path,
+                    command,
+                    |index| index < widgets.len() + computed.len(),
+                    prev,
+                    NAMED,
+                );
+                for (position, computed) in computed.iter().enumerate() {
+                    self.uses(path, binaries(computed));
+                    self.placeholders(
+                        path,
+                        computed,
+                        |index| index < widgets.len() + position,
+                        prev,
+                        NAMED,
+                    );
+                }
+                match Graph::new(widgets) {
+                    Ok(graph) =>
+                        for (position, &index) in graph.order().iter().enumerate() {
+                            let before = &graph.order()[..position];
+                            self.widget(path, before, &widgets[index], prev);
+                        },
+                    Err(e) => self.problems.push(format!("{}: {}", path, e)),
+                }
+            },
+            Action::Select { options, .. } => {
+                if options.is_empty() {
+                    self.problems
+                        .push(format!("{}: the menu has no options", path));
+                }
+                for (key, action) in options {
+                    let path = if path.is_empty() {
+                        key.to_string()
+                    } else {
+                        format!("{}.{}", path, key)
+                    };
+                    self.action(config, &path, action, prev);
+                }
+            },
+            Action::Chain { steps, .. } => {
+                let mut prev = prev;
+                for step in steps {
+                    self.action(config, path, step, prev);
+                    prev = prev || captures(step);
+                }
+            },
+            Action::Menu { path: target, .. } =>
+                if let Err(e) = config.find(target) {
+                    self.problems.push(format!("{}: {}", path, e));
+                },
+            Action::Systemd { .. } => self.uses(path, vec![String::from("systemctl")]),
+        }
+    }
+}
+
+/// The first line printed by `<path> --version`, if it prints one in time
+fn version(path: &str) -> Option<String> {
+    let mut child = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+
+    let start = Instant::now();
+    while child.try_wait().ok()?.is_none() {
+        if start.elapsed() > VERSION_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return None;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let output = child.wait_with_output().ok()?;
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+
+    String::from_utf8_lossy(&text)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(ToOwned::to_owned)
+}
+
+/// Print whether `binary` is installed, returning whether it is
+fn check(binary: &str, users: &str) -> bool {
+    let theme = theme::get();
+    if let Ok(path) = which::which(binary) {
+        let path = path.display().to_string();
+        println!(
+            "  {} {}  {}  {}",
+            theme.success.paint(theme.icons.ok()),
+            theme.success.paint(binary),
+            theme.dimmed.paint(&version(&path).unwrap_or_default()),
+            theme.dimmed.paint(users)
+        );
+        true
+    } else {
+        println!(
+            "  {} {}  {}  {}",
+            theme.error.paint(theme.icons.failed()),
+            theme.error.paint(binary),
+            theme.error.paint("missing"),
+            theme.dimmed.paint(users)
+        );
+        false
+    }
+}
+
+fn report(config: &Config, skip_disabled: bool) -> Report {
+    let mut report = Report {
+        skip_disabled,
+        ..Report::default()
+    };
+    for (key, action) in &config.options {
+        report.action(config, key, action, false);
+    }
+    if let Some(fallback) = &config.fallback {
+        report.action(config, "fallback", fallback, true);
+    }
+    if config.options.is_empty() {
+        report
+            .problems
+            .push(String::from("the menu has no options"));
+    }
+    if let Some(Err(e)) = config.audit.as_ref().map(Audit::rules) {
+        report.problems.push(e.to_string());
+    }
+    if let Some(Err(e)) = config.policy.as_ref().map(Policy::validate) {
+        report.problems.push(e.to_string());
+    }
+    if config.login_shell && config.shell_args.is_some() {
+        report.problems.push(String::from(
+            "login_shell is ignored, as shell_args is given",
+        ));
+    }
+    for key in &config.keys {
+        if [CANCEL_KEY, palette::KEY, listing::KEY].contains(&key.as_str()) {
+            report
+                .problems
+                .push(format!("keys: {} is already used by Jaime", key));
+        }
+    }
+    if let Err(e) = Theme::new(&config.theme) {
+        report.problems.push(e.to_string());
+    }
+    report
+}
+
+/// The problems of the configuration itself: fields that aren't known,
+/// placeholders without a value, empty menus… Disabled actions aren't checked.
+/// They are memoized with the configuration
+pub(crate) fn problems(config: &Config, unknown_fields: &[String]) -> Vec<String> {
+    let mut problems = unknown_fields
+        .iter()
+        .map(|field| format!("{}: unknown field", field))
+        .collect::<Vec<_>>();
+    problems.extend(report(config, true).problems);
+
+    problems
+}
+
+/// The binaries used by the configuration, its shell included, which aren't
+/// installed. Disabled actions aren't checked. Looking them up in `PATH` takes
+/// a while, so it is only done when the problems are errors
+pub(crate) fn missing(config: &Config) -> Vec<String> {
+    let report = report(config, true);
+    let shell = config.shell();
+    let binaries = report
+        .binaries
+        .iter()
+        .map(|(binary, users)| (binary, users.join(", ")));
+
+    std::iter::once((&shell, String::from("shell")))
+        .chain(binaries)
+        .filter(|(binary, _)| which::which(binary).is_err())
+        .map(|(binary, users)| format!("{}: {} is not installed", users, binary))
+        .collect()
+}
+
+/// Check the configuration, printing a report. An error is returned if
+/// anything is missing or invalid
+pub(crate) fn run(config: &Config, unknown_fields: &[String], locked: &[String]) -> Result<()> {
+    let mut report = report(config, false);
+    report.problems.splice(
+        0..0,
+        unknown_fields
+            .iter()
+            .map(|field| format!("{}: unknown field", field)),
+    );
+
+    let mut failures = 0;
+    let theme = theme::get();
+
+    println!("{}", theme.info.paint("Selectors"));
+    println!(
+        "  {} skim (embedded)",
+        theme.success.paint(theme.icons.ok())
+    );
+    // The binaries are only needed with `--fzf` or `--skim-binary`
+    check(FZF_BIN, "used with --fzf");
+    check(SKIM_BIN, "used with --skim-binary");
+
+    println!("{}", theme.info.paint("Shell"));
+    let shell = config.shell();
+    if !check(&shell, "") {
+        failures += 1;
+    }
+
+    println!("{}", theme.info.paint("Binaries"));
+    if report.binaries.is_empty() {
+        println!("  {}", theme.dimmed.paint("none"));
+    }
+    for (binary, users) in &report.binaries {
+        if !check(binary, &users.join(", ")) {
+            failures += 1;
+        }
+    }
+
+    // Expected on a machine without the key, so not a failure
+    if !locked.is_empty() {
+        println!("{}", theme.info.paint("Encrypted sections left out"));
+        for section in locked {
+            println!(
+                "  {} {}",
+                theme.dimmed.paint("-"),
+                theme.dimmed.paint(section)
+            );
+        }
+    }
+
+    if !report.problems.is_empty() {
+        println!("{}", theme.info.paint("Problems"));
+        for problem in &report.problems {
+            println!("  {} {}", theme.error.paint(theme.icons.failed()), problem);
+        }
+    }
+    failures += report.problems.len();
+
+    if failures == 0 {
+        println!("{}", theme.success.paint("Everything looks good"));
+        Ok(())
+    } else {
+        Err(anyhow!("{} problem(s) found", failures))
+    }
+}
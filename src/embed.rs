@@ -0,0 +1,330 @@
+//! Menus assembled in Rust instead of being read from the configuration, for
+//! programs using Jaime as their menu engine. The builders write the fields of
+//! the YAML configuration, so that a menu built in code behaves like one that
+//! is read, and add widgets listing items computed by the program itself
+
+use crate::{
+    app::Handler,
+    cache_directory, config_path, error, exec, palette,
+    runner::{Config, Context, Outcome},
+    source::{Item, ItemSource, SourceContext},
+    theme,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+};
+
+/// A source of items shared with the widgets listing them
+type Shared = Arc<dyn ItemSource + Send + Sync>;
+
+/// A source of items, along with the name it is registered as
+#[derive(Clone)]
+struct Source {
+    name:   String,
+    source: Shared,
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Source")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The sources of the menus that ran, by name
+static SOURCES: Lazy<Mutex<HashMap<String, Shared>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Number of sources created, making up their names
+static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+/// The items listed by the source registered as `name`
+pub(crate) fn items(name: &str, context: &SourceContext<'_>) -> Result<Vec<Item>> {
+    let source = SOURCES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("no item source is registered as {}", name))?;
+
+    source.items(context)
+}
+
+/// Whether a source of items is registered as `name`
+pub(crate) fn has_source(name: &str) -> bool {
+    SOURCES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains_key(name)
+}
+
+/// An action of a menu, built with the fields of the configuration
+#[derive(Debug, Clone)]
+pub struct ActionBuilder {
+    fields:  Mapping,
+    widgets: Vec<Value>,
+    sources: Vec<Source>,
+}
+
+impl ActionBuilder {
+    /// A `Command` action running `command`, whose placeholders are replaced
+    /// by the values of the widgets added to it
+    #[must_use]
+    pub fn command(command: impl Into<String>) -> Self {
+        let mut fields = Mapping::new();
+        fields.insert("type".into(), "Command".into());
+        fields.insert("command".into(), command.into().into());
+
+        Self {
+            fields,
+            widgets: Vec::new(),
+            sources: Vec::new(),
+        }
+    }
+
+    /// Shown next to the key of the action in the menu
+    #[must_use]
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.fields
+            .insert("description".into(), description.into().into());
+        self
+    }
+
+    /// Set any other field of the action, as in the configuration (e.g.,
+    /// `timeout` or `output`)
+    ///
+    /// # Errors
+    /// If `value` can't be written as YAML
+    pub fn field(mut self, name: &str, value: impl Serialize) -> Result<Self> {
+        self.fields
+            .insert(name.into(), serde_yaml::to_value(value)?);
+        Ok(self)
+    }
+
+    /// Add a widget reading a line of text
+    #[must_use]
+    pub fn free_text(self, name: &str) -> Self {
+        self.widget(name, "FreeText", Mapping::new())
+    }
+
+    /// Add a widget listing the lines printed by a shell `command`
+    #[must_use]
+    pub fn from_command(self, name: &str, command: impl Into<String>) -> Self {
+        let mut fields = Mapping::new();
+        fields.insert("command".into(), command.into().into());
+        self.widget(name, "FromCommand", fields)
+    }
+
+    /// Add a widget listing the items returned by `function`, which is given
+    /// the values of the widgets displayed before it
+    #[must_use]
+    pub fn items<F>(self, name: &str, function: F) -> Self
+    where
+        F: Fn(&[String]) -> Result<Vec<String>> + Send + Sync + 'static,
+    {
+        self.source(name, function)
+    }
+
+    /// Add a widget listing the items of `source`
+    #[must_use]
+    pub fn source<S>(mut self, name: &str, source: S) -> Self
+    where
+        S: ItemSource + Send + Sync + 'static,
+    {
+        let registered = format!("{}-{}", name, CREATED.fetch_add(1, Ordering::Relaxed));
+        let mut fields = Mapping::new();
+        fields.insert("source".into(), registered.clone().into());
+        self.sources.push(Source {
+            name:   registered,
+            source: Arc::new(source),
+        });
+        self.widget(name, "Source", fields)
+    }
+
+    fn widget(mut self, name: &str, kind: &str, mut fields: Mapping) -> Self {
+        fields.insert("type".into(), kind.into());
+        fields.insert("name".into(), name.into());
+        self.widgets.push(Value::Mapping(fields));
+        self
+    }
+
+    /// The action as in the configuration, along with its sources
+    fn into_value(mut self) -> (Value, Vec<Source>) {
+        if !self.widgets.is_empty() {
+            self.fields
+                .insert("widgets".into(), Value::Sequence(self.widgets));
+        }
+        (Value::Mapping(self.fields), self.sources)
+    }
+}
+
+/// A menu of actions and of other menus
+#[derive(Debug, Clone, Default)]
+pub struct MenuBuilder {
+    description: Option<String>,
+    options:     Mapping,
+    sources:     Vec<Source>,
+}
+
+impl MenuBuilder {
+    /// An empty menu
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Shown next to the key of the menu in its parent menu, or at the top of
+    /// the root menu
+    #[must_use]
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Add `action` as the option `key`
+    #[must_use]
+    pub fn action(mut self, key: &str, action: ActionBuilder) -> Self {
+        let (value, sources) = action.into_value();
+        self.options.insert(key.into(), value);
+        self.sources.extend(sources);
+        self
+    }
+
+    /// Add `menu` as the option `key`, opening it
+    #[must_use]
+    pub fn menu(mut self, key: &str, menu: MenuBuilder) -> Self {
+        let mut fields = Mapping::new();
+        fields.insert("type".into(), "Select".into());
+        if let Some(description) = menu.description {
+            fields.insert("description".into(), description.into());
+        }
+        fields.insert("options".into(), Value::Mapping(menu.options));
+
+        self.options.insert(key.into(), Value::Mapping(fields));
+        self.sources.extend(menu.sources);
+        self
+    }
+
+    /// The menu, ready to be ran
+    ///
+    /// # Errors
+    /// If the menu has no options, or an action an invalid field
+    pub fn build(self) -> Result<Menu> {
+        if self.options.is_empty() {
+            return Err(anyhow!("the menu has no options"));
+        }
+        let mut fields = Mapping::new();
+        if let Some(description) = self.description {
+            fields.insert("description".into(), description.into());
+        }
+        fields.insert("options".into(), Value::Mapping(self.options));
+
+        Ok(Menu {
+            config:  serde_yaml::from_value(Value::Mapping(fields)).context("invalid menu")?,
+            sources: self.sources,
+        })
+    }
+}
+
+/// A menu built with a [`MenuBuilder`]
+#[derive(Debug, Clone)]
+pub struct Menu {
+    config:  Config,
+    sources: Vec<Source>,
+}
+
+/// Runs menus the way the `jaime` binary does, with the same history and
+/// cache directories
+#[derive(Debug, Clone)]
+pub struct Runner {
+    handler:         Handler,
+    config_path:     PathBuf,
+    cache_directory: PathBuf,
+}
+
+impl Runner {
+    /// A runner taking the options of the command line in `args` (e.g.,
+    /// `--fzf` or `--plain`)
+    ///
+    /// # Errors
+    /// If an option isn't known, or the cache directory can't be created
+    pub fn new(args: &[&str]) -> Result<Self> {
+        let handler = Handler::from_args(args)?;
+        if handler.plain() {
+            colored::control::set_override(false);
+        }
+        let cache_directory = cache_directory()?;
+        fs::create_dir_all(&cache_directory)
+            .context(format!("unable to create: {}", cache_directory.display()))?;
+
+        Ok(Self {
+            handler,
+            config_path: config_path()?,
+            cache_directory,
+        })
+    }
+
+    /// Display `menu` and run the action selected. Returns `false` if a
+    /// selection was cancelled before anything ran
+    ///
+    /// # Errors
+    /// If a command or a selector fails
+    pub fn run(&self, menu: &Menu) -> Result<bool> {
+        SOURCES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(
+                menu.sources
+                    .iter()
+                    .map(|s| (s.name.clone(), Arc::clone(&s.source))),
+            );
+        if let Ok(theme) = theme::Theme::new(&menu.config.theme) {
+            theme::set(theme);
+        }
+        exec::set_startup(menu.config.startup());
+
+        let context = Context {
+            config_path:     self.config_path.clone(),
+            cache_directory: self.cache_directory.clone(),
+            skim:            menu.config.skim.clone(),
+            keys:            menu.config.keys.clone(),
+            explain:         false,
+        };
+        let ran = menu.config.run(&context, &self.handler);
+        // The operations of the palette starting jaime over only leave the
+        // selection here: the program embedding it isn't replaced
+        palette::take_restart();
+
+        match ran {
+            Ok(Outcome::Cancelled) => Ok(false),
+            Ok(_) => Ok(true),
+            Err(e) if error::is_cancelled(&e) || error::is_aborted(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// [`Runner::run`] without blocking the runtime of the program: the menu
+    /// runs on the blocking threads of Tokio, since the selectors and the
+    /// commands take over the terminal until they are done
+    ///
+    /// # Errors
+    /// If a command or a selector fails, or the thread running the menu
+    /// panics
+    #[cfg(feature = "async")]
+    pub async fn run_async(&self, menu: &Menu) -> Result<bool> {
+        let (runner, menu) = (self.clone(), menu.clone());
+        tokio::task::spawn_blocking(move || runner.run(&menu))
+            .await
+            .context("the menu didn't run to completion")?
+    }
+}
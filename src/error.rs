@@ -0,0 +1,80 @@
+//! Failures that callers tell apart. They are carried by [`anyhow::Error`]
+//! like the others, with the context added along the way, and are found back
+//! with `downcast_ref::<JaimeError>()`
+
+use std::{io, time::Duration};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub(crate) enum JaimeError {
+    /// The configuration file isn't valid YAML, or doesn't describe a menu
+    #[error("invalid config: {0}")]
+    ConfigParse(#[from] serde_yaml::Error),
+    /// A command exited with a non-zero status. A `strict` shell may have
+    /// stopped it at a failure, or on a variable that isn't set
+    #[error("{path} failed with exit status {status}{}", strict_hint(*.strict))]
+    CommandFailed {
+        path:   String,
+        status: i32,
+        strict: bool,
+    },
+    /// A selection was cancelled before the command could run
+    #[error("the selection was cancelled")]
+    WidgetCancelled,
+    /// The selector was aborted (e.g., with `ctrl-c`), leaving Jaime
+    #[error("the selection was aborted")]
+    SelectorAborted,
+    /// A command ran longer than its `timeout`, and was killed
+    #[error("{path} timed out after {}s and was killed", .after.as_secs())]
+    TimedOut { path: String, after: Duration },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Points at `strict_shell` when the shell of a command that failed was strict,
+/// as it may not have been meant to be
+fn strict_hint(strict: bool) -> &'static str {
+    if strict {
+        " (the shell stops at the first command failing and on variables that aren't set, which \
+         `strict_shell: false` turns off)"
+    } else {
+        ""
+    }
+}
+
+/// Whether `err` comes from a cancelled selection
+pub(crate) fn is_cancelled(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<JaimeError>(),
+        Some(JaimeError::WidgetCancelled)
+    )
+}
+
+/// Whether `err` comes from an aborted selector
+pub(crate) fn is_aborted(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<JaimeError>(),
+        Some(JaimeError::SelectorAborted)
+    )
+}
+
+/// Whether `err` comes from a command that failed or timed out
+pub(crate) fn is_failure(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<JaimeError>(),
+        Some(JaimeError::CommandFailed { .. } | JaimeError::TimedOut { .. })
+    )
+}
+
+impl JaimeError {
+    /// The status Jaime exits with because of this error
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            Self::CommandFailed { status, .. } => *status,
+            Self::WidgetCancelled | Self::SelectorAborted => 130,
+            // Mirror `timeout(1)` so scripts can tell a timeout apart
+            Self::TimedOut { .. } => 124,
+            Self::ConfigParse(_) | Self::Io(_) => 1,
+        }
+    }
+}
@@ -0,0 +1,802 @@
+use crate::{error::JaimeError, frontend, preview, runner::Context, sandbox, theme};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use signal_hook::{consts::SIGINT, SigId};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, Command, ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often a command with a timeout is checked on
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time given to a process group to exit after `SIGTERM` before it is killed
+const KILL_GRACE: Duration = Duration::from_secs(2);
+
+/// Whether `ctrl-c` exits, as it does when nothing catches it. The handler of
+/// signal-hook stays installed once a signal is registered, so the default
+/// action is ran by it rather than restored
+static SIGINT_DEFAULT: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(true)));
+
+/// The handler running the default action of `SIGINT`, registered once
+static SIGINT_FALLBACK: OnceCell<SigId> = OnceCell::new();
+
+/// Sets a flag when `ctrl-c` is pressed, instead of exiting, until it is
+/// dropped
+#[derive(Debug)]
+pub(crate) struct Interrupt {
+    pressed: Arc<AtomicBool>,
+    id:      SigId,
+}
+
+impl Interrupt {
+    pub(crate) fn catch() -> Result<Self> {
+        SIGINT_FALLBACK.get_or_try_init(|| {
+            signal_hook::flag::register_conditional_default(SIGINT, Arc::clone(&SIGINT_DEFAULT))
+        })?;
+        let pressed = Arc::new(AtomicBool::new(false));
+        let id = signal_hook::flag::register(SIGINT, Arc::clone(&pressed))?;
+        SIGINT_DEFAULT.store(false, Ordering::SeqCst);
+        Ok(Self { pressed, id })
+    }
+
+    /// Whether `ctrl-c` was pressed since it was last taken
+    pub(crate) fn take(&self) -> bool {
+        self.pressed.swap(false, Ordering::Relaxed)
+    }
+
+    /// Whether `ctrl-c` was pressed
+    pub(crate) fn pressed(&self) -> bool {
+        self.pressed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Interrupt {
+    fn drop(&mut self) {
+        signal_hook::low_level::unregister(self.id);
+        SIGINT_DEFAULT.store(true, Ordering::SeqCst);
+    }
+}
+
+/// The terminal handed to the process group of a command with a timeout, so
+/// that the command can read from it and `ctrl-c` reaches it. Jaime gets it
+/// back when it is dropped
+#[cfg(unix)]
+#[derive(Debug)]
+struct Foreground {
+    previous: nix::unistd::Pid,
+    /// What `SIGTTOU` did before, Jaime ignoring it while in the background
+    ttou:     nix::sys::signal::SigAction,
+}
+
+#[cfg(unix)]
+impl Foreground {
+    /// Whether the standard input is a terminal that can be handed over
+    fn available() -> bool {
+        use std::os::unix::io::AsRawFd;
+
+        nix::unistd::isatty(io::stdin().as_raw_fd()).unwrap_or(false)
+    }
+
+    /// Give the terminal to the process group led by `child`
+    #[allow(unsafe_code)]
+    fn give(child: &Child) -> Result<Self> {
+        use nix::{
+            sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
+            unistd::{tcgetpgrp, tcsetpgrp, Pid},
+        };
+        use std::{convert::TryFrom, os::unix::io::AsRawFd};
+
+        let stdin = io::stdin().as_raw_fd();
+        let previous = tcgetpgrp(stdin)?;
+        let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+        // SAFETY: ignoring a signal installs no handler. Otherwise, writing to
+        // the terminal and taking it back would stop Jaime
+        let ttou = unsafe { sigaction(Signal::SIGTTOU, &ignore) }?;
+        // Restores the above if the terminal can't be handed over
+        let foreground = Self { previous, ttou };
+        tcsetpgrp(stdin, Pid::from_raw(i32::try_from(child.id())?))?;
+
+        Ok(foreground)
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Foreground {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        use nix::{
+            sys::signal::{sigaction, Signal},
+            unistd::tcsetpgrp,
+        };
+        use std::os::unix::io::AsRawFd;
+
+        let _ = tcsetpgrp(io::stdin().as_raw_fd(), self.previous);
+        // SAFETY: the action restored is the one that was replaced
+        let _ = unsafe { sigaction(Signal::SIGTTOU, &self.ttou) };
+    }
+}
+
+/// How a command finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Exit {
+    /// The command exited on its own
+    Status(ExitStatus),
+    /// The command was killed after running longer than its timeout
+    TimedOut(Duration),
+    /// The command was cancelled by the user with `ctrl-c`
+    Cancelled,
+}
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Status(status) if status.success() => write!(f, "finished successfully"),
+            Self::Status(status) => match status.code() {
+                Some(code) => write!(f, "failed with exit status {}", code),
+                None => write!(f, "was terminated by a signal"),
+            },
+            Self::TimedOut(after) => write!(f, "timed out after {}s", after.as_secs()),
+            Self::Cancelled => write!(f, "was cancelled"),
+        }
+    }
+}
+
+/// The status a shell would report for a command exiting with `status`: its
+/// exit code, or 128 plus the number of the signal that terminated it
+pub(crate) fn code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// The result of [`execute`]
+#[derive(Debug)]
+pub(crate) struct Finished {
+    pub(crate) exit:   Exit,
+    /// Standard output, if it was captured
+    pub(crate) stdout: Option<String>,
+}
+
+/// The shells the commands are adapted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shell {
+    /// `sh`, and the other POSIX shells
+    Posix,
+    Bash,
+    Zsh,
+    Fish,
+    Nushell,
+}
+
+impl Shell {
+    /// The shell `program` (e.g., `/bin/zsh`) is, if it is known
+    fn of(program: &str) -> Option<Self> {
+        let name = program
+            .split_whitespace()
+            .next()
+            .and_then(|program| Path::new(program).file_name())?;
+
+        match name.to_str()? {
+            "sh" | "dash" | "ash" | "ksh" | "mksh" => Some(Self::Posix),
+            "bash" => Some(Self::Bash),
+            "zsh" => Some(Self::Zsh),
+            "fish" => Some(Self::Fish),
+            "nu" => Some(Self::Nushell),
+            _ => None,
+        }
+    }
+
+    /// Options given to the shell, before the command or the script it runs.
+    /// When `strict`, Bash and Zsh stop at the first failure and on unset
+    /// variables (Fish can't, and Nushell always does). Fish and Nushell don't
+    /// read the configuration of the user, so that the commands run the same
+    /// anywhere, with the `PATH` Jaime was given, as `bash -c` does
+    fn options(self, strict: bool) -> Vec<&'static str> {
+        match (self, strict) {
+            (Self::Zsh, true) => vec![
+                "--shwordsplit", // -y
+                "--no-unset",    // -u
+                "--errexit",     // -e
+            ],
+            (Self::Zsh, false) => vec!["--shwordsplit"],
+            (Self::Bash, true) => vec!["-e", "-u"],
+            (Self::Fish, _) => vec!["--no-config"],
+            (Self::Nushell, _) => vec!["--no-config-file"],
+            (Self::Bash | Self::Posix, _) => vec![],
+        }
+    }
+
+    /// Whether the options make the shell stop at the first failure and on
+    /// unset variables when `strict`
+    fn has_strict_mode(self) -> bool {
+        matches!(self, Self::Bash | Self::Zsh)
+    }
+}
+
+/// How the shell of the configuration is started
+#[derive(Debug, Clone)]
+pub(crate) struct Startup {
+    pub(crate) shell:  String,
+    /// Arguments given to it before the command, instead of the options
+    /// adapted to it
+    pub(crate) args:   Option<Vec<String>>,
+    /// Start it as an interactive login shell, which reads the configuration
+    /// of the user (its functions, aliases...), for the commands of the
+    /// actions ran on the terminal, unless an action says otherwise
+    pub(crate) login:  bool,
+    /// Have the shells stop at the first failure and on unset variables,
+    /// unless an action says otherwise
+    pub(crate) strict: bool,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self {
+            shell:  String::new(),
+            args:   None,
+            login:  false,
+            strict: true,
+        }
+    }
+}
+
+static STARTUP: Lazy<Mutex<Startup>> = Lazy::new(|| Mutex::new(Startup::default()));
+
+/// Whether the shells are strict for the commands of the action running, when
+/// it says so, by [`strict_for`]
+static STRICT: Lazy<Mutex<Option<bool>>> = Lazy::new(|| Mutex::new(None));
+
+/// Start the shell of the configuration as the `startup` says
+pub(crate) fn set_startup(startup: Startup) {
+    *STARTUP.lock().unwrap_or_else(PoisonError::into_inner) = startup;
+}
+
+/// Restores whether the shells are strict once dropped
+#[derive(Debug)]
+pub(crate) struct StrictGuard(Option<bool>);
+
+impl Drop for StrictGuard {
+    fn drop(&mut self) {
+        *STRICT.lock().unwrap_or_else(PoisonError::into_inner) = self.0;
+    }
+}
+
+/// Have the shells strict or not, as the `strict_shell` of an action says, for
+/// its commands ran until the returned guard is dropped
+pub(crate) fn strict_for(strict: Option<bool>) -> StrictGuard {
+    let mut current = STRICT.lock().unwrap_or_else(PoisonError::into_inner);
+    let previous = *current;
+    if strict.is_some() {
+        *current = strict;
+    }
+    StrictGuard(previous)
+}
+
+/// Whether the shell running the command of an action on the terminal is a
+/// login shell, as its `login_shell` says, or else the configuration
+pub(crate) fn login_for(login_shell: Option<bool>) -> bool {
+    login_shell.unwrap_or_else(|| STARTUP.lock().unwrap_or_else(PoisonError::into_inner).login)
+}
+
+/// The options given to `shell`, and whether they make it strict. It is
+/// started as a login shell if `login`
+fn options_of(shell: &str, login: bool) -> (Vec<String>, bool) {
+    let startup = STARTUP.lock().unwrap_or_else(PoisonError::into_inner);
+    if shell == startup.shell {
+        if let Some(args) = &startup.args {
+            return (args.clone(), false);
+        }
+        // The configuration of the user often wouldn't pass the other options
+        if login {
+            return (vec![String::from("-l"), String::from("-i")], false);
+        }
+    }
+
+    let strict = STRICT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .unwrap_or(startup.strict);
+    match Shell::of(shell) {
+        Some(known) => (
+            known
+                .options(strict)
+                .into_iter()
+                .map(ToOwned::to_owned)
+                .collect(),
+            strict && known.has_strict_mode(),
+        ),
+        None => (Vec::new(), false),
+    }
+}
+
+/// Options given to `shell`, before the command or the script it runs. The
+/// configuration only changes those of its own shell, not the interpreters
+/// of the actions
+fn shell_options(shell: &str, login: bool) -> Vec<String> {
+    options_of(shell, login).0
+}
+
+/// Whether `shell`, started as a login shell if `login`, stops at the first
+/// failure and on unset variables, which is worth mentioning when a command
+/// fails
+pub(crate) fn is_strict(shell: &str, login: bool) -> bool {
+    options_of(shell, login).1
+}
+
+/// Arguments given to `shell` in order to run `cmd`, as a login shell if
+/// `login`
+pub(crate) fn shell_args(shell: &str, cmd: &str, login: bool) -> Vec<String> {
+    let mut args = shell_options(shell, login);
+    args.push(String::from("-c"));
+    args.push(cmd.to_owned());
+    args
+}
+
+/// Whether the `interpreter` (e.g., `/bin/bash`, or `python3 -u`) is a shell
+/// whose commands have their placeholders replaced, other interpreters being
+/// given the values as arguments. Nushell is among the latter when it is an
+/// interpreter, for the arguments of its `main`
+pub(crate) fn is_shell(interpreter: &str) -> bool {
+    matches!(Shell::of(interpreter), Some(shell) if shell != Shell::Nushell)
+}
+
+/// The shell command running `script` with the `interpreter`, which isn't a
+/// shell, giving it the `args` as arguments. The script is written to the
+/// `scripts` directory of the cache, named after its contents, so that the
+/// command can be ran again from the history
+pub(crate) fn interpreted(
+    context: &Context,
+    interpreter: &str,
+    script: &str,
+    args: &[String],
+) -> Result<String> {
+    let dir = context.cache_directory.join("scripts");
+    fs::create_dir_all(&dir).context(format!("unable to create: {}", dir.display()))?;
+
+    let path = dir.join(format!("{:x}", preview::digest(script)));
+    if !path.exists() {
+        // Written aside first, so that another session never runs half of it
+        let partial = path.with_extension(process::id().to_string());
+        fs::write(&partial, script)
+            .and_then(|()| fs::rename(&partial, &path))
+            .context(format!("unable to write: {}", path.display()))?;
+    }
+
+    let mut command = vec![
+        interpreter.to_owned(),
+        shlex::try_quote(&path.to_string_lossy())
+            .context("invalid cache directory")?
+            .into_owned(),
+    ];
+    for arg in args {
+        command.push(
+            shlex::try_quote(arg)
+                .context("unable to quote argument")?
+                .into_owned(),
+        );
+    }
+
+    Ok(command.join(" "))
+}
+
+/// Whether `cmd` is ran from a script rather than given to `-c`: when it has
+/// several lines, so that the line numbers in its errors are its own
+fn is_script(cmd: &str) -> bool {
+    cmd.trim_end().contains('\n')
+}
+
+/// A command written to a file of the cache in order to be ran, which is
+/// removed once dropped
+#[derive(Debug)]
+struct Script(PathBuf);
+
+impl Script {
+    fn write(context: &Context, cmd: &str) -> Result<Self> {
+        let dir = context.cache_directory.join("scripts");
+        fs::create_dir_all(&dir).context(format!("unable to create: {}", dir.display()))?;
+
+        let path = dir.join(uuid::Uuid::new_v4().to_string());
+        fs::write(&path, cmd).context(format!("unable to write: {}", path.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o700))
+                .context(format!("unable to make executable: {}", path.display()))?;
+        }
+
+        Ok(Self(path))
+    }
+
+    /// The command running the script: itself when it starts with a shebang,
+    /// `shell` otherwise, as a login shell if `login`
+    fn command(&self, cmd: &str, shell: &str, login: bool) -> Result<Command> {
+        let path = self.0.to_string_lossy();
+        if cmd.starts_with("#!") {
+            sandbox::command(&path, &[], cmd)
+        } else {
+            let mut args = shell_options(shell, login);
+            args.push(path.into_owned());
+            let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+            sandbox::command(shell, &args, cmd)
+        }
+    }
+}
+
+impl Drop for Script {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Build the command used to run `cmd` with the given shell and additional
+/// environment variables, in the sandbox if it is enabled
+pub(crate) fn shell_command(
+    context: &Context,
+    cmd: &str,
+    shell: &str,
+    envs: &[(String, String)],
+) -> Result<Command> {
+    login_command(context, cmd, shell, envs, false)
+}
+
+/// [`shell_command`], as a login shell if `login`
+fn login_command(
+    context: &Context,
+    cmd: &str,
+    shell: &str,
+    envs: &[(String, String)],
+    login: bool,
+) -> Result<Command> {
+    let args = shell_args(shell, cmd, login);
+    let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    let builder = sandbox::command(shell, &args, cmd)?;
+
+    Ok(with_envs(builder, context, envs))
+}
+
+/// `builder` with the cache directory and the additional environment
+/// variables set
+fn with_envs(mut builder: Command, context: &Context, envs: &[(String, String)]) -> Command {
+    builder
+        .env("JAIME_CACHE_DIR", &context.cache_directory)
+        .envs(envs.iter().map(|(k, v)| (k, v)));
+    builder
+}
+
+pub(crate) fn run_shell_command_for_output(
+    context: &Context,
+    cmd: &str,
+    shell: &str,
+    envs: &[(String, String)],
+) -> Result<String> {
+    let output = shell_command(context, cmd, shell, envs)?.output()?;
+    if output.stdout.is_empty() && !output.status.success() {
+        return Err(failed(cmd, output.status, &output.stderr));
+    }
+    Ok(std::str::from_utf8(output.stdout.as_slice())?.to_owned())
+}
+
+/// The error of `cmd` when it failed without printing anything, along with
+/// what it printed on its standard error
+pub(crate) fn failed(cmd: &str, status: ExitStatus, stderr: &[u8]) -> anyhow::Error {
+    match String::from_utf8_lossy(stderr).trim() {
+        "" => anyhow!("`{}` failed ({})", cmd, status),
+        stderr => anyhow!("`{}` failed ({}): {}", cmd, status, stderr),
+    }
+}
+
+/// How [`execute`] runs a command
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Execution<'a> {
+    /// Capture the standard output
+    pub(crate) capture: bool,
+    /// Kill the command if it runs longer than this
+    pub(crate) timeout: Option<Duration>,
+    /// File the output is also written to
+    pub(crate) log:     Option<&'a Path>,
+    /// Let the command read from the terminal, rather than from nothing
+    pub(crate) tty:     bool,
+    /// Start the shell as a login shell, unless the output is captured
+    pub(crate) login:   bool,
+}
+
+/// Run `cmd`, optionally capturing its standard output. A command of several
+/// lines is written to a script, ran by `shell` unless it starts with a
+/// shebang.
+///
+/// When a `timeout` is given, the command is started in its own process group
+/// so that the whole tree can be killed if it runs too long or if the user
+/// presses `ctrl-c`. The group is handed the terminal while it runs, and reads
+/// from nothing when there is no terminal to hand over, as reading from it
+/// would stop the command. So does a command ran while a frontend owns the
+/// standard input. When a `log` is given, the output of the command is also
+/// written to it
+pub(crate) fn execute(
+    context: &Context,
+    cmd: &str,
+    shell: &str,
+    envs: &[(String, String)],
+    Execution {
+        capture,
+        timeout,
+        log,
+        tty,
+        login,
+    }: Execution<'_>,
+) -> Result<Finished> {
+    // The shells computing values don't read the configuration of the user
+    let login = login && !capture;
+    // Kept until the command is done
+    let script = if is_script(cmd) {
+        Some(Script::write(context, cmd)?)
+    } else {
+        None
+    };
+    let mut builder = match &script {
+        Some(script) => with_envs(script.command(cmd, shell, login)?, context, envs),
+        None => login_command(context, cmd, shell, envs, login)?,
+    };
+
+    let output = frontend::output();
+    let piped = log.is_some() || output == frontend::Output::Forwarded;
+    if capture || piped {
+        builder.stdout(Stdio::piped());
+    } else if output == frontend::Output::Stderr {
+        builder.stdout(io::stderr());
+    }
+    if piped {
+        builder.stderr(Stdio::piped());
+    }
+    #[cfg(unix)]
+    let foreground = tty && timeout.is_some() && !frontend::is_set() && Foreground::available();
+    #[cfg(not(unix))]
+    let foreground = false;
+    if !tty || (timeout.is_some() && !foreground) || frontend::is_set() {
+        builder.stdin(Stdio::null());
+    }
+
+    #[cfg(unix)]
+    if timeout.is_some() {
+        use std::os::unix::process::CommandExt;
+        builder.process_group(0);
+    }
+
+    if let (Some(timeout), false) = (timeout, capture) {
+        eprintln!(
+            "{} {}",
+            theme::get().info.paint("[jaime]"),
+            theme::get().dimmed.paint(&format!(
+                "timeout in {}s (ctrl-c to cancel)",
+                timeout.as_secs()
+            ))
+        );
+    }
+
+    // What was printed before (e.g., by a selector leaving the terminal) comes
+    // before the output of the command
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let mut child = builder.spawn().map_err(JaimeError::from)?;
+    // Until the command is done. Without it, the command is stopped if it
+    // reads from the terminal, until it times out
+    #[cfg(unix)]
+    let _foreground = if foreground {
+        Foreground::give(&child).ok()
+    } else {
+        None
+    };
+
+    // Read in a separate thread so a full pipe can't block the child
+    let reader = if capture {
+        child.stdout.take().map(|mut out| {
+            thread::spawn(move || {
+                let mut buf = Vec::new();
+                out.read_to_end(&mut buf).map(|_| buf)
+            })
+        })
+    } else {
+        None
+    };
+    // Only piped when forwarded to the frontend or to the log
+    let log = match log {
+        Some(path) => Some(Arc::new(Mutex::new(
+            OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .context(format!("unable to open: {}", path.display()))?,
+        ))),
+        None => None,
+    };
+    let to_stderr = output == frontend::Output::Stderr;
+    let forwarders = vec![
+        child
+            .stdout
+            .take()
+            .map(|pipe| forward(pipe, to_stderr, log.clone())),
+        child.stderr.take().map(|pipe| forward(pipe, true, log)),
+    ];
+
+    let exit = match timeout {
+        Some(timeout) => wait_timeout(&mut child, timeout, capture)?,
+        None => Exit::Status(child.wait()?),
+    };
+
+    for forwarder in forwarders.into_iter().flatten() {
+        let _ = forwarder.join();
+    }
+    let stdout = match reader {
+        Some(handle) => {
+            let buf = handle
+                .join()
+                .map_err(|_| anyhow!("failed to read command output"))??;
+            Some(String::from_utf8_lossy(&buf).into_owned())
+        },
+        None => None,
+    };
+
+    Ok(Finished { exit, stdout })
+}
+
+/// Give the lines read from `pipe` to the frontend as they come, or write
+/// them to the standard output (or error if `to_stderr`). Either way, they
+/// are written to the `log` as well
+fn forward(
+    pipe: impl Read + Send + 'static,
+    to_stderr: bool,
+    log: Option<Arc<Mutex<File>>>,
+) -> thread::JoinHandle<()> {
+    let append = move |bytes: &[u8]| {
+        if let Some(log) = &log {
+            let _ = log
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .write_all(bytes);
+        }
+    };
+
+    thread::spawn(move || {
+        if frontend::output() == frontend::Output::Forwarded {
+            for line in BufReader::new(pipe).lines() {
+                match line {
+                    Ok(line) => {
+                        append(format!("{}\n", line).as_bytes());
+                        frontend::print(&line);
+                    },
+                    Err(_) => break,
+                }
+            }
+            return;
+        }
+
+        // Not by line, so that progress bars are displayed as they change
+        let mut pipe = pipe;
+        let mut buf = [0; 8192];
+        loop {
+            let read = match pipe.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(read) => read,
+            };
+            append(&buf[..read]);
+            let _ = if to_stderr {
+                io::stderr().write_all(&buf[..read])
+            } else {
+                io::stdout()
+                    .write_all(&buf[..read])
+                    .and_then(|()| io::stdout().flush())
+            };
+        }
+    })
+}
+
+/// Whether the command exited because `ctrl-c` was pressed while it had the
+/// terminal
+#[cfg(unix)]
+fn interrupted(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal() == Some(SIGINT)
+}
+
+#[cfg(not(unix))]
+fn interrupted(_status: ExitStatus) -> bool {
+    false
+}
+
+/// Wait for `child` to exit, killing it if it runs longer than `timeout` or if
+/// `ctrl-c` is pressed. A countdown is shown when the output is not displayed
+fn wait_timeout(child: &mut Child, timeout: Duration, countdown: bool) -> Result<Exit> {
+    let interrupt = Interrupt::catch()?;
+    let start = Instant::now();
+
+    let exit = loop {
+        if let Some(status) = child.try_wait()? {
+            if interrupted(status) {
+                // The rest of the group got it too
+                kill_tree(child)?;
+                break Exit::Cancelled;
+            }
+            break Exit::Status(status);
+        }
+        if interrupt.pressed() {
+            kill_tree(child)?;
+            break Exit::Cancelled;
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            kill_tree(child)?;
+            break Exit::TimedOut(timeout);
+        }
+
+        if countdown {
+            eprint!(
+                "\r{} {}",
+                theme::get().info.paint("[jaime]"),
+                theme::get().dimmed.paint(&format!(
+                    "{}s remaining (ctrl-c to cancel) ",
+                    timeout.saturating_sub(elapsed).as_secs() + 1
+                ))
+            );
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    drop(interrupt);
+    if countdown {
+        eprint!("\r\x1b[K");
+    }
+
+    Ok(exit)
+}
+
+/// Terminate the process group led by `child`, killing it if it doesn't exit
+/// within [`KILL_GRACE`]
+#[cfg(unix)]
+fn kill_tree(child: &mut Child) -> Result<()> {
+    use nix::{
+        sys::signal::{killpg, Signal},
+        unistd::Pid,
+    };
+    use std::convert::TryFrom;
+
+    let pgid = Pid::from_raw(i32::try_from(child.id())?);
+    let _ = killpg(pgid, Signal::SIGTERM);
+
+    let start = Instant::now();
+    while start.elapsed() < KILL_GRACE {
+        if child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    let _ = killpg(pgid, Signal::SIGKILL);
+    child.wait()?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn kill_tree(child: &mut Child) -> Result<()> {
+    child.kill()?;
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! How a command is assembled from its template: the value of each widget and
+//! what transformed it, the computed values, the placeholders and what they
+//! were replaced by, and the variables set in its environment. It is printed
+//! before the command runs with `jaime --explain`, and shown from the summary
+//! of the widgets, for when a placeholder doesn't expand as expected
+
+use crate::{
+    runner::{self, Map, Widget, WidgetConfig},
+    template, theme,
+};
+
+/// What a command is assembled from
+#[derive(Debug)]
+pub(crate) struct Assembly<'a> {
+    pub(crate) template: &'a str,
+    pub(crate) widgets:  &'a [WidgetConfig],
+    /// Values of the widgets, followed by those of the computed commands ran
+    /// so far
+    pub(crate) args:     &'a [String],
+    pub(crate) skipped:  &'a [bool],
+    /// Commands whose output are the values after those of the widgets
+    pub(crate) computed: &'a [String],
+    pub(crate) prev:     Option<&'a str>,
+    /// Values of the named placeholders, such as `{query}`
+    pub(crate) named:    &'a [(&'a str, &'a str)],
+    /// Secrets set in the environment of the command
+    pub(crate) envs:     &'a [(String, String)],
+}
+
+impl Assembly<'_> {
+    /// A value, masked and quoted so that its whitespace shows
+    fn value(&self, value: &str) -> String {
+        format!("{:?}", runner::masked(value, self.envs))
+    }
+
+    /// What was done to the value of `widget` before it was substituted
+    fn transformation(widget: &WidgetConfig) -> Option<String> {
+        let repeated = match &widget.widget {
+            Widget::Repeat {
+                template: Some(template),
+                ..
+            } => Some(format!(
+                "each value quoted and put in `{}`, then joined",
+                template
+            )),
+            Widget::Repeat { .. } => Some(String::from("the values joined")),
+            _ => None,
+        };
+        let mapped = match &widget.map {
+            Some(Map::Template(template)) => Some(format!("put in `{}`", template)),
+            Some(Map::Command(command)) =>
+                Some(format!("replaced by the output of `{}`, quoted", command)),
+            None => None,
+        };
+
+        match (repeated, mapped) {
+            (Some(repeated), Some(mapped)) => Some(format!("{}, {}", repeated, mapped)),
+            (repeated, mapped) => repeated.or(mapped),
+        }
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let theme = theme::get();
+        let mut lines = Vec::new();
+
+        lines.push(format!(
+            "{} {}",
+            theme.info.paint("template"),
+            self.template
+        ));
+
+        if !self.widgets.is_empty() {
+            lines.push(format!("{}", theme.info.paint("widgets")));
+        }
+        for (index, widget) in self.widgets.iter().enumerate() {
+            let name = match &widget.name {
+                Some(name) => format!("{} ({})", name, widget.widget.kind()),
+                None => widget.widget.kind().to_owned(),
+            };
+            let value = if self.skipped.get(index).copied().unwrap_or(false) {
+                theme
+                    .dimmed
+                    .paint("skipped, as its `when` didn't hold")
+                    .to_string()
+            } else {
+                self.args
+                    .get(index)
+                    .map_or_else(String::new, |value| self.value(value))
+            };
+            lines.push(format!(
+                "  {{{}}} {} = {}",
+                index,
+                theme.description.paint(&name),
+                value
+            ));
+            if let Some(transformation) = Self::transformation(widget) {
+                lines.push(format!("      {}", theme.dimmed.paint(&transformation)));
+            }
+        }
+
+        if !self.computed.is_empty() {
+            lines.push(format!("{}", theme.info.paint("computed")));
+        }
+        for (offset, command) in self.computed.iter().enumerate() {
+            let index = self.widgets.len() + offset;
+            let value = self.args.get(index).map_or_else(
+                || theme.dimmed.paint("not ran yet").to_string(),
+                |value| self.value(value),
+            );
+            lines.push(format!("  {{{}}} `{}` = {}", index, command, value));
+        }
+
+        let placeholders = template::placeholders(self.template, self.args, self.prev, self.named);
+        if !placeholders.is_empty() {
+            lines.push(format!("{}", theme.info.paint("placeholders")));
+        }
+        for (name, value) in placeholders {
+            let value = match value {
+                Some(value) => self.value(value),
+                None => theme
+                    .warning
+                    .paint("kept as it is, as it has no value")
+                    .to_string(),
+            };
+            lines.push(format!("  {{{}}} -> {}", name, value));
+        }
+
+        if !self.envs.is_empty() {
+            lines.push(format!("{}", theme.info.paint("environment")));
+        }
+        for (name, _) in self.envs {
+            lines.push(format!(
+                "  {} {}",
+                name,
+                theme.dimmed.paint("(secret, masked as ***)")
+            ));
+        }
+
+        let command = template::expand_with(self.template, self.args, self.prev, self.named);
+        lines.push(format!(
+            "{} {}",
+            theme.info.paint("command"),
+            runner::masked(&command, self.envs)
+        ));
+
+        lines.join("\n")
+    }
+}
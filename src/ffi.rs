@@ -0,0 +1,268 @@
+//! A C interface to the menu engine, behind the `ffi` feature, so that editors
+//! and launchers written in C, C++, or Go can run the menus of a
+//! configuration. The selections can be displayed by the host program through
+//! callbacks instead of the selectors of Jaime.
+//!
+//! Errors are reported by the return values, and their message can be read
+//! with [`jaime_last_error`]. Panics don't cross the interface
+//!
+//! ```c
+//! JaimeConfig *config = jaime_load_config(NULL);
+//! if (!config || jaime_run_path(config, "git.log") < 0)
+//!     fprintf(stderr, "%s\n", jaime_last_error());
+//! jaime_free_config(config);
+//! ```
+
+// Raw pointers come and go through the interface
+#![allow(unsafe_code)]
+
+use crate::{
+    app::Handler,
+    cache, cache_directory, config_path, error, exec,
+    frontend::{self, Frontend},
+    runner::{Config, Context, Outcome},
+    theme,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    ffi::{CStr, CString},
+    fmt::Display,
+    fs,
+    os::raw::{c_char, c_int, c_void},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    ptr,
+};
+
+/// A configuration loaded with [`jaime_load_config`], to be freed with
+/// [`jaime_free_config`]
+#[derive(Debug)]
+pub struct JaimeConfig {
+    config:  Config,
+    context: Context,
+    handler: Handler,
+}
+
+/// Displays a selection of `count` `items`, returning the index of the one
+/// selected, or a negative number if the selection is cancelled
+pub type JaimeSelect =
+    extern "C" fn(data: *mut c_void, items: *const *const c_char, count: usize) -> isize;
+
+/// Reads a line of input starting out as `initial`, returning it, or NULL if
+/// the input is cancelled. The line is copied before the callback is called
+/// again, and isn't freed by Jaime
+pub type JaimeRead = extern "C" fn(
+    data: *mut c_void,
+    prompt: *const c_char,
+    initial: *const c_char,
+) -> *const c_char;
+
+thread_local! {
+    /// The message of the last error of the thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Keep `message` as the last error of the thread
+fn fail(message: impl Display) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(text(&message.to_string())).ok());
+}
+
+/// `text` without the NUL characters C strings can't hold
+fn text(text: &str) -> String {
+    text.replace('\0', "")
+}
+
+/// The value returned by `function`, `None` if it failed or panicked, keeping
+/// the reason as the last error
+fn catch<T>(function: impl FnOnce() -> Result<T>) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(function)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            fail(format!("{:#}", e));
+            None
+        },
+        Err(_) => {
+            fail("jaime panicked");
+            None
+        },
+    }
+}
+
+/// The string at `text`, `None` if NULL
+///
+/// # Safety
+/// `text` is NULL or points to a NUL-terminated string
+unsafe fn string(text: *const c_char) -> Result<Option<String>> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(
+        CStr::from_ptr(text)
+            .to_str()
+            .context("invalid UTF-8 string")?
+            .to_owned(),
+    ))
+}
+
+/// The callbacks of the host program, displaying the selections
+struct Callbacks {
+    select: JaimeSelect,
+    read:   Option<JaimeRead>,
+    data:   *mut c_void,
+}
+
+// The callbacks are only called from the thread running a menu, which the host
+// program is responsible for
+unsafe impl Send for Callbacks {}
+
+impl Frontend for Callbacks {
+    fn select(
+        &mut self,
+        items: &[&str],
+        _preview: Option<&str>,
+        _multi: bool,
+    ) -> Result<Option<Vec<usize>>> {
+        let items = items
+            .iter()
+            .map(|item| CString::new(text(item)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let pointers = items.iter().map(|item| item.as_ptr()).collect::<Vec<_>>();
+
+        let index = (self.select)(self.data, pointers.as_ptr(), pointers.len());
+        Ok(usize::try_from(index)
+            .ok()
+            .filter(|index| *index < items.len())
+            .map(|index| vec![index]))
+    }
+
+    fn read(&mut self, prompt: &str, initial: &str) -> Result<Option<String>> {
+        let read = self
+            .read
+            .ok_or_else(|| anyhow!("no callback was set to read input"))?;
+        let (prompt, initial) = (CString::new(text(prompt))?, CString::new(text(initial))?);
+
+        let line = read(self.data, prompt.as_ptr(), initial.as_ptr());
+        // SAFETY: the callback returns NULL or a NUL-terminated string
+        unsafe { string(line) }
+    }
+}
+
+/// The configuration at `path`, or the one of the user along with its
+/// overlays
+fn load(path: Option<&str>) -> Result<JaimeConfig> {
+    let cache_directory = cache_directory()?;
+    fs::create_dir_all(&cache_directory)
+        .context(format!("unable to create: {}", cache_directory.display()))?;
+    let (config, config_path) = if let Some(path) = path {
+        (cache::parse_file(Path::new(path))?, PathBuf::from(path))
+    } else {
+        let config_path = config_path()?;
+        (
+            cache::load(&config_path, &cache_directory)?.config,
+            config_path,
+        )
+    };
+    if let Ok(theme) = theme::Theme::new(&config.theme) {
+        theme::set(theme);
+    }
+    exec::set_startup(config.startup());
+
+    Ok(JaimeConfig {
+        context: Context {
+            config_path,
+            cache_directory,
+            skim: config.skim.clone(),
+            keys: config.keys.clone(),
+            explain: false,
+        },
+        handler: Handler::from_args(&[])?,
+        config,
+    })
+}
+
+/// Load the configuration at `path`, or the one of the user (along with its
+/// overlays) if `path` is NULL. Returns NULL if it can't be loaded
+///
+/// # Safety
+/// `path` is NULL or points to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn jaime_load_config(path: *const c_char) -> *mut JaimeConfig {
+    catch(|| load(string(path)?.as_deref()))
+        .map_or(ptr::null_mut(), |config| Box::into_raw(Box::new(config)))
+}
+
+/// Free a configuration returned by [`jaime_load_config`]
+///
+/// # Safety
+/// `config` is NULL or was returned by [`jaime_load_config`], and isn't used
+/// afterwards
+#[no_mangle]
+pub unsafe extern "C" fn jaime_free_config(config: *mut JaimeConfig) {
+    if !config.is_null() {
+        drop(Box::from_raw(config));
+    }
+}
+
+/// Run the action at the dotted `path` of the menu, or display the root menu
+/// if `path` is NULL. Returns 0 once the action ran, 1 if a selection was
+/// cancelled, and -1 if it failed
+///
+/// # Safety
+/// `config` was returned by [`jaime_load_config`], and `path` is NULL or
+/// points to a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn jaime_run_path(config: *const JaimeConfig, path: *const c_char) -> c_int {
+    let JaimeConfig {
+        config,
+        context,
+        handler,
+    } = if let Some(config) = config.as_ref() {
+        config
+    } else {
+        fail("no configuration was given");
+        return -1;
+    };
+
+    let outcome = catch(|| {
+        let outcome = match string(path)? {
+            Some(path) => {
+                let (path, action) = config.find(&path)?;
+                action.run_at(context, config, handler, &path, None)
+            },
+            None => config.run(context, handler),
+        };
+        match outcome {
+            Err(e) if error::is_cancelled(&e) || error::is_aborted(&e) => Ok(Outcome::Cancelled),
+            outcome => outcome,
+        }
+    });
+
+    match outcome {
+        Some(Outcome::Cancelled) => 1,
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+/// Display the selections with `select`, and read the input with `read`
+/// (which can be NULL if the menus have no such widgets), both given `data`.
+/// The selectors of Jaime are used again once `select` is NULL
+#[no_mangle]
+pub extern "C" fn jaime_set_selector(
+    select: Option<JaimeSelect>,
+    read: Option<JaimeRead>,
+    data: *mut c_void,
+) {
+    frontend::set(
+        select.map(|select| -> Box<dyn Frontend> { Box::new(Callbacks { select, read, data }) }),
+    );
+}
+
+/// The message of the last error of the thread, or NULL if there was none.
+/// It is valid until the next call failing on the thread
+#[no_mangle]
+pub extern "C" fn jaime_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
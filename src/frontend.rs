@@ -0,0 +1,120 @@
+//! Selections and input handed over to a frontend outside of Jaime, such as a
+//! program embedding it through the C interface. Once one is set, it displays
+//! every selection instead of the backend chosen on the command line
+
+use crate::{plain, table};
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::sync::{Mutex, PoisonError};
+
+/// Displays the selections and reads the input of a menu
+pub(crate) trait Frontend: Send {
+    /// The indices of the `items` selected (several of them only if `multi`),
+    /// `None` when the selection is cancelled. `preview` is the command
+    /// previewing an item, with `{}` in place of the item
+    fn select(
+        &mut self,
+        items: &[&str],
+        preview: Option<&str>,
+        multi: bool,
+    ) -> Result<Option<Vec<usize>>>;
+
+    /// A line of input starting out as `initial`, `None` when cancelled
+    fn read(&mut self, prompt: &str, initial: &str) -> Result<Option<String>>;
+
+    /// Where the commands that don't capture their output print
+    fn output(&self) -> Output {
+        Output::Terminal
+    }
+
+    /// Show a line printed by a command, with [`Output::Forwarded`]
+    fn print(&mut self, _line: &str) {}
+}
+
+/// Where the commands print, while a frontend is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Output {
+    Terminal,
+    /// The standard output belongs to the frontend, the commands print to the
+    /// standard error instead
+    Stderr,
+    /// The lines printed by the commands are given to the frontend
+    Forwarded,
+}
+
+static FRONTEND: Lazy<Mutex<Option<Box<dyn Frontend>>>> = Lazy::new(|| Mutex::new(None));
+
+/// Hand the selections and the input over to `frontend`, or back to the
+/// selectors if `None`
+pub(crate) fn set(frontend: Option<Box<dyn Frontend>>) {
+    *FRONTEND.lock().unwrap_or_else(PoisonError::into_inner) = frontend;
+}
+
+/// Whether a frontend is set
+pub(crate) fn is_set() -> bool {
+    FRONTEND
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Where the commands print
+pub(crate) fn output() -> Output {
+    FRONTEND
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .map_or(Output::Terminal, |frontend| frontend.output())
+}
+
+/// Show a line printed by a command with the frontend
+pub(crate) fn print(line: &str) {
+    if let Some(frontend) = FRONTEND
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        frontend.print(line);
+    }
+}
+
+/// The lines of `input` selected with the frontend, separated by newlines
+pub(crate) fn select(input: &str, preview: Option<&str>, multi: bool) -> Result<Option<String>> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let items = lines
+        .iter()
+        .map(|l| plain::strip_ansi(table::displayed(l)))
+        .collect::<Vec<_>>();
+    let items = items.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let selected = match FRONTEND
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        Some(frontend) => frontend.select(&items, preview, multi)?,
+        None => return Ok(None),
+    };
+
+    Ok(selected
+        .map(|indices| {
+            indices
+                .into_iter()
+                .filter_map(|index| lines.get(index).copied())
+                .collect::<Vec<_>>()
+        })
+        .filter(|lines| !lines.is_empty())
+        .map(|lines| lines.join("\n")))
+}
+
+/// A line of input read with the frontend
+pub(crate) fn read(prompt: &str, initial: &str) -> Result<Option<String>> {
+    match FRONTEND
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        Some(frontend) => frontend.read(prompt, initial),
+        None => Ok(None),
+    }
+}
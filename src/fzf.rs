@@ -0,0 +1,109 @@
+//! Translation of the options of `fzf` in `FZF_DEFAULT_OPTS` into those of the
+//! embedded skim, so that users who only configure `fzf` get the same look from
+//! the default backend. Only the common subset is translated: the height, the
+//! layout, the colors, and the prompt, along with a few flags
+
+/// Flags having the same meaning in skim
+const FLAGS: &[&str] = &[
+    "--reverse",
+    "--tac",
+    "--no-sort",
+    "--inline-info",
+    "--no-mouse",
+];
+
+/// Options with a value having the same meaning in skim, besides `--color`
+const VALUES: &[&str] = &["--height", "--layout", "--margin", "--prompt"];
+
+/// The name of a color of `fzf` in skim, if it has one
+fn color_name(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "fg" => "fg",
+        "bg" => "bg",
+        "hl" => "matched",
+        "fg+" => "current",
+        "bg+" => "current_bg",
+        "hl+" => "current_match",
+        "query" => "query",
+        "info" => "info",
+        "prompt" => "prompt",
+        "pointer" => "cursor",
+        "marker" => "selected",
+        "spinner" => "spinner",
+        "header" => "header",
+        "border" => "border",
+        _ => return None,
+    })
+}
+
+/// A color of `fzf` (an ANSI color name or number, or `#rrggbb`, followed by
+/// attributes) in skim, which doesn't know the names nor the attributes
+fn color_value(value: &str) -> Option<String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+
+    let color = value.split(':').next()?;
+    if color.starts_with('#') || color.parse::<u8>().is_ok() {
+        return Some(color.to_owned());
+    }
+    let (offset, name) = match color.strip_prefix("bright-") {
+        Some(name) => (8, name),
+        None => (0, color),
+    };
+
+    NAMES
+        .iter()
+        .position(|n| *n == name)
+        .map(|index| (index + offset).to_string())
+}
+
+/// A `--color` specification of `fzf` in skim: the base scheme, if any, and
+/// the colors skim knows
+fn colors(spec: &str) -> Vec<String> {
+    spec.split(',')
+        .filter_map(|part| match part.split_once(':') {
+            Some((name, value)) => Some(format!("{}:{}", color_name(name)?, color_value(value)?)),
+            // `dark`, `light`, `16`, and `bw` are also base schemes of skim
+            None if ["dark", "light", "16", "bw"].contains(&part) => Some(part.to_owned()),
+            None => None,
+        })
+        .collect()
+}
+
+/// The options of skim giving the same look as the `fzf` `options`. The other
+/// options are left out
+pub(crate) fn translate(options: &[String]) -> Vec<String> {
+    let mut translated = Vec::new();
+    let mut color = Vec::new();
+
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let (name, inline) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_owned())),
+            None => (option.as_str(), None),
+        };
+
+        if FLAGS.contains(&name) {
+            translated.push(name.to_owned());
+        } else if name == "--color" || VALUES.contains(&name) {
+            let value = match inline.or_else(|| options.next().cloned()) {
+                Some(value) => value,
+                None => break,
+            };
+            match name {
+                "--color" => color.extend(colors(&value)),
+                // The adaptive height of `fzf`
+                "--height" =>
+                    translated.push(format!("--height={}", value.trim_start_matches('~'))),
+                _ => translated.push(format!("{}={}", name, value)),
+            }
+        }
+    }
+
+    if !color.is_empty() {
+        translated.push(format!("--color={}", color.join(",")));
+    }
+
+    translated
+}
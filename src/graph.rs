@@ -0,0 +1,96 @@
+//! Dependencies between the widgets of a command, declared with `depends`.
+//!
+//! A widget is displayed once those it depends on are, and otherwise in the
+//! order the widgets are declared. When a value is changed from the summary,
+//! only the widgets depending on it, directly or not, are displayed again
+
+use crate::runner::WidgetConfig;
+use anyhow::{anyhow, Result};
+
+/// The dependencies of the widgets of a command, indexed like the widgets
+#[derive(Debug, Clone)]
+pub(crate) struct Graph {
+    /// The widgets each widget depends on
+    dependencies: Vec<Vec<usize>>,
+    /// The order the widgets are displayed in
+    order:        Vec<usize>,
+}
+
+impl Graph {
+    /// The dependencies of `widgets`, or why they can't be satisfied
+    pub(crate) fn new(widgets: &[WidgetConfig]) -> Result<Self> {
+        let dependencies = widgets
+            .iter()
+            .enumerate()
+            .map(|(index, widget)| {
+                widget
+                    .depends
+                    .iter()
+                    .map(|wanted| {
+                        resolve(widgets, wanted).ok_or_else(|| {
+                            anyhow!("widget {} depends on an unknown widget: {}", index, wanted)
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // The first widget whose dependencies are all displayed comes next
+        let mut displayed = vec![false; widgets.len()];
+        let mut order = Vec::with_capacity(widgets.len());
+        while order.len() < widgets.len() {
+            let next = (0..widgets.len()).find(|index| {
+                !displayed[*index] && dependencies[*index].iter().all(|d| displayed[*d])
+            });
+            let index = if let Some(index) = next {
+                index
+            } else {
+                let cycle = (0..widgets.len())
+                    .filter(|index| !displayed[*index])
+                    .map(|index| index.to_string())
+                    .collect::<Vec<_>>();
+                return Err(anyhow!("widgets {} depend on each other", cycle.join(", ")));
+            };
+            displayed[index] = true;
+            order.push(index);
+        }
+
+        Ok(Self {
+            dependencies,
+            order,
+        })
+    }
+
+    /// The order the widgets are displayed in
+    pub(crate) fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// The widgets to display again when the value of the widget at `index`
+    /// changes: itself, then those depending on it, in the order they are
+    /// displayed in
+    pub(crate) fn affected(&self, index: usize) -> Vec<usize> {
+        let mut affected = vec![false; self.dependencies.len()];
+        affected[index] = true;
+        // Each widget comes after its dependencies
+        for &other in &self.order {
+            if self.dependencies[other].iter().any(|d| affected[*d]) {
+                affected[other] = true;
+            }
+        }
+
+        self.order
+            .iter()
+            .copied()
+            .filter(|other| affected[*other])
+            .collect()
+    }
+}
+
+/// The index of the widget named `wanted`, or at the index it is
+fn resolve(widgets: &[WidgetConfig], wanted: &str) -> Option<usize> {
+    widgets
+        .iter()
+        .position(|widget| widget.name.as_deref() == Some(wanted))
+        .or_else(|| wanted.parse().ok().filter(|index| *index < widgets.len()))
+}
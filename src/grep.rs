@@ -0,0 +1,106 @@
+//! `jaime grep`: search the commands, previews, and descriptions of the whole
+//! configuration (overlays included) for a pattern, printing where each match
+//! is in the menu, e.g. to find the actions using a tool that was renamed
+
+use crate::{
+    runner::{Action, Config, Map, WidgetConfig},
+    theme,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use regex::{Captures, RegexBuilder};
+
+/// A text that is searched: the path of the action it belongs to, the field it
+/// is in, and the text itself
+type Text<'a> = (String, &'static str, &'a str);
+
+/// The texts of `action` and of those below it
+fn texts<'a>(action: &'a Action, path: &str, found: &mut Vec<Text<'a>>) {
+    if let Some(description) = action.description() {
+        found.push((path.to_owned(), "description", description));
+    }
+
+    match action {
+        Action::Command {
+            command,
+            widgets,
+            computed,
+            ..
+        } => {
+            found.push((path.to_owned(), "command", command));
+            for computed in computed {
+                found.push((path.to_owned(), "computed", computed));
+            }
+            for (index, WidgetConfig { widget, map, .. }) in widgets.iter().flatten().enumerate() {
+                let path = format!("{}[{}]", path, index);
+                if let Some(command) = widget.command() {
+                    found.push((path.clone(), "command", command));
+                }
+                if let Some(preview) = widget.preview() {
+                    found.push((path.clone(), "preview", preview));
+                }
+                if let Some(Map::Command(command) | Map::Template(command)) = map {
+                    found.push((path, "map", command));
+                }
+            }
+        },
+        Action::Select { options, .. } =>
+            for (key, action) in options {
+                let path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                texts(action, &path, found);
+            },
+        Action::Chain { steps, .. } =>
+            for (index, step) in steps.iter().enumerate() {
+                texts(step, &format!("{}/{}", path, index), found);
+            },
+        Action::Menu { path: target, .. } => found.push((path.to_owned(), "path", target)),
+        Action::Systemd { .. } => {},
+    }
+}
+
+/// Print the lines of the texts of `config` matching `pattern`, a regular
+/// expression unless `fixed`, along with where they are in the menu
+pub(crate) fn run(config: &Config, pattern: &str, ignore_case: bool, fixed: bool) -> Result<()> {
+    let theme = theme::get();
+    let regex = RegexBuilder::new(&if fixed {
+        regex::escape(pattern)
+    } else {
+        pattern.to_owned()
+    })
+    .case_insensitive(ignore_case)
+    .build()
+    .context("invalid pattern")?;
+
+    let mut found = Vec::new();
+    for (key, action) in &config.options {
+        texts(action, key, &mut found);
+    }
+    if let Some(fallback) = &config.fallback {
+        texts(fallback, "fallback", &mut found);
+    }
+
+    let mut matches = 0;
+    for (path, field, text) in found {
+        for line in text.lines().filter(|line| regex.is_match(line)) {
+            let highlighted = regex.replace_all(line.trim(), |captures: &Captures<'_>| {
+                theme.warning.paint(&captures[0]).to_string()
+            });
+            println!(
+                "{}  {} {}",
+                theme.key.paint(&path),
+                theme.dimmed.paint(&format!("{}:", field)),
+                highlighted
+            );
+            matches += 1;
+        }
+    }
+
+    if matches == 0 {
+        return Err(anyhow!("nothing matches {}", pattern));
+    }
+
+    Ok(())
+}
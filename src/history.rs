@@ -0,0 +1,293 @@
+//! History of the commands ran, stored as JSON lines in the cache directory
+
+use crate::{
+    app::Handler,
+    builtin, lock,
+    runner::{self, Context},
+    theme,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Size of the output stored with an entry, beyond which only its end is kept
+const OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// A command that was ran
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    /// UNIX timestamp of when the command was started
+    pub(crate) time:    u64,
+    /// Dotted path of the action in the menu
+    pub(crate) path:    String,
+    /// The command, after its placeholders were replaced
+    pub(crate) command: String,
+    /// Values of the widgets of the action, to run it again with some of them
+    /// changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) values:  Vec<String>,
+    /// What the command printed, for those with `capture_output`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output:  Option<String>,
+}
+
+fn file(context: &Context) -> PathBuf {
+    context.cache_directory.join("history.jsonl")
+}
+
+/// Lock the history, so that the entries appended by other sessions aren't lost
+/// while it is rewritten. A file next to it is locked, as the history is
+/// replaced when rewritten, until the returned file is closed
+fn lock(context: &Context) -> Result<File> {
+    let path = context.cache_directory.join("history.lock");
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .context(format!("unable to open: {}", path.display()))?;
+    lock::lock(&file, true)?;
+
+    Ok(file)
+}
+
+/// Replace the history at `path` with `text` at once, so that it is never left
+/// half written
+fn replace(path: &Path, text: &str) -> Result<()> {
+    let partial = path.with_extension("jsonl.tmp");
+    fs::write(&partial, text)
+        .and_then(|()| fs::rename(&partial, path))
+        .context("unable to write the history")
+}
+
+/// Append a command to the history, along with the `values` of the widgets of
+/// its action, returning its entry
+pub(crate) fn record(
+    context: &Context,
+    path: &[String],
+    command: &str,
+    values: &[String],
+) -> Result<Entry> {
+    let entry = Entry {
+        time:    SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        path:    path.join("."),
+        command: command.to_owned(),
+        values:  values.to_vec(),
+        output:  None,
+    };
+
+    let _lock = lock(context)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file(context))
+        .context("unable to open the history")?;
+    writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+
+    Ok(entry)
+}
+
+/// Load the history, oldest entries first. Lines that cannot be parsed are
+/// skipped
+pub(crate) fn load(context: &Context) -> Result<Vec<Entry>> {
+    let path = file(context);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(fs::read_to_string(&path)
+        .context("unable to read the history")?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Rewrite the paths of the history with `rename`, which gives the new path of
+/// the entries to change. Returns the number of entries changed
+pub(crate) fn rename(context: &Context, rename: impl Fn(&str) -> Option<String>) -> Result<usize> {
+    let path = file(context);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let _lock = lock(context)?;
+
+    let mut changed = 0;
+    let mut text = String::new();
+    for line in fs::read_to_string(&path)
+        .context("unable to read the history")?
+        .lines()
+    {
+        match serde_json::from_str::<Entry>(line) {
+            Ok(mut entry) =>
+                if let Some(renamed) = rename(&entry.path) {
+                    entry.path = renamed;
+                    changed += 1;
+                    text.push_str(&serde_json::to_string(&entry)?);
+                } else {
+                    text.push_str(line);
+                },
+            // Kept as is, even if it isn't an entry
+            Err(_) => text.push_str(line),
+        }
+        text.push('\n');
+    }
+
+    if changed > 0 {
+        replace(&path, &text)?;
+    }
+
+    Ok(changed)
+}
+
+/// The entries of the history, oldest first, along with their ID: the number
+/// of their line, which doesn't change as entries are added
+fn numbered(context: &Context) -> Result<Vec<(usize, Entry)>> {
+    let path = file(context);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    Ok(fs::read_to_string(&path)
+        .context("unable to read the history")?
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| Some((index + 1, serde_json::from_str(line).ok()?)))
+        .collect())
+}
+
+/// The end of `output`, when it is longer than [`OUTPUT_LIMIT`]
+fn truncated(output: &str) -> String {
+    if output.len() <= OUTPUT_LIMIT {
+        return output.to_owned();
+    }
+
+    let mut start = output.len() - OUTPUT_LIMIT;
+    while !output.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("[… {} bytes cut]\n{}", start, &output[start..])
+}
+
+/// Store the `output` of the command of `entry`, recorded earlier
+pub(crate) fn set_output(context: &Context, entry: &Entry, output: &str) -> Result<()> {
+    let path = file(context);
+    let _lock = lock(context)?;
+    let text = fs::read_to_string(&path).context("unable to read the history")?;
+    let mut lines = text.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+
+    // The last one, in case the same command was ran in the same second
+    let line = lines
+        .iter_mut()
+        .rev()
+        .find(|line| serde_json::from_str::<Entry>(line).ok().as_ref() == Some(entry))
+        .ok_or_else(|| anyhow!("the entry of the command was removed from the history"))?;
+    *line = serde_json::to_string(&Entry {
+        output: Some(truncated(output)),
+        ..entry.clone()
+    })?;
+
+    let mut text = lines.join("\n");
+    text.push('\n');
+    replace(&path, &text)
+}
+
+/// Print the history with the IDs of the entries, the most recent first, for
+/// `jaime history`
+pub(crate) fn print(context: &Context) -> Result<()> {
+    let theme = theme::get();
+    for (id, entry) in numbered(context)?.iter().rev() {
+        println!(
+            "{}\t{}\t{}\t{}{}",
+            theme.key.paint(&id.to_string()),
+            theme.dimmed.paint(&builtin::relative_time(
+                i64::try_from(entry.time).unwrap_or(i64::MAX)
+            )),
+            entry.path,
+            entry.command,
+            if entry.output.is_some() {
+                theme.dimmed.paint(" (output stored)").to_string()
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// The entry `id` of the history
+pub(crate) fn get(context: &Context, id: usize) -> Result<Entry> {
+    numbered(context)?
+        .into_iter()
+        .find(|(number, _)| *number == id)
+        .map(|(_, entry)| entry)
+        .ok_or_else(|| anyhow!("no entry {} in the history", id))
+}
+
+/// Select an entry of the history, the most recent first
+pub(crate) fn select(context: &Context, handler: &Handler) -> Result<Option<Entry>> {
+    let entries = numbered(context)?;
+    if entries.is_empty() {
+        return Err(anyhow!("no command was ran yet"));
+    }
+
+    let theme = theme::get();
+    let input = entries
+        .iter()
+        .rev()
+        .map(|(id, entry)| {
+            format!(
+                "{}: {} {}",
+                theme.key.paint(&id.to_string()),
+                theme.description.paint(&entry.path),
+                entry.command
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let selected = match runner::select(context, handler, &input, None)? {
+        Some(selected) => selected,
+        None => return Ok(None),
+    };
+    let id = selected.split(':').next().unwrap_or_default();
+
+    id.parse::<usize>()
+        .map_err(|_| anyhow!("unknown entry: {}", id))
+        .and_then(|id| get(context, id))
+        .map(Some)
+}
+
+/// Print the entry `id` of the history along with its output, for `jaime
+/// history show`
+pub(crate) fn show(context: &Context, id: usize) -> Result<()> {
+    let entry = get(context, id)?;
+
+    let theme = theme::get();
+    println!(
+        "{} {}, {}",
+        theme.key.paint(&entry.path),
+        theme.dimmed.paint(&builtin::relative_time(
+            i64::try_from(entry.time).unwrap_or(i64::MAX)
+        )),
+        entry.command
+    );
+    match &entry.output {
+        Some(output) => print!("{}", output),
+        None => println!(
+            "{}",
+            theme
+                .dimmed
+                .paint("The output wasn't stored (see `capture_output`)")
+        ),
+    }
+
+    Ok(())
+}
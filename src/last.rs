@@ -0,0 +1,143 @@
+//! The last command that was ran, taken from the [`history`], offered again to
+//! be ran as is, with the values of some of its widgets changed, edited before
+//! running it, or copied. It is reached from the `@last` entry of the root
+//! menu, or with `jaime last`. Any command of the history is offered the same
+//! way by `jaime history rerun`
+
+use crate::{
+    app::Handler,
+    clipboard, exec, history, resume,
+    runner::{self, Config, Context, Outcome},
+    theme,
+};
+use anyhow::{anyhow, Result};
+
+/// Key of the entry added to the root menu
+pub(crate) const KEY: &str = "@last";
+
+/// What can be done with the last command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Run,
+    Modify,
+    Edit,
+    Copy,
+}
+
+impl Operation {
+    const ALL: [Self; 4] = [Self::Run, Self::Modify, Self::Edit, Self::Copy];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Run => "run",
+            Self::Modify => "modify",
+            Self::Edit => "edit",
+            Self::Copy => "copy",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Run => "Run the command again",
+            Self::Modify => "Change the values of its widgets, then run it",
+            Self::Edit => "Edit the command, then run it",
+            Self::Copy => "Copy the command to the clipboard",
+        }
+    }
+}
+
+/// The last command that was ran, if any
+pub(crate) fn entry(context: &Context) -> Result<Option<history::Entry>> {
+    Ok(history::load(context)?.pop())
+}
+
+/// Run `command` in place of the action at `path`, like the action runs its
+/// own, with the `values` of its widgets recorded in the history. It is refused
+/// like the action would be, when it is restricted or no longer in the menu
+fn rerun(
+    context: &Context,
+    config: &Config,
+    handler: &Handler,
+    path: &str,
+    command: &str,
+    values: Vec<String>,
+) -> Result<Outcome> {
+    let (keys, action) = config.find(path)?;
+    // Along with the menus leading to it, which can't be entered either
+    for end in 1..=keys.len() {
+        let prefix = keys[..end].join(".");
+        if let Some(reason) = config.find(&prefix)?.1.meta().disabled_reason() {
+            return Err(anyhow!("{} is disabled: {}", prefix, reason));
+        }
+    }
+
+    let _strict = exec::strict_for(action.strict_shell());
+    let prepared = action.recorded(config, command, values)?;
+    action.run_prepared(context, config, handler, &keys, prepared)
+}
+
+/// Show the last command along with what can be done with it, and do the
+/// selected operation
+pub(crate) fn run(context: &Context, config: &Config, handler: &Handler) -> Result<Outcome> {
+    let entry = entry(context)?.ok_or_else(|| anyhow!("no command was ran yet"))?;
+    offer(context, config, handler, &entry)
+}
+
+/// Show the command of the history `entry` along with what can be done with
+/// it, and do the selected operation. Its widgets can only be changed when
+/// their values were recorded
+pub(crate) fn offer(
+    context: &Context,
+    config: &Config,
+    handler: &Handler,
+    entry: &history::Entry,
+) -> Result<Outcome> {
+    let input = Operation::ALL
+        .iter()
+        .filter(|op| **op != Operation::Modify || !entry.values.is_empty())
+        .map(|op| {
+            format!(
+                "{}: {} ({})",
+                theme::get().key.paint(op.name()),
+                theme::get().description.paint(op.description()),
+                entry.command
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let selected = match runner::select(context, handler, &input, None)? {
+        Some(selected) => selected,
+        None => return Ok(Outcome::Cancelled),
+    };
+    let name = selected.split(':').next().unwrap_or_default();
+    let operation = Operation::ALL
+        .iter()
+        .find(|op| op.name() == name)
+        .ok_or_else(|| anyhow!("unknown operation: {}", name))?;
+
+    match operation {
+        Operation::Run => rerun(
+            context,
+            config,
+            handler,
+            &entry.path,
+            &entry.command,
+            entry.values.clone(),
+        ),
+        Operation::Modify => {
+            let (path, action) = config.find(&entry.path)?;
+            resume::prefill(entry.values.clone());
+            action.run_at(context, config, handler, &path, None)
+        },
+        Operation::Edit => match runner::readline_with("> ", &entry.command)? {
+            Some(command) if !command.trim().is_empty() =>
+                rerun(context, config, handler, &entry.path, &command, Vec::new()),
+            _ => Ok(Outcome::Cancelled),
+        },
+        Operation::Copy => {
+            clipboard::copy(&entry.command)?;
+            Ok(Outcome::Done)
+        },
+    }
+}
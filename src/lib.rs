@@ -0,0 +1,465 @@
+//! Jaime: menus of shell commands, picked with a fuzzy selector, whose
+//! arguments are picked the same way with widgets.
+//!
+//! Besides the `jaime` binary, the menus can be assembled in Rust with
+//! [`MenuBuilder`] and [`ActionBuilder`], and ran with a [`Runner`], along with
+//! widgets listing items computed by the program itself
+
+#![deny(
+    clippy::all,
+    clippy::complexity,
+    clippy::correctness,
+    clippy::pedantic,
+    clippy::perf,
+    clippy::style
+)]
+#![deny(
+    absolute_paths_not_starting_with_crate,
+    anonymous_parameters,
+    bad_style,
+    const_err,
+    dead_code,
+    ellipsis_inclusive_range_patterns,
+    exported_private_dependencies,
+    ill_formed_attribute_input,
+    improper_ctypes,
+    keyword_idents,
+    macro_use_extern_crate,
+    meta_variable_misuse, // May have false positives
+    missing_abi,
+    missing_debug_implementations, // can affect compile time/code size
+    no_mangle_generic_items,
+    non_shorthand_field_patterns,
+    noop_method_call,
+    overflowing_literals,
+    path_statements,
+    patterns_in_fns_without_body,
+    pointer_structural_match,
+    private_in_public,
+    pub_use_of_private_extern_crate,
+    semicolon_in_expressions_from_macros,
+    single_use_lifetimes,
+    trivial_casts,
+    trivial_numeric_casts,
+    unaligned_references,
+    unconditional_recursion,
+    unreachable_pub,
+    unsafe_code,
+    unused,
+    unused_allocation,
+    unused_comparisons,
+    unused_extern_crates,
+    unused_import_braces,
+    unused_lifetimes,
+    unused_parens,
+    unused_qualifications,
+    variant_size_differences,
+    while_true
+)]
+#![allow(clippy::too_many_lines)]
+
+mod app;
+mod audit;
+mod bench;
+mod builtin;
+mod cache;
+mod clipboard;
+mod condition;
+mod crypt;
+mod diff;
+mod docs;
+mod doctor;
+mod embed;
+mod error;
+mod exec;
+mod explain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod frontend;
+mod fzf;
+mod graph;
+mod grep;
+mod history;
+mod last;
+mod lines;
+mod listing;
+mod lock;
+mod menu;
+mod metrics;
+mod mv;
+mod notify;
+mod nvim;
+mod pager;
+mod palette;
+mod params;
+mod plain;
+mod policy;
+mod preview;
+mod protocol;
+mod recall;
+mod resume;
+mod runner;
+mod runs;
+mod sandbox;
+mod secrets;
+mod service;
+mod session;
+mod source;
+mod starter;
+mod table;
+mod template;
+mod text;
+mod theme;
+mod tree;
+#[cfg(feature = "tui")]
+mod tui;
+mod user;
+#[cfg(feature = "web")]
+mod web;
+mod yaml;
+
+use anyhow::{Context as AnyhowContext, Result};
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::PathBuf,
+    process,
+};
+
+pub use embed::{ActionBuilder, Menu, MenuBuilder, Runner};
+pub use source::{Item, ItemSource, SourceContext};
+
+/// The path of the configuration, in the XDG configuration directory
+fn config_path() -> Result<PathBuf> {
+    Ok(env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|d| d.join(".config")))
+        .context("Invalid configuration directory")?
+        .join("jaime")
+        .join("config.yml"))
+}
+
+/// The directory of the memoized configuration and of the history, in the XDG
+/// cache directory
+fn cache_directory() -> Result<PathBuf> {
+    Ok(env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| dirs::home_dir().map(|d| d.join(".cache")))
+        .context("Invalid cache directory")?
+        .join("jaime"))
+}
+
+fn actual_main() -> Result<()> {
+    let app = app::Handler::parse();
+
+    if app.plain() {
+        colored::control::set_override(false);
+    }
+
+    if let Some((kind, item)) = app.preview() {
+        return kind.parse::<preview::Builtin>()?.run(item);
+    }
+
+    let create_dir = |path: &PathBuf| -> Result<()> {
+        if path.exists() {
+            Ok(())
+        } else {
+            fs::create_dir_all(path).context(format!("unable to create: {}", path.display()))?;
+            Ok(())
+        }
+    };
+
+    let config_path = config_path()?;
+
+    if let Some((topics, print)) = app.init_config() {
+        return starter::init(&config_path, &topics, print);
+    }
+
+    if let Some((listen, allow_remote, print)) = app.install_service() {
+        return service::install(listen, allow_remote, print, &cache_directory()?);
+    }
+
+    create_dir(&config_path)?;
+
+    let cache_directory = cache_directory()?;
+
+    create_dir(&cache_directory)?;
+
+    if app.cache_rebuild() {
+        cache::rebuild(&config_path, &cache_directory)?;
+        println!("Memoized {}", cache::path(&cache_directory).display());
+        return Ok(());
+    }
+
+    if let Some((scheme, recipients, file)) = app.encrypt() {
+        let text = read_input(file)?;
+        print!("{}", crypt::encrypt_section(&text, scheme, &recipients)?);
+        return Ok(());
+    }
+
+    if app.decrypt() {
+        let text = read_input(app.decrypt_file())?;
+        print!(
+            "{}",
+            crypt::decrypt_section(&text, &crypt::identity(&config_path))?
+        );
+        return Ok(());
+    }
+
+    let cache::Loaded {
+        mut config,
+        unknown_fields,
+        locked,
+        mut problems,
+    } = cache::load(&config_path, &cache_directory)?;
+    // An invalid theme is reported with the other problems
+    if let Ok(theme) = theme::Theme::new(&config.theme) {
+        theme::set(theme);
+    }
+    exec::set_startup(config.startup());
+
+    if app.doctor() {
+        return doctor::run(&config, &unknown_fields, &locked);
+    }
+
+    if let Some((old, new)) = app.diff() {
+        return diff::run(old, new, &config);
+    }
+
+    if let Some((pattern, ignore_case, fixed)) = app.grep() {
+        return grep::run(&config, pattern, ignore_case, fixed);
+    }
+
+    if let Some((path, json)) = app.tree() {
+        return tree::run(&config, path, app.tree_depth()?, json);
+    }
+
+    if let Some(as_man) = app.docs() {
+        print!("{}", docs::render(&config, as_man));
+        return Ok(());
+    }
+
+    let strict = app.strict() || config.strict;
+    if strict {
+        problems.extend(doctor::missing(&config));
+    }
+    if !problems.is_empty() {
+        let list = problems
+            .iter()
+            .map(|problem| format!("  {}", problem))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if strict {
+            return Err(anyhow::anyhow!(
+                "{} problem(s) in config:\n{}",
+                problems.len(),
+                list
+            ));
+        }
+        eprintln!(
+            "{}: {} problem(s) in config (see `jaime doctor`):\n{}",
+            theme::get().warning.paint("[jaime warning]"),
+            problems.len(),
+            list
+        );
+    }
+    config.add_tag_menu();
+
+    if app.sandbox() {
+        sandbox::enable(app.sandbox_policy())?;
+    }
+
+    if let Some(path) = app.record() {
+        session::record_to(path)?;
+    } else if let Some(path) = app.replay() {
+        session::replay_from(path)?;
+    }
+
+    let context = runner::Context {
+        config_path,
+        cache_directory,
+        skim: config.skim.clone(),
+        keys: config.keys.clone(),
+        explain: app.explain(),
+    };
+
+    if let Some((old, new)) = app.mv() {
+        return mv::run(&context, old, new);
+    }
+
+    if app.nvim_server() {
+        colored::control::set_override(false);
+        return nvim::run(&context, &config, &app);
+    }
+
+    if app.frontend_protocol() {
+        colored::control::set_override(false);
+        return protocol::run(&context, &config, &app);
+    }
+
+    if let Some((address, systemd, allow_remote)) = app.serve() {
+        colored::control::set_override(false);
+        #[cfg(feature = "web")]
+        return web::run(context, config, app.clone(), web::Listen {
+            address,
+            systemd,
+            allow_remote,
+        });
+        #[cfg(not(feature = "web"))]
+        {
+            let _ = (systemd, allow_remote);
+            return Err(anyhow::anyhow!(
+                "jaime was built without the `web` feature ({})",
+                address
+            ));
+        }
+    }
+
+    if let Some(path) = app.run_path() {
+        let (path, action) = config.find(path)?;
+        let parameters = params::parse(&app.run_parameters())?;
+        if !parameters.is_empty() {
+            params::give(parameters);
+        }
+        return finish(&action.run_at(&context, &config, &app, &path, None)?);
+    }
+
+    if app.last() {
+        return finish(&last::run(&context, &config, &app)?);
+    }
+
+    if let Some((path, kill)) = app.jobs_attach() {
+        return lock::attach(&context, path, kill);
+    }
+
+    if app.jobs() {
+        return lock::list(&context);
+    }
+
+    if let Some(id) = app.history_show() {
+        return history::show(&context, id?);
+    }
+
+    if app.history_rerun() {
+        let entry = match app.rerun_id() {
+            Some(id) => history::get(&context, id?)?,
+            None => match history::select(&context, &app)? {
+                Some(entry) => entry,
+                None => return finish(&runner::Outcome::Cancelled),
+            },
+        };
+        return finish(&last::offer(&context, &config, &app, &entry)?);
+    }
+
+    if app.history() {
+        return history::print(&context);
+    }
+
+    if app.runs_ls() {
+        return runs::print(&context);
+    }
+
+    if app.runs_open() {
+        return runs::open(&context, app.run_id());
+    }
+
+    if let Some(bench) = app.bench() {
+        let (path, runs) = bench?;
+        return bench::run(&context, &config, path, runs);
+    }
+
+    if app.tags() {
+        if !config.options.contains_key(runner::TAGS_KEY) {
+            return Err(anyhow::anyhow!("none of the actions have tags"));
+        }
+        let path = app.tag().map_or_else(
+            || runner::TAGS_KEY.to_owned(),
+            |tag| format!("{}.{}", runner::TAGS_KEY, tag),
+        );
+        let (path, action) = config.find(&path)?;
+        return finish(&action.run_at(&context, &config, &app, &path, None)?);
+    }
+
+    if app.tui() {
+        #[cfg(feature = "tui")]
+        return tui::run(&context, &config, &app);
+        #[cfg(not(feature = "tui"))]
+        return Err(anyhow::anyhow!("jaime was built without the `tui` feature"));
+    }
+
+    finish(&config.run(&context, &app)?)
+}
+
+/// Exit with an error if the action that ran was cancelled, so that scripts can
+/// tell
+fn finish(outcome: &runner::Outcome) -> Result<()> {
+    match outcome {
+        runner::Outcome::Cancelled => Err(error::JaimeError::WidgetCancelled.into()),
+        _ => Ok(()),
+    }
+}
+
+/// The contents of `file`, or of the standard input when there is none
+fn read_input(file: Option<&str>) -> Result<String> {
+    if let Some(file) = file {
+        return fs::read_to_string(file).context(format!("unable to read: {}", file));
+    }
+
+    let mut text = String::new();
+    io::stdin()
+        .read_to_string(&mut text)
+        .context("unable to read the standard input")?;
+    Ok(text)
+}
+
+/// Start jaime over with `command`, once an operation of the palette left the
+/// selection for it
+fn restart(mut command: process::Command) -> ! {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        println!("unable to restart jaime: {}", command.exec());
+        process::exit(1);
+    }
+
+    #[cfg(not(unix))]
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(err) => {
+            println!("unable to restart jaime: {}", err);
+            process::exit(1);
+        },
+    }
+}
+
+/// Run the `jaime` binary, exiting with an error code if it fails
+pub fn main() {
+    let result = actual_main();
+    if let Some(command) = palette::take_restart() {
+        restart(command);
+    }
+
+    match result {
+        Ok(()) => {},
+        // The selection was cancelled or aborted, there is nothing to report
+        Err(err) if error::is_aborted(&err) || error::is_cancelled(&err) => process::exit(130),
+        Err(err) => {
+            println!("{}", err);
+            // Some errors already include their cause in their message
+            let mut shown = err.to_string();
+            for cause in err.chain().skip(1) {
+                let cause = cause.to_string();
+                if !shown.contains(&cause) {
+                    println!("  {}", cause);
+                }
+                shown = cause;
+            }
+            process::exit(
+                err.downcast_ref::<error::JaimeError>()
+                    .map_or(1, error::JaimeError::exit_code),
+            );
+        },
+    }
+}
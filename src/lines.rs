@@ -0,0 +1,67 @@
+//! Post-processing of the lines listed by `FromCommand` widgets, so configs do
+//! not need `sort -u | grep` suffixes
+
+use crate::text;
+use anyhow::{Context as AnyhowContext, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How the lines are sorted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Sort {
+    /// Lines are kept in the order of the output
+    None,
+    /// Alphabetical order, ignoring case and diacritics
+    Alpha,
+    /// By the number the line starts with. Lines not starting with a number
+    /// are placed last
+    Numeric,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// The number a line starts with
+fn leading_number(line: &str) -> Option<f64> {
+    line.split_whitespace().next()?.parse().ok()
+}
+
+/// Filter, deduplicate, and sort the lines
+pub(crate) fn process<'a>(
+    lines: Vec<&'a str>,
+    sort: Sort,
+    unique: bool,
+    filter: Option<&str>,
+) -> Result<Vec<&'a str>> {
+    let filter = filter
+        .map(Regex::new)
+        .transpose()
+        .context("invalid filter")?;
+
+    // Lines are deduplicated by their normalized form, so that differently
+    // encoded but identical lines are only listed once
+    let mut seen = HashSet::new();
+    let mut lines = lines
+        .into_iter()
+        .filter(|line| filter.as_ref().map_or(true, |re| re.is_match(line)))
+        .filter(|line| !unique || seen.insert(text::normalize(line)))
+        .collect::<Vec<_>>();
+
+    match sort {
+        Sort::None => {},
+        Sort::Alpha => lines.sort_by(|a, b| text::compare(a, b)),
+        Sort::Numeric => lines.sort_by(|a, b| match (leading_number(a), leading_number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => text::compare(a, b),
+        }),
+    }
+
+    Ok(lines)
+}
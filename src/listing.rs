@@ -0,0 +1,94 @@
+//! Output of a command read a page at a time, so that an accidentally huge
+//! listing (e.g., `find /`) neither freezes the selector nor has to finish
+//! before anything is displayed
+
+use anyhow::{Context as AnyhowContext, Result};
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+/// The key loading the next page of items
+pub(crate) const KEY: &str = "ctrl-l";
+
+/// The lines of a running command, read as more of them are asked for. The
+/// command is blocked on its output in between, and killed once the listing
+/// is dropped
+#[derive(Debug)]
+pub(crate) struct Listing {
+    child:  Child,
+    reader: BufReader<ChildStdout>,
+    lines:  Vec<String>,
+    /// Number of lines read per page
+    page:   usize,
+    /// Whether the command's output has been read to the end
+    done:   bool,
+}
+
+impl Listing {
+    /// Run `command`, without reading anything yet
+    pub(crate) fn spawn(mut command: Command, page: usize) -> Result<Self> {
+        let mut child = command
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .context("unable to run the command listing the items")?;
+        let stdout = child.stdout.take().context("no output to read")?;
+
+        Ok(Self {
+            child,
+            reader: BufReader::new(stdout),
+            lines: Vec::new(),
+            page: page.max(1),
+            done: false,
+        })
+    }
+
+    /// Read the next page of lines, unless the output already ended
+    pub(crate) fn next_page(&mut self) -> Result<()> {
+        let mut line = String::new();
+        let mut read = 0;
+
+        while !self.done && read < self.page {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                self.done = true;
+            } else {
+                self.lines
+                    .push(line.trim_end_matches(&['\n', '\r'][..]).to_owned());
+                read += 1;
+            }
+        }
+        // Find out whether there is anything past the page, so the last page
+        // doesn't offer to load more
+        if !self.done && self.reader.fill_buf()?.is_empty() {
+            self.done = true;
+        }
+
+        Ok(())
+    }
+
+    /// The output read so far
+    pub(crate) fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// The line displayed after the items while there are more to load
+    pub(crate) fn more(&self) -> Option<String> {
+        if self.done {
+            None
+        } else {
+            Some(format!(
+                "…and more (press {} to load {} more)",
+                KEY, self.page
+            ))
+        }
+    }
+}
+
+impl Drop for Listing {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,349 @@
+//! Locks of the `exclusive` commands, so that a long-running command (a
+//! deployment, a backup…) isn't launched twice from different sessions.
+//!
+//! The lock is a file in the `locks` directory of the cache, locked while the
+//! command runs, and released by the system if Jaime dies. It says who runs the
+//! command since when, and the output of the command is written next to it, so
+//! that another session can follow it
+
+use crate::{
+    app::Handler,
+    builtin,
+    exec::Interrupt,
+    runner::{self, Context},
+    theme, user,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::PathBuf,
+    process, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How often the output of a command running elsewhere is read
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Who holds a lock, written in it
+#[derive(Serialize, Deserialize, Debug)]
+struct Holder {
+    pid:  u32,
+    user: String,
+    /// UNIX timestamp of when the command was started
+    time: u64,
+}
+
+/// A lock held while a command runs, released when dropped
+#[derive(Debug)]
+pub(crate) struct Lock {
+    /// Locked until closed
+    _file:          File,
+    /// Where the output of the command is written
+    pub(crate) log: PathBuf,
+}
+
+/// How [`acquire`] went
+#[derive(Debug)]
+pub(crate) enum Acquired {
+    /// The command can run
+    Locked(Lock),
+    /// The command was already running, and its output was followed until it
+    /// finished
+    Followed,
+    /// The command was already running, and the user cancelled
+    Cancelled,
+}
+
+/// What can be done when the command is already running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Choice {
+    Follow,
+    Wait,
+}
+
+impl Choice {
+    const ALL: [Self; 2] = [Self::Follow, Self::Wait];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Follow => "follow",
+            Self::Wait => "wait",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::Follow => "Follow its output until it finishes",
+            Self::Wait => "Wait for it to finish, then run it again",
+        }
+    }
+}
+
+/// The lock and output files of the command at `path`
+fn files(context: &Context, path: &[String]) -> Result<(PathBuf, PathBuf)> {
+    let directory = context.cache_directory.join("locks");
+    fs::create_dir_all(&directory).context(format!("unable to create: {}", directory.display()))?;
+    let name = path.join(".").replace('/', "_");
+
+    Ok((
+        directory.join(format!("{}.lock", name)),
+        directory.join(format!("{}.log", name)),
+    ))
+}
+
+/// Lock `file`, waiting for it to be released if `wait`. Returns whether it
+/// was locked
+#[cfg(unix)]
+pub(crate) fn lock(file: &File, wait: bool) -> Result<bool> {
+    use nix::{
+        errno::Errno,
+        fcntl::{flock, FlockArg},
+    };
+    use std::os::unix::io::AsRawFd;
+
+    let arg = if wait {
+        FlockArg::LockExclusive
+    } else {
+        FlockArg::LockExclusiveNonblock
+    };
+    match flock(file.as_raw_fd(), arg) {
+        Ok(()) => Ok(true),
+        Err(Errno::EWOULDBLOCK) => Ok(false),
+        Err(e) => Err(anyhow!("unable to lock: {}", e)),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn lock(_file: &File, _wait: bool) -> Result<bool> {
+    Ok(true)
+}
+
+/// Lock the command at `path`, waiting for it to be released if `wait`
+fn try_acquire(context: &Context, path: &[String], wait: bool) -> Result<Option<Lock>> {
+    let (lock_path, log) = files(context, path)?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        // Only once locked
+        .truncate(false)
+        .open(&lock_path)
+        .context(format!("unable to open: {}", lock_path.display()))?;
+    if !lock(&file, wait)? {
+        return Ok(None);
+    }
+
+    let holder = Holder {
+        pid:  process::id(),
+        user: user::name().to_owned(),
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+    };
+    file.set_len(0)?;
+    file.write_all(serde_json::to_string(&holder)?.as_bytes())?;
+    File::create(&log).context(format!("unable to create: {}", log.display()))?;
+
+    Ok(Some(Lock { _file: file, log }))
+}
+
+/// Who holds the lock of the command at `path`, if it can be read
+fn holder(context: &Context, path: &[String]) -> Option<Holder> {
+    let (lock_path, _) = files(context, path).ok()?;
+    serde_json::from_str(&fs::read_to_string(lock_path).ok()?).ok()
+}
+
+/// Print the output of the command at `path` as it comes, until its lock is
+/// released. `ctrl-c` stops following it, or if `kill`, interrupts the command
+/// first (as if `ctrl-c` was pressed in its session) and follows it until it
+/// exits
+fn follow(context: &Context, path: &[String], kill: bool) -> Result<()> {
+    let (_, log) = files(context, path)?;
+    let mut file = File::open(&log).context(format!("unable to open: {}", log.display()))?;
+    let ctrl_c = Interrupt::catch()?;
+
+    let mut stdout = io::stdout();
+    let mut buf = Vec::new();
+    let mut killed = false;
+    let result = loop {
+        // Checked before reading, so that the end of the output is printed
+        let finished = match released(context, path) {
+            Ok(finished) => finished,
+            Err(e) => break Err(e),
+        };
+        buf.clear();
+        if let Err(e) = file
+            .read_to_end(&mut buf)
+            .and_then(|_| stdout.write_all(&buf))
+            .and_then(|()| stdout.flush())
+        {
+            break Err(e.into());
+        }
+        if finished {
+            break Ok(());
+        }
+
+        if ctrl_c.take() {
+            if !kill || killed {
+                break Ok(());
+            }
+            if let Some(holder) = holder(context, path) {
+                if let Err(e) = interrupt(holder.pid) {
+                    break Err(e);
+                }
+            }
+            killed = true;
+        }
+        thread::sleep(FOLLOW_INTERVAL);
+    };
+
+    drop(ctrl_c);
+    result
+}
+
+/// Send `SIGINT` to the process group of the session `pid`, which Jaime and
+/// the command it runs are part of
+#[cfg(unix)]
+fn interrupt(pid: u32) -> Result<()> {
+    use nix::{
+        sys::signal::{killpg, Signal},
+        unistd::{getpgid, Pid},
+    };
+
+    let group = getpgid(Some(Pid::from_raw(i32::try_from(pid)?)))?;
+    killpg(group, Signal::SIGINT)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn interrupt(_pid: u32) -> Result<()> {
+    Err(anyhow!(
+        "interrupting a command of another session is only supported on Unix"
+    ))
+}
+
+/// Whether the lock of the command at `path` was released. It is only taken
+/// until the file is closed, at the end of the function
+fn released(context: &Context, path: &[String]) -> Result<bool> {
+    let (lock_path, _) = files(context, path)?;
+    let file =
+        File::open(&lock_path).context(format!("unable to open: {}", lock_path.display()))?;
+    lock(&file, false)
+}
+
+/// Lock the command at `path` before running it. When it is already running,
+/// say since when and offer to follow its output or to wait for it
+pub(crate) fn acquire(context: &Context, handler: &Handler, path: &[String]) -> Result<Acquired> {
+    if let Some(lock) = try_acquire(context, path, false)? {
+        return Ok(Acquired::Locked(lock));
+    }
+
+    let since = match holder(context, path) {
+        Some(holder) => format!(
+            ", started {} by {} (pid {})",
+            builtin::relative_time(i64::try_from(holder.time).unwrap_or(i64::MAX)),
+            holder.user,
+            holder.pid
+        ),
+        None => String::new(),
+    };
+    eprintln!(
+        "{} {} is already running{}",
+        theme::get().info.paint("[jaime]"),
+        path.join("."),
+        since
+    );
+
+    let input = Choice::ALL
+        .iter()
+        .map(|choice| {
+            format!(
+                "{}: {}",
+                theme::get().key.paint(choice.name()),
+                theme::get().description.paint(choice.description())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let selected = match runner::select(context, handler, &input, None)? {
+        Some(selected) => selected,
+        None => return Ok(Acquired::Cancelled),
+    };
+    let name = selected.split(':').next().unwrap_or_default();
+
+    match Choice::ALL.iter().find(|choice| choice.name() == name) {
+        Some(Choice::Follow) => {
+            follow(context, path, false)?;
+            Ok(Acquired::Followed)
+        },
+        Some(Choice::Wait) => {
+            eprintln!(
+                "{} {}",
+                theme::get().info.paint("[jaime]"),
+                theme::get()
+                    .dimmed
+                    .paint(&format!("waiting for {} to finish", path.join(".")))
+            );
+            Ok(try_acquire(context, path, true)?.map_or(Acquired::Cancelled, Acquired::Locked))
+        },
+        None => Err(anyhow!("unknown choice: {}", name)),
+    }
+}
+
+/// The dotted paths of the commands running, along with who runs them since
+/// when
+fn running(context: &Context) -> Result<Vec<(String, Option<Holder>)>> {
+    let directory = context.cache_directory.join("locks");
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut running = Vec::new();
+    for entry in
+        fs::read_dir(&directory).context(format!("unable to read: {}", directory.display()))?
+    {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        let path = match name.strip_suffix(".lock") {
+            Some(path) => path.split('.').map(ToOwned::to_owned).collect::<Vec<_>>(),
+            None => continue,
+        };
+        if !released(context, &path)? {
+            running.push((path.join("."), holder(context, &path)));
+        }
+    }
+    running.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(running)
+}
+
+/// Print the `exclusive` commands running, for `jaime jobs`
+pub(crate) fn list(context: &Context) -> Result<()> {
+    for (path, holder) in running(context)? {
+        match holder {
+            Some(holder) => println!(
+                "{}\t{}\t{}\t{}",
+                theme::get().key.paint(&path),
+                builtin::relative_time(i64::try_from(holder.time).unwrap_or(i64::MAX)),
+                holder.user,
+                holder.pid
+            ),
+            None => println!("{}", theme::get().key.paint(&path)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Follow the output of the `exclusive` command at the dotted `path` running
+/// in another session, for `jaime jobs attach`
+pub(crate) fn attach(context: &Context, path: &str, kill: bool) -> Result<()> {
+    if !running(context)?.iter().any(|(running, _)| running == path) {
+        return Err(anyhow!("{} isn't running", path));
+    }
+
+    let path = path.split('.').map(ToOwned::to_owned).collect::<Vec<_>>();
+    follow(context, &path, kill)
+}
@@ -0,0 +1,132 @@
+//! Lines listing the entries of a menu in the selectors
+
+/// Parameters of the escape codes of the styles used for the entries (e.g.,
+/// `1;32`, the ones `colored` writes)
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Styles<'a> {
+    pub(crate) key:         &'a str,
+    pub(crate) description: &'a str,
+    /// Disabled entries, the reasons they are, and the tags
+    pub(crate) dimmed:      &'a str,
+}
+
+/// Start of the lines heading the entries of a group, which can't be ran
+pub(crate) const GROUP: &str = "── ";
+
+/// What is shown of an entry of a menu
+#[derive(Debug)]
+pub(crate) struct Entry<'a> {
+    pub(crate) key:         &'a str,
+    pub(crate) description: Option<&'a str>,
+    /// Why the entry can't be ran, if it is disabled
+    pub(crate) reason:      Option<String>,
+    pub(crate) tags:        &'a [String],
+    /// The entries of a group follow each other, after its heading
+    pub(crate) group:       Option<&'a str>,
+}
+
+/// Appends styled text to a buffer, without the intermediate strings that
+/// `colored` allocates
+#[derive(Debug)]
+struct Writer<'a> {
+    out:    &'a mut String,
+    color:  bool,
+    styles: Styles<'a>,
+}
+
+impl Writer<'_> {
+    fn start(&mut self, style: &str) {
+        if self.color {
+            self.out.push_str("\x1b[");
+            self.out.push_str(style);
+            self.out.push('m');
+        }
+    }
+
+    fn end(&mut self) {
+        if self.color {
+            self.out.push_str("\x1b[0m");
+        }
+    }
+
+    fn styled(&mut self, style: &str, text: &str) {
+        self.start(style);
+        self.out.push_str(text);
+        self.end();
+    }
+
+    /// Append the line of `entry`
+    fn entry(&mut self, entry: &Entry<'_>) {
+        let disabled = entry.reason.is_some();
+        let Styles {
+            key,
+            description: description_style,
+            dimmed,
+        } = self.styles;
+
+        self.styled(if disabled { dimmed } else { key }, entry.key);
+        if let Some(description) = entry.description {
+            self.out.push_str(": ");
+            self.styled(
+                if disabled { dimmed } else { description_style },
+                description,
+            );
+        }
+        if let Some(reason) = &entry.reason {
+            self.out.push_str("  ");
+            self.start(dimmed);
+            self.out.push('(');
+            self.out.push_str(reason);
+            self.out.push(')');
+            self.end();
+        }
+        // Dimmed, but still matched by the selectors
+        if !entry.tags.is_empty() {
+            self.out.push_str("  ");
+            self.start(dimmed);
+            for (index, tag) in entry.tags.iter().enumerate() {
+                if index > 0 {
+                    self.out.push(' ');
+                }
+                self.out.push_str(tag);
+            }
+            self.end();
+        }
+    }
+}
+
+/// The lines of `entries` separated by newlines, written into a single buffer
+/// as the entries are produced, in `styles`
+// `'_` can't be used in an `impl Trait` argument
+#[allow(single_use_lifetimes)]
+pub(crate) fn render<'a>(
+    entries: impl IntoIterator<Item = Entry<'a>>,
+    styles: Styles<'_>,
+) -> String {
+    let entries = entries.into_iter();
+    let mut out = String::with_capacity(entries.size_hint().0 * 32);
+    let mut writer = Writer {
+        out: &mut out,
+        color: colored::control::SHOULD_COLORIZE.should_colorize(),
+        styles,
+    };
+
+    let mut group = None;
+    for (index, entry) in entries.enumerate() {
+        if index > 0 {
+            writer.out.push('\n');
+        }
+        // A heading starts each group
+        if let Some(name) = entry.group.filter(|name| group != Some(*name)) {
+            writer.start(writer.styles.dimmed);
+            writer.out.push_str(GROUP);
+            writer.out.push_str(name);
+            writer.end();
+            writer.out.push('\n');
+        }
+        group = entry.group;
+        writer.entry(&entry);
+    }
+
+    out
+}
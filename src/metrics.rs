@@ -0,0 +1,158 @@
+//! Counters of the commands ran, for the operations teams observing how shared
+//! menus are used.
+//!
+//! The counters are kept in `metrics.json` in the cache directory, so that they
+//! add up across the invocations of Jaime, and are exposed in the Prometheus
+//! text format: on `/metrics` by `jaime serve`, and in the file read by the
+//! textfile collector of the node exporter when `metrics.textfile` is given
+
+use crate::{lock, runner::Context};
+use anyhow::{Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs::{self, OpenOptions},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Settings of the metrics, which are only recorded when they are given
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct Metrics {
+    /// File rewritten with the counters after each command, for the textfile
+    /// collector of the node exporter
+    pub(crate) textfile: Option<String>,
+}
+
+/// The counters of an action
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+struct Counter {
+    runs:     u64,
+    failures: u64,
+    /// Time spent running the command, in seconds
+    seconds:  f64,
+}
+
+/// The counters, keyed by the dotted path of the actions
+type Counters = BTreeMap<String, Counter>;
+
+/// Name, help, and value of the counters exposed
+type Family = (&'static str, &'static str, fn(&Counter) -> String);
+
+fn file(context: &Context) -> PathBuf {
+    context.cache_directory.join("metrics.json")
+}
+
+fn load(context: &Context) -> Result<Counters> {
+    let path = file(context);
+    if !path.exists() {
+        return Ok(Counters::new());
+    }
+
+    serde_json::from_str(&fs::read_to_string(&path).context("unable to read the metrics")?)
+        .context("invalid metrics")
+}
+
+/// Count a command ran for `elapsed`, when the metrics are enabled
+pub(crate) fn record(
+    context: &Context,
+    metrics: Option<&Metrics>,
+    path: &[String],
+    failed: bool,
+    elapsed: Duration,
+) -> Result<()> {
+    let metrics = match metrics {
+        Some(metrics) => metrics,
+        None => return Ok(()),
+    };
+
+    // Commands finishing at the same time in other sessions would otherwise
+    // lose each other's counts. The lock is released when the file is closed
+    let lock_path = context.cache_directory.join("metrics.lock");
+    let lock_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&lock_path)
+        .context(format!("unable to open: {}", lock_path.display()))?;
+    lock::lock(&lock_file, true)?;
+
+    let mut counters = load(context)?;
+    let counter = counters.entry(path.join(".")).or_default();
+    counter.runs += 1;
+    if failed {
+        counter.failures += 1;
+    }
+    counter.seconds += elapsed.as_secs_f64();
+    // `jaime serve` may read the file at any time, so it is replaced at once
+    let partial = file(context).with_extension("json.tmp");
+    fs::write(&partial, serde_json::to_string(&counters)?)
+        .and_then(|()| fs::rename(&partial, file(context)))
+        .context("unable to write the metrics")?;
+
+    if let Some(textfile) = &metrics.textfile {
+        let textfile = match (textfile.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(textfile),
+        };
+        // The collector may read the file at any time, so it is replaced at
+        // once rather than written in place
+        let partial = textfile.with_extension("prom.tmp");
+        fs::write(&partial, format(&counters))
+            .and_then(|()| fs::rename(&partial, &textfile))
+            .with_context(|| format!("unable to write {}", textfile.display()))?;
+    }
+    drop(lock_file);
+
+    Ok(())
+}
+
+/// The counters recorded so far, in the Prometheus text format
+#[cfg(feature = "web")]
+pub(crate) fn render(context: &Context) -> Result<String> {
+    Ok(format(&load(context)?))
+}
+
+fn format(counters: &Counters) -> String {
+    let mut text = String::new();
+    let families: [Family; 3] = [
+        ("jaime_runs_total", "Commands ran, by action", |counter| {
+            counter.runs.to_string()
+        }),
+        (
+            "jaime_failures_total",
+            "Commands that failed, timed out, or were cancelled, by action",
+            |counter| counter.failures.to_string(),
+        ),
+        (
+            "jaime_run_seconds_total",
+            "Time spent running the commands, by action",
+            |counter| counter.seconds.to_string(),
+        ),
+    ];
+
+    for (name, help, value) in &families {
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+        let _ = writeln!(text, "# TYPE {} counter", name);
+        for (path, counter) in counters {
+            let _ = writeln!(
+                text,
+                "{}{{action=\"{}\"}} {}",
+                name,
+                escape(path),
+                value(counter)
+            );
+        }
+    }
+
+    text
+}
+
+/// `label` escaped to be a label value
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
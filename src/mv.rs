@@ -0,0 +1,270 @@
+//! `jaime mv`: move an action to another dotted path of the menu by editing
+//! the lines of the configuration, so that its comments and layout are kept.
+//! The `Menu` actions leading to it, and the history, are updated as well.
+//!
+//! Only block mappings are edited. The result is parsed again before being
+//! written, and the move is refused if the action didn't end up, unchanged, at
+//! its new path
+
+use crate::{history, runner::Context};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_yaml::Value;
+use std::fs;
+
+/// The `path` field of a `Menu` action, with the quotes and the comment around
+/// its value
+static MENU_PATH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^(\s*path:\s*)(["']?)([^"'#\s]+)(["']?)(\s*(?:#.*)?)$"#).unwrap());
+
+/// Number of spaces before the first character of `line`
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Whether `line` has no content, being empty or a comment
+fn is_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+/// The key starting `line` as written, and the rest of the line from the
+/// colon following it, if `line` starts with the key of a mapping
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let colon = line.char_indices().find_map(|(index, c)| {
+        let followed = line[index + 1..].chars().next();
+        (c == ':' && followed.map_or(true, char::is_whitespace)).then_some(index)
+    })?;
+
+    Some(line.split_at(colon))
+}
+
+/// The key starting `line`, unquoted, if it is the key of a mapping
+fn key(line: &str) -> Option<&str> {
+    let (key, _) = split_key(line)?;
+
+    Some(
+        key.strip_prefix('"')
+            .and_then(|k| k.strip_suffix('"'))
+            .or_else(|| key.strip_prefix('\'').and_then(|k| k.strip_suffix('\'')))
+            .unwrap_or(key),
+    )
+}
+
+/// The index past the last line of the value of the key at `start`. The
+/// trailing empty lines are excluded, along with the comments that aren't
+/// indented below the key, which are taken as belonging to what follows
+fn block_end(lines: &[String], start: usize) -> usize {
+    let indent = indentation(&lines[start]);
+    let mut end = start + 1;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indentation(line) > indent {
+            end = index + 1;
+        } else if !is_blank(line) {
+            break;
+        }
+    }
+    end
+}
+
+/// The keys of the mapping that is the value of the key at `parent`, with the
+/// indices of their lines
+fn children(lines: &[String], parent: usize) -> Vec<(&str, usize)> {
+    let end = block_end(lines, parent);
+    let mut indent = None;
+
+    lines[parent + 1..end]
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !is_blank(line))
+        .filter(|(_, line)| *indent.get_or_insert(indentation(line)) == indentation(line))
+        .filter_map(|(offset, line)| Some((key(line)?, parent + 1 + offset)))
+        .collect()
+}
+
+/// The index of the line of the `options` key of the menu at `path` (the root
+/// menu if empty)
+fn options_line(lines: &[String], path: &[&str]) -> Result<usize> {
+    let mut options = lines
+        .iter()
+        .position(|line| indentation(line) == 0 && key(line) == Some("options"))
+        .ok_or_else(|| anyhow!("`options:` wasn't found at the start of a line"))?;
+
+    for (depth, segment) in path.iter().enumerate() {
+        let entry = entry_line(lines, options, segment)
+            .ok_or_else(|| anyhow!("{} wasn't found in the file", path[..=depth].join(".")))?;
+        options = children(lines, entry)
+            .into_iter()
+            .find(|(key, _)| *key == "options")
+            .map(|(_, index)| index)
+            .ok_or_else(|| anyhow!("{} isn't a menu", path[..=depth].join(".")))?;
+    }
+
+    Ok(options)
+}
+
+/// The index of the line of the option `key` of the `options` at `options`
+fn entry_line(lines: &[String], options: usize, key: &str) -> Option<usize> {
+    children(lines, options)
+        .into_iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, index)| index)
+}
+
+/// The action at `path` of the configuration `value`
+fn value_at<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    let (last, menus) = path.split_last()?;
+    let mut options = value.get("options")?;
+    for menu in menus {
+        options = options.get(menu)?.get("options")?;
+    }
+    options.get(last)
+}
+
+/// `key` written as a key of a mapping, quoted if needed
+fn quoted(key: &str) -> String {
+    serde_yaml::to_string(&Value::from(key)).map_or_else(
+        |_| key.to_owned(),
+        |text| text.trim_start_matches("---").trim().to_owned(),
+    )
+}
+
+/// The path an action at `path` has once the action at `old` is moved to
+/// `new`, if it is that action or one below it
+fn moved(path: &str, old: &str, new: &str) -> Option<String> {
+    if path == old {
+        Some(new.to_owned())
+    } else {
+        path.strip_prefix(old)
+            .filter(|rest| rest.starts_with('.'))
+            .map(|rest| format!("{}{}", new, rest))
+    }
+}
+
+/// The configuration `text` with the action at `old` moved to `new`, and the
+/// number of `Menu` actions leading to it that were updated
+fn edit(text: &str, old: &[&str], new: &[&str]) -> Result<(String, usize)> {
+    let value = serde_yaml::from_str::<Value>(text).context("invalid configuration")?;
+    let action = value_at(&value, old)
+        .ok_or_else(|| anyhow!("{} does not exist in the menu", old.join(".")))?;
+    if value_at(&value, new).is_some() {
+        return Err(anyhow!("{} already exists in the menu", new.join(".")));
+    }
+    if new.starts_with(old) {
+        return Err(anyhow!("{} can't be moved into itself", old.join(".")));
+    }
+    let (key, parent) = new.split_last().context("empty menu path")?;
+    if !parent.is_empty()
+        && value_at(&value, parent)
+            .and_then(|p| p.get("options"))
+            .is_none()
+    {
+        return Err(anyhow!("{} isn't a menu", parent.join(".")));
+    }
+
+    let mut lines = text.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+    let (old_key, old_parent) = old.split_last().context("empty menu path")?;
+    let old_options = options_line(&lines, old_parent)?;
+    let start = entry_line(&lines, old_options, old_key)
+        .ok_or_else(|| anyhow!("{} wasn't found in the file", old.join(".")))?;
+    if children(&lines, old_options).len() == 1 && old_parent != parent {
+        return Err(anyhow!(
+            "{} is the only option of its menu, which can't be left empty",
+            old.join(".")
+        ));
+    }
+
+    // The comments right above the action go along with it
+    let mut first = start;
+    while first > 0
+        && lines[first - 1].trim_start().starts_with('#')
+        && indentation(&lines[first - 1]) == indentation(&lines[start])
+    {
+        first -= 1;
+    }
+    let end = block_end(&lines, start);
+    let old_indent = indentation(&lines[start]);
+    let mut block = lines.drain(first..end).collect::<Vec<_>>();
+
+    let options = options_line(&lines, parent)?;
+    // The menu is left empty when the action is renamed in place
+    let indent = children(&lines, options)
+        .first()
+        .map_or(old_indent, |(_, index)| indentation(&lines[*index]));
+    for line in &mut block {
+        if !line.trim().is_empty() {
+            let strip = indentation(line).min(old_indent);
+            *line = format!("{}{}", " ".repeat(indent), &line[strip..]);
+        }
+    }
+    let renamed = split_key(&block[start - first])
+        .map(|(_, rest)| format!("{}{}{}", " ".repeat(indent), quoted(key), rest));
+    if let Some(renamed) = renamed {
+        block[start - first] = renamed;
+    }
+
+    let at = block_end(&lines, options);
+    lines.splice(at..at, block);
+
+    let mut edited = lines.join("\n");
+    edited.push('\n');
+    let parsed = serde_yaml::from_str::<Value>(&edited).ok();
+    if parsed.as_ref().and_then(|value| value_at(value, new)) != Some(action) {
+        return Err(anyhow!(
+            "unable to move {} in the file, its layout isn't supported",
+            old.join(".")
+        ));
+    }
+
+    // The `Menu` actions leading to the moved action, or below it
+    let (old, new) = (old.join("."), new.join("."));
+    let mut references = 0;
+    for line in &mut lines {
+        let updated = MENU_PATH.captures(line).and_then(|captures| {
+            let target = moved(&captures[3], &old, &new)?;
+            Some(format!(
+                "{}{}{}{}{}",
+                &captures[1], &captures[2], target, &captures[4], &captures[5]
+            ))
+        });
+        if let Some(updated) = updated {
+            *line = updated;
+            references += 1;
+        }
+    }
+    let mut edited = lines.join("\n");
+    edited.push('\n');
+    serde_yaml::from_str::<Value>(&edited).context("unable to update the `Menu` actions")?;
+
+    Ok((edited, references))
+}
+
+/// Move the action at the dotted path `old` of the configuration to `new`,
+/// updating the `Menu` actions and the history
+pub(crate) fn run(context: &Context, old: &str, new: &str) -> Result<()> {
+    let path = &context.config_path;
+    let text = fs::read_to_string(path).context(format!("unable to read: {}", path.display()))?;
+    let (edited, references) = edit(
+        &text,
+        &old.split('.').collect::<Vec<_>>(),
+        &new.split('.').collect::<Vec<_>>(),
+    )?;
+    fs::write(path, edited).context(format!("unable to write: {}", path.display()))?;
+    let entries = history::rename(context, |path| moved(path, old, new))?;
+
+    println!(
+        "Moved {} to {}, updating {} menu reference(s) and {} history entr{}",
+        old,
+        new,
+        references,
+        entries,
+        if entries == 1 { "y" } else { "ies" }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+use crate::exec::Exit;
+use anyhow::Result;
+use notify_rust::Notification;
+use std::time::Duration;
+
+/// Send a desktop notification stating how the action at `path` finished
+pub(crate) fn send(path: &str, exit: &Exit, elapsed: Duration) -> Result<()> {
+    Notification::new()
+        .summary("jaime")
+        .body(&format!("{} {} after {}s", path, exit, elapsed.as_secs()))
+        .show()?;
+
+    Ok(())
+}
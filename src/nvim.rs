@@ -0,0 +1,254 @@
+//! `jaime nvim-server`: msgpack-RPC on the standard input and output, for a
+//! Neovim plugin starting Jaime with `jobstart(['jaime', 'nvim-server'],
+//! {'rpc': v:true})`. The plugin displays the menu itself (e.g., with
+//! telescope), and has Jaime prepare the command of an action to run it in a
+//! terminal of its own (e.g., toggleterm).
+//!
+//! Requests:
+//! - `menu`: the tree of the menu, as printed by `jaime tree --json`
+//! - `prepare(path)`: display the widgets of the command at the dotted `path`,
+//!   returning `{command, shell, env, output}`, or nil if a selection was
+//!   cancelled
+//!
+//! The widgets are displayed in Neovim as well: Jaime calls
+//! `require('jaime').select(items, multi)` and `require('jaime').input(prompt,
+//! initial)` through `nvim_exec_lua`, which answer with the `select`
+//! notification (the indices of the selected items, counting from 0) and the
+//! `input` notification (the text). Both send nil when cancelled
+
+use crate::{
+    app::Handler,
+    audit, error, exec,
+    frontend::{self, Frontend, Output},
+    history, jaime_error, policy,
+    runner::{self, Action, Config, Context, Prepared},
+    tree,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::{self, BufReader, ErrorKind, Stdin, Stdout, Write},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// Type of the msgpack-RPC messages
+const REQUEST: u64 = 0;
+const RESPONSE: u64 = 1;
+const NOTIFICATION: u64 = 2;
+
+/// The standard input and output, where the messages go
+#[derive(Debug)]
+struct Channel {
+    input:  BufReader<Stdin>,
+    output: Stdout,
+}
+
+impl Channel {
+    /// The next message, `None` once Neovim closed the channel
+    fn read(&mut self) -> Result<Option<Value>> {
+        match rmp_serde::from_read(&mut self.input) {
+            Ok(message) => Ok(Some(message)),
+            Err(rmp_serde::decode::Error::InvalidMarkerRead(e))
+                if e.kind() == ErrorKind::UnexpectedEof =>
+                Ok(None),
+            Err(e) => Err(anyhow!("invalid message: {}", e)),
+        }
+    }
+
+    fn write(&mut self, message: &impl Serialize) -> Result<()> {
+        rmp_serde::encode::write_named(&mut self.output, message)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    /// Answer the request `id` with `result`
+    fn respond(&mut self, id: u64, result: Result<Value>) -> Result<()> {
+        match result {
+            Ok(result) => self.write(&(RESPONSE, id, Value::Null, result)),
+            Err(e) => self.write(&(RESPONSE, id, format!("{:#}", e), Value::Null)),
+        }
+    }
+
+    /// Call the function `name` of the Lua module of the plugin with `args`,
+    /// returning the value of the notification `answer` it sends back
+    fn call(&mut self, name: &str, args: &impl Serialize, answer: &str) -> Result<Value> {
+        self.write(&(
+            NOTIFICATION,
+            "nvim_exec_lua",
+            (format!("require('jaime').{}(...)", name), args),
+        ))?;
+
+        loop {
+            let message = self
+                .read()?
+                .ok_or_else(|| anyhow!("Neovim closed the channel"))?;
+            if let Some((id, _, _)) = request(&message) {
+                self.respond(id, Err(anyhow!("jaime is waiting for {}", answer)))?;
+            } else if let Some((method, params)) = notification(&message) {
+                if method == answer {
+                    return Ok(params.first().cloned().unwrap_or(Value::Null));
+                }
+            }
+        }
+    }
+}
+
+fn lock(channel: &Mutex<Channel>) -> MutexGuard<'_, Channel> {
+    channel.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The id, method, and parameters of `message` if it is a request
+fn request(message: &Value) -> Option<(u64, &str, &[Value])> {
+    match message.as_array()?.as_slice() {
+        [kind, id, method, params] if kind.as_u64() == Some(REQUEST) =>
+            Some((id.as_u64()?, method.as_str()?, params.as_array()?)),
+        _ => None,
+    }
+}
+
+/// The method and parameters of `message` if it is a notification
+fn notification(message: &Value) -> Option<(&str, &[Value])> {
+    match message.as_array()?.as_slice() {
+        [kind, method, params] if kind.as_u64() == Some(NOTIFICATION) =>
+            Some((method.as_str()?, params.as_array()?)),
+        _ => None,
+    }
+}
+
+/// Displays the widgets in Neovim
+#[derive(Debug)]
+struct Neovim {
+    channel: Arc<Mutex<Channel>>,
+}
+
+impl Frontend for Neovim {
+    fn select(
+        &mut self,
+        items: &[&str],
+        _preview: Option<&str>,
+        multi: bool,
+    ) -> Result<Option<Vec<usize>>> {
+        let selected = lock(&self.channel).call("select", &(items, multi), "select")?;
+
+        Ok(match selected {
+            Value::Array(indices) => Some(
+                indices
+                    .iter()
+                    .filter_map(Value::as_u64)
+                    .filter_map(|index| usize::try_from(index).ok())
+                    .collect(),
+            ),
+            Value::Number(index) => index
+                .as_u64()
+                .and_then(|index| usize::try_from(index).ok())
+                .map(|index| vec![index]),
+            _ => None,
+        })
+    }
+
+    fn read(&mut self, prompt: &str, initial: &str) -> Result<Option<String>> {
+        let line = lock(&self.channel).call("input", &(prompt, initial), "input")?;
+        Ok(line.as_str().map(ToOwned::to_owned))
+    }
+
+    fn output(&self) -> Output {
+        Output::Stderr
+    }
+}
+
+/// A command whose widgets were displayed, for the plugin to run
+#[derive(Serialize, Debug)]
+struct Runnable {
+    command: String,
+    shell:   String,
+    env:     BTreeMap<String, String>,
+    output:  runner::Output,
+}
+
+/// Display the widgets of the command at `path`, returning what runs it, or
+/// `null` if a selection was cancelled
+fn prepare(context: &Context, config: &Config, handler: &Handler, path: &str) -> Result<Value> {
+    let (path, action) = config.find(path)?;
+    if let Some(reason) = action.meta().disabled_reason() {
+        return Err(anyhow!("{} is disabled: {}", path.join("."), reason));
+    }
+    let output = match action {
+        Action::Command { output, .. } => *output,
+        _ => return Err(anyhow!("{} isn't a command", path.join("."))),
+    };
+
+    let _strict = exec::strict_for(action.strict_shell());
+    let prepared = match action.prepare(context, config, handler, &path, None) {
+        Err(e) if error::is_cancelled(&e) || error::is_aborted(&e) => None,
+        prepared => prepared?,
+    };
+    let Prepared {
+        command,
+        shell,
+        envs,
+        values,
+    } = match prepared {
+        Some(prepared) => prepared,
+        None => return Ok(Value::Null),
+    };
+    if !policy::check(config.policy.as_ref(), &path, &command)? {
+        return Ok(Value::Null);
+    }
+
+    if let Err(e) = history::record(context, &path, &command, &values) {
+        jaime_error!("failed to record history: {}", e);
+    }
+    if let Err(e) = audit::record(
+        context,
+        config.audit.as_ref(),
+        &path,
+        &runner::masked(&command, &envs),
+    ) {
+        jaime_error!("failed to record the audit log: {}", e);
+    }
+
+    Ok(serde_json::to_value(Runnable {
+        command,
+        shell,
+        env: envs.into_iter().collect(),
+        output,
+    })?)
+}
+
+/// Answer the requests of the Neovim plugin until it closes the channel
+pub(crate) fn run(context: &Context, config: &Config, handler: &Handler) -> Result<()> {
+    let channel = Arc::new(Mutex::new(Channel {
+        input:  BufReader::new(io::stdin()),
+        output: io::stdout(),
+    }));
+    frontend::set(Some(Box::new(Neovim {
+        channel: Arc::clone(&channel),
+    })));
+
+    loop {
+        let message = match lock(&channel).read()? {
+            Some(message) => message,
+            None => return Ok(()),
+        };
+        let (id, method, params) = match request(&message) {
+            Some(request) => request,
+            // Notifications that came too late, e.g., after a timeout
+            None => continue,
+        };
+
+        let result = match method {
+            "menu" => serde_json::to_value(tree::nodes(&config.options, None))
+                .context("unable to send the menu"),
+            "prepare" => params
+                .first()
+                .and_then(Value::as_str)
+                .context("`prepare` takes the dotted path of a command")
+                .and_then(|path| prepare(context, config, handler, path)),
+            _ => Err(anyhow!("unknown method: {}", method)),
+        };
+        lock(&channel).respond(id, result)?;
+    }
+}
@@ -0,0 +1,60 @@
+use crate::theme;
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use std::{
+    env,
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+
+/// Pager used when `$PAGER` is not set
+const DEFAULT_PAGER: &str = "less -R";
+
+/// Display `text` in the user's `$PAGER`, falling back to `less`.
+///
+/// If neither is available, the text is printed and the user is prompted to
+/// continue, so the output doesn't flash by
+pub(crate) fn page(text: &str) -> Result<()> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let args = shlex::split(&pager).ok_or_else(|| anyhow!("invalid $PAGER: {}", pager))?;
+
+    match args.split_first() {
+        Some((bin, rest)) if which::which(bin).is_ok() => {
+            let mut child = Command::new(bin)
+                .args(rest)
+                .stdin(Stdio::piped())
+                .spawn()
+                .context(format!("failed to spawn {}", bin))?;
+
+            // The pager may exit before reading everything (e.g., `q`)
+            let _ = child
+                .stdin
+                .as_mut()
+                .context("failed to open stdin")?
+                .write_all(text.as_bytes());
+
+            child.wait()?;
+            Ok(())
+        },
+        _ => scroll(text),
+    }
+}
+
+/// Builtin fallback: print the text and wait for the user
+fn scroll(text: &str) -> Result<()> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    writeln!(stdout, "{}", text)?;
+    write!(
+        stdout,
+        "{}",
+        theme::get().dimmed.paint("-- press enter to continue --")
+    )?;
+    stdout.flush()?;
+
+    io::stdin().read_line(&mut String::new())?;
+    Ok(())
+}
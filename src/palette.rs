@@ -0,0 +1,182 @@
+//! Palette of jaime's own operations, opened by pressing `ctrl-p` in any
+//! selector
+
+use crate::{builtin, history, pager, runner::Context, theme};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use once_cell::sync::Lazy;
+use std::{
+    convert::TryFrom,
+    env,
+    process::Command,
+    sync::{Mutex, PoisonError},
+};
+
+/// Key opening the palette
+pub(crate) const KEY: &str = "ctrl-p";
+
+/// The command starting jaime over, once the selection is left. It is only
+/// ran by [`crate::main`], so the programs embedding jaime aren't replaced
+static RESTART: Lazy<Mutex<Option<Command>>> = Lazy::new(|| Mutex::new(None));
+
+/// An operation of the palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    ReloadConfig,
+    ToggleBackend,
+    EditConfig,
+    History,
+    Keybindings,
+}
+
+impl Operation {
+    const ALL: [Self; 5] = [
+        Self::ReloadConfig,
+        Self::ToggleBackend,
+        Self::EditConfig,
+        Self::History,
+        Self::Keybindings,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::ReloadConfig => "reload-config",
+            Self::ToggleBackend => "toggle-backend",
+            Self::EditConfig => "edit-config",
+            Self::History => "history",
+            Self::Keybindings => "keybindings",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Self::ReloadConfig => "Reload the configuration and start over",
+            Self::ToggleBackend => "Switch to the next selector (skim, fzf, sk)",
+            Self::EditConfig => "Open the configuration in $EDITOR, then reload it",
+            Self::History => "Show the commands that were ran",
+            Self::Keybindings => "Show the keybindings",
+        }
+    }
+
+    /// Run the operation. Returns whether jaime is to be started over, for
+    /// the operations that change the configuration or the selector
+    fn run(self, context: &Context) -> Result<bool> {
+        match self {
+            Self::ReloadConfig => restart(&[]),
+            Self::ToggleBackend => {
+                let args = env::args().skip(1).collect::<Vec<_>>();
+                let is = |long: &str, short: &str| args.iter().any(|a| a == long || a == short);
+
+                let next: &[&str] = if is("--fzf", "-f") {
+                    &["--skim-binary"]
+                } else if is("--skim-binary", "-s") {
+                    &[]
+                } else {
+                    &["--fzf"]
+                };
+                restart(next)
+            },
+            Self::EditConfig => {
+                let editor = env::var("VISUAL")
+                    .or_else(|_| env::var("EDITOR"))
+                    .unwrap_or_else(|_| String::from("vi"));
+                let mut words = shlex::split(&editor).unwrap_or_else(|| vec![editor.clone()]);
+                let program = words.remove(0);
+
+                Command::new(&program)
+                    .args(words)
+                    .arg(&context.config_path)
+                    .status()
+                    .context(format!("failed to run {}", program))?;
+                restart(&[])
+            },
+            Self::History => {
+                let entries = history::load(context)?;
+                if entries.is_empty() {
+                    return pager::page("No commands were ran yet").map(|()| false);
+                }
+
+                let text = entries
+                    .iter()
+                    .rev()
+                    .map(|entry| {
+                        format!(
+                            "{} {} {}",
+                            theme::get().dimmed.paint(&builtin::relative_time(
+                                i64::try_from(entry.time).unwrap_or_default()
+                            )),
+                            theme::get().key.paint(&entry.path),
+                            entry.command
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                pager::page(&text).map(|()| false)
+            },
+            Self::Keybindings => pager::page(&format!(
+                "{}  open this palette\n{}  select\n{}  select several items (when allowed)\n{}  \
+                 cancel",
+                theme::get().key.paint(KEY),
+                theme::get().key.paint("enter "),
+                theme::get().key.paint("tab   "),
+                theme::get().key.paint("esc   "),
+            ))
+            .map(|()| false),
+        }
+    }
+}
+
+/// Have jaime started over with the same arguments, replacing the ones
+/// choosing the selector with `backend` when toggling it
+fn restart(backend: &[&str]) -> Result<bool> {
+    let exe = env::current_exe().context("unable to find the jaime executable")?;
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    if !backend.is_empty() || args.iter().any(|a| a == "--skim-binary" || a == "-s") {
+        args.retain(|a| !matches!(a.as_str(), "--fzf" | "-f" | "--skim-binary" | "-s"));
+        args.extend(backend.iter().map(|&a| a.to_owned()));
+    }
+
+    let mut command = Command::new(exe);
+    command.args(args);
+    *RESTART.lock().unwrap_or_else(PoisonError::into_inner) = Some(command);
+
+    Ok(true)
+}
+
+/// The command starting jaime over, when an operation asked for it
+pub(crate) fn take_restart() -> Option<Command> {
+    RESTART
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
+
+/// Show the palette, letting the user `pick` an operation, and run it.
+/// Returns whether jaime is to be started over, leaving the selection
+pub(crate) fn open(
+    context: &Context,
+    pick: impl FnOnce(String) -> Result<Option<String>>,
+) -> Result<bool> {
+    let input = Operation::ALL
+        .iter()
+        .map(|op| {
+            format!(
+                "{}: {}",
+                theme::get().key.paint(op.name()),
+                theme::get().description.paint(op.description())
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let selected = match pick(input)? {
+        Some(selected) => selected,
+        None => return Ok(false),
+    };
+    let name = selected.split(':').next().unwrap_or_default();
+
+    Operation::ALL
+        .iter()
+        .find(|op| op.name() == name)
+        .ok_or_else(|| anyhow!("unknown operation: {}", name))?
+        .run(context)
+}
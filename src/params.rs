@@ -0,0 +1,111 @@
+//! Values given to the widgets of a command on the command line, as in `jaime
+//! run deploy env=staging region={pick}`: the widgets given a value aren't
+//! displayed, and those given `{pick}` are. Once parameters are given, the
+//! command is meant to run unattended, so the other widgets have to be
+//! optional (they are then empty)
+
+use crate::runner::WidgetConfig;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+};
+
+/// Given instead of a value, to display the widget
+pub(crate) const PICK: &str = "{pick}";
+
+/// What a widget is given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Parameter {
+    /// Its value, the widget isn't displayed
+    Value(String),
+    /// The widget is displayed
+    Pick,
+}
+
+/// Parameters of the widgets, by name (or index)
+pub(crate) type Parameters = Vec<(String, Parameter)>;
+
+/// Parameters given to the next command, by [`give`]
+static GIVEN: Lazy<Mutex<Option<Parameters>>> = Lazy::new(|| Mutex::new(None));
+
+/// Parse the `name=value` arguments
+pub(crate) fn parse(args: &[&str]) -> Result<Parameters> {
+    args.iter()
+        .map(|arg| {
+            let (name, value) = arg
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid parameter, expected name=value: {}", arg))?;
+            let parameter = if value == PICK {
+                Parameter::Pick
+            } else {
+                Parameter::Value(value.to_owned())
+            };
+            Ok((name.to_owned(), parameter))
+        })
+        .collect()
+}
+
+/// Give the `parameters` to the widgets of the next command
+pub(crate) fn give(parameters: Parameters) {
+    *GIVEN.lock().unwrap_or_else(PoisonError::into_inner) = Some(parameters);
+}
+
+/// The parameters given by [`give`], for the command starting
+pub(crate) fn take() -> Option<Parameters> {
+    GIVEN.lock().unwrap_or_else(PoisonError::into_inner).take()
+}
+
+/// The values of the `widgets` that aren't displayed, by index: those given a
+/// value, and the optional ones not given anything, which are empty
+pub(crate) fn resolve(
+    parameters: &[(String, Parameter)],
+    widgets: &[WidgetConfig],
+) -> Result<BTreeMap<usize, String>> {
+    let name = |index: usize| {
+        widgets[index]
+            .name
+            .clone()
+            .unwrap_or_else(|| index.to_string())
+    };
+
+    let mut given = BTreeMap::new();
+    for (wanted, parameter) in parameters {
+        let index = widgets
+            .iter()
+            .enumerate()
+            .position(|(index, widget)| {
+                widget.name.as_deref() == Some(wanted.as_str()) || index.to_string() == *wanted
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "no widget is named {} (the widgets are: {})",
+                    wanted,
+                    (0..widgets.len()).map(name).collect::<Vec<_>>().join(", ")
+                )
+            })?;
+        given.insert(index, parameter);
+    }
+
+    let mut values = BTreeMap::new();
+    for (index, widget) in widgets.iter().enumerate() {
+        match given.get(&index) {
+            Some(Parameter::Value(value)) => {
+                values.insert(index, value.clone());
+            },
+            Some(Parameter::Pick) => {},
+            None if widget.optional => {
+                values.insert(index, String::new());
+            },
+            None =>
+                return Err(anyhow!(
+                    "{0} has no value: give {0}=<value>, or {0}={1} to select it",
+                    name(index),
+                    PICK
+                )),
+        }
+    }
+
+    Ok(values)
+}
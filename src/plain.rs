@@ -0,0 +1,149 @@
+//! Screen reader friendly selector, used with `--plain`.
+//!
+//! Items are printed on stdout as a numbered list without any styling, and the
+//! choice is read from a prompt, so that nothing depends on an alternate screen
+
+use crate::{error::JaimeError, menu, table, text};
+use anyhow::{Context as AnyhowContext, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rustyline::{error::ReadlineError, Editor};
+use std::process::Command;
+
+/// Escape sequences used to style text
+static ANSI: Lazy<Regex> = Lazy::new(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap());
+
+/// Explanation of the answers the prompt accepts
+const HELP: &str = "Type a number, /text to filter, ?number to preview, or q to cancel";
+
+/// What was answered at the prompt
+#[derive(Debug)]
+pub(crate) enum Answer {
+    /// The chosen lines, separated by newlines, and the text they were
+    /// filtered with
+    Lines { lines: String, query: String },
+    /// Text matching none of the lines
+    Query(String),
+}
+
+/// Remove the escape sequences styling `text`
+pub(crate) fn strip_ansi(text: &str) -> String {
+    ANSI.replace_all(text, "").into_owned()
+}
+
+/// Print the output of the preview command for `line`
+fn preview(command: &str, line: &str) -> Result<()> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command.replace(
+            "{}",
+            &shlex::try_quote(table::displayed(line)).context("unable to quote item")?,
+        ))
+        .output()?;
+
+    println!(
+        "{}",
+        strip_ansi(&String::from_utf8_lossy(&output.stdout)).trim_end()
+    );
+    Ok(())
+}
+
+/// Let the user choose among the lines of `input`. When `multi` is set,
+/// several numbers can be given and the lines are separated by newlines
+pub(crate) fn select(
+    input: &str,
+    preview_command: Option<&str>,
+    multi: bool,
+) -> Result<Option<Answer>> {
+    let lines = input
+        .lines()
+        .map(strip_ansi)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>();
+    let mut shown = lines.iter().collect::<Vec<_>>();
+    let mut query = String::new();
+    let mut rl = Editor::<()>::new();
+
+    println!("{}", HELP);
+    loop {
+        if shown.is_empty() {
+            println!("No items");
+        }
+        // The headings of the groups aren't numbered, so they can't be chosen
+        let mut choices = Vec::with_capacity(shown.len());
+        for line in &shown {
+            if line.starts_with(menu::GROUP) {
+                println!("{}", line);
+            } else {
+                choices.push(*line);
+                println!("{}) {}", choices.len(), table::displayed(line));
+            }
+        }
+
+        let prompt = if multi {
+            "Choose one or more, separated by spaces: "
+        } else {
+            "Choose one: "
+        };
+        let answer = match rl.readline(prompt) {
+            Ok(answer) => answer.trim().to_owned(),
+            Err(ReadlineError::Interrupted) => return Err(JaimeError::SelectorAborted.into()),
+            Err(ReadlineError::Eof) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        if answer.is_empty() || answer == "q" {
+            return Ok(None);
+        }
+
+        if let Some(filter) = answer.strip_prefix('/') {
+            query = String::from(filter);
+            shown = lines
+                .iter()
+                .filter(|line| text::contains(table::displayed(line), filter))
+                .collect();
+            continue;
+        }
+
+        let pick = |number: &str| {
+            number
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|n| choices.get(n))
+        };
+
+        if let Some(number) = answer.strip_prefix('?') {
+            match (pick(number), preview_command) {
+                (Some(line), Some(command)) => preview(command, line)?,
+                (Some(_), None) => println!("There is nothing to preview"),
+                (None, _) => println!("{} is not in the list", number),
+            }
+            continue;
+        }
+
+        let numbers = answer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|n| !n.is_empty())
+            .collect::<Vec<_>>();
+        let chosen = numbers.iter().map(|n| pick(n)).collect::<Option<Vec<_>>>();
+
+        match chosen {
+            Some(chosen) if multi || chosen.len() == 1 =>
+                return Ok(Some(Answer::Lines {
+                    lines: chosen
+                        .into_iter()
+                        .map(|line| line.as_str())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    query,
+                })),
+            Some(_) => println!("Choose a single item"),
+            // Text found in none of the lines is accepted as it is
+            None if numbers.iter().any(|n| n.parse::<usize>().is_err())
+                && !lines.iter().any(|line| text::contains(line, &answer)) =>
+                return Ok(Some(Answer::Query(answer))),
+            None => println!("{} is not a valid choice. {}", answer, HELP),
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Rules the commands must follow before they run, to guard a shared
+//! configuration against templates rendering into something destructive (an
+//! `rm -rf {}` with an empty selection, for one).
+//!
+//! The rules are regular expressions matched against the commands once their
+//! placeholders are replaced. A command matching a `deny` rule is refused, even
+//! if it matches an `allow` rule. When there are `allow` rules, a command has
+//! to match one of them
+
+use crate::{runner, theme};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Settings of the policy, which is only enforced when they are given
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct Policy {
+    /// Regular expressions of the commands allowed. The commands matching none
+    /// of them are denied, unless there are none
+    #[serde(default)]
+    pub(crate) allow:       Vec<String>,
+    /// Regular expressions of the commands denied
+    #[serde(default)]
+    pub(crate) deny:        Vec<String>,
+    /// Offer to run a denied command anyway, once confirmed, instead of
+    /// refusing it
+    #[serde(default, rename = "override")]
+    pub(crate) overridable: bool,
+}
+
+/// Compile the `rules` of the list `name`, or return the first one invalid
+fn compile(name: &str, rules: &[String]) -> Result<Vec<Regex>> {
+    rules
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| {
+            Regex::new(rule).map_err(|e| anyhow!("policy.{}.{}: invalid rule: {}", name, index, e))
+        })
+        .collect()
+}
+
+impl Policy {
+    /// Check that the rules are valid
+    pub(crate) fn validate(&self) -> Result<()> {
+        compile("allow", &self.allow)?;
+        compile("deny", &self.deny)?;
+        Ok(())
+    }
+
+    /// Why `command` is denied, if it is
+    fn denial(&self, command: &str) -> Result<Option<String>> {
+        if let Some(rule) = compile("deny", &self.deny)?
+            .into_iter()
+            .find(|rule| rule.is_match(command))
+        {
+            return Ok(Some(format!("it matches the deny rule `{}`", rule)));
+        }
+
+        let allow = compile("allow", &self.allow)?;
+        if !allow.is_empty() && !allow.iter().any(|rule| rule.is_match(command)) {
+            return Ok(Some(String::from("it matches none of the allow rules")));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Whether `command`, ran for the action at `path`, can run under the
+/// `policy`. A denied command is an error, unless the policy can be
+/// overridden: the user is then asked whether to run it anyway
+pub(crate) fn check(policy: Option<&Policy>, path: &[String], command: &str) -> Result<bool> {
+    let policy = match policy {
+        Some(policy) => policy,
+        None => return Ok(true),
+    };
+    let reason = match policy.denial(command)? {
+        Some(reason) => reason,
+        None => return Ok(true),
+    };
+
+    if !policy.overridable {
+        return Err(anyhow!(
+            "{}: `{}` is denied by the policy, as {}",
+            path.join("."),
+            command,
+            reason
+        ));
+    }
+
+    let prompt = format!(
+        "{} `{}` is denied by the policy, as {}. Run it anyway? [y/N] ",
+        theme::get().info.paint("[policy]"),
+        command,
+        reason
+    );
+    let answer = runner::readline_with(&prompt, "")?.map(|a| a.trim().to_lowercase());
+
+    Ok(matches!(answer.as_deref(), Some("y" | "yes")))
+}
@@ -0,0 +1,402 @@
+use crate::{
+    builtin::{self, docker, git, kube, process, systemd},
+    runner::Context,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use colored::Colorize;
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::ThemeSet,
+    parsing::{SyntaxReference, SyntaxSet},
+    util::{as_24_bit_terminal_escaped, LinesWithEndings},
+};
+
+/// Prefix used in the configuration to select a builtin previewer
+const BUILTIN_PREFIX: &str = "builtin:";
+
+/// Maximum number of lines displayed by the builtin previewers
+const MAX_LINES: usize = 500;
+
+/// Maximum number of bytes read from a file by the file previewer, so large
+/// files aren't loaded entirely for the few lines displayed
+const MAX_BYTES: u64 = 256 * 1024;
+
+/// Previewers implemented within `jaime`, so previews work without having
+/// external tools such as `bat`, `exa`, or `jq` installed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Builtin {
+    /// Syntax highlighted file contents (directories are listed)
+    File,
+    /// Directory listing
+    Dir,
+    /// Pretty-printed JSON, from either a file or the item itself
+    Json,
+    /// Cluster and server information of a Kubernetes context
+    KubeContext,
+    /// Kubernetes contexts using a namespace
+    KubeNamespace,
+    /// Inspection of a Docker container
+    DockerContainer,
+    /// Inspection of a Docker image
+    DockerImage,
+    /// Inspection of a Docker volume
+    DockerVolume,
+    /// Recent commits of a Git branch
+    GitBranch,
+    /// Commit of a Git tag
+    GitTag,
+    /// Changes to a file in a Git repository
+    GitFile,
+    /// Changes of a Git stash
+    GitStash,
+    /// Details and changes of a Git commit
+    GitCommit,
+    /// Value of an environment variable
+    EnvVar,
+    /// Details of a running process
+    Process,
+    /// Status of a systemd unit of the system manager
+    SystemdUnit,
+    /// Status of a systemd unit of the user manager
+    SystemdUserUnit,
+}
+
+impl FromStr for Builtin {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "file" => Ok(Self::File),
+            "dir" => Ok(Self::Dir),
+            "json" => Ok(Self::Json),
+            "kube-context" => Ok(Self::KubeContext),
+            "kube-namespace" => Ok(Self::KubeNamespace),
+            "docker-container" => Ok(Self::DockerContainer),
+            "docker-image" => Ok(Self::DockerImage),
+            "docker-volume" => Ok(Self::DockerVolume),
+            "git-branch" => Ok(Self::GitBranch),
+            "git-tag" => Ok(Self::GitTag),
+            "git-file" => Ok(Self::GitFile),
+            "git-stash" => Ok(Self::GitStash),
+            "git-commit" => Ok(Self::GitCommit),
+            "env-var" => Ok(Self::EnvVar),
+            "process" => Ok(Self::Process),
+            "systemd-unit" => Ok(Self::SystemdUnit),
+            "systemd-user-unit" => Ok(Self::SystemdUserUnit),
+            _ => Err(anyhow!("unknown builtin previewer: {}", s)),
+        }
+    }
+}
+
+impl Builtin {
+    /// Names accepted on the command line
+    pub(crate) const NAMES: &'static [&'static str] = &[
+        "file",
+        "dir",
+        "json",
+        "kube-context",
+        "kube-namespace",
+        "docker-container",
+        "docker-image",
+        "docker-volume",
+        "git-branch",
+        "git-tag",
+        "git-file",
+        "git-stash",
+        "git-commit",
+        "env-var",
+        "process",
+        "systemd-unit",
+        "systemd-user-unit",
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Dir => "dir",
+            Self::Json => "json",
+            Self::KubeContext => "kube-context",
+            Self::KubeNamespace => "kube-namespace",
+            Self::DockerContainer => "docker-container",
+            Self::DockerImage => "docker-image",
+            Self::DockerVolume => "docker-volume",
+            Self::GitBranch => "git-branch",
+            Self::GitTag => "git-tag",
+            Self::GitFile => "git-file",
+            Self::GitStash => "git-stash",
+            Self::GitCommit => "git-commit",
+            Self::EnvVar => "env-var",
+            Self::Process => "process",
+            Self::SystemdUnit => "systemd-unit",
+            Self::SystemdUserUnit => "systemd-user-unit",
+        }
+    }
+
+    /// The command passed to the selector, which invokes `jaime` itself with
+    /// the selector's `placeholder` (e.g., `{}`) as the item
+    pub(crate) fn command(self, placeholder: &str) -> Result<String> {
+        let exe = env::current_exe().context("unable to find the jaime executable")?;
+        let exe = exe.to_string_lossy();
+
+        Ok(format!(
+            "{} preview {} {}",
+            shlex::try_quote(&exe).context("invalid executable path")?,
+            self.name(),
+            placeholder
+        ))
+    }
+
+    /// Write the preview of `item` to standard output. The previews of
+    /// builtin widgets receive the whole line, which is turned back into the
+    /// value of the item
+    pub(crate) fn run(self, item: &str) -> Result<()> {
+        let path = Path::new(item);
+        let value = builtin::value_of(item);
+        let item = value.as_str();
+
+        match self {
+            Self::Dir => preview_dir(path),
+            Self::File if path.is_dir() => preview_dir(path),
+            Self::File => preview_file(path),
+            Self::Json => preview_json(item),
+            Self::KubeContext => kube::preview_context(item),
+            Self::KubeNamespace => kube::preview_namespace(item),
+            Self::DockerContainer => docker::preview(docker::Kind::Container, item),
+            Self::DockerImage => docker::preview(docker::Kind::Image, item),
+            Self::DockerVolume => docker::preview(docker::Kind::Volume, item),
+            Self::GitBranch => git::preview(git::Kind::Branch, item),
+            Self::GitTag => git::preview(git::Kind::Tag, item),
+            Self::GitFile => git::preview(git::Kind::File, item),
+            Self::GitStash => git::preview(git::Kind::Stash, item),
+            Self::GitCommit => git::preview(git::Kind::Commit, item),
+            Self::EnvVar => builtin::env::preview(item),
+            Self::Process => process::preview(item),
+            Self::SystemdUnit => systemd::preview(false, item),
+            Self::SystemdUserUnit => systemd::preview(true, item),
+        }
+    }
+}
+
+/// Whether the `preview` field of a widget names a builtin previewer
+pub(crate) fn is_builtin(preview: &str) -> bool {
+    preview.trim().starts_with(BUILTIN_PREFIX)
+}
+
+/// Turn the `preview` field of a widget into the command given to the
+/// selector, expanding builtin previewers and optionally caching the output
+/// for `cache`
+pub(crate) fn resolve(context: &Context, preview: &str, cache: Option<Duration>) -> Result<String> {
+    let preview = match preview.trim().strip_prefix(BUILTIN_PREFIX) {
+        Some(name) => name.parse::<Builtin>()?.command("{}")?,
+        None => preview.to_owned(),
+    };
+
+    match cache {
+        Some(ttl) => cached(context, &preview, ttl),
+        None => Ok(preview),
+    }
+}
+
+/// FNV-1a hash of `text`, naming the files of the cache written after their
+/// contents. Unlike the `DefaultHasher` of the standard library, it is the same
+/// across versions of Rust, so the files stay valid when `jaime` is rebuilt
+pub(crate) fn digest(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Prefix of the files caching the output of `preview`, one per item
+pub(crate) fn cache_prefix(context: &Context, preview: &str) -> Result<PathBuf> {
+    let dir = context.cache_directory.join("preview");
+    fs::create_dir_all(&dir).context(format!("unable to create: {}", dir.display()))?;
+
+    Ok(dir.join(format!("{:x}", digest(preview))))
+}
+
+/// Write the preview `script` to `$JAIME_CACHE_DIR/preview/scripts`, and
+/// return the command running it with the item as its first argument, followed
+/// by the values of the widgets displayed before (`args`): `$2` is the value of
+/// `{0}`, `$3` the one of `{1}`... The script runs with `sh`, unless it starts
+/// with a shebang
+pub(crate) fn script(context: &Context, script: &str, args: &[String]) -> Result<String> {
+    let dir = context.cache_directory.join("preview").join("scripts");
+    fs::create_dir_all(&dir).context(format!("unable to create: {}", dir.display()))?;
+
+    let script = if script.starts_with("#!") {
+        script.to_owned()
+    } else {
+        format!("#!/bin/sh\n{}", script)
+    };
+    // Named after its contents, so it is only written once
+    let path = dir.join(format!("{:x}", digest(&script)));
+    if !path.exists() {
+        // Written aside and made executable first, so that another selector
+        // never runs half of it
+        let partial = path.with_extension(std::process::id().to_string());
+        fs::write(&partial, &script).context(format!("unable to write: {}", partial.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&partial, fs::Permissions::from_mode(0o755))
+                .context(format!("unable to make executable: {}", partial.display()))?;
+        }
+        fs::rename(&partial, &path).context(format!("unable to write: {}", path.display()))?;
+    }
+
+    let mut command = vec![
+        shlex::try_quote(&path.to_string_lossy())
+            .context("invalid cache directory")?
+            .into_owned(),
+        String::from("{}"),
+    ];
+    for arg in args {
+        command.push(
+            shlex::try_quote(arg)
+                .context("unable to quote argument")?
+                .into_owned(),
+        );
+    }
+
+    Ok(command.join(" "))
+}
+
+/// Wrap a preview command in a small script that caches its output under
+/// `$JAIME_CACHE_DIR/preview`.
+///
+/// The cache file is keyed by both the preview command and the item being
+/// previewed, so the (possibly expensive) command is only ran once per item.
+/// The files older than `ttl` are removed first, so they are ran again
+fn cached(context: &Context, preview: &str, ttl: Duration) -> Result<String> {
+    let prefix = cache_prefix(context, preview)?;
+    expire(&prefix, ttl)?;
+    let prefix = prefix.to_string_lossy();
+    let prefix = shlex::try_quote(&prefix).context("invalid cache directory")?;
+
+    Ok(format!(
+        "f={prefix}-$(printf '%s' {{}} | cksum | tr -d ' \\t')\nif [ ! -s \"$f\" ]; then\n{{ \
+         {preview}\n}} > \"$f.$$\" && mv -f \"$f.$$\" \"$f\" || {{ cat \"$f.$$\"; rm -f \
+         \"$f.$$\"; exit 1; }}\nfi\ncat \"$f\"",
+        prefix = prefix,
+        preview = preview,
+    ))
+}
+
+/// Remove the cached outputs starting with `prefix` that are older than `ttl`
+fn expire(prefix: &Path, ttl: Duration) -> Result<()> {
+    let (dir, name) = match (prefix.parent(), prefix.file_name()) {
+        (Some(dir), Some(name)) => (dir, format!("{}-", name.to_string_lossy())),
+        _ => return Ok(()),
+    };
+    let now = SystemTime::now();
+
+    for entry in fs::read_dir(dir).context(format!("unable to read: {}", dir.display()))? {
+        let entry = entry?;
+        if !entry.file_name().to_string_lossy().starts_with(&name) {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .map_or(true, |modified| {
+                now.duration_since(modified).unwrap_or_default() >= ttl
+            });
+        if expired {
+            // Another selector may have removed it already
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the highlighted contents of `text`, using `syntax` if found
+fn highlight(text: &str, syntax: Option<&SyntaxReference>, syntaxes: &SyntaxSet) -> Result<()> {
+    let themes = ThemeSet::load_defaults();
+    let syntax = syntax.unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &themes.themes["base16-ocean.dark"]);
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    for line in LinesWithEndings::from(text).take(MAX_LINES) {
+        let ranges = highlighter.highlight_line(line, syntaxes)?;
+        write!(stdout, "{}", as_24_bit_terminal_escaped(&ranges[..], false))?;
+    }
+    writeln!(stdout, "\x1b[0m")?;
+
+    Ok(())
+}
+
+fn preview_file(path: &Path) -> Result<()> {
+    let file = fs::File::open(path).context(format!("unable to open: {}", path.display()))?;
+    let size = file.metadata()?.len();
+    let mut bytes = Vec::new();
+    file.take(MAX_BYTES).read_to_end(&mut bytes)?;
+
+    if bytes.iter().take(8192).any(|b| *b == 0) {
+        println!("{}", format!("<binary file: {} bytes>", size).yellow());
+        return Ok(());
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+    let syntax = syntaxes
+        .find_syntax_for_file(path)?
+        .or_else(|| syntaxes.find_syntax_by_first_line(&text));
+
+    highlight(&text, syntax, &syntaxes)
+}
+
+fn preview_dir(path: &Path) -> Result<()> {
+    let mut entries = fs::read_dir(path)
+        .context(format!("unable to read: {}", path.display()))?
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let is_dir = entry.file_type().map_or(false, |t| t.is_dir());
+            (!is_dir, entry.file_name().to_string_lossy().to_string())
+        })
+        .collect::<Vec<_>>();
+
+    // Directories first, then alphabetical
+    entries.sort();
+
+    for (is_file, name) in entries.iter().take(MAX_LINES) {
+        if *is_file {
+            println!("{}", name);
+        } else {
+            println!("{}/", name.blue().bold());
+        }
+    }
+
+    Ok(())
+}
+
+fn preview_json(item: &str) -> Result<()> {
+    let path = Path::new(item);
+    let text = if path.is_file() {
+        fs::read_to_string(path).context(format!("unable to read: {}", path.display()))?
+    } else {
+        item.to_owned()
+    };
+
+    print_json(&serde_json::from_str(&text).context("invalid JSON")?)
+}
+
+/// Print a JSON value, pretty-printed and highlighted
+pub(crate) fn print_json(value: &serde_json::Value) -> Result<()> {
+    let syntaxes = SyntaxSet::load_defaults_newlines();
+
+    highlight(
+        &serde_json::to_string_pretty(value)?,
+        syntaxes.find_syntax_by_extension("json"),
+        &syntaxes,
+    )
+}
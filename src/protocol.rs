@@ -0,0 +1,252 @@
+//! `jaime --frontend-protocol`: newline-delimited JSON on the standard input
+//! and output, so that another interface (a GUI, a web page, an editor plugin)
+//! can drive the menus while Jaime expands the templates, memoizes, and runs
+//! the commands.
+//!
+//! Requests, one JSON object per line:
+//! - `{"method": "list", "path": …}`: the menu below `path` (the root menu if
+//!   absent), answered with a `menu` event
+//! - `{"method": "run", "path": …}`: run the action at `path` (display the root
+//!   menu if absent), answered with a `done` event once it ran
+//! - `{"method": "select", "indices": […]}`: the items selected among those of
+//!   the last `select` event, counting from 0, or `null` to cancel
+//! - `{"method": "answer", "text": …}`: the text typed for the last `input`
+//!   event, or `null` to cancel
+//!
+//! Events, one JSON object per line, with their name in `event`: `menu`,
+//! `select` (`items`, `preview`, `multi`), `input` (`prompt`, `initial`),
+//! `done` (`outcome`, and `output` if it was captured), and `error`
+//! (`message`). The commands print to the standard error, the standard output
+//! being left to the events
+
+use crate::{
+    app::Handler,
+    error,
+    frontend::{self, Frontend, Output},
+    runner::{Action, Config, Context, Outcome},
+    tree,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Stdin, Stdout, Write},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// A request of the frontend
+#[derive(Deserialize, Debug)]
+#[serde(tag = "method", rename_all = "lowercase")]
+enum Request {
+    List {
+        #[serde(default)]
+        path: Option<String>,
+    },
+    Run {
+        #[serde(default)]
+        path: Option<String>,
+    },
+    Select {
+        indices: Option<Vec<usize>>,
+    },
+    Answer {
+        text: Option<String>,
+    },
+}
+
+/// A message sent to the frontend
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Event<'a> {
+    Menu {
+        options: Vec<tree::Node<'a>>,
+    },
+    Select {
+        items:   &'a [&'a str],
+        preview: Option<&'a str>,
+        multi:   bool,
+    },
+    Input {
+        prompt:  &'a str,
+        initial: &'a str,
+    },
+    Done {
+        outcome: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output:  Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// The standard input and output, where the requests and events go
+#[derive(Debug)]
+struct Channel {
+    input:  BufReader<Stdin>,
+    output: Stdout,
+}
+
+impl Channel {
+    /// The next request, `None` once the input is closed. Invalid requests are
+    /// answered with an error and skipped
+    fn read(&mut self) -> Result<Option<Request>> {
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(request) => return Ok(Some(request)),
+                Err(e) => self.send(&Event::Error {
+                    message: format!("invalid request: {}", e),
+                })?,
+            }
+        }
+    }
+
+    fn send(&mut self, event: &Event<'_>) -> Result<()> {
+        serde_json::to_writer(&mut self.output, event)?;
+        writeln!(self.output)?;
+        self.output.flush()?;
+        Ok(())
+    }
+
+    /// Send `event` and wait for the request answering it, which `answer`
+    /// returns the value of. The other requests are refused meanwhile
+    fn ask<T>(&mut self, event: &Event<'_>, answer: impl Fn(Request) -> Option<T>) -> Result<T> {
+        self.send(event)?;
+        loop {
+            let request = self
+                .read()?
+                .ok_or_else(|| anyhow!("the frontend closed the input"))?;
+            match answer(request) {
+                Some(value) => return Ok(value),
+                None => self.send(&Event::Error {
+                    message: String::from("waiting for an answer to the last event"),
+                })?,
+            }
+        }
+    }
+}
+
+fn lock(channel: &Mutex<Channel>) -> MutexGuard<'_, Channel> {
+    channel.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Displays the widgets with the frontend
+#[derive(Debug)]
+struct Protocol {
+    channel: Arc<Mutex<Channel>>,
+}
+
+impl Frontend for Protocol {
+    fn select(
+        &mut self,
+        items: &[&str],
+        preview: Option<&str>,
+        multi: bool,
+    ) -> Result<Option<Vec<usize>>> {
+        lock(&self.channel).ask(
+            &Event::Select {
+                items,
+                preview,
+                multi,
+            },
+            |request| match request {
+                Request::Select { indices } => Some(indices),
+                _ => None,
+            },
+        )
+    }
+
+    fn read(&mut self, prompt: &str, initial: &str) -> Result<Option<String>> {
+        lock(&self.channel).ask(&Event::Input { prompt, initial }, |request| match request {
+            Request::Answer { text } => Some(text),
+            _ => None,
+        })
+    }
+
+    fn output(&self) -> Output {
+        Output::Stderr
+    }
+}
+
+/// The options of the menu at `path`, the root menu if `None`
+fn list<'a>(config: &'a Config, path: Option<&str>) -> Result<Vec<tree::Node<'a>>> {
+    match path {
+        None => Ok(tree::nodes(&config.options, None)),
+        Some(path) => match config.find(path)? {
+            (_, Action::Select { options, .. }) => Ok(tree::nodes(options, None)),
+            (path, _) => Err(anyhow!("{} isn't a menu", path.join("."))),
+        },
+    }
+}
+
+/// Run the action at `path`, the root menu if `None`
+pub(crate) fn run_path(
+    context: &Context,
+    config: &Config,
+    handler: &Handler,
+    path: Option<&str>,
+) -> Result<Outcome> {
+    let outcome = match path {
+        None => config.run(context, handler),
+        Some(path) => {
+            let (path, action) = config.find(path)?;
+            action.run_at(context, config, handler, &path, None)
+        },
+    };
+    match outcome {
+        Err(e) if error::is_cancelled(&e) || error::is_aborted(&e) => Ok(Outcome::Cancelled),
+        outcome => outcome,
+    }
+}
+
+/// Answer the requests of the frontend until it closes the input
+pub(crate) fn run(context: &Context, config: &Config, handler: &Handler) -> Result<()> {
+    let channel = Arc::new(Mutex::new(Channel {
+        input:  BufReader::new(io::stdin()),
+        output: io::stdout(),
+    }));
+    frontend::set(Some(Box::new(Protocol {
+        channel: Arc::clone(&channel),
+    })));
+
+    loop {
+        let request = match lock(&channel).read()? {
+            Some(request) => request,
+            None => return Ok(()),
+        };
+
+        let event = match request {
+            Request::List { path } =>
+                list(config, path.as_deref()).map(|options| Event::Menu { options }),
+            Request::Run { path } =>
+                run_path(context, config, handler, path.as_deref()).map(|outcome| match outcome {
+                    Outcome::Cancelled => Event::Done {
+                        outcome: "cancelled",
+                        output:  None,
+                    },
+                    Outcome::Captured(output) => Event::Done {
+                        outcome: "captured",
+                        output:  Some(output),
+                    },
+                    Outcome::Done => Event::Done {
+                        outcome: "done",
+                        output:  None,
+                    },
+                }),
+            Request::Select { .. } | Request::Answer { .. } =>
+                Err(anyhow!("nothing is waiting for an answer")),
+        };
+        let event = event.unwrap_or_else(|e| Event::Error {
+            message: format!("{:#}", e),
+        });
+        lock(&channel)
+            .send(&event)
+            .context("unable to write to the standard output")?;
+    }
+}
@@ -0,0 +1,50 @@
+//! The values typed in the `FreeText` widgets that have a `name`, stored in the
+//! cache directory by name, so that they can be recalled by the widgets of the
+//! same name
+
+use crate::runner::Context;
+use anyhow::{Context as AnyhowContext, Result};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// Number of values kept for each name
+const KEPT: usize = 100;
+
+/// The values typed, oldest first, keyed by the name of the widgets
+type Values = BTreeMap<String, Vec<String>>;
+
+fn file(context: &Context) -> PathBuf {
+    context.cache_directory.join("values.json")
+}
+
+fn load_all(context: &Context) -> Result<Values> {
+    let path = file(context);
+    if !path.exists() {
+        return Ok(Values::new());
+    }
+
+    serde_json::from_str(&fs::read_to_string(&path).context("unable to read the values typed")?)
+        .context("invalid values typed")
+}
+
+/// The values typed in the widgets named `name`, oldest first
+pub(crate) fn load(context: &Context, name: &str) -> Result<Vec<String>> {
+    Ok(load_all(context)?.remove(name).unwrap_or_default())
+}
+
+/// Remember `value` as the last one typed in the widgets named `name`
+pub(crate) fn remember(context: &Context, name: &str, value: &str) -> Result<()> {
+    if value.trim().is_empty() {
+        return Ok(());
+    }
+
+    let mut all = load_all(context)?;
+    let values = all.entry(name.to_owned()).or_default();
+    values.retain(|typed| typed != value);
+    values.push(value.to_owned());
+    if values.len() > KEPT {
+        values.drain(..values.len() - KEPT);
+    }
+
+    fs::write(file(context), serde_json::to_string(&all)?)
+        .context("unable to write the values typed")
+}
@@ -0,0 +1,144 @@
+//! The values of the widgets of a command, saved as they are selected, so that
+//! a flow that was cancelled partway (or whose terminal died) can be resumed
+//! from where it was left the next time the command is ran.
+//!
+//! The values recorded in the history can also be given to the next flow, to
+//! run a command again with only some of them changed
+
+use crate::{
+    app::Handler,
+    builtin,
+    error::JaimeError,
+    runner::{self, Context},
+    theme,
+};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    fs,
+    path::PathBuf,
+    sync::{Mutex, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Values given to the next flow, by [`prefill`]
+static PREFILLED: Lazy<Mutex<Option<Vec<String>>>> = Lazy::new(|| Mutex::new(None));
+
+/// The values selected for a command
+#[derive(Serialize, Deserialize, Debug)]
+struct Saved {
+    /// UNIX timestamp of when the last value was selected
+    time:    u64,
+    /// Number of widgets of the command, the values being dropped when it
+    /// changes
+    widgets: usize,
+    /// Values of the widgets, by index
+    values:  BTreeMap<usize, String>,
+}
+
+/// The saved values, keyed by the dotted path of the commands
+type Flows = BTreeMap<String, Saved>;
+
+fn file(context: &Context) -> PathBuf {
+    context.cache_directory.join("resume.json")
+}
+
+fn load(context: &Context) -> Result<Flows> {
+    let path = file(context);
+    if !path.exists() {
+        return Ok(Flows::new());
+    }
+
+    serde_json::from_str(&fs::read_to_string(&path).context("unable to read the saved values")?)
+        .context("invalid saved values")
+}
+
+fn store(context: &Context, flows: &Flows) -> Result<()> {
+    fs::write(file(context), serde_json::to_string(flows)?)
+        .context("unable to write the saved values")
+}
+
+/// Save the `values` selected so far for the command at `path`, which has
+/// `widgets` widgets
+pub(crate) fn save(
+    context: &Context,
+    path: &[String],
+    widgets: usize,
+    values: BTreeMap<usize, String>,
+) -> Result<()> {
+    let mut flows = load(context)?;
+    flows.insert(path.join("."), Saved {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        widgets,
+        values,
+    });
+    store(context, &flows)
+}
+
+/// Forget the values of the command at `path`, once it is ready to run
+pub(crate) fn clear(context: &Context, path: &[String]) -> Result<()> {
+    let mut flows = load(context)?;
+    if flows.remove(&path.join(".")).is_some() {
+        store(context, &flows)?;
+    }
+    Ok(())
+}
+
+/// The values saved for the command at `path`, which has `widgets` widgets, if
+/// the user chooses to resume from them. Empty when there are none, or when
+/// the user starts over
+pub(crate) fn offer(
+    context: &Context,
+    handler: &Handler,
+    path: &[String],
+    widgets: usize,
+) -> Result<BTreeMap<usize, String>> {
+    let saved = match load(context)?.remove(&path.join(".")) {
+        Some(saved) if saved.widgets == widgets && !saved.values.is_empty() => saved,
+        _ => return Ok(BTreeMap::new()),
+    };
+
+    let theme = theme::get();
+    let input = format!(
+        "{}: {}\n{}: {}",
+        theme.key.paint("resume"),
+        theme.description.paint(&format!(
+            "Resume where it was left {} ({} of {} values selected)",
+            builtin::relative_time(i64::try_from(saved.time).unwrap_or(i64::MAX)),
+            saved.values.len(),
+            widgets
+        )),
+        theme.key.paint("restart"),
+        theme.description.paint("Select all the values again"),
+    );
+    let selected =
+        runner::select(context, handler, &input, None)?.ok_or(JaimeError::WidgetCancelled)?;
+
+    match selected.split(':').next().unwrap_or_default() {
+        "resume" => Ok(saved.values),
+        "restart" => {
+            clear(context, path)?;
+            Ok(BTreeMap::new())
+        },
+        key => Err(anyhow!("unknown choice: {}", key)),
+    }
+}
+
+/// Start the next flow with the `values` of its widgets, and show its summary
+/// so that some of them can be changed
+pub(crate) fn prefill(values: Vec<String>) {
+    *PREFILLED.lock().unwrap_or_else(PoisonError::into_inner) = Some(values);
+}
+
+/// The values given by [`prefill`], for the flow starting
+pub(crate) fn take_prefilled() -> Option<Vec<String>> {
+    PREFILLED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take()
+}
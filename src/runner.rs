@@ -1,61 +1,469 @@
 #[allow(unused)]
 use anyhow::{anyhow, Context as AnyhowContext, Result};
-use colored::Colorize;
+use indexmap::IndexMap;
 use once_cell::sync::Lazy;
-use rustyline::{error::ReadlineError, Editor};
+use rustyline::{
+    error::ReadlineError, Cmd, ConditionalEventHandler, Editor, Event as LineEvent, EventContext,
+    EventHandler, KeyEvent, RepeatCount,
+};
 use serde::{Deserialize, Serialize};
 use skim::{
-    prelude::{SkimItemReader, SkimItemReaderOption, SkimOptionsBuilder},
+    prelude::{Event, SkimItemReader, SkimItemReaderOption, SkimOptionsBuilder},
     Skim,
 };
 
-use crate::app::Handler;
+use crate::{
+    app::Handler,
+    audit::{self, Audit},
+    builtin::{
+        self, color, docker, emoji,
+        flags::{self, Flag},
+        generate, git, json, kube, systemd,
+    },
+    clipboard, condition, embed,
+    error::{self, JaimeError},
+    exec::{self, run_shell_command_for_output, Execution, Exit},
+    explain::Assembly,
+    frontend, fzf,
+    graph::Graph,
+    history, last,
+    lines::{self, Sort},
+    listing::{self, Listing},
+    lock::{self, Acquired},
+    menu,
+    metrics::{self, Metrics},
+    notify, pager, palette, params, plain,
+    policy::{self, Policy},
+    preview, recall, resume,
+    runs::{self, Retention, Run},
+    sandbox,
+    secrets::{self, Secrets},
+    session::{self, Step},
+    source::{Item, ItemSource, Shell, SourceContext},
+    table::{self, Format, Table},
+    template, text,
+    theme::{self, ThemeConfig},
+    user,
+};
 use std::{
     collections::HashMap,
-    env,
-    io::{Cursor, Write},
-    path::PathBuf,
-    process::{self, Command, Stdio},
-    sync::atomic::{AtomicUsize, Ordering},
+    convert::TryFrom,
+    env, fs,
+    io::{self, Cursor, Read, Write},
+    iter,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 #[macro_export]
 macro_rules! jaime_error {
     ($($err:tt)*) => ({
-        eprintln!("{}: {}", "[jaime error]".red().bold(), format!($($err)*));
+        eprintln!(
+            "{}: {}",
+            $crate::theme::get().error.paint("[jaime error]"),
+            format!($($err)*)
+        );
     })
 }
 
 static NUM_RUNS: Lazy<AtomicUsize> = Lazy::new(|| AtomicUsize::new(0));
 
+/// What was typed in the last selection: the query, and the key accepting it
+/// when it isn't `enter`
+#[derive(Debug, Clone, Default)]
+struct Typed {
+    query: String,
+    key:   String,
+}
+
+impl Typed {
+    /// The placeholders replaced with what was typed in the commands
+    fn placeholders(&self) -> [(&str, &str); 2] {
+        [("query", &self.query), ("key", &self.key)]
+    }
+}
+
+static TYPED: Lazy<Mutex<Typed>> = Lazy::new(|| Mutex::new(Typed::default()));
+
+fn set_typed(query: &str, key: &str) {
+    *TYPED.lock().unwrap() = Typed {
+        query: query.to_owned(),
+        key:   key.to_owned(),
+    };
+}
+
+fn typed() -> Typed {
+    TYPED.lock().unwrap().clone()
+}
+
 #[cfg(not(windows))]
-const FZF_BIN: &str = "fzf";
+pub(crate) const FZF_BIN: &str = "fzf";
 #[cfg(windows)]
-const FZF_BIN: &str = "fzf.exe";
+pub(crate) const FZF_BIN: &str = "fzf.exe";
 
 #[cfg(not(windows))]
-const SKIM_BIN: &str = "sk";
+pub(crate) const SKIM_BIN: &str = "sk";
 #[cfg(windows)]
-const SKIM_BIN: &str = "sk.exe";
+pub(crate) const SKIM_BIN: &str = "sk.exe";
 
 #[derive(Debug)]
 pub(crate) struct Context {
+    pub(crate) config_path:     PathBuf,
     pub(crate) cache_directory: PathBuf,
+    /// Options of the embedded skim selector, from the configuration
+    pub(crate) skim:            SkimSettings,
+    /// Keys accepting a selection besides `enter`, from the configuration
+    pub(crate) keys:            Vec<String>,
+    /// Print how the commands are assembled before they run
+    pub(crate) explain:         bool,
+}
+
+/// Options of the embedded skim selector, which would otherwise have to be set
+/// in `SKIM_DEFAULT_OPTIONS`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub(crate) struct SkimSettings {
+    /// Select items and scroll with the mouse
+    pub(crate) mouse:          bool,
+    /// Key toggling the preview window, if any
+    pub(crate) preview_toggle: Option<String>,
+}
+
+impl Default for SkimSettings {
+    fn default() -> Self {
+        Self {
+            mouse:          true,
+            preview_toggle: Some(String::from("ctrl-/")),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct Config {
-    pub(crate) options:     HashMap<String, Action>,
-    pub(crate) shell:       Option<String>,
-    pub(crate) description: Option<String>,
+    pub(crate) options:      Options,
+    pub(crate) shell:        Option<String>,
+    /// Arguments given to the shell before the command, instead of those
+    /// adapted to it
+    pub(crate) shell_args:   Option<Vec<String>>,
+    /// Start the shell as an interactive login shell, so that the commands
+    /// can use the functions and aliases of the user
+    #[serde(default)]
+    pub(crate) login_shell:  bool,
+    /// Have the shell stop at the first failure and on unset variables (the
+    /// default)
+    pub(crate) strict_shell: Option<bool>,
+    pub(crate) description:  Option<String>,
+    /// Send a notification when a command takes longer than this many seconds
+    pub(crate) notify_after: Option<u64>,
+    /// Secrets available to every command as environment variables
+    #[serde(default)]
+    pub(crate) secrets:      Secrets,
+    #[serde(default)]
+    pub(crate) skim:         SkimSettings,
+    /// Fail on the problems of the configuration instead of warning about them
+    #[serde(default)]
+    pub(crate) strict:       bool,
+    /// What cancelling a selection does
+    #[serde(default)]
+    pub(crate) cancel:       Cancel,
+    /// Log the commands ran, redacted
+    #[serde(default)]
+    pub(crate) audit:        Option<Audit>,
+    /// Count the commands ran, by action
+    #[serde(default)]
+    pub(crate) metrics:      Option<Metrics>,
+    /// How long the directories of the runs are kept
+    #[serde(default)]
+    pub(crate) runs:         Retention,
+    /// Rules the commands must follow to run
+    #[serde(default)]
+    pub(crate) policy:       Option<Policy>,
+    /// Ran with the query of the root menu as `{prev}` when it matches none
+    /// of its keys
+    pub(crate) fallback:     Option<Box<Action>>,
+    /// Keys accepting a selection besides `enter`, replacing `{key}` in the
+    /// commands
+    #[serde(default)]
+    pub(crate) keys:         Vec<String>,
+    /// Colors and icons of Jaime's output
+    #[serde(default)]
+    pub(crate) theme:        ThemeConfig,
+}
+
+/// What cancelling a selection (with `esc`) does. `ctrl-c` always quits
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Cancel {
+    /// Quit Jaime
+    Quit,
+    /// Go back to the menu the action was selected from. Cancelling the root
+    /// menu quits
+    Back,
+}
+
+impl Default for Cancel {
+    fn default() -> Self {
+        Self::Quit
+    }
+}
+
+/// Options of a menu, keyed by name. The keys are reference counted, as they
+/// are cloned into the paths of the actions ran
+pub(crate) type Options = IndexMap<Arc<str>, Action>;
+
+/// Path of the [`Config::fallback`] action, in the history
+const FALLBACK_KEY: &str = "fallback";
+
+/// Key of the menu grouping the actions by tag, at the root of the menu
+pub(crate) const TAGS_KEY: &str = "@tags";
+
+/// Add the actions of `options` that aren't submenus to the menu of each of
+/// their tags, as references to their dotted path
+fn group_by_tag(options: &Options, parent: &[String], tags: &mut IndexMap<Arc<str>, Options>) {
+    for (key, action) in options {
+        let mut path = parent.to_vec();
+        path.push(key.to_string());
+
+        if !action.meta().is_listed() {
+            continue;
+        }
+        if let Action::Select { options, .. } = action {
+            group_by_tag(options, &path, tags);
+            continue;
+        }
+
+        for tag in action.tags() {
+            // Dots would be taken as submenus when the key is part of a path
+            tags.entry(tag.as_str().into()).or_default().insert(
+                path.join("/").into(),
+                Action::Menu {
+                    meta: Meta {
+                        description: action.description().map(ToOwned::to_owned),
+                        ..Meta::default()
+                    },
+                    path: path.join("."),
+                },
+            );
+        }
+    }
 }
 
 impl Config {
-    #[must_use]
-    pub(crate) fn into_action(self) -> Action {
-        Action::Select {
-            options:     self.options,
-            description: self.description,
+    /// The shell commands are ran with, defaulting to `$SHELL`
+    pub(crate) fn shell(&self) -> String {
+        self.shell.as_ref().map_or(
+            env::var("SHELL").unwrap_or_else(|_| "sh".to_string()),
+            ToOwned::to_owned,
+        )
+    }
+
+    /// How the shell is started, given to [`exec::set_startup`]
+    pub(crate) fn startup(&self) -> exec::Startup {
+        exec::Startup {
+            shell:  self.shell(),
+            args:   self.shell_args.clone(),
+            login:  self.login_shell,
+            strict: self.strict_shell.unwrap_or(true),
+        }
+    }
+
+    /// Display the root menu, and run the selected action
+    pub(crate) fn run(&self, context: &Context, handler: &Handler) -> Result<Outcome> {
+        run_select(&self.options, context, self, handler, &[], None)
+    }
+
+    /// Add a menu of the tags at [`TAGS_KEY`], where each tag is a submenu of
+    /// the actions having it. Nothing is added if no action has tags, or if
+    /// the key is already used
+    pub(crate) fn add_tag_menu(&mut self) {
+        let mut tags = IndexMap::new();
+        group_by_tag(&self.options, &[], &mut tags);
+
+        if tags.is_empty() {
+            return;
+        }
+
+        let options = tags
+            .into_iter()
+            .map(|(tag, options)| {
+                let description = match options.len() {
+                    1 => String::from("1 action"),
+                    n => format!("{} actions", n),
+                };
+                (tag, Action::Select {
+                    meta: Meta {
+                        description: Some(description),
+                        ..Meta::default()
+                    },
+                    options,
+                })
+            })
+            .collect();
+
+        self.options
+            .entry(TAGS_KEY.into())
+            .or_insert(Action::Select {
+                meta: Meta {
+                    description: Some(String::from("Actions grouped by tag")),
+                    ..Meta::default()
+                },
+                options,
+            });
+    }
+
+    /// Find the action at a dotted `path` of the menu (e.g., `git.log`),
+    /// following the [`Action::Menu`] references along the way, including
+    /// those in the middle of the path. The `allow_users` and `allow_groups`
+    /// of the menus along the path (and of the references followed) apply to
+    /// the actions under them, the restrictions of the action itself being
+    /// left to the caller
+    pub(crate) fn find(&self, path: &str) -> Result<(Vec<String>, &Action)> {
+        self.resolve(path, &mut Vec::new())
+    }
+
+    /// [`Config::find`], where `following` are the references being resolved,
+    /// which `path` can't refer back to
+    fn resolve(&self, path: &str, following: &mut Vec<String>) -> Result<(Vec<String>, &Action)> {
+        if following.iter().any(|p| p == path) {
+            return Err(anyhow!("{} refers back to itself", path));
+        }
+        following.push(path.to_owned());
+
+        let mut options = &self.options;
+        let mut action: Option<&Action> = None;
+        let mut keys = Vec::new();
+
+        for key in path.split('.') {
+            if let Some(parent) = action {
+                if let Some(restriction) = parent.meta().restriction() {
+                    return Err(anyhow!("{} is disabled: {}", keys.join("."), restriction));
+                }
+                options = match parent {
+                    Action::Select { options: inner, .. } => inner,
+                    Action::Menu { path: next, .. } => {
+                        let (target, found) = self.resolve(next, following)?;
+                        match found {
+                            Action::Select {
+                                meta,
+                                options: inner,
+                            } => {
+                                if let Some(restriction) = meta.restriction() {
+                                    return Err(anyhow!(
+                                        "{} is disabled: {}",
+                                        target.join("."),
+                                        restriction
+                                    ));
+                                }
+                                keys = target;
+                                inner
+                            },
+                            _ =>
+                                return Err(anyhow!(
+                                    "{} refers to {}, which isn't a menu, so {} does not exist",
+                                    keys.join("."),
+                                    next,
+                                    path
+                                )),
+                        }
+                    },
+                    _ =>
+                        return Err(anyhow!(
+                            "{} isn't a menu, so {} does not exist",
+                            keys.join("."),
+                            path
+                        )),
+                };
+            }
+            let (key, found) = lookup(options, key)
+                .ok_or_else(|| anyhow!("{} does not exist in the menu", path))?;
+            keys.push(key.to_string());
+            action = Some(found);
+        }
+
+        let found = match action {
+            Some(Action::Menu { meta, path: next }) => {
+                if let Some(restriction) = meta.restriction() {
+                    return Err(anyhow!("{} is disabled: {}", keys.join("."), restriction));
+                }
+                self.resolve(next, following)
+            },
+            Some(action) => Ok((keys, action)),
+            None => Err(anyhow!("empty menu path")),
+        };
+        following.pop();
+        found
+    }
+}
+
+/// A widget of an [`Action::Command`], along with the options shared by all
+/// widgets
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct WidgetConfig {
+    #[serde(flatten)]
+    pub(crate) widget:           Widget,
+    /// Transformation applied to the selected item before it is substituted
+    pub(crate) map:              Option<Map>,
+    /// Cancelling the widget substitutes an empty value, instead of
+    /// cancelling the whole action
+    #[serde(default)]
+    pub(crate) optional:         bool,
+    /// Name of the value in the summary of the widgets, the type of the
+    /// widget by default
+    pub(crate) name:             Option<String>,
+    /// Names or indices of the widgets whose values the widget uses. It is
+    /// displayed after them, and again when one of them is changed from the
+    /// summary
+    #[serde(default)]
+    pub(crate) depends:          Vec<String>,
+    /// Condition on the values of the widgets displayed before it (e.g.,
+    /// `{0} == 'ssh'`), the widget being skipped when it doesn't hold
+    pub(crate) when:             Option<String>,
+    /// Backend displaying the widget, instead of the one chosen on the
+    /// command line
+    pub(crate) selector:         Option<Backend>,
+    /// Options given to the selector displaying the widget (e.g.,
+    /// `--height 90%`), after those of the environment
+    pub(crate) selector_options: Option<String>,
+}
+
+/// Transformation of the item selected with a widget
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Map {
+    /// Shell command whose output replaces the item, with `{}` replaced by
+    /// the quoted item
+    Command(String),
+    /// Template in which `{}` is replaced by the item
+    Template(String),
+}
+
+impl Map {
+    fn apply(
+        &self,
+        context: &Context,
+        shell: &str,
+        envs: &[(String, String)],
+        item: &str,
+    ) -> Result<String> {
+        match self {
+            Map::Template(template) => Ok(template.replace("{}", item)),
+            Map::Command(command) => {
+                let item = shlex::try_quote(item).context("unable to quote item")?;
+                let output = run_shell_command_for_output(
+                    context,
+                    &command.replace("{}", &item),
+                    shell,
+                    envs,
+                )?;
+
+                Ok(output.trim_end_matches('\n').to_owned())
+            },
         }
     }
 }
@@ -64,92 +472,630 @@ impl Config {
 #[serde(tag = "type")]
 pub(crate) enum Widget {
     FromCommand {
-        command: String,
-        preview: Option<String>,
+        command:        String,
+        preview:        Option<String>,
+        /// Multi-line script previewing the items, given the item and the
+        /// values of the widgets before as arguments, instead of `preview`
+        preview_script: Option<String>,
+        /// Cache the output of the preview command for each item, for this
+        /// many seconds
+        preview_cache:  Option<u64>,
+        /// Whether the output is read as lines or as a table
+        #[serde(default)]
+        format:         Format,
+        #[serde(default)]
+        table:          Table,
+        /// Order of the items
+        #[serde(default)]
+        sort:           Sort,
+        /// Remove duplicate items
+        #[serde(default)]
+        unique:         bool,
+        /// Only keep the items matching this regular expression
+        filter:         Option<String>,
+        /// Read and display this many items at a time, the next ones being
+        /// loaded on demand
+        max_items:      Option<usize>,
     },
     FreeText,
+    /// Kubernetes contexts from the kubeconfig
+    KubeContext,
+    /// Kubernetes namespaces, of the given context or the current one
+    KubeNamespace {
+        context: Option<String>,
+    },
+    /// Docker containers, including stopped ones if `all` is set
+    DockerContainer {
+        #[serde(default)]
+        all: bool,
+    },
+    /// Docker images
+    DockerImage,
+    /// Docker volumes
+    DockerVolume,
+    /// Local Git branches, with their upstream status
+    GitBranch,
+    /// Git tags
+    GitTag,
+    /// Changed and untracked files of a Git repository
+    GitFile,
+    /// Git stashes
+    GitStash,
+    /// Git commits reachable from `HEAD`, the most recent `max` ones (500 by
+    /// default)
+    GitCommit {
+        max: Option<usize>,
+    },
+    /// Running processes, optionally only the ones matching `filter`. The
+    /// PIDs of the selected processes are substituted, separated by spaces
+    Process {
+        filter: Option<String>,
+        #[serde(default)]
+        multi:  bool,
+    },
+    /// Values found at `pointer` (a JSON pointer or a jq-like path) in the
+    /// JSON or YAML output of `command`. The raw value is substituted
+    JsonPick {
+        command: String,
+        pointer: Option<String>,
+    },
+    /// Flags of `command`, parsed from its `--help` output or its man page.
+    /// The selected flags, along with the arguments prompted for, are
+    /// substituted
+    FlagPicker {
+        command: String,
+    },
+    /// Environment variables, substituting their name or value. The values of
+    /// the variables matching one of the `mask` patterns are hidden
+    EnvVar {
+        #[serde(default)]
+        substitute: builtin::env::Substitute,
+        mask:       Option<Vec<String>>,
+    },
+    /// systemd units of the system manager, or of the user manager if `user`
+    /// is set. Inactive units are included if `all` is set
+    SystemdUnit {
+        #[serde(default)]
+        user: bool,
+        #[serde(default)]
+        all:  bool,
+    },
+    /// Emoji, searchable by name and keywords. The emoji is substituted
+    Emoji,
+    /// Named colors, substituted in `format` (hex by default)
+    Color {
+        #[serde(default)]
+        format: color::Format,
+    },
+    /// A value generated without prompting: a UUID, a password, hexadecimal
+    /// digits, or placeholder text
+    Generate {
+        kind:    generate::Kind,
+        /// Length of the password or the hexadecimal digits, or number of
+        /// words of the text
+        length:  Option<usize>,
+        /// Include symbols in the password
+        #[serde(default)]
+        symbols: bool,
+    },
+    /// A secret from the operating system's keyring, prompted for and stored
+    /// on first use. The placeholder is replaced with a reference to the
+    /// environment variable holding the secret, not the secret itself
+    Secret {
+        service: String,
+        account: String,
+        /// Name of the environment variable (`JAIME_SECRET_<index>` by default)
+        env:     Option<String>,
+    },
+    /// Display `widget` again and again, until the list of values is done.
+    /// The values are substituted separated by `separator` (a space by
+    /// default), each one replacing `{}` in `template` if given, quoted
+    Repeat {
+        widget:    Box<Widget>,
+        #[serde(default)]
+        until:     Until,
+        separator: Option<String>,
+        template:  Option<String>,
+    },
+    /// Items listed by a function of the program running the menu, registered
+    /// under the name `source` by [`crate::ActionBuilder::source`]
+    Source {
+        source: String,
+    },
+}
+
+/// How the list of values of a [`Widget::Repeat`] is done
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Until {
+    /// Cancelling the widget ends the list. Cancelling it before any value is
+    /// given cancels the widget
+    Cancel,
+    /// An empty value ends the list, cancelling the widget cancels it
+    Empty,
+}
+
+impl Default for Until {
+    fn default() -> Self {
+        Self::Cancel
+    }
+}
+
+impl Widget {
+    /// The `type` of the widget in the configuration
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Widget::FromCommand { .. } => "FromCommand",
+            Widget::FreeText => "FreeText",
+            Widget::KubeContext => "KubeContext",
+            Widget::KubeNamespace { .. } => "KubeNamespace",
+            Widget::DockerContainer { .. } => "DockerContainer",
+            Widget::DockerImage => "DockerImage",
+            Widget::DockerVolume => "DockerVolume",
+            Widget::GitBranch => "GitBranch",
+            Widget::GitTag => "GitTag",
+            Widget::GitFile => "GitFile",
+            Widget::GitStash => "GitStash",
+            Widget::GitCommit { .. } => "GitCommit",
+            Widget::Process { .. } => "Process",
+            Widget::JsonPick { .. } => "JsonPick",
+            Widget::FlagPicker { .. } => "FlagPicker",
+            Widget::EnvVar { .. } => "EnvVar",
+            Widget::SystemdUnit { .. } => "SystemdUnit",
+            Widget::Emoji => "Emoji",
+            Widget::Color { .. } => "Color",
+            Widget::Generate { .. } => "Generate",
+            Widget::Secret { .. } => "Secret",
+            Widget::Repeat { .. } => "Repeat",
+            Widget::Source { .. } => "Source",
+        }
+    }
+
+    /// The widget displayed to get a value, which is the one repeated by a
+    /// [`Widget::Repeat`]
+    pub(crate) fn displayed(&self) -> &Widget {
+        match self {
+            Widget::Repeat { widget, .. } => widget.displayed(),
+            _ => self,
+        }
+    }
+
+    /// The shell command the widget runs, if it runs one
+    pub(crate) fn command(&self) -> Option<&str> {
+        match self.displayed() {
+            Widget::FromCommand { command, .. }
+            | Widget::JsonPick { command, .. }
+            | Widget::FlagPicker { command } => Some(command),
+            _ => None,
+        }
+    }
+
+    /// The shell command previewing the items of the widget, if it has one
+    pub(crate) fn preview(&self) -> Option<&str> {
+        match self.displayed() {
+            Widget::FromCommand {
+                preview,
+                preview_script,
+                ..
+            } => preview_script.as_deref().or(preview.as_deref()),
+            _ => None,
+        }
+    }
+}
+
+/// `command` with the values of `envs` (secrets among them) hidden, so that it
+/// can be shown in an error
+pub(crate) fn masked(command: &str, envs: &[(String, String)]) -> String {
+    envs.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .fold(command.to_owned(), |command, (_, value)| {
+            command.replace(value.as_str(), "***")
+        })
+}
+
+/// Where the output of a command is sent
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Output {
+    /// Output is displayed on the terminal
+    Terminal,
+    /// Output is copied to the clipboard
+    Clipboard,
+    /// Output is displayed in `$PAGER`
+    Pager,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self::Terminal
+    }
+}
+
+/// Options shared by every kind of action
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct Meta {
+    /// Shown next to the action's key in a selector
+    pub(crate) description:  Option<String>,
+    /// Keywords matched by the selectors along with the key and the
+    /// description (e.g., `k8s` for a `kubernetes` entry)
+    #[serde(default)]
+    pub(crate) tags:         Vec<String>,
+    /// Heading the action is listed under, along with the other actions of
+    /// the menu in the same group
+    pub(crate) group:        Option<String>,
+    /// Leave the action out of the menus, so that it can only be ran with
+    /// `jaime run <path>`
+    #[serde(default)]
+    pub(crate) hidden:       bool,
+    /// Show the action greyed out, without allowing it to run
+    #[serde(default)]
+    pub(crate) disabled:     bool,
+    /// Why the action is disabled
+    pub(crate) reason:       Option<String>,
+    /// Binaries needed by the action, which is disabled if any is missing from
+    /// `PATH`
+    #[serde(default)]
+    pub(crate) requires:     Vec<String>,
+    /// Users allowed to run the action. When this or `allow_groups` is given,
+    /// anyone else is denied
+    #[serde(default)]
+    pub(crate) allow_users:  Vec<String>,
+    /// Groups whose members are allowed to run the action
+    #[serde(default)]
+    pub(crate) allow_groups: Vec<String>,
+    /// What the users who aren't allowed see of the action
+    #[serde(default)]
+    pub(crate) denied:       Denied,
+    /// What a [`Action::Chain`] does when the action fails as one of its steps
+    #[serde(default)]
+    pub(crate) on_failure:   OnFailure,
+}
+
+/// What a chain does when one of its steps fails (exits with a non-zero
+/// status, or times out): `stop`, `continue`, or `run:<path>`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum OnFailure {
+    /// Fail the chain, without running the remaining steps
+    Stop,
+    /// Run the remaining steps as if the step succeeded
+    Continue,
+    /// Run the action at this dotted path of the menu in place of the step,
+    /// then the remaining steps
+    Run(String),
+}
+
+impl Default for OnFailure {
+    fn default() -> Self {
+        Self::Stop
+    }
+}
+
+impl TryFrom<String> for OnFailure {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "stop" => Ok(Self::Stop),
+            "continue" => Ok(Self::Continue),
+            _ => match value.strip_prefix("run:").map(str::trim) {
+                Some(path) if !path.is_empty() => Ok(Self::Run(path.to_owned())),
+                _ => Err(format!(
+                    "invalid on_failure: {}, expected stop, continue, or run:<path>",
+                    value
+                )),
+            },
+        }
+    }
+}
+
+impl From<OnFailure> for String {
+    fn from(on_failure: OnFailure) -> Self {
+        match on_failure {
+            OnFailure::Stop => String::from("stop"),
+            OnFailure::Continue => String::from("continue"),
+            OnFailure::Run(path) => format!("run:{}", path),
+        }
+    }
+}
+
+/// What is shown of an action to a user who isn't allowed to run it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Denied {
+    /// Show the action greyed out
+    Disable,
+    /// Leave the action out of the menus
+    Hide,
+}
+
+impl Default for Denied {
+    fn default() -> Self {
+        Self::Disable
+    }
+}
+
+/// Whether binaries were found in `PATH`, so they are only looked up once
+static FOUND: Lazy<Mutex<HashMap<String, bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl Meta {
+    /// Whether the current user may run the action, according to
+    /// `allow_users` and `allow_groups`
+    pub(crate) fn is_allowed(&self) -> bool {
+        (self.allow_users.is_empty() && self.allow_groups.is_empty())
+            || self.allow_users.iter().any(|u| u == user::name())
+            || self.allow_groups.iter().any(|g| user::groups().contains(g))
+    }
+
+    /// Who may run the action, if the current user may not
+    pub(crate) fn restriction(&self) -> Option<String> {
+        if self.is_allowed() {
+            return None;
+        }
+        let allowed = self
+            .allow_users
+            .iter()
+            .cloned()
+            .chain(self.allow_groups.iter().map(|g| format!("@{}", g)))
+            .collect::<Vec<_>>();
+        Some(format!("restricted to {}", allowed.join(", ")))
+    }
+
+    /// Whether the action is listed in the menus, neither `hidden` nor hidden
+    /// from a user who isn't allowed to run it
+    pub(crate) fn is_listed(&self) -> bool {
+        !self.hidden && (self.denied == Denied::Disable || self.is_allowed())
+    }
+
+    /// Why the action can't run, if it is disabled, restricted to other users,
+    /// or requires a binary that is missing
+    pub(crate) fn disabled_reason(&self) -> Option<String> {
+        if self.disabled {
+            return Some(
+                self.reason
+                    .clone()
+                    .unwrap_or_else(|| String::from("disabled")),
+            );
+        }
+        if let Some(restriction) = self.restriction() {
+            return Some(restriction);
+        }
+
+        let mut found = FOUND.lock().unwrap_or_else(PoisonError::into_inner);
+        let missing = self
+            .requires
+            .iter()
+            .filter(|bin| {
+                !*found
+                    .entry((*bin).clone())
+                    .or_insert_with(|| which::which(bin).is_ok())
+            })
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+
+        (!missing.is_empty()).then(|| format!("requires {}", missing.join(", ")))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub(crate) enum Action {
     Command {
-        description: Option<String>,
-        command:     String,
-        widgets:     Option<Vec<Widget>>,
+        #[serde(flatten)]
+        meta:           Meta,
+        /// Ran from a script when it has several lines, so that the line
+        /// numbers in its errors are its own
+        command:        String,
+        /// Program running `command`, instead of the shell of the
+        /// configuration. When it isn't a shell (e.g., `python3`), the values
+        /// are given to `command` as arguments instead of replacing its
+        /// placeholders
+        interpreter:    Option<String>,
+        /// Whether the shell stops at the first failure and on unset
+        /// variables, instead of the `strict_shell` of the configuration
+        strict_shell:   Option<bool>,
+        /// Whether the shell running the command is an interactive login
+        /// shell, instead of the `login_shell` of the configuration. The
+        /// shells of its widgets never are
+        login_shell:    Option<bool>,
+        /// Whether the command can read from the terminal (the default), for
+        /// those that are interactive themselves. Otherwise it reads nothing
+        tty:            Option<bool>,
+        widgets:        Option<Vec<WidgetConfig>>,
+        #[serde(default)]
+        output:         Output,
+        /// Capture the output, making it available as `{prev}` to the next
+        /// step of a [`Action::Chain`]
+        #[serde(default)]
+        capture:        bool,
+        /// Send a desktop notification when the command finishes
+        #[serde(default)]
+        notify:         bool,
+        /// Kill the command if it runs longer than this many seconds
+        timeout:        Option<u64>,
+        /// Refuse to run the command while it runs in another session
+        #[serde(default)]
+        exclusive:      bool,
+        /// Store the output of the command (its end, when it is long) with
+        /// its entry of the history
+        #[serde(default)]
+        capture_output: bool,
+        /// Secrets available to the command and its widgets as environment
+        /// variables
+        #[serde(default)]
+        secrets:        Secrets,
+        /// Show the values of the widgets before running the command, so that
+        /// any of them can be changed. By default, only when the command has
+        /// at least [`SUMMARY_WIDGETS`] widgets
+        summary:        Option<bool>,
+        /// Shell commands whose output is substituted after the values of the
+        /// widgets, without prompting: the first one is `{N}`, `N` being the
+        /// number of widgets. Each one can use the values before it
+        #[serde(default)]
+        computed:       Vec<String>,
     },
     Select {
-        description: Option<String>,
-        options:     HashMap<String, Action>,
+        #[serde(flatten)]
+        meta:    Meta,
+        options: Options,
+    },
+    /// Run several actions in order
+    Chain {
+        #[serde(flatten)]
+        meta:  Meta,
+        steps: Vec<Action>,
+    },
+    /// Jump to the action at a dotted `path` of the menu (e.g., `git.log`)
+    Menu {
+        #[serde(flatten)]
+        meta: Meta,
+        path: String,
+    },
+    /// Builtin menu to start, stop, restart, or show the status of a systemd
+    /// unit
+    Systemd {
+        #[serde(flatten)]
+        meta: Meta,
+        #[serde(default)]
+        user: bool,
     },
 }
 
-fn run_shell(context: &Context, cmd: &str, shell: &str) -> Result<()> {
-    let mut builder = Command::new(shell);
+/// What displays the selections
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Backend {
+    /// The embedded `skim` library
+    Skim,
+    /// The `sk` binary
+    SkimBinary,
+    /// The `fzf` binary
+    Fzf,
+    /// Numbered lines read from the terminal
+    Plain,
+    /// The frontend a program embedding Jaime set
+    #[serde(skip)]
+    Frontend,
+}
 
-    if shell == "zsh" {
-        builder.arg("--shwordsplit");
-        builder.arg("--no-unset");
-        builder.arg("--errexit");
-    } else if shell == "bash" {
-        builder.arg("-e");
-        builder.arg("-u");
+impl Backend {
+    /// The backend chosen on the command line
+    fn of(handler: &Handler) -> Self {
+        if handler.plain() {
+            Self::Plain
+        } else if handler.fzf() {
+            Self::Fzf
+        } else if handler.skim() {
+            Self::SkimBinary
+        } else {
+            Self::Skim
+        }
     }
+}
 
-    builder
-        .arg("-c")
-        .arg(cmd)
-        .env("JAIME_CACHE_DIR", &context.cache_directory)
-        .status()?;
+/// The backend and options of the selector, when a widget being displayed
+/// overrides them
+#[derive(Debug, Clone, Default)]
+struct Selector {
+    backend: Option<Backend>,
+    options: Vec<String>,
+}
 
-    Ok(())
+static SELECTOR: Lazy<Mutex<Selector>> = Lazy::new(|| Mutex::new(Selector::default()));
+
+/// Overrides the selector until it is dropped
+#[derive(Debug)]
+struct SelectorGuard;
+
+impl SelectorGuard {
+    fn set(backend: Option<Backend>, options: Option<&str>) -> Result<Self> {
+        let options = match options {
+            Some(options) => shlex::split(options)
+                .ok_or_else(|| anyhow!("invalid selector_options: {}", options))?,
+            None => Vec::new(),
+        };
+        *SELECTOR.lock().unwrap() = Selector { backend, options };
+
+        Ok(Self)
+    }
 }
 
-fn run_shell_command_for_output(context: &Context, cmd: &str, shell: &str) -> Result<String> {
-    let mut builder = Command::new(shell);
+impl Drop for SelectorGuard {
+    fn drop(&mut self) {
+        *SELECTOR.lock().unwrap_or_else(PoisonError::into_inner) = Selector::default();
+    }
+}
 
-    if shell == "zsh" {
-        builder.arg("--shwordsplit"); // -y
-        builder.arg("--no-unset"); // -u
-        builder.arg("--errexit"); // -e
-    } else if shell == "bash" {
-        builder.arg("-e");
-        builder.arg("-u");
+/// The backend displaying the selections, the one of the widget being
+/// displayed if it has one
+fn backend(handler: &Handler) -> Backend {
+    if frontend::is_set() {
+        return Backend::Frontend;
     }
+    SELECTOR
+        .lock()
+        .unwrap()
+        .backend
+        .unwrap_or_else(|| Backend::of(handler))
+}
 
-    Ok(std::str::from_utf8(
-        builder
-            .arg("-c")
-            .arg(cmd)
-            .env("JAIME_CACHE_DIR", &context.cache_directory)
-            .output()?
-            .stdout
-            .as_slice(),
-    )?
-    .to_owned())
+/// The options of the widget being displayed given to the selector
+fn selector_options() -> Vec<String> {
+    SELECTOR.lock().unwrap().options.clone()
 }
 
 /// Display selection with the `skim` library
-fn display_selector(input: String, preview: Option<&str>) -> Option<String> {
+fn display_selector(
+    settings: &SkimSettings,
+    keys: &[String],
+    input: String,
+    preview: Option<&str>,
+    multi: bool,
+    palette: bool,
+    more: bool,
+) -> Selected {
     let mut skim_args = Vec::new();
     let default_height = String::from("50%");
     let default_margin = String::from("0%");
     let default_layout = String::from("default");
+    let default_prompt = String::from("> ");
     // This is the default settings within the skim 'src/' folder
     let default_theme = String::from(
         "matched:108,matched_bg:0,current:254,current_bg:236,current_match:151,current_match_bg:\
          236,spinner:148,info:144,prompt:110,cursor:161,selected:168,header:109,border:59",
     );
 
-    skim_args.extend(
-        std::env::var("SKIM_DEFAULT_OPTIONS")
-            .ok()
-            .and_then(|val| shlex::split(&val))
-            .unwrap_or_default(),
-    );
+    // The options are looked up in order, so those of the widget come first
+    skim_args.extend(selector_options());
+    match std::env::var("SKIM_DEFAULT_OPTIONS") {
+        Ok(val) => skim_args.extend(shlex::split(&val).unwrap_or_default()),
+        // The look of `fzf` is used when skim isn't configured
+        Err(_) => skim_args.extend(fzf::translate(
+            &env::var("FZF_DEFAULT_OPTS")
+                .ok()
+                .and_then(|val| shlex::split(&val))
+                .unwrap_or_default(),
+        )),
+    }
+
+    let mut bind = skim_args
+        .iter()
+        .filter(|arg| arg.contains("--bind"))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    let preview_toggle = settings
+        .preview_toggle
+        .as_ref()
+        .map(|key| format!("{}:toggle-preview", key));
+    if let Some(preview_toggle) = &preview_toggle {
+        bind.push(preview_toggle);
+    }
 
+    let indexed = table::is_indexed(&input);
+    let preview = preview_of(preview, indexed);
     let options = SkimOptionsBuilder::default()
-        .preview(preview)
+        .preview(preview.as_deref())
+        .delimiter(indexed.then_some("\x1f"))
         .margin(Some(
             skim_args
                 .iter()
@@ -196,24 +1142,39 @@ fn display_selector(input: String, preview: Option<&str>) -> Option<String> {
                         .map_or(&default_theme, |pos| &skim_args[pos + 1])
                 }),
         ))
-        .bind(
+        .prompt(Some(
             skim_args
                 .iter()
-                .filter(|arg| arg.contains("--bind"))
-                .map(String::as_str)
-                .collect::<Vec<_>>(),
-        )
+                .find_map(|arg| arg.strip_prefix("--prompt="))
+                .unwrap_or_else(|| {
+                    skim_args
+                        .iter()
+                        .position(|arg| arg == "--prompt")
+                        .and_then(|pos| skim_args.get(pos + 1))
+                        .unwrap_or(&default_prompt)
+                }),
+        ))
+        .bind(bind)
+        .no_mouse(!settings.mouse || skim_args.iter().any(|arg| arg == "--no-mouse"))
         .reverse(skim_args.iter().any(|arg| arg.contains("--reverse")))
         .tac(skim_args.iter().any(|arg| arg.contains("--tac")))
         .nosort(skim_args.iter().any(|arg| arg.contains("--no-sort")))
         .inline_info(skim_args.iter().any(|arg| arg.contains("--inline-info")))
-        .multi(false)
+        .multi(multi)
+        .expect(Some(expect_keys(keys, palette, more)))
         .build()
         .unwrap();
 
     // `SkimItemReader` is a helper to turn any `BufRead` into a stream of
     // `SkimItem` `SkimItem` was implemented for `AsRef<str>` by default
-    let item_reader_opts = SkimItemReaderOption::default().ansi(true).build();
+    let item_reader_opts =
+        SkimItemReaderOption::default().ansi(!skim_args.iter().any(|arg| arg == "--no-ansi"));
+    let item_reader_opts = if indexed {
+        item_reader_opts.delimiter("\x1f").with_nth("2..")
+    } else {
+        item_reader_opts
+    }
+    .build();
     let item_reader = SkimItemReader::new(item_reader_opts);
     let items = item_reader.of_bufread(Cursor::new(input));
 
@@ -223,250 +1184,2017 @@ fn display_selector(input: String, preview: Option<&str>) -> Option<String> {
 
     let selected_items = Skim::run_with(&options, Some(items));
 
-    selected_items
-        .map_or_else(Vec::new, |out| {
+    let selected_items = match selected_items {
+        Some(out) => {
             if out.is_abort {
-                process::exit(130);
+                return Selected::Aborted;
+            }
+            match &out.final_event {
+                Event::EvActAccept(Some(key)) => set_typed(&out.query, key),
+                _ => set_typed(&out.query, ""),
+            }
+            match &out.final_event {
+                Event::EvActAccept(Some(key)) if key == CANCEL_KEY => return Selected::Nothing,
+                Event::EvActAccept(Some(key)) if key == palette::KEY => return Selected::Palette,
+                Event::EvActAccept(Some(key)) if key == listing::KEY => return Selected::More,
+                Event::EvActAccept(_) if out.selected_items.is_empty() && !out.query.is_empty() =>
+                    return Selected::Unmatched(out.query),
+                _ => {},
             }
             out.selected_items
-        })
-        .get(0)
-        .map(|selected| selected.output().to_string())
+        },
+        None => Vec::new(),
+    };
+
+    if selected_items.is_empty() {
+        Selected::Nothing
+    } else {
+        Selected::Lines(
+            selected_items
+                .iter()
+                .map(|selected| selected.output().to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+}
+
+/// The preview command given to a selector, previewing the columns displayed
+/// rather than the whole line when the items are `indexed` rows
+fn preview_of(preview: Option<&str>, indexed: bool) -> Option<String> {
+    preview.map(|preview| {
+        if indexed {
+            preview.replace("{}", table::PREVIEW_FIELD)
+        } else {
+            preview.to_owned()
+        }
+    })
+}
+
+/// The key cancelling a selection. It is expected by the selectors, which
+/// would otherwise abort on it the same way as on `ctrl-c`, which quits
+pub(crate) const CANCEL_KEY: &str = "esc";
+
+/// The keys accepting a selection besides `enter`, separated by commas: those
+/// of Jaime, then the `keys` of the configuration
+fn expect_keys(keys: &[String], palette: bool, more: bool) -> String {
+    [
+        (true, CANCEL_KEY),
+        (palette, palette::KEY),
+        (more, listing::KEY),
+    ]
+    .iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, key)| *key)
+    .chain(keys.iter().map(String::as_str))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Read the output of a selector binary ran with `--print-query` and
+/// `--expect`, whose first lines are the query and the key that was pressed to
+/// accept the selection
+fn expected(stdout: &str, palette: bool, more: bool) -> Selected {
+    let stdout = stdout.strip_suffix('\n').unwrap_or(stdout);
+
+    let (query, stdout) = stdout.split_once('\n').unwrap_or((stdout, ""));
+    let (key, lines) = stdout.split_once('\n').unwrap_or((stdout, ""));
+    set_typed(query, key);
+    match key {
+        CANCEL_KEY => Selected::Nothing,
+        palette::KEY if palette => Selected::Palette,
+        listing::KEY if more => Selected::More,
+        _ if lines.is_empty() && query.is_empty() => Selected::Nothing,
+        _ if lines.is_empty() => Selected::Unmatched(query.into()),
+        _ => Selected::Lines(lines.into()),
+    }
+}
+
+/// Where a selector binary reads its items from
+#[derive(Debug)]
+enum Items<'a> {
+    Text(&'a str),
+    /// The output of a command, read by the selector itself
+    Pipe(ChildStdout),
+}
+
+impl<'a> Items<'a> {
+    /// Whether the items are rows of a table preceded by their index
+    fn is_indexed(&self) -> bool {
+        matches!(self, Items::Text(text) if table::is_indexed(text))
+    }
+
+    /// Set the standard input of the selector, returning the text that has to
+    /// be written to it once it is spawned
+    fn connect(self, command: &mut Command) -> Option<&'a str> {
+        match self {
+            Items::Text(text) => {
+                command.stdin(Stdio::piped());
+                Some(text)
+            },
+            Items::Pipe(stdout) => {
+                command.stdin(Stdio::from(stdout));
+                None
+            },
+        }
+    }
+}
+
+/// Write the `input` of a selector binary to its standard input, when the items
+/// are given as text. The selector may exit before reading all of them
+fn feed(child: &mut Child, input: Option<&str>) -> Result<(), JaimeError> {
+    if let (Some(input), Some(stdin)) = (input, child.stdin.as_mut()) {
+        match stdin.write_all(input.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(JaimeError::Io(e)),
+            _ => {},
+        }
+    }
+
+    Ok(())
 }
 
 /// Display selection with the `fzf` binary
-fn display_selector_fzf(input: &str, preview: Option<&str>) -> Option<String> {
+fn display_selector_fzf(
+    items: Items<'_>,
+    keys: &[String],
+    preview: Option<&str>,
+    multi: bool,
+    palette: bool,
+    more: bool,
+) -> Result<Selected> {
     // Spawn fzf
     let mut command = Command::new(FZF_BIN);
+    command.arg("--ansi");
+    if multi {
+        command.arg("--multi");
+    }
+    command.arg("--print-query");
+    command.arg(format!("--expect={}", expect_keys(keys, palette, more)));
+    let indexed = items.is_indexed();
+    if indexed {
+        command.args(&table::SELECTOR_OPTIONS);
+    }
+    let preview = preview_of(preview, indexed);
 
-    if let Some(prev) = preview {
+    if let Some(prev) = &preview {
         command.arg("--preview").arg(prev);
         command.arg("--preview-window").arg(":nohidden");
     } else {
         command.arg("--preview-window").arg(":hidden");
     }
-    command
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit());
+    let input = items.connect(&mut command);
+    command.stdout(Stdio::piped()).stderr(Stdio::inherit());
 
+    command.args(selector_options());
     if let Some(fzf_opts) = env::var_os("FZF_DEFAULT_OPTS") {
         command.env("FZF_DEFAULT_OPTS", fzf_opts);
     }
 
-    let mut child = command.spawn().expect("failed to spawn fzf");
-
-    child
-        .stdin
-        .as_mut()
-        .unwrap()
-        .write_all(input.as_bytes())
-        .expect("failed to feed list of items to fzf");
-
-    let output = child.wait_with_output().expect("failed to select with fzf");
-
-    // No item selected on non-zero exit code
-    if !output.status.success() {
-        return None;
-    }
-
-    // Get selected item, assert validity
-    let stdout = std::str::from_utf8(&output.stdout).unwrap();
-    let stdout = stdout.strip_suffix('\n').unwrap_or(stdout);
+    let mut child = command
+        .spawn()
+        .map_err(JaimeError::Io)
+        .context("failed to spawn fzf")?;
+    feed(&mut child, input).context("failed to feed list of items to fzf")?;
+    let output = child
+        .wait_with_output()
+        .map_err(JaimeError::Io)
+        .context("failed to select with fzf")?;
 
-    Some(stdout.into())
+    // Interrupted with `ctrl-c`, or nothing matched the query
+    Ok(match output.status.code() {
+        Some(0 | 1) => expected(&String::from_utf8_lossy(&output.stdout), palette, more),
+        Some(130) => Selected::Aborted,
+        _ => Selected::Nothing,
+    })
 }
 
 /// Display selection with the `skim` binary
-fn display_selector_skim(input: &str, preview: Option<&str>) -> Option<String> {
+fn display_selector_skim(
+    items: Items<'_>,
+    keys: &[String],
+    preview: Option<&str>,
+    multi: bool,
+    palette: bool,
+    more: bool,
+) -> Result<Selected> {
     let mut command = Command::new(SKIM_BIN);
-    if let Some(prev) = preview {
+    command.arg("--ansi");
+    if multi {
+        command.arg("--multi");
+    }
+    command.arg("--print-query");
+    command.arg(format!("--expect={}", expect_keys(keys, palette, more)));
+    let indexed = items.is_indexed();
+    if indexed {
+        command.args(&table::SELECTOR_OPTIONS);
+    }
+    let preview = preview_of(preview, indexed);
+    if let Some(prev) = &preview {
         command.arg("--preview").arg(prev);
         command.arg("--preview-window").arg(":nohidden");
     } else {
         command.arg("--preview-window").arg(":hidden");
     }
-    command
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::inherit());
+    let input = items.connect(&mut command);
+    command.stdout(Stdio::piped()).stderr(Stdio::inherit());
 
+    command.args(selector_options());
     if let Some(skim_opts) = env::var_os("SKIM_DEFAULT_OPTIONS") {
         command.env("SKIM_DEFAULT_OPTIONS", skim_opts);
     }
 
-    let mut child = command.spawn().expect("failed to spawn skim");
-
-    // Communicate list of items to skim
-    child
-        .stdin
-        .as_mut()
-        .unwrap()
-        .write_all(input.as_bytes())
-        .expect("failed to feed list of items to skim");
-
+    let mut child = command
+        .spawn()
+        .map_err(JaimeError::Io)
+        .context("failed to spawn skim")?;
+    feed(&mut child, input).context("failed to feed list of items to skim")?;
     let output = child
         .wait_with_output()
-        .expect("failed to select with skim");
-
-    // No item selected on non-zero exit code
-    if !output.status.success() {
-        return None;
-    }
+        .map_err(JaimeError::Io)
+        .context("failed to select with skim")?;
 
-    // Get selected item, assert validity
-    let stdout = std::str::from_utf8(&output.stdout).unwrap();
-    let stdout = stdout.strip_suffix('\n').unwrap_or(stdout);
+    // Interrupted with `ctrl-c`, or nothing matched the query
+    Ok(match output.status.code() {
+        Some(0 | 1) => expected(&String::from_utf8_lossy(&output.stdout), palette, more),
+        Some(130) => Selected::Aborted,
+        _ => Selected::Nothing,
+    })
+}
 
-    Some(stdout.into())
+/// What the user did in a selector
+#[derive(Debug)]
+enum Selected {
+    /// Lines were selected, separated by newlines
+    Lines(String),
+    /// The key opening the palette was pressed
+    Palette,
+    /// The next page of a listing was asked for
+    More,
+    /// The selector was aborted, which leaves Jaime
+    Aborted,
+    /// Nothing was selected
+    Nothing,
+    /// The query, matching none of the lines, was accepted
+    Unmatched(String),
 }
 
-fn readline() -> Result<String> {
+/// Take the selection from the session being replayed, or make it with
+/// `select` otherwise. The selection is recorded when a session is
+fn session_selection(select: impl FnOnce() -> Result<Selected>) -> Result<Selected> {
+    // Set by the selector, if it has a query
+    set_typed("", "");
+    let selected = match session::replay(Step::is_selection) {
+        Some(Step::Select { value, query, key }) => {
+            set_typed(&query, &key);
+            Selected::Lines(value)
+        },
+        Some(Step::Key { key }) if key == listing::KEY => Selected::More,
+        Some(Step::Key { .. }) => Selected::Palette,
+        Some(Step::Query { query }) => {
+            set_typed(&query, "");
+            Selected::Unmatched(query)
+        },
+        Some(_) => Selected::Nothing,
+        None => select()?,
+    };
+
+    let step = match &selected {
+        Selected::Lines(value) => {
+            let Typed { query, key } = typed();
+            Step::Select {
+                value: value.clone(),
+                query,
+                key,
+            }
+        },
+        Selected::Palette => Step::Key {
+            key: palette::KEY.into(),
+        },
+        Selected::More => Step::Key {
+            key: listing::KEY.into(),
+        },
+        Selected::Nothing => Step::Cancel,
+        Selected::Unmatched(query) => Step::Query {
+            query: query.clone(),
+        },
+        Selected::Aborted => return Ok(selected),
+    };
+    session::record(&step);
+
+    Ok(selected)
+}
+
+/// Display a selection with the backend chosen on the command line
+fn display(
+    context: &Context,
+    handler: &Handler,
+    input: String,
+    preview: Option<&str>,
+    multi: bool,
+    palette: bool,
+    more: bool,
+) -> Result<Selected> {
+    session_selection(|| display_with(context, handler, input, preview, multi, palette, more))
+}
+
+/// Display a selection with the backend chosen on the command line, leaving
+/// the session alone
+fn display_with(
+    context: &Context,
+    handler: &Handler,
+    input: String,
+    preview: Option<&str>,
+    multi: bool,
+    palette: bool,
+    more: bool,
+) -> Result<Selected> {
+    Ok(match backend(handler) {
+        Backend::Plain => match plain::select(&input, preview, multi) {
+            Ok(Some(plain::Answer::Lines { lines, query })) => {
+                set_typed(&query, "");
+                Selected::Lines(lines)
+            },
+            Ok(Some(plain::Answer::Query(query))) => {
+                set_typed(&query, "");
+                Selected::Unmatched(query)
+            },
+            Ok(None) => Selected::Nothing,
+            Err(e) if error::is_aborted(&e) => Selected::Aborted,
+            Err(e) => {
+                jaime_error!("{}", e);
+                Selected::Nothing
+            },
+        },
+        Backend::Frontend => match frontend::select(&input, preview, multi) {
+            Ok(Some(lines)) => Selected::Lines(lines),
+            Ok(None) => Selected::Nothing,
+            Err(e) => {
+                jaime_error!("{}", e);
+                Selected::Nothing
+            },
+        },
+        Backend::Fzf => display_selector_fzf(
+            Items::Text(&input),
+            &context.keys,
+            preview,
+            multi,
+            palette,
+            more,
+        )?,
+        Backend::SkimBinary => display_selector_skim(
+            Items::Text(&input),
+            &context.keys,
+            preview,
+            multi,
+            palette,
+            more,
+        )?,
+        Backend::Skim => display_selector(
+            &context.skim,
+            &context.keys,
+            input,
+            preview,
+            multi,
+            palette,
+            more,
+        ),
+    })
+}
+
+/// Display a selection with the backend chosen on the command line. When
+/// `multi` is set, several lines can be selected and are separated by newlines.
+///
+/// The [`palette`] can be opened from the selection, which is displayed again
+/// once the operation is done. Aborting the selector is an error, so that Jaime
+/// exits once everything is unwound
+fn select_with(
+    context: &Context,
+    handler: &Handler,
+    input: &str,
+    preview: Option<&str>,
+    multi: bool,
+) -> Result<Option<String>> {
+    let selected = select_or_query(context, handler, input, preview, multi)?;
+    Ok(if let Selected::Lines(lines) = selected {
+        Some(lines)
+    } else {
+        None
+    })
+}
+
+/// Display a selection like [`select_with`], returning the selected lines
+/// ([`Selected::Lines`]), the query if it matched none of them
+/// ([`Selected::Unmatched`]), or [`Selected::Nothing`]
+fn select_or_query(
+    context: &Context,
+    handler: &Handler,
+    input: &str,
+    preview: Option<&str>,
+    multi: bool,
+) -> Result<Selected> {
+    loop {
+        match display(
+            context,
+            handler,
+            input.to_owned(),
+            preview,
+            multi,
+            true,
+            false,
+        )? {
+            Selected::More => return Ok(Selected::Nothing),
+            Selected::Aborted => return Err(JaimeError::SelectorAborted.into()),
+            Selected::Palette =>
+                if open_palette(context, handler) {
+                    return Err(JaimeError::SelectorAborted.into());
+                },
+            selected => return Ok(selected),
+        }
+    }
+}
+
+/// Display the lines of a [`Listing`] read so far, followed by a line offering
+/// to load the next page when there is one. Returns [`Selected::More`] when
+/// that line is selected or its key is pressed
+fn select_page(
+    context: &Context,
+    handler: &Handler,
+    input: &str,
+    listing: &Listing,
+    preview: Option<&str>,
+) -> Result<Selected> {
+    // Hidden like the index of the rows when they have one
+    let more = listing.more().map(|line| {
+        if table::is_indexed(input) {
+            table::with_index(input.lines().count(), &line)
+        } else {
+            line
+        }
+    });
+    let input = match &more {
+        Some(line) if input.is_empty() => line.clone(),
+        Some(line) => format!("{}\n{}", input, line),
+        None => input.to_owned(),
+    };
+
+    loop {
+        match display(
+            context,
+            handler,
+            input.clone(),
+            preview,
+            false,
+            true,
+            more.is_some(),
+        )? {
+            Selected::Palette =>
+                if open_palette(context, handler) {
+                    return Ok(Selected::Aborted);
+                },
+            Selected::Lines(line) if more.as_deref() == Some(line.as_str()) =>
+                return Ok(Selected::More),
+            selected => return Ok(selected),
+        }
+    }
+}
+
+/// Open the [`palette`] from a selection, with the same backend. Returns
+/// whether the selection is left for jaime to be started over
+fn open_palette(context: &Context, handler: &Handler) -> bool {
+    let pick = |input| {
+        Ok(
+            match display(context, handler, input, None, false, false, false)? {
+                Selected::Lines(line) => Some(line),
+                Selected::Palette
+                | Selected::More
+                | Selected::Nothing
+                | Selected::Unmatched(_)
+                | Selected::Aborted => None,
+            },
+        )
+    };
+    palette::open(context, pick).unwrap_or_else(|e| {
+        jaime_error!("{}", e);
+        false
+    })
+}
+
+/// Display the output of `command` with the selector binary chosen on the
+/// command line, connecting the standard output of the command to the
+/// standard input of the selector. Huge listings are then shown as they come,
+/// without being read into memory first
+fn select_streamed(
+    context: &Context,
+    handler: &Handler,
+    command: &str,
+    shell: &str,
+    envs: &[(String, String)],
+    preview: Option<&str>,
+) -> Result<Option<String>> {
+    loop {
+        let mut listing = exec::shell_command(context, command, shell, envs)?;
+        let mut child = listing
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context(format!("unable to run: {}", command))?;
+        let items = Items::Pipe(child.stdout.take().context("no output to read")?);
+        // Collected rather than printed over the selector
+        let errors = child.stderr.take().map(|mut stderr| {
+            thread::spawn(move || {
+                let mut buf = Vec::new();
+                let _ = stderr.read_to_end(&mut buf);
+                buf
+            })
+        });
+
+        let selected = session_selection(|| {
+            if backend(handler) == Backend::Fzf {
+                display_selector_fzf(items, &context.keys, preview, false, true, false)
+            } else {
+                display_selector_skim(items, &context.keys, preview, false, true, false)
+            }
+        });
+
+        // The command may still be listing items once one is selected. Only
+        // when it exited on its own does its status tell whether it failed
+        let exited = child.try_wait()?;
+        let _ = child.kill();
+        let _ = child.wait();
+        let errors = errors
+            .and_then(|errors| errors.join().ok())
+            .unwrap_or_default();
+
+        match selected? {
+            Selected::Lines(lines) => return Ok(Some(lines)),
+            Selected::Nothing | Selected::Unmatched(_) => match exited {
+                Some(status) if !status.success() =>
+                    return Err(exec::failed(command, status, &errors)),
+                _ => return Ok(None),
+            },
+            Selected::More => return Ok(None),
+            Selected::Aborted => return Err(JaimeError::SelectorAborted.into()),
+            // The command is ran again once the palette is closed
+            Selected::Palette =>
+                if open_palette(context, handler) {
+                    return Err(JaimeError::SelectorAborted.into());
+                },
+        }
+    }
+}
+
+/// Display a selection of a single line
+pub(crate) fn select(
+    context: &Context,
+    handler: &Handler,
+    input: &str,
+    preview: Option<&str>,
+) -> Result<Option<String>> {
+    select_with(context, handler, input, preview, false)
+}
+
+/// Display the items of a builtin widget, returning the selected value
+fn select_builtin(
+    context: &Context,
+    handler: &Handler,
+    items: &[builtin::Item],
+    preview: preview::Builtin,
+) -> Result<Option<String>> {
+    select_builtin_with(context, handler, items, Some(preview), false)
+}
+
+/// Display the items of a builtin widget, returning the selected values
+/// separated by spaces when `multi` is set
+fn select_builtin_with(
+    context: &Context,
+    handler: &Handler,
+    items: &[builtin::Item],
+    preview: Option<preview::Builtin>,
+    multi: bool,
+) -> Result<Option<String>> {
+    let preview = preview.map(|p| p.command("{}")).transpose()?;
+
+    Ok(select_with(
+        context,
+        handler,
+        &builtin::render(items),
+        preview.as_deref(),
+        multi,
+    )?
+    .map(|s| {
+        s.lines()
+            .map(builtin::value_of)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }))
+}
+
+/// Read a line, returning `None` when cancelled with `ctrl-d`. `ctrl-c` aborts
+/// like it does in the selectors
+fn readline(prompt: &str) -> Result<Option<String>> {
+    readline_with(prompt, "")
+}
+
+/// Read a line starting out as `initial`, which can be edited
+pub(crate) fn readline_with(prompt: &str, initial: &str) -> Result<Option<String>> {
+    match read_line(prompt, initial, &[])? {
+        Entered::Line(line) => Ok(line),
+        // Only with a history
+        Entered::Search(typed) => Ok(Some(typed)),
+    }
+}
+
+/// What was typed at a prompt
+#[derive(Debug)]
+enum Entered {
+    /// The line, `None` when cancelled
+    Line(Option<String>),
+    /// `ctrl-r` was pressed with this text typed, to search the history
+    Search(String),
+}
+
+/// Sets its flag and accepts the line when the key it is bound to is pressed
+#[derive(Debug)]
+struct SearchKey(Arc<AtomicBool>);
+
+impl ConditionalEventHandler for SearchKey {
+    fn handle(&self, _: &LineEvent, _: RepeatCount, _: bool, _: &EventContext<'_>) -> Option<Cmd> {
+        self.0.store(true, Ordering::Relaxed);
+        Some(Cmd::AcceptLine)
+    }
+}
+
+/// Read a line starting out as `initial`, the lines of the `history` being
+/// recalled with the arrows. When there is a history, `ctrl-r` stops reading
+/// to search it
+fn read_line(prompt: &str, initial: &str, history: &[String]) -> Result<Entered> {
+    if let Some(Step::Input { value }) = session::replay(|s| matches!(s, Step::Input { .. })) {
+        session::record(&Step::Input {
+            value: value.clone(),
+        });
+        return Ok(Entered::Line(Some(value)));
+    }
+    if frontend::is_set() {
+        let line = frontend::read(prompt, initial)?;
+        if let Some(value) = &line {
+            session::record(&Step::Input {
+                value: value.clone(),
+            });
+        }
+        return Ok(Entered::Line(line));
+    }
     let mut rl = Editor::<()>::new();
+    let search = Arc::new(AtomicBool::new(false));
+    if !history.is_empty() {
+        for line in history {
+            rl.add_history_entry(line.as_str());
+        }
+        rl.bind_sequence(
+            KeyEvent::ctrl('R'),
+            EventHandler::Conditional(Box::new(SearchKey(Arc::clone(&search)))),
+        );
+    }
 
-    let line = rl.readline("> ");
+    let line = rl.readline_with_initial(prompt, (initial, ""));
     match line {
-        Ok(line) => Ok(line),
-        Err(ReadlineError::Interrupted) => Err(anyhow!("Interrupted")),
-        Err(ReadlineError::Eof) => Err(anyhow!("EOF")),
+        Ok(line) if search.load(Ordering::Relaxed) => Ok(Entered::Search(line)),
+        Ok(line) => {
+            session::record(&Step::Input {
+                value: line.clone(),
+            });
+            Ok(Entered::Line(Some(line)))
+        },
+        Err(ReadlineError::Interrupted) => Err(JaimeError::SelectorAborted.into()),
+        Err(ReadlineError::Eof) => Ok(Entered::Line(None)),
         Err(err) => Err(err.into()),
     }
 }
 
-impl Action {
-    /// # Errors
-    /// Could return an error if the configuration file is unable to be parsed
-    ///
-    /// # Panics
-    /// Should never panic. Unwraps after checking for valid command
-    pub(crate) fn run(&self, context: &Context, config: &Config, handler: &Handler) -> Result<()> {
-        let shell = &config.shell.as_ref().map_or(
-            env::var("SHELL").unwrap_or_else(|_| "sh".to_string()),
-            ToOwned::to_owned,
-        );
+/// The result of running an [`Action`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// The action ran to completion
+    Done,
+    /// The action ran and its output was captured
+    Captured(String),
+    /// The user cancelled a selection before the action could run
+    Cancelled,
+}
 
-        match self {
-            Action::Command {
-                command, widgets, ..
+/// Find the option named `key`, comparing the normalized keys if there is no
+/// exact match so that differently encoded but identical keys are found
+fn lookup<'a>(options: &'a Options, key: &str) -> Option<(&'a Arc<str>, &'a Action)> {
+    options.get_key_value(key).or_else(|| {
+        let key = text::normalize(key);
+        options.iter().find(|(k, _)| text::normalize(k) == key)
+    })
+}
+
+/// Keys of the options listed in a menu (those that aren't hidden): the actions
+/// without a group come first, then those of each group in the order the groups
+/// first appear in, each sorted by key
+fn listed_keys(options: &Options) -> Vec<&Arc<str>> {
+    let mut groups = Vec::new();
+    for group in options.values().filter_map(Action::group) {
+        if !groups.contains(&group) {
+            groups.push(group);
+        }
+    }
+    let position = |action: &Action| {
+        action
+            .group()
+            .and_then(|group| groups.iter().position(|g| *g == group))
+    };
+
+    let mut keys = options
+        .iter()
+        .filter(|(_, action)| action.meta().is_listed())
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    keys.sort_by(|a, b| {
+        position(&options[*a])
+            .cmp(&position(&options[*b]))
+            .then_with(|| text::compare(a, b))
+    });
+    keys
+}
+
+/// What the widgets of a command are displayed with
+#[derive(Debug, Clone, Copy)]
+struct Widgets<'a> {
+    context: &'a Context,
+    handler: &'a Handler,
+    shell:   &'a str,
+    prev:    Option<&'a str>,
+    /// Path of the command, which the values are saved under as they are
+    /// selected
+    path:    &'a [String],
+}
+
+impl Widgets<'_> {
+    /// Display the widget at `index`, given the values of the widgets displayed
+    /// before it (`args`), returning its value. `None` when the selection is
+    /// cancelled
+    fn select(
+        self,
+        index: usize,
+        WidgetConfig {
+            widget, map, name, ..
+        }: &WidgetConfig,
+        args: &[String],
+        envs: &mut Vec<(String, String)>,
+    ) -> Result<Option<String>> {
+        match (
+            self.display(index, widget, name.as_deref(), args, envs)?,
+            map,
+        ) {
+            (Some(picked), Some(map)) =>
+                Ok(Some(map.apply(self.context, self.shell, envs, &picked)?)),
+            (picked, _) => Ok(picked),
+        }
+    }
+
+    /// Read the value of the `FreeText` widget named `name`, the values typed
+    /// in the widgets of that name being recalled with the arrows, and searched
+    /// with the selector on `ctrl-r`
+    fn recall(self, name: &str) -> Result<Option<String>> {
+        let history = recall::load(self.context, name)?;
+        let mut initial = String::new();
+        loop {
+            match read_line("> ", &initial, &history)? {
+                Entered::Line(line) => {
+                    if let Some(line) = &line {
+                        if let Err(e) = recall::remember(self.context, name, line) {
+                            jaime_error!("failed to remember the value typed: {}", e);
+                        }
+                    }
+                    return Ok(line);
+                },
+                Entered::Search(typed) => {
+                    let input = history.iter().rev().cloned().collect::<Vec<_>>().join("\n");
+                    initial = select(self.context, self.handler, &input, None)?.unwrap_or(typed);
+                },
+            }
+        }
+    }
+
+    /// Display `widget`, of the widget at `index` named `name`, returning the
+    /// selected item
+    fn display(
+        self,
+        index: usize,
+        widget: &Widget,
+        name: Option<&str>,
+        args: &[String],
+        envs: &mut Vec<(String, String)>,
+    ) -> Result<Option<String>> {
+        let Self {
+            context,
+            handler,
+            shell,
+            prev,
+            ..
+        } = self;
+        let picked;
+
+        match widget {
+            Widget::FreeText =>
+                match name.map_or_else(|| readline("> "), |name| self.recall(name))? {
+                    Some(line) => picked = line,
+                    None => return Ok(None),
+                },
+            Widget::Secret {
+                service,
+                account,
+                env,
+            } => {
+                let name = env
+                    .clone()
+                    .unwrap_or_else(|| format!("JAIME_SECRET_{}", index));
+                envs.push((name.clone(), secrets::keyring(service, account)?));
+                picked = format!("${{{}}}", name);
+            },
+            Widget::FromCommand {
+                command,
+                preview,
+                preview_script,
+                preview_cache,
+                format,
+                table,
+                sort,
+                unique,
+                filter,
+                max_items,
             } => {
-                let mut args: Vec<String> = Vec::new();
+                let command = template::expand(command, args, prev);
 
-                if let Some(widgets) = widgets {
-                    for (index, widget) in widgets.iter().enumerate() {
-                        match widget {
-                            Widget::FreeText => {
-                                args.push(readline()?);
-                            },
-                            Widget::FromCommand { command, preview } => {
-                                let mut command = command.clone();
-                                for (i, arg) in args.iter().enumerate().take(index) {
-                                    command = command.replace(&format!("{{{}}}", i), arg);
-                                }
+                // Ran by the selectors, out of the sandbox
+                let preview = match preview_script {
+                    Some(_) if sandbox::is_enabled() => None,
+                    Some(script) => {
+                        let p = preview::script(context, script, args)?;
+                        Some(preview::resolve(
+                            context,
+                            &p,
+                            preview_cache.map(Duration::from_secs),
+                        )?)
+                    },
+                    None => preview
+                        .as_ref()
+                        .filter(|p| !sandbox::is_enabled() || preview::is_builtin(p))
+                        .map(|p| {
+                            let p = template::expand::<&str>(p, &[], prev);
+                            preview::resolve(context, &p, preview_cache.map(Duration::from_secs))
+                        })
+                        .transpose()?,
+                };
 
-                                let output =
-                                    run_shell_command_for_output(context, &command, shell)?;
-
-                                let selected_command = if handler.fzf() {
-                                    display_selector_fzf(
-                                        &output,
-                                        preview.as_ref().map(|s| s.as_ref()),
-                                    )
-                                } else if handler.skim() {
-                                    display_selector_skim(
-                                        &output,
-                                        preview.as_ref().map(|s| s.as_ref()),
-                                    )
-                                } else {
-                                    display_selector(output, preview.as_ref().map(|s| s.as_ref()))
-                                };
-
-                                if let Some(selected_command) = selected_command {
-                                    args.push(selected_command);
-                                } else {
-                                    return Ok(());
-                                }
+                // Nothing is done with the lines within Jaime, so they
+                // can go straight from the command to the selector
+                let streamed = matches!(backend(handler), Backend::Fzf | Backend::SkimBinary)
+                    && matches!(format, Format::Lines)
+                    && *sort == Sort::None
+                    && !*unique
+                    && filter.is_none()
+                    && max_items.is_none();
+
+                let selected_command = if streamed {
+                    select_streamed(context, handler, &command, shell, envs, preview.as_deref())?
+                } else if let Some(max_items) = max_items {
+                    let mut listing = Listing::spawn(
+                        exec::shell_command(context, &command, shell, envs)?,
+                        *max_items,
+                    )?;
+                    loop {
+                        listing.next_page()?;
+                        let output = listing.text();
+                        let lines = match format {
+                            Format::Lines => output.lines().collect(),
+                            Format::Table => table.body(output.lines()),
+                        };
+                        let lines = lines::process(lines, *sort, *unique, filter.as_deref())?;
+
+                        let (input, rows) = match format {
+                            Format::Lines => (lines.join("\n"), None),
+                            Format::Table => {
+                                let rows = table.parse(&lines)?;
+                                (rows.input(), Some(rows))
                             },
+                        };
+                        match select_page(context, handler, &input, &listing, preview.as_deref())? {
+                            Selected::Lines(s) =>
+                                break match rows {
+                                    Some(rows) => rows.value_of(&s).map(ToOwned::to_owned),
+                                    None => Some(s),
+                                },
+                            // The next page is read and everything is displayed again
+                            Selected::More => {},
+                            Selected::Palette | Selected::Nothing | Selected::Unmatched(_) =>
+                                break None,
+                            Selected::Aborted => return Err(JaimeError::SelectorAborted.into()),
                         }
                     }
+                } else {
+                    let items = Shell {
+                        command: &command,
+                        shell,
+                    }
+                    .items(&SourceContext {
+                        values: args,
+                        context,
+                        envs,
+                    })?;
+                    let lines = items.iter().map(Item::text);
+                    let lines = match format {
+                        Format::Lines => lines.collect(),
+                        Format::Table => table.body(lines),
+                    };
+                    let lines = lines::process(lines, *sort, *unique, filter.as_deref())?;
+
+                    match format {
+                        Format::Lines =>
+                            select(context, handler, &lines.join("\n"), preview.as_deref())?,
+                        Format::Table => {
+                            let rows = table.parse(&lines)?;
+                            select(context, handler, &rows.input(), preview.as_deref())?
+                                .and_then(|s| rows.value_of(&s).map(ToOwned::to_owned))
+                        },
+                    }
+                };
+
+                if let Some(selected_command) = selected_command {
+                    picked = selected_command;
+                } else {
+                    return Ok(None);
                 }
+            },
+            Widget::KubeContext => {
+                match select_builtin(
+                    context,
+                    handler,
+                    &kube::contexts()?,
+                    preview::Builtin::KubeContext,
+                )? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                }
+            },
+            Widget::KubeNamespace {
+                context: kube_context,
+            } => {
+                let kube_context = kube_context
+                    .as_ref()
+                    .map(|c| template::expand(c, args, prev));
+
+                match select_builtin(
+                    context,
+                    handler,
+                    &kube::namespaces(kube_context.as_deref())?,
+                    preview::Builtin::KubeNamespace,
+                )? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                }
+            },
+            Widget::DockerContainer { .. } | Widget::DockerImage | Widget::DockerVolume => {
+                let (kind, all, preview) = match widget {
+                    Widget::DockerContainer { all } => (
+                        docker::Kind::Container,
+                        *all,
+                        preview::Builtin::DockerContainer,
+                    ),
+                    Widget::DockerImage =>
+                        (docker::Kind::Image, false, preview::Builtin::DockerImage),
+                    _ => (docker::Kind::Volume, false, preview::Builtin::DockerVolume),
+                };
 
-                let mut command = command.clone();
+                match select_builtin(context, handler, &docker::list(kind, all)?, preview)? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                }
+            },
+            Widget::GitBranch
+            | Widget::GitTag
+            | Widget::GitFile
+            | Widget::GitStash
+            | Widget::GitCommit { .. } => {
+                let (kind, max, preview) = match widget {
+                    Widget::GitBranch => (git::Kind::Branch, None, preview::Builtin::GitBranch),
+                    Widget::GitTag => (git::Kind::Tag, None, preview::Builtin::GitTag),
+                    Widget::GitFile => (git::Kind::File, None, preview::Builtin::GitFile),
+                    Widget::GitStash => (git::Kind::Stash, None, preview::Builtin::GitStash),
+                    _ => (
+                        git::Kind::Commit,
+                        match widget {
+                            Widget::GitCommit { max } => *max,
+                            _ => None,
+                        },
+                        preview::Builtin::GitCommit,
+                    ),
+                };
 
-                for (index, arg) in args.iter().enumerate() {
-                    command = command.replace(&format!("{{{}}}", index), arg);
+                match select_builtin(context, handler, &git::list(kind, max)?, preview)? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                }
+            },
+            Widget::Process { filter, multi } => {
+                match select_builtin_with(
+                    context,
+                    handler,
+                    &builtin::process::list(filter.as_deref())?,
+                    Some(preview::Builtin::Process),
+                    *multi,
+                )? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
                 }
+            },
+            Widget::JsonPick { command, pointer } => {
+                let command = template::expand(command, args, prev);
+
+                let output = run_shell_command_for_output(context, &command, shell, envs)?;
+                let value = json::parse(&output)?;
+                let values = json::pick(&value, pointer.as_deref())?
+                    .into_iter()
+                    .map(json::raw)
+                    .collect::<Vec<_>>();
 
-                run_shell(context, &command, shell)
+                // Lines are found back by their position, as values may
+                // contain newlines
+                let lines = values
+                    .iter()
+                    .map(|v| v.replace('\n', " "))
+                    .collect::<Vec<_>>();
+                let preview = values
+                    .iter()
+                    .any(|v| v.starts_with('{') || v.starts_with('['))
+                    .then(|| preview::Builtin::Json.command("{}"))
+                    .transpose()?;
+
+                match select(context, handler, &lines.join("\n"), preview.as_deref())? {
+                    Some(selected) => {
+                        let idx = lines
+                            .iter()
+                            .position(|l| *l == selected)
+                            .unwrap_or_default();
+                        picked = values[idx].clone();
+                    },
+                    None => return Ok(None),
+                }
             },
-            Action::Select {
-                options,
-                description: _,
-            } => {
-                let input = options
-                    .keys()
-                    .map(|k| {
-                        if let Some(Action::Select {
-                            description: Some(description),
-                            ..
-                        }) = options.get(k)
-                        {
-                            format!("{}: {}", k.green().bold(), description.magenta())
-                        } else if let Some(Action::Command {
-                            description: Some(description),
-                            ..
-                        }) = options.get(k)
-                        {
-                            format!("{}: {}", k.green().bold(), description.magenta())
-                        } else {
-                            k.green().bold().to_string()
-                        }
-                    })
-                    .collect::<Vec<String>>()
-                    .join("\n");
-
-                let selected_command =
-                    if handler.has_command() && NUM_RUNS.load(Ordering::Relaxed) == 0 {
-                        let cmd = handler.command().map(ToString::to_string).unwrap();
-                        if options.keys().any(|k| *k == cmd) {
-                            Some(cmd)
-                        } else {
-                            let avail = options.keys().fold(String::new(), |mut acc, k| {
-                                acc.push_str(&format!("{}, ", k.yellow()));
-                                acc
-                            });
-                            jaime_error!(
-                                "{} is an invalid selection and doesn't match any of the keys you \
-                                 have in your configuration file.\nAvailable keys are: {}",
-                                cmd.green(),
-                                avail
-                                    .strip_suffix(", ")
-                                    .map_or(avail.clone(), ToString::to_string)
-                            );
-                            process::exit(1);
-                        }
-                    } else if handler.fzf() {
-                        display_selector_fzf(&input, None)
-                    } else if handler.skim() {
-                        display_selector_skim(&input, None)
-                    } else {
-                        display_selector(input, None)
+            Widget::FlagPicker { command } => {
+                let command = template::expand(command, args, prev);
+
+                let mut flags = flags::parse(&run_shell_command_for_output(
+                    context,
+                    &format!("{} --help 2>&1", command),
+                    shell,
+                    envs,
+                )?);
+                if flags.is_empty() {
+                    flags = flags::parse(&run_shell_command_for_output(
+                        context,
+                        &format!("man {} 2>/dev/null", flags::man_page(&command)),
+                        shell,
+                        envs,
+                    )?);
+                }
+                if flags.is_empty() {
+                    return Err(anyhow!("no flags found for {}", command));
+                }
+
+                let items = flags.iter().map(Flag::to_item).collect::<Vec<_>>();
+                let selected = match select_builtin_with(context, handler, &items, None, true)? {
+                    Some(selected) => selected,
+                    None => return Ok(None),
+                };
+
+                let mut chosen = Vec::new();
+                for name in selected.split(' ') {
+                    let flag = match flags.iter().find(|f| f.name == name) {
+                        Some(flag) => flag,
+                        None => continue,
                     };
 
-                selected_command.map_or(Ok(()), |selected_command| {
-                    match options.get(
-                        &selected_command
-                            .contains(':')
-                            .then(|| selected_command.split(':').collect::<Vec<_>>()[0].to_string())
-                            .unwrap_or(selected_command),
-                    ) {
-                        Some(widget) => {
-                            NUM_RUNS.fetch_add(1, Ordering::Relaxed);
-                            widget.run(context, config, handler)
+                    chosen.push(match &flag.arg {
+                        Some(arg) => {
+                            let prompt = if flag.optional {
+                                format!("{} [{}]> ", flag.name, arg)
+                            } else {
+                                format!("{} {}> ", flag.name, arg)
+                            };
+                            match readline(&prompt)? {
+                                Some(value) => flag.with_arg(&value),
+                                None => return Ok(None),
+                            }
                         },
-                        None => Ok(()),
+                        None => flag.name.clone(),
+                    });
+                }
+
+                picked = chosen.join(" ");
+            },
+            Widget::EnvVar { substitute, mask } => {
+                let mask = builtin::env::mask_or_default(mask.as_deref());
+                let preview = builtin::env::preview_command(&mask)?;
+
+                match select_with(
+                    context,
+                    handler,
+                    &builtin::render(&builtin::env::list(&mask)),
+                    Some(&preview),
+                    false,
+                )?
+                .map(|s| builtin::value_of(&s))
+                {
+                    Some(selected) =>
+                        picked = match substitute {
+                            builtin::env::Substitute::Name => selected,
+                            builtin::env::Substitute::Value => builtin::env::value(&selected)?,
+                        },
+                    None => return Ok(None),
+                }
+            },
+            Widget::SystemdUnit { user, all } => {
+                let preview = if *user {
+                    preview::Builtin::SystemdUserUnit
+                } else {
+                    preview::Builtin::SystemdUnit
+                };
+
+                match select_builtin(context, handler, &systemd::units(*user, *all)?, preview)? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                }
+            },
+            Widget::Source { source } => {
+                let items = embed::items(source, &SourceContext {
+                    values: args,
+                    context,
+                    envs,
+                })?;
+                let items = items.iter().map(Item::text).collect::<Vec<_>>();
+                match select(context, handler, &items.join("\n"), None)? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                }
+            },
+            Widget::Emoji =>
+                match select_builtin_with(context, handler, &emoji::list(), None, false)? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                },
+            Widget::Color { format } =>
+                match select_builtin_with(context, handler, &color::list(*format)?, None, false)? {
+                    Some(selected) => picked = selected,
+                    None => return Ok(None),
+                },
+            Widget::Generate {
+                kind,
+                length,
+                symbols,
+            } => picked = generate::generate(*kind, *length, *symbols),
+            Widget::Repeat {
+                widget,
+                until,
+                separator,
+                template,
+            } => {
+                let mut values = Vec::new();
+                loop {
+                    match self.display(index, widget, name, args, envs)? {
+                        Some(value) if value.is_empty() && *until == Until::Empty => break,
+                        Some(value) => values.push(value),
+                        None if *until == Until::Cancel && !values.is_empty() => break,
+                        None => return Ok(None),
                     }
-                })
+                }
+
+                if let Some(template) = template {
+                    values = values
+                        .iter()
+                        .map(|value| {
+                            Ok(template.replace(
+                                "{}",
+                                &shlex::try_quote(value).context("unable to quote value")?,
+                            ))
+                        })
+                        .collect::<Result<_>>()?;
+                }
+                picked = values.join(separator.as_deref().unwrap_or(" "));
+            },
+        }
+
+        Ok(Some(picked))
+    }
+
+    /// Display the widgets at `indices` in turn, setting their value in `args`.
+    /// The widgets whose `when` condition doesn't hold are skipped, with an
+    /// empty value
+    fn display_all(
+        self,
+        widgets: &[WidgetConfig],
+        indices: &[usize],
+        args: &mut [String],
+        skipped: &mut [bool],
+        envs: &mut Vec<(String, String)>,
+    ) -> Result<()> {
+        for (position, &index) in indices.iter().enumerate() {
+            let config = &widgets[index];
+            let shown = match &config.when {
+                Some(when) => condition::evaluate(when, args, self.prev).with_context(|| {
+                    format!(
+                        "widget {} ({}): invalid condition `{}`",
+                        index,
+                        config.widget.kind(),
+                        when
+                    )
+                })?,
+                None => true,
+            };
+
+            skipped[index] = !shown;
+            args[index] = if shown {
+                self.value(index, config, args, envs)?
+            } else {
+                String::new()
+            };
+
+            // The widgets not displayed here were before
+            let pending = &indices[position + 1..];
+            let values = args
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !skipped[*index] && !pending.contains(index))
+                .map(|(index, value)| (index, value.clone()))
+                .collect();
+            if let Err(e) = resume::save(self.context, self.path, widgets.len(), values) {
+                jaime_error!("failed to save the values selected: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The value of the widget at `index`, with errors naming the widget. An
+    /// optional widget that is cancelled has an empty value
+    fn value(
+        self,
+        index: usize,
+        config: &WidgetConfig,
+        args: &[String],
+        envs: &mut Vec<(String, String)>,
+    ) -> Result<String> {
+        let rendered = config
+            .widget
+            .command()
+            .map(|command| masked(&template::expand(command, args, self.prev), envs));
+
+        let _selector = SelectorGuard::set(config.selector, config.selector_options.as_deref())?;
+        let value = self
+            .select(index, config, args, envs)
+            .with_context(|| match &rendered {
+                Some(command) => format!(
+                    "widget {} ({}) running `{}`",
+                    index,
+                    config.widget.kind(),
+                    command
+                ),
+                None => format!("widget {} ({})", index, config.widget.kind()),
+            })?;
+
+        match value {
+            Some(value) => Ok(value),
+            None if config.optional => Ok(String::new()),
+            None => Err(JaimeError::WidgetCancelled.into()),
+        }
+    }
+}
+
+/// Number of widgets from which their values are summarized before running a
+/// command, unless its `summary` says otherwise
+const SUMMARY_WIDGETS: usize = 3;
+
+/// Show the values of the widgets along with the command they make, so that
+/// any of them can be displayed again before running it, along with the
+/// widgets depending on it. Returns the values once the command is accepted
+fn summarize(
+    displayed: Widgets<'_>,
+    action: &Action,
+    graph: &Graph,
+    mut args: Vec<String>,
+    skipped: &mut [bool],
+    envs: &mut Vec<(String, String)>,
+) -> Result<Vec<String>> {
+    let (command, widgets, computed) = match action {
+        Action::Command {
+            command,
+            widgets: Some(widgets),
+            computed,
+            ..
+        } => (command, widgets, computed),
+        _ => return Ok(args),
+    };
+    // What is typed in the summary isn't what the command is given, which is
+    // what was typed in the last widget displayed
+    let mut accepted = typed();
+    loop {
+        let rendered = masked(
+            &template::expand_with(command, &args, displayed.prev, &accepted.placeholders()),
+            envs,
+        );
+        let theme = theme::get();
+        let input = iter::once(format!("{}: {}", theme.key.paint("run"), rendered))
+            .chain(iter::once(format!(
+                "{}: {}",
+                theme.key.paint("explain"),
+                theme.description.paint("Show how the command is assembled")
+            )))
+            .chain(
+                widgets
+                    .iter()
+                    .zip(&args)
+                    .enumerate()
+                    .filter(|(index, _)| !skipped[*index])
+                    .map(|(index, (widget, value))| {
+                        format!(
+                            "{}: {}: {}",
+                            theme.key.paint(&index.to_string()),
+                            theme.description.paint(
+                                widget
+                                    .name
+                                    .as_deref()
+                                    .unwrap_or_else(|| widget.widget.kind())
+                            ),
+                            masked(value, envs)
+                        )
+                    }),
+            )
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let selected = select(displayed.context, displayed.handler, &input, None)?
+            .ok_or(JaimeError::WidgetCancelled)?;
+        *TYPED.lock().unwrap() = accepted.clone();
+        let key = selected.split(':').next().unwrap_or_default();
+        if key == "run" {
+            return Ok(args);
+        }
+        if key == "explain" {
+            let assembly = Assembly {
+                template: command,
+                widgets,
+                args: &args,
+                skipped,
+                computed,
+                prev: displayed.prev,
+                named: &accepted.placeholders(),
+                envs,
+            };
+            pager::page(&format!("{}\n", assembly.render()))?;
+            continue;
+        }
+
+        let index = key
+            .parse::<usize>()
+            .ok()
+            .filter(|index| *index < widgets.len())
+            .ok_or_else(|| anyhow!("unknown entry of the summary: {}", key))?;
+        displayed.display_all(widgets, &graph.affected(index), &mut args, skipped, envs)?;
+        accepted = typed();
+    }
+}
+
+/// File of the directory of a run that its output is written to, when it is
+/// stored in the history
+const OUTPUT_LOG: &str = "output.log";
+
+/// Store the output of the command of the history `entry`, written to `log`,
+/// along with its standard output if it was captured instead
+fn store_output(
+    context: &Context,
+    entry: &history::Entry,
+    log: &Path,
+    stdout: Option<&str>,
+) -> Result<()> {
+    let mut output = String::from_utf8_lossy(
+        &fs::read(log).context(format!("unable to read: {}", log.display()))?,
+    )
+    .into_owned();
+    output.push_str(stdout.unwrap_or_default());
+
+    history::set_output(context, entry, &output)
+}
+
+/// A command whose widgets were displayed, ready to be ran
+#[derive(Debug)]
+pub(crate) struct Prepared {
+    pub(crate) command: String,
+    pub(crate) shell:   String,
+    /// Secrets and other variables set in the command's environment
+    pub(crate) envs:    Vec<(String, String)>,
+    /// Values of the widgets
+    pub(crate) values:  Vec<String>,
+}
+
+impl Action {
+    pub(crate) fn meta(&self) -> &Meta {
+        match self {
+            Action::Command { meta, .. }
+            | Action::Select { meta, .. }
+            | Action::Chain { meta, .. }
+            | Action::Menu { meta, .. }
+            | Action::Systemd { meta, .. } => meta,
+        }
+    }
+
+    /// The `type` of the action in the configuration
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Action::Command { .. } => "Command",
+            Action::Select { .. } => "Select",
+            Action::Chain { .. } => "Chain",
+            Action::Menu { .. } => "Menu",
+            Action::Systemd { .. } => "Systemd",
+        }
+    }
+
+    /// Whether the shell running the commands of the action is strict, if it
+    /// says so
+    pub(crate) fn strict_shell(&self) -> Option<bool> {
+        match self {
+            Action::Command { strict_shell, .. } => *strict_shell,
+            _ => None,
+        }
+    }
+
+    /// Whether the shell running the command of the action is a login shell,
+    /// if it says so
+    pub(crate) fn login_shell(&self) -> Option<bool> {
+        match self {
+            Action::Command { login_shell, .. } => *login_shell,
+            _ => None,
+        }
+    }
+
+    /// Whether the command of the action can read from the terminal
+    pub(crate) fn tty(&self) -> bool {
+        match self {
+            Action::Command { tty, .. } => tty.unwrap_or(true),
+            _ => true,
+        }
+    }
+
+    /// The program running the command of the action: its `interpreter`, or
+    /// the shell of the configuration
+    pub(crate) fn interpreter(&self, config: &Config) -> String {
+        match self {
+            Action::Command {
+                interpreter: Some(interpreter),
+                ..
+            } => interpreter.clone(),
+            _ => config.shell(),
+        }
+    }
+
+    /// Whether the command of the action is ran by its interpreter, with its
+    /// placeholders replaced. Other interpreters are given the values as
+    /// arguments, and started by the shell of the configuration. The shell of
+    /// the configuration has its placeholders replaced, even when it isn't one
+    /// of those known
+    fn is_shell(&self, config: &Config) -> bool {
+        let interpreter = self.interpreter(config);
+        interpreter == config.shell() || exec::is_shell(&interpreter)
+    }
+
+    /// The description shown next to the action's key in a selector
+    pub(crate) fn description(&self) -> Option<&str> {
+        self.meta().description.as_deref()
+    }
+
+    /// The keywords the action can be found by
+    pub(crate) fn tags(&self) -> &[String] {
+        &self.meta().tags
+    }
+
+    /// The group the action is listed in
+    pub(crate) fn group(&self) -> Option<&str> {
+        self.meta().group.as_deref()
+    }
+
+    /// Display the widgets of an [`Action::Command`], returning the command to
+    /// run with the selected items substituted. `None` is returned if the
+    /// action is not a command, and [`JaimeError::WidgetCancelled`] if a
+    /// selection is cancelled
+    pub(crate) fn prepare(
+        &self,
+        context: &Context,
+        config: &Config,
+        handler: &Handler,
+        path: &[String],
+        prev: Option<&str>,
+    ) -> Result<Option<Prepared>> {
+        let (command, widgets, secrets, summary, computed) = match self {
+            Action::Command {
+                command,
+                widgets,
+                secrets,
+                summary,
+                computed,
+                ..
+            } => (command, widgets, secrets, summary, computed),
+            _ => return Ok(None),
+        };
+        let shell = &config.shell();
+
+        let mut args: Vec<String> = Vec::new();
+        let mut skipped = Vec::new();
+        let mut envs = secrets::resolve(&config.secrets, secrets)?;
+
+        if let Some(widgets) = widgets {
+            let displayed = Widgets {
+                context,
+                handler,
+                shell,
+                prev,
+                path,
+            };
+            let graph = Graph::new(widgets)?;
+            // The values of the widgets not displayed yet are empty
+            args = vec![String::new(); widgets.len()];
+            skipped = vec![false; widgets.len()];
+            // Values recorded in the history, to be changed from the summary,
+            // except those of the secrets which are fetched again
+            let prefilled = resume::take_prefilled().filter(|values| values.len() == widgets.len());
+            let modifying = prefilled.is_some();
+            // Given on the command line, in which case the command runs
+            // unattended besides the widgets to pick
+            let given = params::take();
+            let scripted = given.is_some();
+            let resumed = match (given, prefilled) {
+                (Some(given), _) => params::resolve(&given, widgets)?,
+                (None, Some(values)) => values
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(index, _)| !matches!(widgets[*index].widget, Widget::Secret { .. }))
+                    .collect(),
+                (None, None) => resume::offer(context, handler, path, widgets.len())?,
+            };
+            let order = graph
+                .order()
+                .iter()
+                .copied()
+                .filter(|index| !resumed.contains_key(index))
+                .collect::<Vec<_>>();
+            for (index, value) in resumed {
+                args[index] = value;
+            }
+            displayed.display_all(widgets, &order, &mut args, &mut skipped, &mut envs)?;
+            if !scripted && (modifying || summary.unwrap_or(widgets.len() >= SUMMARY_WIDGETS)) {
+                args = summarize(displayed, self, &graph, args, &mut skipped, &mut envs)?;
+            }
+        }
+
+        let typed = typed();
+        let named = typed.placeholders();
+        for computed in computed {
+            let rendered = template::expand_with(computed, &args, prev, &named);
+            let output = run_shell_command_for_output(context, &rendered, shell, &envs)
+                .with_context(|| {
+                    format!(
+                        "computed {} running `{}`",
+                        args.len(),
+                        masked(&rendered, &envs)
+                    )
+                })?;
+            args.push(output.trim_end_matches('\n').to_owned());
+        }
+
+        if context.explain {
+            eprintln!(
+                "{}",
+                Assembly {
+                    template: command,
+                    widgets: widgets.as_deref().unwrap_or_default(),
+                    args: &args,
+                    skipped: &skipped,
+                    computed,
+                    prev,
+                    named: &named,
+                    envs: &envs,
+                }
+                .render()
+            );
+        }
+
+        let (command, shell) = if self.is_shell(config) {
+            let command = template::expand_with(command, &args, prev, &named).into_owned();
+            (command, self.interpreter(config))
+        } else {
+            // Given the values as arguments rather than in its text, and
+            // started by the shell
+            let command = exec::interpreted(context, &self.interpreter(config), command, &args)?;
+            (command, shell.clone())
+        };
+        if let Err(e) = resume::clear(context, path) {
+            jaime_error!("failed to forget the values selected: {}", e);
+        }
+
+        let values = args[..widgets.as_ref().map_or(0, Vec::len)].to_vec();
+        Ok(Some(Prepared {
+            command,
+            shell,
+            envs,
+            values,
+        }))
+    }
+
+    /// The `command` of the action recorded in the history along with the
+    /// `values` of its widgets, to be ran again. Its secrets are fetched again
+    pub(crate) fn recorded(
+        &self,
+        config: &Config,
+        command: &str,
+        values: Vec<String>,
+    ) -> Result<Prepared> {
+        let secrets = match self {
+            Action::Command { secrets, .. } => secrets,
+            _ => return Err(anyhow!("only the commands can be ran again")),
+        };
+        // The commands of the other interpreters were recorded as the shell
+        // command starting them
+        let shell = if self.is_shell(config) {
+            self.interpreter(config)
+        } else {
+            config.shell()
+        };
+
+        Ok(Prepared {
+            command: command.to_owned(),
+            shell,
+            envs: secrets::resolve(&config.secrets, secrets)?,
+            values,
+        })
+    }
+
+    /// Run the action found at `path` in the menu, replacing `{prev}` with the
+    /// output captured by the previous step of a [`Action::Chain`]
+    pub(crate) fn run_at(
+        &self,
+        context: &Context,
+        config: &Config,
+        handler: &Handler,
+        path: &[String],
+        prev: Option<&str>,
+    ) -> Result<Outcome> {
+        if let Some(reason) = self.meta().disabled_reason() {
+            return Err(anyhow!("{} is disabled: {}", path.join("."), reason));
+        }
+
+        match self {
+            Action::Command { strict_shell, .. } => {
+                // Its widgets are displayed, and its command ran, under it
+                let _strict = exec::strict_for(*strict_shell);
+                let prepared = match self.prepare(context, config, handler, path, prev) {
+                    Ok(Some(prepared)) => prepared,
+                    Ok(None) => return Ok(Outcome::Cancelled),
+                    Err(e) if error::is_cancelled(&e) => return Ok(Outcome::Cancelled),
+                    Err(e) if error::is_aborted(&e) => return Err(e),
+                    Err(e) => return Err(e.context(format!("{} failed", path.join(".")))),
+                };
+
+                self.run_prepared(context, config, handler, path, prepared)
+            },
+            Action::Menu { path: target, .. } => {
+                let (path, action) = config.find(target)?;
+                action.run_at(context, config, handler, &path, prev)
+            },
+            Action::Systemd { user, .. } =>
+                systemd::actions(*user).run_at(context, config, handler, path, prev),
+            Action::Chain { steps, .. } => {
+                let mut prev = prev.map(ToOwned::to_owned);
+
+                for step in steps {
+                    let outcome = match step.run_at(context, config, handler, path, prev.as_deref())
+                    {
+                        Err(e) if error::is_failure(&e) => match &step.meta().on_failure {
+                            OnFailure::Stop => return Err(e),
+                            OnFailure::Continue => {
+                                jaime_error!("{}, continuing", e);
+                                Outcome::Done
+                            },
+                            OnFailure::Run(target) => {
+                                jaime_error!("{}, running {} instead", e, target);
+                                let (target, action) = config.find(target)?;
+                                action.run_at(context, config, handler, &target, prev.as_deref())?
+                            },
+                        },
+                        outcome => outcome?,
+                    };
+                    match outcome {
+                        Outcome::Captured(output) => prev = Some(output),
+                        Outcome::Done => {},
+                        Outcome::Cancelled => return Ok(Outcome::Cancelled),
+                    }
+                }
+
+                Ok(prev.map_or(Outcome::Done, Outcome::Captured))
+            },
+            Action::Select { options, .. } =>
+                run_select(options, context, config, handler, path, prev),
+        }
+    }
+
+    /// Run the `prepared` command of the [`Action::Command`] found at `path`:
+    /// it is locked when `exclusive`, recorded in the history, the audit log
+    /// and the runs, and its output is sent where it goes
+    pub(crate) fn run_prepared(
+        &self,
+        context: &Context,
+        config: &Config,
+        handler: &Handler,
+        path: &[String],
+        prepared: Prepared,
+    ) -> Result<Outcome> {
+        let (output, capture, notify, timeout, exclusive, capture_output) = match self {
+            Action::Command {
+                output,
+                capture,
+                notify,
+                timeout,
+                exclusive,
+                capture_output,
+                ..
+            } => (output, capture, notify, timeout, exclusive, capture_output),
+            _ => return Err(anyhow!("{} isn't a command", path.join("."))),
+        };
+        let Prepared {
+            command,
+            shell,
+            envs,
+            values,
+        } = prepared;
+
+        if !session::run_command(&path.join("."), &masked(&command, &envs)) {
+            return Ok(Outcome::Cancelled);
+        }
+        if !policy::check(config.policy.as_ref(), path, &command)? {
+            return Ok(Outcome::Cancelled);
+        }
+        let lock = if *exclusive {
+            match lock::acquire(context, handler, path)? {
+                Acquired::Locked(lock) => Some(lock),
+                Acquired::Followed => return Ok(Outcome::Done),
+                Acquired::Cancelled => return Ok(Outcome::Cancelled),
+            }
+        } else {
+            None
+        };
+        let entry = match history::record(context, path, &command, &values) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                jaime_error!("failed to record history: {}", e);
+                None
+            },
+        };
+        if let Err(e) = audit::record(
+            context,
+            config.audit.as_ref(),
+            path,
+            &masked(&command, &envs),
+        ) {
+            jaime_error!("failed to record the audit log: {}", e);
+        }
+        let run = match runs::start(context, &config.runs, path, &masked(&command, &envs)) {
+            Ok(run) => Some(run),
+            Err(e) => {
+                jaime_error!("failed to create the directory of the run: {}", e);
+                None
+            },
+        };
+        let run_envs = envs
+            .iter()
+            .cloned()
+            .chain(run.as_ref().map(Run::env))
+            .collect::<Vec<_>>();
+        // The output is stored from where it is written as it comes
+        let log = match &lock {
+            Some(lock) => Some(lock.log.clone()),
+            None if *capture_output => run.as_ref().map(|run| run.dir.join(OUTPUT_LOG)),
+            None => None,
+        };
+
+        let start = Instant::now();
+        let captured = *output != Output::Terminal || *capture;
+        let login = exec::login_for(self.login_shell()) && !captured;
+        let finished = exec::execute(context, &command, &shell, &run_envs, Execution {
+            capture: captured,
+            timeout: timeout.map(Duration::from_secs),
+            log: log.as_deref(),
+            tty: self.tty(),
+            login,
+        })
+        .with_context(|| {
+            format!(
+                "{} failed running `{}`",
+                path.join("."),
+                masked(&command, &envs)
+            )
+        })?;
+        let elapsed = start.elapsed();
+        if let (true, Some(entry), Some(log)) = (*capture_output, &entry, &log) {
+            if let Err(e) = store_output(context, entry, log, finished.stdout.as_deref()) {
+                jaime_error!("failed to store the output: {}", e);
+            }
+        }
+        if let Some(run) = run {
+            if let Err(e) = run.finish(&finished.exit, elapsed) {
+                jaime_error!("failed to record the run: {}", e);
+            }
+        }
+
+        let failed = !matches!(finished.exit, Exit::Status(status) if status.success());
+        if let Err(e) = metrics::record(context, config.metrics.as_ref(), path, failed, elapsed) {
+            jaime_error!("failed to record the metrics: {}", e);
+        }
+
+        let slow = config
+            .notify_after
+            .map_or(false, |secs| elapsed >= Duration::from_secs(secs));
+        if *notify || slow {
+            if let Err(e) = notify::send(&path.join("."), &finished.exit, elapsed) {
+                jaime_error!("failed to send notification: {}", e);
+            }
+        }
+
+        match finished.exit {
+            Exit::TimedOut(after) =>
+                return Err(JaimeError::TimedOut {
+                    path: path.join("."),
+                    after,
+                }
+                .into()),
+            Exit::Cancelled => {
+                jaime_error!("{} was cancelled", path.join("."));
+                return Ok(Outcome::Cancelled);
             },
+            Exit::Status(status) if !status.success() =>
+                return Err(JaimeError::CommandFailed {
+                    path:   path.join("."),
+                    status: exec::code(status),
+                    strict: exec::is_strict(&shell, login),
+                }
+                .into()),
+            Exit::Status(_) => {},
+        }
+
+        let stdout = match finished.stdout {
+            Some(stdout) => stdout,
+            None => return Ok(Outcome::Done),
+        };
+        let stdout = stdout.strip_suffix('\n').unwrap_or(&stdout);
+
+        match output {
+            Output::Clipboard => clipboard::copy(stdout),
+            Output::Pager => pager::page(stdout),
+            Output::Terminal => Ok(()),
+        }
+        .with_context(|| format!("{} failed to send its output", path.join(".")))?;
+
+        Ok(if *capture {
+            Outcome::Captured(stdout.to_owned())
+        } else {
+            Outcome::Done
+        })
+    }
+}
+
+/// Display the options of a menu, and run the selected one
+fn run_select(
+    options: &Options,
+    context: &Context,
+    config: &Config,
+    handler: &Handler,
+    path: &[String],
+    prev: Option<&str>,
+) -> Result<Outcome> {
+    // The root menu ends with the last command that was ran, unless the key
+    // is taken
+    let last = if path.is_empty() && !options.contains_key(last::KEY) {
+        last::entry(context)?.map(|entry| format!("Last run: {}", entry.command))
+    } else {
+        None
+    };
+    let input = menu::render(
+        listed_keys(options)
+            .into_iter()
+            .map(|key| {
+                let action = &options[key];
+                menu::Entry {
+                    key,
+                    description: action.description(),
+                    reason: action.meta().disabled_reason(),
+                    tags: action.tags(),
+                    group: action.group(),
+                }
+            })
+            .chain(last.as_deref().map(|description| menu::Entry {
+                key:         last::KEY,
+                description: Some(description),
+                reason:      None,
+                tags:        &[],
+                group:       None,
+            })),
+        theme::get().menu(),
+    );
+
+    loop {
+        let from_command = handler.has_command() && NUM_RUNS.load(Ordering::Relaxed) == 0;
+
+        let selected_command = if from_command {
+            let cmd = handler.command().map(ToString::to_string).unwrap();
+            if let Some((key, _)) =
+                lookup(options, &cmd).filter(|(_, action)| action.meta().is_listed())
+            {
+                Some(key.to_string())
+            } else {
+                let avail = listed_keys(options)
+                    .into_iter()
+                    .fold(String::new(), |mut acc, k| {
+                        acc.push_str(&format!("{}, ", theme::get().warning.paint(k)));
+                        acc
+                    });
+                return Err(anyhow!(
+                    "{} is an invalid selection and doesn't match any of the keys you have in \
+                     your configuration file.\nAvailable keys are: {}",
+                    theme::get().key.paint(&cmd),
+                    avail
+                        .strip_suffix(", ")
+                        .map_or(avail.clone(), ToString::to_string)
+                ));
+            }
+        } else {
+            match select_or_query(context, handler, &input, None, false)? {
+                Selected::Lines(lines) => Some(lines),
+                // A query matching no key of the root menu is given to the
+                // fallback
+                Selected::Unmatched(query) if path.is_empty() && config.fallback.is_some() => {
+                    let fallback = config.fallback.as_deref().unwrap();
+                    NUM_RUNS.fetch_add(1, Ordering::Relaxed);
+                    let path = [String::from(FALLBACK_KEY)];
+                    match fallback.run_at(context, config, handler, &path, Some(&query))? {
+                        Outcome::Cancelled if config.cancel == Cancel::Back => continue,
+                        outcome => return Ok(outcome),
+                    }
+                },
+                _ => None,
+            }
+        };
+
+        let selected_command = match selected_command {
+            Some(selected_command) => selected_command,
+            None => return Ok(Outcome::Cancelled),
+        };
+        // Nothing to run under a group heading
+        if selected_command.starts_with(menu::GROUP) {
+            continue;
+        }
+        // The key is followed by the description, the reason it is
+        // disabled, or the tags
+        let key = selected_command
+            .split(':')
+            .next()
+            .and_then(|k| k.split("  ").next())
+            .unwrap_or_default();
+
+        if last.is_some() && key == last::KEY {
+            NUM_RUNS.fetch_add(1, Ordering::Relaxed);
+            match last::run(context, config, handler)? {
+                Outcome::Cancelled if config.cancel == Cancel::Back => continue,
+                outcome => return Ok(outcome),
+            }
+        }
+
+        let (key, widget) = match lookup(options, key) {
+            Some(found) => found,
+            None => return Ok(Outcome::Cancelled),
+        };
+
+        if let Some(reason) = widget.meta().disabled_reason() {
+            if from_command {
+                return Err(anyhow!("{} is disabled: {}", key, reason));
+            }
+            jaime_error!("{} is disabled: {}", key, reason);
+            continue;
+        }
+
+        NUM_RUNS.fetch_add(1, Ordering::Relaxed);
+        let mut path = path.to_vec();
+        path.push(key.to_string());
+        match widget.run_at(context, config, handler, &path, prev)? {
+            Outcome::Cancelled if config.cancel == Cancel::Back => {},
+            outcome => return Ok(outcome),
         }
     }
 }
@@ -0,0 +1,253 @@
+//! A directory created for each command ran, in the `runs` directory of the
+//! cache, where the command can leave its artifacts (reports, logs,
+//! screenshots…). Its path is given to the command as `JAIME_RUN_DIR`, and it
+//! holds a `run.json` describing the run, so that the past runs can be listed
+//! and opened with `jaime runs`. The oldest runs are pruned as new ones are
+//! made, following the `runs` settings of the configuration
+
+use crate::{builtin, exec::Exit, runner::Context, theme};
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::TryFrom,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Variable holding the directory of the run, in the environment of the
+/// command
+pub(crate) const ENV: &str = "JAIME_RUN_DIR";
+
+/// Describes the run, in its directory
+const INFO: &str = "run.json";
+
+/// How long the directories of the runs are kept
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub(crate) struct Retention {
+    /// Number of runs kept
+    pub(crate) keep: usize,
+    /// Runs older than this many days are removed, whatever their number
+    pub(crate) days: Option<u64>,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            keep: 50,
+            days: None,
+        }
+    }
+}
+
+/// What is known of a run, written in its directory
+#[derive(Serialize, Deserialize, Debug)]
+struct Info {
+    /// UNIX timestamp of when the command was started
+    time:    u64,
+    /// Dotted path of the action in the menu
+    path:    String,
+    /// The command, with the secrets masked
+    command: String,
+    /// How the command exited, `None` while it runs
+    exit:    Option<String>,
+    /// Time the command ran for
+    seconds: Option<f64>,
+}
+
+/// The directory of a command running
+#[derive(Debug)]
+pub(crate) struct Run {
+    pub(crate) dir: PathBuf,
+    info:           Info,
+}
+
+fn directory(context: &Context) -> PathBuf {
+    context.cache_directory.join("runs")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn write_info(dir: &Path, info: &Info) -> Result<()> {
+    let path = dir.join(INFO);
+    fs::write(&path, serde_json::to_string_pretty(info)?)
+        .context(format!("unable to write: {}", path.display()))
+}
+
+/// Create the directory of the run of `command` (masked), for the action at
+/// `path`, then prune the runs that aren't kept by the `retention`
+pub(crate) fn start(
+    context: &Context,
+    retention: &Retention,
+    path: &[String],
+    command: &str,
+) -> Result<Run> {
+    let runs = directory(context);
+    fs::create_dir_all(&runs).context(format!("unable to create: {}", runs.display()))?;
+
+    let info = Info {
+        time:    now(),
+        path:    path.join("."),
+        command: command.to_owned(),
+        exit:    None,
+        seconds: None,
+    };
+    // Named after the time, so that sorting the names sorts the runs
+    let name = format!("{}-{}", info.time, process::id());
+    let mut dir = runs.join(&name);
+    let mut attempt = 1;
+    loop {
+        match fs::create_dir(&dir) {
+            Ok(()) => break,
+            // Ran twice in the same second, by a chain
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                attempt += 1;
+                dir = runs.join(format!("{}-{}", name, attempt));
+            },
+            Err(e) => return Err(e).context(format!("unable to create: {}", dir.display())),
+        }
+    }
+    write_info(&dir, &info)?;
+
+    prune(context, retention, &dir)?;
+
+    Ok(Run { dir, info })
+}
+
+impl Run {
+    /// The variable giving the directory to the command
+    pub(crate) fn env(&self) -> (String, String) {
+        (ENV.to_owned(), self.dir.to_string_lossy().into_owned())
+    }
+
+    /// Record how the command exited, after `elapsed`
+    pub(crate) fn finish(mut self, exit: &Exit, elapsed: Duration) -> Result<()> {
+        self.info.exit = Some(exit.to_string());
+        self.info.seconds = Some(elapsed.as_secs_f64());
+        write_info(&self.dir, &self.info)
+    }
+}
+
+/// The directories of the runs, oldest first, with what is known of them
+fn list(context: &Context) -> Result<Vec<(PathBuf, Option<Info>)>> {
+    let runs = directory(context);
+    if !runs.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut list = Vec::new();
+    for entry in fs::read_dir(&runs).context(format!("unable to read: {}", runs.display()))? {
+        let dir = entry?.path();
+        if dir.is_dir() {
+            let info = fs::read_to_string(dir.join(INFO))
+                .ok()
+                .and_then(|text| serde_json::from_str(&text).ok());
+            list.push((dir, info));
+        }
+    }
+    list.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(list)
+}
+
+/// Remove the runs beyond the `keep` most recent ones, and those older than
+/// `days`, except the one in `current`
+fn prune(context: &Context, retention: &Retention, current: &Path) -> Result<()> {
+    let list = list(context)?;
+    let oldest = retention
+        .days
+        .map(|days| now().saturating_sub(days * 24 * 60 * 60));
+    let extra = list.len().saturating_sub(retention.keep.max(1));
+
+    for (index, (dir, info)) in list.iter().enumerate() {
+        let expired = match (oldest, info) {
+            (Some(oldest), Some(info)) => info.time < oldest,
+            _ => false,
+        };
+        if (index < extra || expired) && dir != current {
+            fs::remove_dir_all(dir).context(format!("unable to remove: {}", dir.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The name of the directory of a run, which identifies it
+fn name_of(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Print the past runs, most recent first, for `jaime runs ls`
+pub(crate) fn print(context: &Context) -> Result<()> {
+    let theme = theme::get();
+    for (dir, info) in list(context)?.iter().rev() {
+        let artifacts = fs::read_dir(dir).map_or(0, |entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_name() != INFO)
+                .count()
+        });
+        match info {
+            Some(info) => println!(
+                "{}\t{}\t{}\t{}\t{} artifact(s)",
+                theme.key.paint(&name_of(dir)),
+                builtin::relative_time(i64::try_from(info.time).unwrap_or(i64::MAX)),
+                info.path,
+                theme
+                    .dimmed
+                    .paint(info.exit.as_deref().unwrap_or("is running")),
+                artifacts
+            ),
+            None => println!(
+                "{}\t{} artifact(s)",
+                theme.key.paint(&name_of(dir)),
+                artifacts
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Open the directory of the run `id` (the last one by default) in the file
+/// manager, or print it when there is none, for `jaime runs open`
+pub(crate) fn open(context: &Context, id: Option<&str>) -> Result<()> {
+    let list = list(context)?;
+    let dir = match id {
+        Some(wanted) => list.iter().find(|(dir, _)| name_of(dir) == wanted),
+        None => list.last(),
+    }
+    .map(|(dir, _)| dir)
+    .ok_or_else(|| match id {
+        Some(id) => anyhow!("no run: {}", id),
+        None => anyhow!("no command was ran yet"),
+    })?;
+
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    if which::which(opener).is_err() {
+        println!("{}", dir.display());
+        return Ok(());
+    }
+
+    Command::new(opener)
+        .arg(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context(format!("failed to spawn {}", opener))?;
+
+    Ok(())
+}
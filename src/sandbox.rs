@@ -0,0 +1,200 @@
+//! `jaime --sandbox`: the commands of the configuration (those of the actions,
+//! widgets, and secrets) run under a restricted wrapper, so that a menu shared
+//! by someone else can be tried without trusting it.
+//!
+//! `bwrap` (bubblewrap) is used if it is installed, then `firejail`. Without
+//! either, each command is confirmed before it runs. The policy, given with the
+//! flag rather than by the configuration tried, says whether the commands have
+//! the network, where they can write, and which commands are refused outright
+
+use crate::theme;
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rustyline::Editor;
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Mutex, PoisonError},
+};
+
+/// What the commands are allowed to do
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Policy {
+    /// Whether the commands can use the network
+    network:  bool,
+    /// Directories (or files) the commands can write to. Everything else is
+    /// read-only
+    writable: Vec<String>,
+    /// Regular expressions of the commands refused, matched against the
+    /// commands with their placeholders replaced
+    deny:     Vec<String>,
+}
+
+/// What runs the commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wrapper {
+    Bubblewrap,
+    Firejail,
+    /// Each command is confirmed
+    Confirm,
+}
+
+#[derive(Debug)]
+struct Sandbox {
+    wrapper:  Wrapper,
+    network:  bool,
+    writable: Vec<PathBuf>,
+    deny:     Vec<Regex>,
+}
+
+static SANDBOX: Lazy<Mutex<Option<Sandbox>>> = Lazy::new(|| Mutex::new(None));
+
+/// `path` with a leading `~` replaced by the home directory
+fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Run the commands in the sandbox, with the policy of the YAML file `policy`
+/// if given (no network, and nothing writable, otherwise)
+pub(crate) fn enable(policy: Option<&Path>) -> Result<()> {
+    let policy = match policy {
+        Some(path) => serde_yaml::from_str::<Policy>(
+            &fs::read_to_string(path).context(format!("unable to read: {}", path.display()))?,
+        )
+        .context(format!("invalid sandbox policy: {}", path.display()))?,
+        None => Policy::default(),
+    };
+    let deny = policy
+        .deny
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| {
+            Regex::new(rule).map_err(|e| anyhow!("deny.{}: invalid rule: {}", index, e))
+        })
+        .collect::<Result<_>>()?;
+
+    let wrapper = if which::which("bwrap").is_ok() {
+        Wrapper::Bubblewrap
+    } else if which::which("firejail").is_ok() {
+        Wrapper::Firejail
+    } else {
+        eprintln!(
+            "{} {}",
+            theme::get().info.paint("[sandbox]"),
+            theme::get()
+                .dimmed
+                .paint("neither bwrap nor firejail is installed, each command will be confirmed")
+        );
+        Wrapper::Confirm
+    };
+
+    *SANDBOX.lock().unwrap_or_else(PoisonError::into_inner) = Some(Sandbox {
+        wrapper,
+        network: policy.network,
+        writable: policy
+            .writable
+            .iter()
+            .map(|path| expand_home(path))
+            .collect(),
+        deny,
+    });
+
+    Ok(())
+}
+
+/// Whether the commands run in the sandbox
+pub(crate) fn is_enabled() -> bool {
+    SANDBOX
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+}
+
+/// Whether the commands are confirmed on the terminal before they run, there
+/// being no wrapper
+pub(crate) fn confirms() -> bool {
+    matches!(
+        &*SANDBOX.lock().unwrap_or_else(PoisonError::into_inner),
+        Some(Sandbox {
+            wrapper: Wrapper::Confirm,
+            ..
+        })
+    )
+}
+
+/// The command running `program` with `args`, in the sandbox when it is
+/// enabled. `script` is what the policy is matched against, and what is
+/// confirmed without a wrapper
+pub(crate) fn command(program: &str, args: &[&str], script: &str) -> Result<Command> {
+    let sandbox = SANDBOX.lock().unwrap_or_else(PoisonError::into_inner);
+    let sandbox = if let Some(sandbox) = &*sandbox {
+        sandbox
+    } else {
+        let mut command = Command::new(program);
+        command.args(args);
+        return Ok(command);
+    };
+
+    if let Some(rule) = sandbox.deny.iter().find(|rule| rule.is_match(script)) {
+        return Err(anyhow!(
+            "`{}` is denied by the sandbox policy ({})",
+            script,
+            rule
+        ));
+    }
+
+    let mut command = match sandbox.wrapper {
+        Wrapper::Bubblewrap => {
+            let mut command = Command::new("bwrap");
+            command
+                .args(&["--ro-bind", "/", "/", "--dev", "/dev", "--proc", "/proc"])
+                .args(&["--tmpfs", "/tmp", "--unshare-pid", "--die-with-parent"])
+                // Not to push input to the terminal with TIOCSTI (CVE-2017-5226)
+                .arg("--new-session");
+            for path in sandbox.writable.iter().filter(|path| path.exists()) {
+                command.arg("--bind").arg(path).arg(path);
+            }
+            if !sandbox.network {
+                command.arg("--unshare-net");
+            }
+            command.arg("--").arg(program);
+            command
+        },
+        Wrapper::Firejail => {
+            let mut command = Command::new("firejail");
+            command.args(&["--quiet", "--private-tmp", "--read-only=/"]);
+            for path in &sandbox.writable {
+                command.arg(format!("--read-write={}", path.display()));
+            }
+            if !sandbox.network {
+                command.arg("--net=none");
+            }
+            command.arg("--").arg(program);
+            command
+        },
+        Wrapper::Confirm => {
+            let prompt = format!(
+                "{} run `{}`? [y/N] ",
+                theme::get().info.paint("[sandbox]"),
+                script
+            );
+            let answer = Editor::<()>::new()
+                .readline(&prompt)
+                .map(|a| a.trim().to_lowercase());
+            if !matches!(answer.as_deref(), Ok("y" | "yes")) {
+                return Err(anyhow!("`{}` was refused", script));
+            }
+            Command::new(program)
+        },
+    };
+    command.args(args);
+
+    Ok(command)
+}
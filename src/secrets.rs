@@ -0,0 +1,88 @@
+use crate::sandbox;
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, process::Command};
+
+/// A map of environment variable names to the secret they hold
+pub(crate) type Secrets = HashMap<String, Secret>;
+
+/// Where the value of a secret comes from.
+///
+/// Secrets are injected into the environment of the commands that are ran, so
+/// they never appear on the command line, in process listings, or in history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Secret {
+    /// An entry in the `pass` password store (only the first line is used)
+    Pass(String),
+    /// A command whose output is the secret
+    Command(String),
+}
+
+impl Secret {
+    fn resolve(&self) -> Result<String> {
+        let (mut command, what) = match self {
+            Self::Pass(entry) => {
+                let mut command = Command::new("pass");
+                command.arg("show").arg(entry);
+                (command, format!("pass entry {}", entry))
+            },
+            Self::Command(cmd) => (
+                sandbox::command("sh", &["-c", cmd], cmd)?,
+                String::from("secret command"),
+            ),
+        };
+
+        let output = command
+            .output()
+            .context(format!("failed to retrieve {}", what))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to retrieve {}: {}",
+                what,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let value = String::from_utf8(output.stdout)?;
+        let value = match self {
+            Self::Pass(_) => value.lines().next().unwrap_or_default().to_owned(),
+            Self::Command(_) => value.trim_end_matches('\n').to_owned(),
+        };
+
+        Ok(value)
+    }
+}
+
+/// Resolve the global secrets merged with the secrets of an action (which take
+/// precedence) into environment variables
+pub(crate) fn resolve(global: &Secrets, action: &Secrets) -> Result<Vec<(String, String)>> {
+    let mut merged = global.iter().collect::<HashMap<_, _>>();
+    merged.extend(action.iter());
+
+    merged
+        .into_iter()
+        .map(|(name, secret)| Ok((name.clone(), secret.resolve()?)))
+        .collect()
+}
+
+/// Retrieve a secret from the operating system's keyring, prompting for it and
+/// storing it on first use
+pub(crate) fn keyring(service: &str, account: &str) -> Result<String> {
+    let entry = keyring::Entry::new(service, account)?;
+
+    match entry.get_password() {
+        Ok(secret) => Ok(secret),
+        Err(keyring::Error::NoEntry) => {
+            let secret = rpassword::prompt_password(format!(
+                "Secret for {} ({}) is not stored yet: ",
+                service, account
+            ))?;
+            entry
+                .set_password(&secret)
+                .context("failed to store secret in keyring")?;
+            Ok(secret)
+        },
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,165 @@
+//! Running `jaime serve` as a systemd user service, started on demand by
+//! socket activation: systemd listens on the address, and starts the service
+//! with the socket once something (e.g., a hotkey opening the page) connects
+
+use anyhow::{anyhow, Context, Result};
+use rand::{distributions::Alphanumeric, Rng};
+use std::{env, fs, net::SocketAddr, path::Path};
+
+/// Name of the units, `jaime.socket` and `jaime.service`
+const UNIT: &str = "jaime";
+
+/// File of the cache holding the token of the page started by systemd, which
+/// has to outlive the service
+const TOKEN: &str = "web-token";
+
+/// A new random token, required by the API of the page
+pub(crate) fn new_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// The token of the page started by systemd, created the first time
+pub(crate) fn token(cache_directory: &Path) -> Result<String> {
+    let path = cache_directory.join(TOKEN);
+    if let Ok(token) = fs::read_to_string(&path) {
+        if !token.trim().is_empty() {
+            return Ok(token.trim().to_owned());
+        }
+    }
+
+    fs::create_dir_all(cache_directory)
+        .context(format!("unable to create: {}", cache_directory.display()))?;
+    let token = new_token();
+    fs::write(&path, &token).context(format!("unable to write: {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))
+            .context(format!("unable to restrict: {}", path.display()))?;
+    }
+
+    Ok(token)
+}
+
+/// Refuse to listen on an `address` other machines can reach, unless
+/// `allow_remote`: anyone holding the token can run the commands of the menu
+pub(crate) fn check_address(address: &SocketAddr, allow_remote: bool) -> Result<()> {
+    if address.ip().is_loopback() || allow_remote {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} can be reached from other machines, give --allow-remote to listen on it anyway",
+            address
+        ))
+    }
+}
+
+/// The socket unit listening on `listen`, and the service it starts
+fn units(listen: &str, allow_remote: bool) -> Result<[(String, String); 2]> {
+    let exe = env::current_exe().context("unable to find the path of jaime")?;
+
+    Ok([
+        (
+            format!("{}.socket", UNIT),
+            format!(
+                "[Unit]\nDescription=Jaime menu \
+                 page\n\n[Socket]\nListenStream={}\n\n[Install]\nWantedBy=sockets.target\n",
+                listen
+            ),
+        ),
+        (
+            format!("{}.service", UNIT),
+            format!(
+                "[Unit]\nDescription=Jaime menu \
+                 page\nRequires={}.socket\n\n[Service]\nExecStart={} serve --systemd{}\n",
+                UNIT,
+                exe.display(),
+                if allow_remote { " --allow-remote" } else { "" }
+            ),
+        ),
+    ])
+}
+
+/// Write the units serving the menu on `listen` to the directory of the user
+/// units, or print them, along with the address of the page and its token
+pub(crate) fn install(
+    listen: &str,
+    allow_remote: bool,
+    print: bool,
+    cache_directory: &Path,
+) -> Result<()> {
+    let address = listen
+        .parse::<SocketAddr>()
+        .context(format!("invalid address: {}", listen))?;
+    check_address(&address, allow_remote)?;
+    let units = units(listen, allow_remote)?;
+    let url = format!("http://{}/?token={}", address, token(cache_directory)?);
+
+    if print {
+        for (name, text) in &units {
+            println!("# {}\n{}", name, text);
+        }
+        println!("# The page: {}", url);
+        return Ok(());
+    }
+
+    let directory = dirs::config_dir()
+        .map(|config| config.join("systemd").join("user"))
+        .ok_or_else(|| anyhow!("unable to find the configuration directory"))?;
+    fs::create_dir_all(&directory).context(format!("unable to create: {}", directory.display()))?;
+    for (name, text) in &units {
+        let path = directory.join(name);
+        fs::write(&path, text).context(format!("unable to write: {}", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+    println!(
+        "Start listening with: systemctl --user daemon-reload && systemctl --user enable --now \
+         {}.socket",
+        UNIT
+    );
+    println!("The page is then at {}", url);
+
+    Ok(())
+}
+
+/// The socket passed by systemd when the service was started by socket
+/// activation
+#[cfg(all(feature = "web", unix))]
+#[allow(unsafe_code)]
+pub(crate) fn activated() -> Result<std::net::TcpListener> {
+    use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+    use std::{os::unix::io::FromRawFd, process};
+
+    /// The sockets are passed from this descriptor on
+    const LISTEN_FDS_START: i32 = 3;
+
+    let pid = env::var("LISTEN_PID")
+        .ok()
+        .and_then(|pid| pid.parse::<u32>().ok());
+    let fds = env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|fds| fds.parse::<u32>().ok());
+    // Not for the commands ran
+    for name in &["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        env::remove_var(name);
+    }
+    match (pid, fds) {
+        (Some(pid), Some(fds)) if pid == process::id() && fds > 0 => {},
+        _ => return Err(anyhow!("no socket was passed by systemd")),
+    }
+
+    fcntl(LISTEN_FDS_START, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))
+        .context("invalid socket passed by systemd")?;
+    // SAFETY: systemd passed a listening socket as the descriptor, which
+    // nothing else owns
+    Ok(unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FDS_START) })
+}
+
+#[cfg(all(feature = "web", not(unix)))]
+pub(crate) fn activated() -> Result<std::net::TcpListener> {
+    Err(anyhow!("socket activation is only supported on Unix"))
+}
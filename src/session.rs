@@ -0,0 +1,221 @@
+//! Recording the selections, inputs, and commands of a session with
+//! `--record <file>`, and driving a session the same way again with
+//! `--replay <file>`, for demos and to reproduce problems with a
+//! configuration.
+//!
+//! The file has one JSON object per step. When replaying, each step is
+//! confirmed before it is used: declining it displays the selector (or runs
+//! the command) as usual, and quitting leaves the rest of the session to the
+//! user
+
+use crate::theme;
+use anyhow::{Context as AnyhowContext, Result};
+use once_cell::sync::Lazy;
+use rustyline::Editor;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+};
+
+/// Something done during a session
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum Step {
+    /// Lines selected in a selector, separated by newlines, the query they
+    /// were found with, and the key accepting them if it isn't `enter`
+    Select {
+        value: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        query: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        key:   String,
+    },
+    /// A key accepting a selector (e.g., opening the palette)
+    Key { key: String },
+    /// A selection was cancelled
+    Cancel,
+    /// A query matching none of the lines of a selector was accepted
+    Query { query: String },
+    /// A line typed at a prompt
+    Input { value: String },
+    /// A command about to run, with the values of secrets masked
+    Command { path: String, command: String },
+}
+
+impl Step {
+    /// Whether the step is the result of a selector, rather than of a prompt
+    /// or a command
+    pub(crate) fn is_selection(&self) -> bool {
+        matches!(
+            self,
+            Step::Select { .. } | Step::Key { .. } | Step::Cancel | Step::Query { .. }
+        )
+    }
+
+    fn describe(&self) -> String {
+        let key = &theme::get().key;
+        match self {
+            Step::Select { value, .. } =>
+                format!("select {}", key.paint(&value.replace('\n', ", "))),
+            Step::Key { key: pressed } => format!("press {}", key.paint(pressed)),
+            Step::Cancel => String::from("cancel the selection"),
+            Step::Query { query } => format!("search {}", key.paint(query)),
+            Step::Input { value } => format!("type {}", key.paint(value)),
+            Step::Command { path, command } => format!("run {}: {}", path, key.paint(command)),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Session {
+    Off,
+    Recording(File),
+    Replaying(VecDeque<Step>),
+}
+
+static SESSION: Lazy<Mutex<Session>> = Lazy::new(|| Mutex::new(Session::Off));
+
+/// Record the steps of the session into `path`, which is truncated
+pub(crate) fn record_to(path: &Path) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .context(format!("unable to create: {}", path.display()))?;
+    *SESSION.lock().unwrap() = Session::Recording(file);
+
+    Ok(())
+}
+
+/// Replay the steps recorded in `path`
+pub(crate) fn replay_from(path: &Path) -> Result<()> {
+    let text = fs::read_to_string(path).context(format!("unable to read: {}", path.display()))?;
+    let steps = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, line)| {
+            serde_json::from_str(line).context(format!(
+                "invalid step on line {} of {}",
+                index + 1,
+                path.display()
+            ))
+        })
+        .collect::<Result<_>>()?;
+    *SESSION.lock().unwrap() = Session::Replaying(steps);
+
+    Ok(())
+}
+
+/// Append `step` to the recording, if a session is recorded. The recording
+/// stops at the first failure to write it
+pub(crate) fn record(step: &Step) {
+    let mut session = SESSION.lock().unwrap();
+    if let Session::Recording(file) = &mut *session {
+        let written = serde_json::to_string(step)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(file, "{}", line)?));
+        if let Err(e) = written {
+            eprintln!(
+                "{}: failed to record the session: {}",
+                theme::get().error.paint("[jaime error]"),
+                e
+            );
+            *session = Session::Off;
+        }
+    }
+}
+
+/// The answer to the confirmation of a replayed step
+enum Answer {
+    Yes,
+    No,
+    Quit,
+}
+
+fn confirm(step: &Step) -> Answer {
+    let prompt = format!(
+        "{} {}? [Y/n/q] ",
+        theme::get().info.paint("[replay]"),
+        step.describe()
+    );
+    let answer = Editor::<()>::new()
+        .readline(&prompt)
+        .map(|a| a.trim().to_lowercase());
+
+    match answer.as_deref() {
+        Ok("" | "y" | "yes") => Answer::Yes,
+        Ok("n" | "no") => Answer::No,
+        _ => Answer::Quit,
+    }
+}
+
+/// The next recorded step, once confirmed, when replaying a session and that
+/// step is of the kind `wanted` tells. The replay stops if the session took
+/// another turn than the recorded one
+pub(crate) fn replay(wanted: impl Fn(&Step) -> bool) -> Option<Step> {
+    let mut session = SESSION.lock().unwrap();
+    let steps = match &mut *session {
+        Session::Replaying(steps) => steps,
+        _ => return None,
+    };
+
+    let step = if let Some(step) = steps.pop_front() {
+        step
+    } else {
+        *session = Session::Off;
+        return None;
+    };
+    if !wanted(&step) {
+        eprintln!(
+            "{} the session no longer matches the recording (expected to {}), replay stopped",
+            theme::get().info.paint("[replay]"),
+            step.describe()
+        );
+        *session = Session::Off;
+        return None;
+    }
+
+    match confirm(&step) {
+        Answer::Yes => Some(step),
+        Answer::No => None,
+        Answer::Quit => {
+            *session = Session::Off;
+            None
+        },
+    }
+}
+
+/// Whether the command about to run should run, confirming it when replaying
+/// a session. The command is recorded when recording one
+pub(crate) fn run_command(path: &str, command: &str) -> bool {
+    let step = Step::Command {
+        path:    path.to_owned(),
+        command: command.to_owned(),
+    };
+    record(&step);
+
+    // The recorded command is shown when the selections led to another one
+    match replay(|s| matches!(s, Step::Command { .. })) {
+        Some(recorded) if recorded != step => {
+            eprintln!(
+                "{} now {}",
+                theme::get().info.paint("[replay]"),
+                step.describe()
+            );
+            true
+        },
+        Some(_) => true,
+        // Declined, unless the replay is over
+        None => !replaying(),
+    }
+}
+
+fn replaying() -> bool {
+    matches!(&*SESSION.lock().unwrap(), Session::Replaying(_))
+}
@@ -0,0 +1,90 @@
+//! Where the items of a widget come from. A `FromCommand` widget lists the
+//! lines printed by a shell command, while a program running a menu with the
+//! library can list its own items natively (from an API, a database…) without
+//! shelling out
+
+use crate::{exec::run_shell_command_for_output, runner::Context};
+use anyhow::Result;
+
+/// An item displayed by a widget, to be selected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    text: String,
+}
+
+impl Item {
+    /// An item displayed as `text`, which is the value of the widget once
+    /// selected
+    #[must_use]
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+
+    /// The text displayed
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl From<String> for Item {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<&str> for Item {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+/// What the items of a widget are listed with
+#[derive(Debug)]
+pub struct SourceContext<'a> {
+    pub(crate) values:  &'a [String],
+    pub(crate) context: &'a Context,
+    pub(crate) envs:    &'a [(String, String)],
+}
+
+impl SourceContext<'_> {
+    /// The values of the widgets displayed before this one, by index
+    #[must_use]
+    pub fn values(&self) -> &[String] {
+        self.values
+    }
+}
+
+/// Lists the items of a widget
+pub trait ItemSource {
+    /// The items to select from, in the order they are displayed
+    ///
+    /// # Errors
+    /// If the items can't be listed, which stops the action
+    fn items(&self, context: &SourceContext<'_>) -> Result<Vec<Item>>;
+}
+
+/// A function given the values of the widgets displayed before
+impl<F> ItemSource for F
+where
+    F: Fn(&[String]) -> Result<Vec<String>>,
+{
+    fn items(&self, context: &SourceContext<'_>) -> Result<Vec<Item>> {
+        Ok(self(context.values)?.into_iter().map(Item::from).collect())
+    }
+}
+
+/// The lines printed by a shell command, as listed by `FromCommand`
+#[derive(Debug)]
+pub(crate) struct Shell<'a> {
+    pub(crate) command: &'a str,
+    pub(crate) shell:   &'a str,
+}
+
+impl ItemSource for Shell<'_> {
+    fn items(&self, context: &SourceContext<'_>) -> Result<Vec<Item>> {
+        let output =
+            run_shell_command_for_output(context.context, self.command, self.shell, context.envs)?;
+        Ok(output.lines().map(Item::from).collect())
+    }
+}
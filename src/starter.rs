@@ -0,0 +1,149 @@
+//! `jaime init-config`: menus for common tools, bundled in the binary and added
+//! to the configuration, so that a new user has a working menu tree right away
+//! and can adapt it from there
+
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use serde_yaml::Value;
+use std::{fs, path::Path};
+
+/// The topics having a starter menu, with the YAML of the menu: a single
+/// option of the root menu, named after the topic
+const TOPICS: &[(&str, &str)] = &[
+    ("git", include_str!("starter/git.yml")),
+    ("docker", include_str!("starter/docker.yml")),
+    ("k8s", include_str!("starter/k8s.yml")),
+    ("tmux", include_str!("starter/tmux.yml")),
+];
+
+/// Names accepted on the command line
+pub(crate) const NAMES: &[&str] = &["git", "docker", "k8s", "tmux", "all"];
+
+/// `text` with its lines indented by `indent`
+fn indent(text: &str, indent: &str) -> String {
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::from("\n")
+            } else {
+                format!("{}{}\n", indent, line)
+            }
+        })
+        .collect()
+}
+
+/// The configuration `text` with `menus` inserted at the start of its
+/// `options`, indented like the options already there
+fn insert(text: &str, menus: &[&str]) -> Result<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let start = lines
+        .iter()
+        .position(|line| line.split('#').next().unwrap_or_default().trim_end() == "options:")
+        .ok_or_else(|| anyhow!("`options:` wasn't found at the start of a line"))?;
+    let indentation = lines[start + 1..]
+        .iter()
+        .find(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .filter(|indentation| !indentation.is_empty())
+        .unwrap_or("  ");
+
+    let mut inserted = lines[..=start].join("\n");
+    inserted.push('\n');
+    for menu in menus {
+        inserted.push_str(&indent(menu, indentation));
+    }
+    for line in &lines[start + 1..] {
+        inserted.push_str(line);
+        inserted.push('\n');
+    }
+
+    Ok(inserted)
+}
+
+/// Add the menus of `topics` (all of them when empty or containing `all`) to
+/// the configuration at `config_path`, creating it if needed. The options
+/// already in the configuration are left alone. With `print`, the menus are
+/// printed instead
+pub(crate) fn init(config_path: &Path, topics: &[&str], print: bool) -> Result<()> {
+    let wanted = TOPICS
+        .iter()
+        .filter(|(name, _)| topics.is_empty() || topics.contains(&"all") || topics.contains(name))
+        .collect::<Vec<_>>();
+
+    if print {
+        print!(
+            "options:\n{}",
+            wanted
+                .iter()
+                .map(|(_, menu)| indent(menu, "  "))
+                .collect::<String>()
+        );
+        return Ok(());
+    }
+
+    let text = if config_path.is_file() {
+        fs::read_to_string(config_path)
+            .context(format!("unable to read: {}", config_path.display()))?
+    } else {
+        String::new()
+    };
+    let existing = if text.trim().is_empty() {
+        Value::Null
+    } else {
+        serde_yaml::from_str::<Value>(&text).context("invalid configuration")?
+    };
+
+    let mut added = Vec::new();
+    let mut menus = Vec::new();
+    for (name, menu) in wanted {
+        if existing.get("options").and_then(|o| o.get(name)).is_some() {
+            println!("{} is already in the configuration", name);
+        } else {
+            added.push(*name);
+            menus.push(*menu);
+        }
+    }
+    if menus.is_empty() {
+        return Ok(());
+    }
+
+    let text = if text.trim().is_empty() {
+        format!(
+            "options:\n{}",
+            menus
+                .iter()
+                .map(|menu| indent(menu, "  "))
+                .collect::<String>()
+        )
+    } else {
+        insert(&text, &menus)?
+    };
+    // The menus must end up among the options, whatever the layout of the file
+    let parsed = serde_yaml::from_str::<Value>(&text).ok();
+    let options = parsed.as_ref().and_then(|value| value.get("options"));
+    if !added
+        .iter()
+        .all(|name| options.and_then(|o| o.get(name)).is_some())
+    {
+        return Err(anyhow!(
+            "unable to add the menus to {}, print them with `jaime init-config --print` to copy \
+             them by hand",
+            config_path.display()
+        ));
+    }
+
+    // Older versions created a directory in place of a missing configuration
+    if config_path.is_dir() {
+        fs::remove_dir(config_path).context(format!(
+            "unable to remove the directory: {}",
+            config_path.display()
+        ))?;
+    }
+    if let Some(directory) = config_path.parent() {
+        fs::create_dir_all(directory)
+            .context(format!("unable to create: {}", directory.display()))?;
+    }
+    fs::write(config_path, text).context(format!("unable to write: {}", config_path.display()))?;
+    println!("Added {} to {}", added.join(", "), config_path.display());
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+//! Tabular output of commands (e.g., `docker ps`), split into columns so that
+//! only some of them are displayed and a single one is substituted.
+//!
+//! Rows can look the same once only some columns are displayed, so each is
+//! given to the selector preceded by its index, which the selectors hide
+
+use anyhow::{anyhow, Context as AnyhowContext, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Separates the index of a row from the columns displayed
+const INDEX_DELIMITER: char = '\x1f';
+
+/// Options of `fzf` and `skim` hiding the index of the rows
+pub(crate) const SELECTOR_OPTIONS: [&str; 4] = ["--delimiter", "\x1f", "--with-nth", "2.."];
+
+/// The field of the selectors' previews replaced by the columns displayed,
+/// instead of `{}` which would include the index
+pub(crate) const PREVIEW_FIELD: &str = "{2..}";
+
+/// `line` preceded by the `index` of its row
+pub(crate) fn with_index(index: usize, line: &str) -> String {
+    format!("{}{}{}", index, INDEX_DELIMITER, line)
+}
+
+/// The index of the row of `line`, and the columns displayed
+fn split(line: &str) -> Option<(usize, &str)> {
+    let (index, rest) = line.split_once(INDEX_DELIMITER)?;
+    Some((index.parse().ok()?, rest))
+}
+
+/// Whether the lines of `input` are rows preceded by their index
+pub(crate) fn is_indexed(input: &str) -> bool {
+    input
+        .lines()
+        .next()
+        .map_or(false, |line| split(line).is_some())
+}
+
+/// `line` as it is displayed, without the index of its row
+pub(crate) fn displayed(line: &str) -> &str {
+    split(line).map_or(line, |(_, rest)| rest)
+}
+
+/// How the output of a `FromCommand` widget is read
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    /// Each line is an item, substituted as is
+    Lines,
+    /// Each line is a row of a table
+    Table,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::Lines
+    }
+}
+
+/// Options of the `table` format
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub(crate) struct Table {
+    /// Number of header lines skipped
+    header:    usize,
+    /// Regular expression the columns are split on (whitespace by default)
+    delimiter: Option<String>,
+    /// Columns displayed, in order (all of them by default)
+    columns:   Option<Vec<usize>>,
+    /// Column substituted when a row is selected
+    value:     usize,
+}
+
+/// The rows of a table, as displayed and as substituted
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Rows {
+    /// Displayed columns, aligned
+    pub(crate) lines:  Vec<String>,
+    /// Substituted column of each line
+    pub(crate) values: Vec<String>,
+}
+
+impl Rows {
+    /// The lines given to the selector, preceded by their index
+    pub(crate) fn input(&self) -> String {
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(index, line)| with_index(index, line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// The value of a selected line, found by its index
+    pub(crate) fn value_of(&self, selected: &str) -> Option<&str> {
+        split(selected).and_then(|(index, _)| self.values.get(index).map(String::as_str))
+    }
+}
+
+impl Table {
+    /// The lines of the output, without the header
+    pub(crate) fn body<'a>(&self, lines: impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+        lines.skip(self.header).collect()
+    }
+
+    /// Split the lines of the body into rows
+    pub(crate) fn parse(&self, body: &[&str]) -> Result<Rows> {
+        let delimiter = Regex::new(self.delimiter.as_deref().unwrap_or(r"\s+"))
+            .context("invalid table delimiter")?;
+
+        let rows = body
+            .iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| delimiter.split(line.trim()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+
+        let count = rows.iter().map(Vec::len).max().unwrap_or_default();
+        let columns = self.columns.clone().unwrap_or_else(|| (0..count).collect());
+        if let Some(column) = columns
+            .iter()
+            .chain(Some(&self.value))
+            .find(|&&c| count > 0 && c >= count)
+        {
+            return Err(anyhow!(
+                "column {} is out of range ({} columns)",
+                column,
+                count
+            ));
+        }
+
+        let widths = columns
+            .iter()
+            .map(|&c| {
+                rows.iter()
+                    .map(|row| row.get(c).map_or(0, |s| s.chars().count()))
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+
+        let lines = rows
+            .iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .zip(&widths)
+                    .map(|(&c, &width)| {
+                        format!("{:width$}", row.get(c).unwrap_or(&""), width = width)
+                    })
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_owned()
+            })
+            .collect();
+
+        let values = rows
+            .iter()
+            .map(|row| row.get(self.value).unwrap_or(&"").to_string())
+            .collect();
+
+        Ok(Rows { lines, values })
+    }
+}
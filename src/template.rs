@@ -0,0 +1,341 @@
+//! Expansion of the `{0}`, `{1}`, ... and `{prev}` placeholders of commands,
+//! along with named ones such as `{query}`.
+//!
+//! Templates can also have blocks, for optional arguments and repeated flags:
+//! `{{#if 0}}...{{else}}...{{/if}}` keeps its first part when the placeholder
+//! `{0}` has a value that isn't blank, and `{{#each 0}}...{{/each}}` repeats
+//! its content for each item of `{0}` (its lines if it has several, its shell
+//! words otherwise), `{{this}}` being replaced by the item, quoted if it has to
+//! be. Other double braces, such as those of a Go template, are left alone
+
+use std::borrow::Cow;
+
+/// The value of the placeholder `name`, if it has one
+fn value<'a, S: AsRef<str>>(
+    name: &str,
+    args: &'a [S],
+    prev: Option<&'a str>,
+    named: &[(&str, &'a str)],
+) -> Option<&'a str> {
+    if name == "prev" {
+        prev
+    } else if let Some((_, value)) = named.iter().find(|(n, _)| *n == name) {
+        Some(value)
+    } else if !name.is_empty() && name.bytes().all(|b| b.is_ascii_digit()) {
+        name.parse::<usize>()
+            .ok()
+            .and_then(|index| args.get(index))
+            .map(AsRef::as_ref)
+    } else {
+        None
+    }
+}
+
+/// Replace `{N}` with `args[N]`, and `{prev}` with `prev`.
+///
+/// The template is scanned once, so the substituted text is never expanded
+/// again (e.g., a selected item containing `{1}`). Other braces, such as
+/// those of an `awk` program, and the placeholders without a value are kept as
+/// they are. The template is borrowed back when nothing was replaced
+pub(crate) fn expand<'a, S: AsRef<str>>(
+    template: &'a str,
+    args: &[S],
+    prev: Option<&str>,
+) -> Cow<'a, str> {
+    expand_with(template, args, prev, &[])
+}
+
+/// Replace the placeholders like [`expand`], along with those named in
+/// `named` (e.g., `{query}`) by their value, and render the blocks. A
+/// template whose blocks are invalid is expanded as if it had none
+pub(crate) fn expand_with<'a, S: AsRef<str>>(
+    template: &'a str,
+    args: &[S],
+    prev: Option<&str>,
+    named: &[(&str, &str)],
+) -> Cow<'a, str> {
+    if !template.contains("{{") {
+        return substitute(template, args, prev, named);
+    }
+
+    match blocks(template) {
+        Ok(nodes) if nodes.iter().any(|node| !matches!(node, Node::Text(_))) => {
+            let mut expanded = String::with_capacity(template.len());
+            render(&nodes, args, prev, named, None, &mut expanded);
+            Cow::Owned(expanded)
+        },
+        _ => substitute(template, args, prev, named),
+    }
+}
+
+/// Replace the placeholders of `template`, which has no blocks
+fn substitute<'a, S: AsRef<str>>(
+    template: &'a str,
+    args: &[S],
+    prev: Option<&str>,
+    named: &[(&str, &str)],
+) -> Cow<'a, str> {
+    let mut expanded = String::new();
+    // End of the part of the template already copied into `expanded`
+    let mut copied = 0;
+    let mut from = 0;
+
+    while let Some(open) = template[from..].find('{').map(|i| i + from) {
+        let close = match template[open + 1..].find('}') {
+            Some(i) => open + 1 + i,
+            None => break,
+        };
+
+        match value(&template[open + 1..close], args, prev, named) {
+            Some(value) => {
+                if expanded.is_empty() {
+                    expanded.reserve(template.len() + value.len());
+                }
+                expanded.push_str(&template[copied..open]);
+                expanded.push_str(value);
+                copied = close + 1;
+                from = close + 1;
+            },
+            None => from = open + 1,
+        }
+    }
+
+    if copied == 0 {
+        Cow::Borrowed(template)
+    } else {
+        expanded.push_str(&template[copied..]);
+        Cow::Owned(expanded)
+    }
+}
+
+/// The placeholders of `template` (e.g., `{0}` or `{prev}`), each one once
+/// along with the value it is replaced by, `None` for those kept as they are
+pub(crate) fn placeholders<'a, S: AsRef<str>>(
+    template: &'a str,
+    args: &'a [S],
+    prev: Option<&'a str>,
+    named: &[(&str, &'a str)],
+) -> Vec<(&'a str, Option<&'a str>)> {
+    let mut placeholders: Vec<(&str, Option<&str>)> = Vec::new();
+    let mut from = 0;
+
+    while let Some(open) = template[from..].find('{').map(|i| i + from) {
+        let close = match template[open + 1..].find('}') {
+            Some(i) => open + 1 + i,
+            None => break,
+        };
+        let name = &template[open + 1..close];
+        // Not the braces of an `awk` program, or of a shell variable
+        let is_name = !template[..open].ends_with('$')
+            && !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if is_name && !placeholders.iter().any(|(n, _)| *n == name) {
+            placeholders.push((name, value(name, args, prev, named)));
+        }
+        from = open + 1;
+    }
+
+    placeholders
+}
+
+/// A tag of a block, between double braces
+#[derive(Debug, Clone, Copy)]
+enum Tag<'a> {
+    If(&'a str),
+    Else,
+    EndIf,
+    Each(&'a str),
+    EndEach,
+    This,
+}
+
+impl Tag<'_> {
+    fn name(self) -> &'static str {
+        match self {
+            Tag::If(_) => "{{#if}}",
+            Tag::Else => "{{else}}",
+            Tag::EndIf => "{{/if}}",
+            Tag::Each(_) => "{{#each}}",
+            Tag::EndEach => "{{/each}}",
+            Tag::This => "{{this}}",
+        }
+    }
+}
+
+/// A part of a template
+#[derive(Debug)]
+enum Node<'a> {
+    Text(&'a str),
+    /// The item of the innermost `{{#each}}`, quoted
+    This,
+    If {
+        name:      &'a str,
+        then:      Vec<Node<'a>>,
+        otherwise: Vec<Node<'a>>,
+    },
+    Each {
+        name: &'a str,
+        body: Vec<Node<'a>>,
+    },
+}
+
+/// The tag at the start of `text`, which starts with double braces, along
+/// with its length
+fn tag(text: &str) -> Option<(Tag<'_>, usize)> {
+    let close = text.find("}}")?;
+    let tag = match &text[2..close] {
+        "else" => Tag::Else,
+        "/if" => Tag::EndIf,
+        "/each" => Tag::EndEach,
+        "this" => Tag::This,
+        inner =>
+            if let Some(name) = inner.strip_prefix("#if ") {
+                Tag::If(name.trim())
+            } else if let Some(name) = inner.strip_prefix("#each ") {
+                Tag::Each(name.trim())
+            } else {
+                return None;
+            },
+    };
+
+    Some((tag, close + 2))
+}
+
+/// A text or a tag of a template
+#[derive(Debug)]
+enum Token<'a> {
+    Text(&'a str),
+    Tag(Tag<'a>),
+}
+
+fn tokens(template: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    // Start of the text not yet in a token
+    let mut text = 0;
+    let mut from = 0;
+
+    while let Some(open) = template[from..].find("{{").map(|i| i + from) {
+        match tag(&template[open..]) {
+            Some((tag, len)) => {
+                if open > text {
+                    tokens.push(Token::Text(&template[text..open]));
+                }
+                tokens.push(Token::Tag(tag));
+                from = open + len;
+                text = from;
+            },
+            None => from = open + 1,
+        }
+    }
+    if text < template.len() {
+        tokens.push(Token::Text(&template[text..]));
+    }
+
+    tokens
+}
+
+/// The nodes of `tokens` up to the tag ending the block they are in, which is
+/// returned along with them. `{{this}}` is only a tag `in_each`
+fn build<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+    in_each: bool,
+) -> Result<(Vec<Node<'a>>, Option<Tag<'a>>), String> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Tag(Tag::This) => nodes.push(if in_each {
+                Node::This
+            } else {
+                Node::Text("{{this}}")
+            }),
+            Token::Tag(Tag::If(name)) => {
+                let (then, end) = build(tokens, in_each)?;
+                let otherwise = match end {
+                    Some(Tag::EndIf) => Vec::new(),
+                    Some(Tag::Else) => match build(tokens, in_each)? {
+                        (otherwise, Some(Tag::EndIf)) => otherwise,
+                        _ => return Err(String::from("{{else}} without {{/if}}")),
+                    },
+                    _ => return Err(String::from("{{#if}} without {{/if}}")),
+                };
+                nodes.push(Node::If {
+                    name,
+                    then,
+                    otherwise,
+                });
+            },
+            Token::Tag(Tag::Each(name)) => match build(tokens, true)? {
+                (body, Some(Tag::EndEach)) => nodes.push(Node::Each { name, body }),
+                _ => return Err(String::from("{{#each}} without {{/each}}")),
+            },
+            Token::Tag(end) => return Ok((nodes, Some(end))),
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+/// The blocks of `template`, or why they are invalid
+fn blocks(template: &str) -> Result<Vec<Node<'_>>, String> {
+    match build(&mut tokens(template).into_iter(), false)? {
+        (nodes, None) => Ok(nodes),
+        (_, Some(tag)) => Err(format!("unexpected {}", tag.name())),
+    }
+}
+
+/// Check that the blocks of `template` are valid
+pub(crate) fn check(template: &str) -> Result<(), String> {
+    blocks(template).map(|_| ())
+}
+
+/// The items an `{{#each}}` repeats its content for
+fn items(value: &str) -> Vec<String> {
+    if value.trim_end_matches('\n').contains('\n') {
+        value
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ToOwned::to_owned)
+            .collect()
+    } else {
+        shlex::split(value)
+            .unwrap_or_else(|| value.split_whitespace().map(ToOwned::to_owned).collect())
+    }
+}
+
+/// Append the expansion of `nodes` to `expanded`, `item` being the item of the
+/// innermost `{{#each}}`
+fn render<S: AsRef<str>>(
+    nodes: &[Node<'_>],
+    args: &[S],
+    prev: Option<&str>,
+    named: &[(&str, &str)],
+    item: Option<&str>,
+    expanded: &mut String,
+) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => expanded.push_str(&substitute(text, args, prev, named)),
+            Node::This => {
+                let item = item.unwrap_or_default();
+                expanded.push_str(&shlex::try_quote(item).unwrap_or(Cow::Borrowed(item)));
+            },
+            Node::If {
+                name,
+                then,
+                otherwise,
+            } => {
+                let set = value(name, args, prev, named).map_or(false, |v| !v.trim().is_empty());
+                let nodes = if set { then } else { otherwise };
+                render(nodes, args, prev, named, item, expanded);
+            },
+            Node::Each { name, body } =>
+                for item in items(value(name, args, prev, named).unwrap_or_default()) {
+                    render(body, args, prev, named, Some(&item), expanded);
+                },
+        }
+    }
+}
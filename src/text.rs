@@ -0,0 +1,34 @@
+//! Unicode handling of menu keys and items, so that text which looks the same
+//! is matched and sorted the same regardless of how it was encoded or accented
+
+use std::cmp::Ordering;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// The canonical composition (NFC) of `text`, so that e.g. `é` written as `e`
+/// followed by a combining accent equals the precomposed `é`
+pub(crate) fn normalize(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// Lowercase `text` and strip its diacritics, giving the key used for
+/// case and accent insensitive matching (e.g., `Élan` and `elan`)
+pub(crate) fn fold(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Compare `a` and `b` as a reader would: ignoring case and diacritics first,
+/// so that accented words sort next to their unaccented counterparts, then
+/// by their normalized form to keep the order total
+pub(crate) fn compare(a: &str, b: &str) -> Ordering {
+    fold(a)
+        .cmp(&fold(b))
+        .then_with(|| normalize(a).cmp(&normalize(b)))
+}
+
+/// Whether `text` contains `pattern`, ignoring case and diacritics
+pub(crate) fn contains(text: &str, pattern: &str) -> bool {
+    fold(text).contains(&fold(pattern))
+}
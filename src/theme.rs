@@ -0,0 +1,287 @@
+//! Colors of Jaime's own output: the entries of the menus, the prompts, and the
+//! messages. A `theme` block of the configuration picks a preset and overrides
+//! any of its styles, which are applied once the configuration is loaded
+
+use crate::menu;
+use anyhow::{anyhow, Result};
+use colored::{Color, ColoredString, Colorize};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+
+/// A foreground color and attributes, written in the configuration as words
+/// separated by spaces (e.g., `bold #88c0d0`, `italic bright-magenta`)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Style {
+    color:      Option<Color>,
+    attributes: Vec<Attribute>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Attribute {
+    Bold,
+    Dimmed,
+    Italic,
+    Underline,
+}
+
+impl Attribute {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word {
+            "bold" => Self::Bold,
+            "dimmed" => Self::Dimmed,
+            "italic" => Self::Italic,
+            "underline" => Self::Underline,
+            _ => return None,
+        })
+    }
+
+    /// The parameter of the escape sequence setting the attribute
+    fn code(self) -> &'static str {
+        match self {
+            Self::Bold => "1",
+            Self::Dimmed => "2",
+            Self::Italic => "3",
+            Self::Underline => "4",
+        }
+    }
+}
+
+impl Style {
+    fn parse(spec: &str) -> Result<Self> {
+        let mut style = Self::default();
+        for word in spec.split_whitespace() {
+            if let Some(attribute) = Attribute::parse(word) {
+                style.attributes.push(attribute);
+                continue;
+            }
+            let color = match word.strip_prefix('#') {
+                Some(hex) => hex_color(hex),
+                None => word.replace(&['-', '_'][..], " ").parse().ok(),
+            };
+            style.color =
+                Some(color.ok_or_else(|| anyhow!("unknown color or attribute: {}", word))?);
+        }
+
+        Ok(style)
+    }
+
+    /// `text` in the style
+    pub(crate) fn paint(&self, text: &str) -> ColoredString {
+        let mut painted = ColoredString::from(text);
+        if let Some(color) = self.color {
+            painted = painted.color(color);
+        }
+        for attribute in &self.attributes {
+            painted = match attribute {
+                Attribute::Bold => painted.bold(),
+                Attribute::Dimmed => painted.dimmed(),
+                Attribute::Italic => painted.italic(),
+                Attribute::Underline => painted.underline(),
+            };
+        }
+        painted
+    }
+
+    /// The parameters of the escape sequence starting the style (e.g., `1;32`)
+    fn code(&self) -> String {
+        let color = self.color.map(|color| color.to_fg_str());
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.code())
+            .chain(color.as_deref())
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+}
+
+/// The color of a `rrggbb` code
+fn hex_color(hex: &str) -> Option<Color> {
+    let component = |range| hex.get(range).and_then(|c| u8::from_str_radix(c, 16).ok());
+    if hex.len() != 6 {
+        return None;
+    }
+
+    Some(Color::TrueColor {
+        r: component(0..2)?,
+        g: component(2..4)?,
+        b: component(4..6)?,
+    })
+}
+
+/// The styles a theme starts from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Preset {
+    /// The colors of the terminal
+    Default,
+    Nord,
+    Gruvbox,
+    /// No colors, only attributes
+    Mono,
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self::Default
+    }
+}
+
+impl Preset {
+    /// The styles of the key, description, dimmed text, success, error,
+    /// warning, and information
+    fn styles(self) -> [&'static str; 7] {
+        match self {
+            Self::Default => [
+                "bold green",
+                "magenta",
+                "dimmed",
+                "bold green",
+                "bold red",
+                "bold yellow",
+                "bold blue",
+            ],
+            Self::Nord => [
+                "bold #88c0d0",
+                "#b48ead",
+                "#4c566a",
+                "bold #a3be8c",
+                "bold #bf616a",
+                "bold #ebcb8b",
+                "bold #81a1c1",
+            ],
+            Self::Gruvbox => [
+                "bold #b8bb26",
+                "#d3869b",
+                "#928374",
+                "bold #b8bb26",
+                "bold #fb4934",
+                "bold #fabd2f",
+                "bold #83a598",
+            ],
+            Self::Mono => ["bold", "", "dimmed", "bold", "bold", "bold", "bold"],
+        }
+    }
+}
+
+/// Icons marking what went well or not
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Icons {
+    Unicode,
+    Ascii,
+    /// Glyphs of the Nerd Fonts
+    Nerd,
+}
+
+impl Default for Icons {
+    fn default() -> Self {
+        Self::Unicode
+    }
+}
+
+impl Icons {
+    pub(crate) fn ok(self) -> &'static str {
+        match self {
+            Self::Unicode => "✔",
+            Self::Ascii => "+",
+            Self::Nerd => "\u{f00c}",
+        }
+    }
+
+    pub(crate) fn failed(self) -> &'static str {
+        match self {
+            Self::Unicode => "✘",
+            Self::Ascii => "x",
+            Self::Nerd => "\u{f00d}",
+        }
+    }
+}
+
+/// The `theme` block of the configuration. The styles that aren't given are
+/// those of the preset
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub(crate) struct ThemeConfig {
+    #[serde(default)]
+    pub(crate) preset:      Preset,
+    /// Keys of the menus
+    pub(crate) key:         Option<String>,
+    /// Descriptions of the menus
+    pub(crate) description: Option<String>,
+    /// Details, tags, and disabled entries
+    pub(crate) dimmed:      Option<String>,
+    pub(crate) success:     Option<String>,
+    pub(crate) error:       Option<String>,
+    pub(crate) warning:     Option<String>,
+    /// Prefixes of the informational messages, and headings
+    pub(crate) info:        Option<String>,
+    #[serde(default)]
+    pub(crate) icons:       Icons,
+}
+
+/// The styles of Jaime's output
+#[derive(Debug, Clone)]
+pub(crate) struct Theme {
+    pub(crate) key:         Style,
+    pub(crate) description: Style,
+    pub(crate) dimmed:      Style,
+    pub(crate) success:     Style,
+    pub(crate) error:       Style,
+    pub(crate) warning:     Style,
+    pub(crate) info:        Style,
+    pub(crate) icons:       Icons,
+    /// The escape codes of the key, description, and dimmed styles
+    menu:                   [String; 3],
+}
+
+impl Theme {
+    /// The theme of the configuration, or the first style that is invalid
+    pub(crate) fn new(config: &ThemeConfig) -> Result<Self> {
+        let [key, description, dimmed, success, error, warning, info] = config.preset.styles();
+        let style = |name: &str, given: &Option<String>, preset: &str| {
+            Style::parse(given.as_deref().unwrap_or(preset))
+                .map_err(|e| anyhow!("theme.{}: {}", name, e))
+        };
+
+        let key = style("key", &config.key, key)?;
+        let description = style("description", &config.description, description)?;
+        let dimmed = style("dimmed", &config.dimmed, dimmed)?;
+
+        Ok(Self {
+            menu: [key.code(), description.code(), dimmed.code()],
+            key,
+            description,
+            dimmed,
+            success: style("success", &config.success, success)?,
+            error: style("error", &config.error, error)?,
+            warning: style("warning", &config.warning, warning)?,
+            info: style("info", &config.info, info)?,
+            icons: config.icons,
+        })
+    }
+
+    /// The styles of the entries of the menus
+    pub(crate) fn menu(&self) -> menu::Styles<'_> {
+        let [key, description, dimmed] = &self.menu;
+        menu::Styles {
+            key,
+            description,
+            dimmed,
+        }
+    }
+}
+
+/// The default theme, until the one of the configuration is applied
+static DEFAULT: Lazy<Theme> =
+    Lazy::new(|| Theme::new(&ThemeConfig::default()).expect("the default theme is valid"));
+
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+/// Apply `theme` to the rest of the output
+pub(crate) fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The theme in use
+pub(crate) fn get() -> &'static Theme {
+    THEME.get().unwrap_or(&DEFAULT)
+}
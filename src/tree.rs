@@ -0,0 +1,181 @@
+//! `jaime tree`: the hierarchy of the menu printed at once, with the
+//! descriptions and what each action runs, to review a large configuration
+//! without navigating it
+
+use crate::{
+    runner::{Action, Config, Options},
+    theme,
+};
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+/// Number of characters of a command shown in the tree
+const SUMMARY_WIDTH: usize = 60;
+
+/// An action of the menu, along with those below it
+#[derive(Serialize, Debug)]
+pub(crate) struct Node<'a> {
+    key:         &'a str,
+    #[serde(rename = "type")]
+    kind:        &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    /// What the action runs or leads to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    runs:        Option<String>,
+    #[serde(skip_serializing_if = "is_false")]
+    hidden:      bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    options:     Vec<Node<'a>>,
+    /// Number of options left out below the maximum depth
+    #[serde(skip)]
+    cut:         usize,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)]
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// What `action` runs or leads to, on a single line
+fn runs(action: &Action) -> Option<String> {
+    match action {
+        Action::Command { command, .. } =>
+            Some(command.split_whitespace().collect::<Vec<_>>().join(" ")),
+        Action::Chain { steps, .. } => {
+            let steps = steps.iter().filter_map(runs).collect::<Vec<_>>();
+            Some(steps.join(" ; "))
+        },
+        Action::Menu { path, .. } => Some(format!("→ {}", path)),
+        Action::Systemd { user, .. } => Some(String::from(if *user {
+            "systemctl --user"
+        } else {
+            "systemctl"
+        })),
+        Action::Select { .. } => None,
+    }
+}
+
+/// The node of `action`, with `depth` levels of the menu below it
+fn node<'a>(key: &'a str, action: &'a Action, depth: Option<usize>) -> Node<'a> {
+    let mut node = Node {
+        key,
+        kind: action.kind(),
+        description: action.description(),
+        runs: runs(action),
+        hidden: action.meta().hidden,
+        options: Vec::new(),
+        cut: 0,
+    };
+    if let Action::Select { options, .. } = action {
+        match depth {
+            Some(0) => node.cut = options.len(),
+            _ => node.options = nodes(options, depth),
+        }
+    }
+    node
+}
+
+/// The nodes of `options`, along with `depth` levels of the menu below them
+/// minus one
+pub(crate) fn nodes(options: &Options, depth: Option<usize>) -> Vec<Node<'_>> {
+    options
+        .iter()
+        .map(|(key, action)| node(key, action, depth.map(|d| d.saturating_sub(1))))
+        .collect()
+}
+
+/// `text` cut to [`SUMMARY_WIDTH`] characters
+fn shorten(text: &str) -> String {
+    if text.chars().count() <= SUMMARY_WIDTH {
+        text.to_owned()
+    } else {
+        format!(
+            "{}…",
+            text.chars().take(SUMMARY_WIDTH - 1).collect::<String>()
+        )
+    }
+}
+
+/// Print `nodes`, each line starting with `prefix`
+fn print(nodes: &[Node<'_>], prefix: &str) {
+    let theme = theme::get();
+
+    for (index, node) in nodes.iter().enumerate() {
+        let last = index + 1 == nodes.len();
+        let mut line = format!(
+            "{}{}{}",
+            prefix,
+            theme.dimmed.paint(if last { "└── " } else { "├── " }),
+            theme.key.paint(node.key)
+        );
+        if let Some(description) = node.description {
+            let _ = write!(line, "  {}", theme.description.paint(description));
+        }
+        if let Some(runs) = &node.runs {
+            let _ = write!(line, "  {}", theme.dimmed.paint(&shorten(runs)));
+        }
+        if node.cut > 0 {
+            let _ = write!(
+                line,
+                "  {}",
+                theme.dimmed.paint(&format!("({} options)", node.cut))
+            );
+        }
+        if node.hidden {
+            let _ = write!(line, "  {}", theme.warning.paint("hidden"));
+        }
+        println!("{}", line);
+
+        print(
+            &node.options,
+            &format!("{}{}", prefix, if last { "    " } else { "│   " }),
+        );
+    }
+}
+
+/// Print the menu below `path` (the root menu if `None`) as a tree, or as JSON
+/// if `json`. With `depth`, only that many levels of the menu are printed
+pub(crate) fn run(
+    config: &Config,
+    path: Option<&str>,
+    depth: Option<usize>,
+    json: bool,
+) -> Result<()> {
+    let theme = theme::get();
+
+    if let Some(path) = path {
+        let (_, action) = config.find(path)?;
+        let node = node(path, action, depth);
+        if json {
+            println!("{}", serde_json::to_string_pretty(&node)?);
+        } else {
+            let mut root = theme.key.paint(path).to_string();
+            if let Some(description) = node.description {
+                let _ = write!(root, "  {}", theme.description.paint(description));
+            }
+            if let Some(runs) = &node.runs {
+                let _ = write!(root, "  {}", theme.dimmed.paint(&shorten(runs)));
+            }
+            println!("{}", root);
+            print(&node.options, "");
+        }
+        return Ok(());
+    }
+
+    let nodes = nodes(&config.options, depth);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&nodes)?);
+    } else {
+        println!(
+            "{}",
+            theme
+                .description
+                .paint(config.description.as_deref().unwrap_or("jaime"))
+        );
+        print(&nodes, "");
+    }
+
+    Ok(())
+}
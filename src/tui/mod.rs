@@ -0,0 +1,601 @@
+//! Full-screen frontend showing the menu as a collapsible tree, with a fuzzy
+//! filter, a panel describing the selected action, and a keybinding bar.
+//!
+//! Commands chosen in the tree run in a pane next to it, so that the menu stays
+//! available to launch more of them. Other actions, and commands whose output
+//! isn't sent to the terminal, are ran with the terminal restored as usual
+
+mod pane;
+mod tree;
+
+use crate::{
+    app::Handler,
+    audit, error, exec, history, jaime_error, policy,
+    runner::{self, Action, Config, Context, Output},
+    sandbox,
+};
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use pane::Pane;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+use std::{
+    io::{self, Stderr},
+    time::Duration,
+};
+use tree::{Row, Tree};
+
+/// How often the panes are redrawn while waiting for a key
+const TICK: Duration = Duration::from_millis(50);
+
+/// Number of lines scrolled by `page-up` and `page-down`
+const SCROLL_LINES: usize = 10;
+
+type Term = Terminal<CrosstermBackend<Stderr>>;
+
+/// Where keys are sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Menu,
+    Pane,
+}
+
+/// What to do once a key is handled
+#[derive(Debug)]
+enum Step {
+    Continue,
+    /// Run the action at the given path
+    Launch(Vec<String>),
+    Quit,
+}
+
+/// Areas of the screen
+#[derive(Debug, Clone, Copy)]
+struct Areas {
+    filter:      Rect,
+    tree:        Rect,
+    description: Rect,
+    pane:        Rect,
+    bar:         Rect,
+}
+
+impl Areas {
+    fn new(size: Rect) -> Self {
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Min(3),
+                Constraint::Length(1),
+            ])
+            .split(size);
+        let main = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(outer[1]);
+        let left = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(main[0]);
+
+        Self {
+            filter:      outer[0],
+            tree:        left[0],
+            description: left[1],
+            pane:        main[1],
+            bar:         outer[2],
+        }
+    }
+
+    /// The area inside the border of the pane
+    fn pane_inner(&self) -> Rect {
+        Block::default().borders(Borders::ALL).inner(self.pane)
+    }
+}
+
+/// State of the frontend
+#[derive(Debug)]
+struct App<'a> {
+    tree:     Tree<'a>,
+    filter:   String,
+    selected: ListState,
+    panes:    Vec<Pane>,
+    /// Index of the pane that is displayed
+    current:  usize,
+    focus:    Focus,
+    /// Error displayed in place of the keybinding bar until the next key
+    message:  Option<String>,
+}
+
+impl App<'_> {
+    fn key(&mut self, key: KeyEvent) -> Result<Step> {
+        self.message = None;
+
+        if self.focus == Focus::Pane && !self.panes.is_empty() {
+            self.pane_key(key)?;
+            Ok(Step::Continue)
+        } else {
+            self.focus = Focus::Menu;
+            Ok(self.menu_key(key))
+        }
+    }
+
+    /// Keys sent to the pane. Those that aren't used to scroll, switch, or
+    /// close panes are sent to the command
+    fn pane_key(&mut self, key: KeyEvent) -> Result<()> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => self.focus = Focus::Menu,
+            KeyCode::Char('n') if ctrl => self.current = (self.current + 1) % self.panes.len(),
+            KeyCode::Char('x') if ctrl => {
+                self.panes.remove(self.current);
+                self.current = self.current.min(self.panes.len().saturating_sub(1));
+            },
+            KeyCode::PageUp => self.panes[self.current].scroll(true, SCROLL_LINES),
+            KeyCode::PageDown => self.panes[self.current].scroll(false, SCROLL_LINES),
+            _ => self.panes[self.current].send(key)?,
+        }
+
+        Ok(())
+    }
+
+    fn menu_key(&mut self, key: KeyEvent) -> Step {
+        let rows = self.tree.rows(&self.filter);
+        let index = self.selected.selected().unwrap_or_default();
+        let row = rows.get(index);
+
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                return Step::Quit,
+            KeyCode::Esc if self.filter.is_empty() => return Step::Quit,
+            KeyCode::Esc => self.filter.clear(),
+            KeyCode::Tab if !self.panes.is_empty() => self.focus = Focus::Pane,
+            KeyCode::Up => self.selected.select(Some(index.saturating_sub(1))),
+            KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                self.selected.select(Some(index.saturating_sub(1))),
+            KeyCode::Down => self.selected.select(Some(index + 1)),
+            KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                self.selected.select(Some(index + 1)),
+            KeyCode::Right =>
+                if let Some(row) = row.filter(|r| r.is_menu()) {
+                    self.tree.expand(&row.path);
+                },
+            KeyCode::Left => {
+                if let Some(row) = row {
+                    if row.expanded {
+                        self.tree.collapse(&row.path);
+                    } else if row.path.len() > 1 && self.filter.is_empty() {
+                        // Jump to the parent menu and collapse it
+                        let parent = &row.path[..row.path.len() - 1];
+                        self.tree.collapse(parent);
+                        let position = self.tree.rows("").iter().position(|r| r.path == parent);
+                        self.selected.select(position);
+                    }
+                }
+            },
+            KeyCode::Enter => match row {
+                Some(row) if row.is_menu() && self.filter.is_empty() => self.tree.toggle(&row.path),
+                Some(row) if !row.is_menu() => return Step::Launch(row.path.clone()),
+                _ => {},
+            },
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.selected.select(Some(0));
+            },
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.selected.select(Some(0));
+            },
+            _ => {},
+        }
+
+        Step::Continue
+    }
+
+    /// Run the action at `path`. Commands sending their output to the terminal
+    /// run in a new pane, once their widgets are displayed
+    fn launch(
+        &mut self,
+        terminal: &mut Term,
+        context: &Context,
+        config: &Config,
+        handler: &Handler,
+        path: &[String],
+    ) -> Result<()> {
+        let (path, action) = config.find(&path.join("."))?;
+        if let Some(reason) = action.meta().disabled_reason() {
+            return Err(anyhow!("{} is disabled: {}", path.join("."), reason));
+        }
+
+        if let Action::Command {
+            widgets,
+            output: Output::Terminal,
+            capture: false,
+            timeout: None,
+            exclusive: false,
+            capture_output: false,
+            ..
+        } = action
+        {
+            // The selectors, and the policy overridden, need the terminal to
+            // themselves
+            let interactive =
+                widgets.as_ref().map_or(false, |w| !w.is_empty()) || config.policy.is_some();
+            // Until the pane is started
+            let _strict = exec::strict_for(action.strict_shell());
+            if interactive {
+                leave(terminal)?;
+            }
+            let prepared = action
+                .prepare(context, config, handler, &path, None)
+                .and_then(|prepared| match prepared {
+                    Some(p) if !policy::check(config.policy.as_ref(), &path, &p.command)? =>
+                        Ok(None),
+                    prepared => Ok(prepared),
+                });
+            if interactive {
+                enter(terminal)?;
+            }
+
+            let prepared = match prepared {
+                Err(e) if error::is_cancelled(&e) => None,
+                prepared => prepared?,
+            };
+            if let Some(prepared) = prepared {
+                if let Err(e) = history::record(context, &path, &prepared.command, &prepared.values)
+                {
+                    self.message = Some(format!("failed to record history: {}", e));
+                }
+                if let Err(e) = audit::record(
+                    context,
+                    config.audit.as_ref(),
+                    &path,
+                    &runner::masked(&prepared.command, &prepared.envs),
+                ) {
+                    self.message = Some(format!("failed to record the audit log: {}", e));
+                }
+
+                let area = Areas::new(terminal.size()?).pane_inner();
+                // The sandbox asks on the terminal
+                let confirms = sandbox::confirms();
+                if confirms {
+                    leave(terminal)?;
+                }
+                let pane = Pane::spawn(
+                    context,
+                    path.join("."),
+                    &prepared,
+                    area,
+                    exec::login_for(action.login_shell()),
+                );
+                if confirms {
+                    enter(terminal)?;
+                }
+                // Refused by the sandbox
+                let pane = match pane {
+                    Err(e) if sandbox::is_enabled() => {
+                        self.message = Some(format!("{}", e));
+                        return Ok(());
+                    },
+                    pane => pane?,
+                };
+                self.panes.push(pane);
+                self.current = self.panes.len() - 1;
+                self.focus = Focus::Pane;
+            }
+
+            return Ok(());
+        }
+
+        leave(terminal)?;
+        if let Err(e) = action.run_at(context, config, handler, &path, None) {
+            jaime_error!("{}", e);
+        }
+        eprint!("{} press enter to return", "[jaime]".blue().bold());
+        io::stdin().read_line(&mut String::new())?;
+        enter(terminal)
+    }
+
+    fn draw(&mut self, frame: &mut Frame<'_>) {
+        let areas = Areas::new(frame.size());
+        let rows = self.tree.rows(&self.filter);
+        let index = self
+            .selected
+            .selected()
+            .unwrap_or_default()
+            .min(rows.len().saturating_sub(1));
+        self.selected.select(Some(index));
+
+        let border =
+            |focused: bool| Style::default().fg(if focused { Color::Blue } else { Color::Reset });
+
+        frame.render_widget(
+            Paragraph::new(format!("> {}", self.filter))
+                .block(Block::default().borders(Borders::ALL).title("Filter")),
+            areas.filter,
+        );
+
+        let items = rows.iter().map(render_row).collect::<Vec<_>>();
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .border_style(border(self.focus == Focus::Menu))
+                        .title("Menu"),
+                )
+                .highlight_style(Style::default().bg(Color::DarkGray)),
+            areas.tree,
+            &mut self.selected,
+        );
+
+        frame.render_widget(
+            Paragraph::new(rows.get(index).map(describe).unwrap_or_default())
+                .wrap(Wrap { trim: false })
+                .block(Block::default().borders(Borders::ALL).title("Description")),
+            areas.description,
+        );
+
+        let focused = self.focus == Focus::Pane;
+        let count = self.panes.len();
+        let title = match self.panes.get(self.current) {
+            Some(pane) => {
+                let scrolled = match pane.scrollback() {
+                    0 => String::new(),
+                    lines => format!(", scrolled up {} lines", lines),
+                };
+                format!(
+                    "[{}/{}] {} ({}{})",
+                    self.current + 1,
+                    count,
+                    pane.title,
+                    pane.status(),
+                    scrolled
+                )
+            },
+            None => String::from("Output"),
+        };
+        frame.render_widget(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(border(focused))
+                .title(title),
+            areas.pane,
+        );
+        let inner = areas.pane_inner();
+        match self.panes.get_mut(self.current) {
+            Some(pane) => {
+                if let Err(e) = pane.resize(inner) {
+                    self.message = Some(e.to_string());
+                }
+                pane.render(inner, frame.buffer_mut(), focused);
+            },
+            None => frame.render_widget(
+                Paragraph::new("Commands chosen in the menu run here")
+                    .style(Style::default().fg(Color::DarkGray)),
+                inner,
+            ),
+        }
+
+        let bar: &[(&str, &str)] = if focused {
+            &[
+                ("esc", "menu"),
+                ("pgup/pgdn", "scroll"),
+                ("ctrl-n", "next pane"),
+                ("ctrl-x", "close pane"),
+            ]
+        } else {
+            &[
+                ("enter", "run/expand"),
+                ("→/←", "expand/collapse"),
+                ("↑/↓", "move"),
+                ("tab", "pane"),
+                ("type", "filter"),
+                ("esc", "clear/quit"),
+            ]
+        };
+        let bar = match &self.message {
+            Some(message) => Line::from(Span::styled(
+                message.clone(),
+                Style::default().fg(Color::Red),
+            )),
+            None => Line::from(
+                bar.iter()
+                    .flat_map(|(key, what)| {
+                        vec![
+                            Span::styled(
+                                *key,
+                                Style::default()
+                                    .fg(Color::Green)
+                                    .add_modifier(Modifier::BOLD),
+                            ),
+                            Span::raw(format!(" {}  ", what)),
+                        ]
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        };
+        frame.render_widget(Paragraph::new(bar), areas.bar);
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut Term,
+        context: &Context,
+        config: &Config,
+        handler: &Handler,
+    ) -> Result<()> {
+        loop {
+            for pane in &mut self.panes {
+                pane.poll();
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(TICK)? {
+                continue;
+            }
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            match self.key(key)? {
+                Step::Continue => {},
+                Step::Quit => return Ok(()),
+                Step::Launch(path) => {
+                    if let Err(e) = self.launch(terminal, context, config, handler, &path) {
+                        self.message = Some(e.to_string());
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// A row of the tree, indented by its depth
+fn render_row<'a>(row: &Row<'a>) -> ListItem<'a> {
+    let icon = match (row.is_menu(), row.expanded) {
+        (true, true) => "▾ ",
+        (true, false) => "▸ ",
+        (false, _) => "  ",
+    };
+    let key = if row.depth == 0 {
+        row.path.join(".")
+    } else {
+        row.path.last().cloned().unwrap_or_default()
+    };
+
+    let disabled = row.action.meta().disabled_reason().is_some();
+    let grey = Style::default().fg(Color::DarkGray);
+
+    let mut spans = vec![
+        Span::raw(format!("{}{}", "  ".repeat(row.depth), icon)),
+        Span::styled(
+            key,
+            if disabled {
+                grey
+            } else {
+                Style::default()
+                    .fg(Color::Green)
+                    .add_modifier(Modifier::BOLD)
+            },
+        ),
+    ];
+    if let Some(description) = row.action.description() {
+        spans.push(Span::styled(
+            format!("  {}", description),
+            if disabled {
+                grey
+            } else {
+                Style::default().fg(Color::Magenta)
+            },
+        ));
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+/// The lines of the description panel
+fn describe<'a>(row: &Row<'a>) -> Vec<Line<'a>> {
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let field = |name: &'a str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{}: ", name), bold),
+            Span::raw(value),
+        ])
+    };
+
+    let mut lines = vec![field("path", row.path.join("."))];
+    if let Some(description) = row.action.description() {
+        lines.push(field("description", description.to_owned()));
+    }
+    if !row.action.tags().is_empty() {
+        lines.push(field("tags", row.action.tags().join(", ")));
+    }
+    if let Some(reason) = row.action.meta().disabled_reason() {
+        lines.push(field("disabled", reason));
+    }
+
+    match row.action {
+        Action::Command {
+            command, widgets, ..
+        } => {
+            lines.push(field("type", String::from("Command")));
+            lines.push(field(
+                "widgets",
+                widgets.as_ref().map_or(0, Vec::len).to_string(),
+            ));
+            lines.push(Line::from(""));
+            lines.extend(command.lines().map(|l| Line::from(l.to_owned())));
+        },
+        Action::Select { options, .. } => {
+            lines.push(field("type", String::from("Select")));
+            lines.push(field("options", options.len().to_string()));
+        },
+        Action::Chain { steps, .. } => {
+            lines.push(field("type", String::from("Chain")));
+            lines.push(field("steps", steps.len().to_string()));
+        },
+        Action::Menu { path, .. } => {
+            lines.push(field("type", String::from("Menu")));
+            lines.push(field("target", path.clone()));
+        },
+        Action::Systemd { user, .. } => {
+            lines.push(field("type", String::from("Systemd")));
+            lines.push(field(
+                "manager",
+                String::from(if *user { "user" } else { "system" }),
+            ));
+        },
+    }
+
+    lines
+}
+
+/// Switch to the alternate screen, in raw mode
+fn enter(terminal: &mut Term) -> Result<()> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stderr(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    Ok(())
+}
+
+/// Restore the terminal
+fn leave(terminal: &mut Term) -> Result<()> {
+    terminal::disable_raw_mode()?;
+    execute!(io::stderr(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    Ok(())
+}
+
+/// Run the frontend until the user quits
+pub(crate) fn run(context: &Context, config: &Config, handler: &Handler) -> Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stderr()))?;
+    enter(&mut terminal)?;
+
+    let mut app = App {
+        tree:     Tree::new(&config.options),
+        filter:   String::new(),
+        selected: ListState::default(),
+        panes:    Vec::new(),
+        current:  0,
+        focus:    Focus::Menu,
+        message:  None,
+    };
+    let result = app.event_loop(&mut terminal, context, config, handler);
+
+    leave(&mut terminal)?;
+    result
+}
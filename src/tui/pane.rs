@@ -0,0 +1,280 @@
+//! A command ran in a pseudo-terminal, whose output is displayed in a pane of
+//! the TUI
+
+use crate::{
+    exec,
+    runner::{Context, Prepared},
+    sandbox,
+};
+use anyhow::{anyhow, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use portable_pty::{native_pty_system, Child, CommandBuilder, ExitStatus, MasterPty, PtySize};
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+};
+use std::{
+    env, fmt,
+    io::{Read, Write},
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
+
+/// Number of lines kept once they scroll out of the pane
+const SCROLLBACK: usize = 10_000;
+
+/// A running (or finished) command
+pub(crate) struct Pane {
+    /// Dotted path of the action that was ran
+    pub(crate) title: String,
+    parser:           Arc<Mutex<vt100::Parser>>,
+    master:           Box<dyn MasterPty + Send>,
+    writer:           Box<dyn Write + Send>,
+    child:            Box<dyn Child + Send + Sync>,
+    exit:             Option<ExitStatus>,
+    size:             (u16, u16),
+}
+
+impl fmt::Debug for Pane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pane")
+            .field("title", &self.title)
+            .field("exit", &self.exit)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Pane {
+    /// Run a prepared command in a pseudo-terminal the size of `area`, by a
+    /// login shell if `login`
+    pub(crate) fn spawn(
+        context: &Context,
+        title: String,
+        prepared: &Prepared,
+        area: Rect,
+        login: bool,
+    ) -> Result<Self> {
+        let size = (area.height.max(1), area.width.max(1));
+        let pair = native_pty_system().openpty(PtySize {
+            rows:         size.0,
+            cols:         size.1,
+            pixel_width:  0,
+            pixel_height: 0,
+        })?;
+
+        // Wrapped like the commands ran out of the TUI
+        let args = exec::shell_args(&prepared.shell, &prepared.command, login);
+        let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+        let command = sandbox::command(&prepared.shell, &args, &prepared.command)?;
+        let mut builder = CommandBuilder::new(command.get_program());
+        builder.args(command.get_args());
+        builder.cwd(env::current_dir()?);
+        builder.env("JAIME_CACHE_DIR", &context.cache_directory);
+        for (key, value) in &prepared.envs {
+            builder.env(key, value);
+        }
+
+        let child = pair.slave.spawn_command(builder)?;
+        // Only the child keeps the slave open, so the reader ends when it exits
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(size.0, size.1, SCROLLBACK)));
+
+        let output = Arc::clone(&parser);
+        thread::spawn(move || {
+            let mut buf = [0; 4096];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                if let Ok(mut parser) = output.lock() {
+                    parser.process(&buf[..n]);
+                }
+            }
+        });
+
+        Ok(Self {
+            title,
+            parser,
+            master: pair.master,
+            writer,
+            child,
+            exit: None,
+            size,
+        })
+    }
+
+    fn parser(&self) -> MutexGuard<'_, vt100::Parser> {
+        self.parser
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    /// Check whether the command exited
+    pub(crate) fn poll(&mut self) {
+        if self.exit.is_none() {
+            self.exit = self.child.try_wait().ok().flatten();
+        }
+    }
+
+    /// Short description of the state of the command
+    pub(crate) fn status(&self) -> String {
+        match &self.exit {
+            None => String::from("running"),
+            Some(status) if status.success() => String::from("finished"),
+            Some(status) => format!("failed ({})", status.exit_code()),
+        }
+    }
+
+    /// Scroll the output up (into the scrollback) or down by `lines`
+    pub(crate) fn scroll(&mut self, up: bool, lines: usize) {
+        let mut parser = self.parser();
+        let current = parser.screen().scrollback();
+        parser.set_scrollback(if up {
+            current + lines
+        } else {
+            current.saturating_sub(lines)
+        });
+    }
+
+    /// Number of lines the output is scrolled up by
+    pub(crate) fn scrollback(&self) -> usize {
+        self.parser().screen().scrollback()
+    }
+
+    /// Send a key to the command
+    pub(crate) fn send(&mut self, key: KeyEvent) -> Result<()> {
+        let bytes = key_bytes(key, self.parser().screen().application_cursor());
+        if !bytes.is_empty() && self.exit.is_none() {
+            self.writer.write_all(&bytes)?;
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Resize the pseudo-terminal to fit `area`
+    pub(crate) fn resize(&mut self, area: Rect) -> Result<()> {
+        let size = (area.height.max(1), area.width.max(1));
+        if size != self.size {
+            self.size = size;
+            self.parser().set_size(size.0, size.1);
+            self.master
+                .resize(PtySize {
+                    rows:         size.0,
+                    cols:         size.1,
+                    pixel_width:  0,
+                    pixel_height: 0,
+                })
+                .map_err(|e| anyhow!("unable to resize the pane: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Draw the screen of the pseudo-terminal, along with its cursor when
+    /// `focused`
+    pub(crate) fn render(&self, area: Rect, buf: &mut Buffer, focused: bool) {
+        let parser = self.parser();
+        let screen = parser.screen();
+
+        for row in 0..area.height {
+            for col in 0..area.width {
+                let cell = match screen.cell(row, col) {
+                    Some(cell) if !cell.is_wide_continuation() => cell,
+                    _ => continue,
+                };
+
+                let mut style = Style::default()
+                    .fg(color(cell.fgcolor()))
+                    .bg(color(cell.bgcolor()));
+                for (set, modifier) in &[
+                    (cell.bold(), Modifier::BOLD),
+                    (cell.italic(), Modifier::ITALIC),
+                    (cell.underline(), Modifier::UNDERLINED),
+                    (cell.inverse(), Modifier::REVERSED),
+                ] {
+                    if *set {
+                        style = style.add_modifier(*modifier);
+                    }
+                }
+
+                let contents = cell.contents();
+                buf.get_mut(area.x + col, area.y + row)
+                    .set_symbol(if contents.is_empty() { " " } else { &contents })
+                    .set_style(style);
+            }
+        }
+
+        let (row, col) = screen.cursor_position();
+        if focused
+            && !screen.hide_cursor()
+            && screen.scrollback() == 0
+            && self.exit.is_none()
+            && row < area.height
+            && col < area.width
+        {
+            buf.get_mut(area.x + col, area.y + row)
+                .set_style(Style::default().add_modifier(Modifier::REVERSED));
+        }
+    }
+}
+
+impl Drop for Pane {
+    fn drop(&mut self) {
+        if self.exit.is_none() {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+fn color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::Reset,
+        vt100::Color::Idx(index) => Color::Indexed(index),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+/// The bytes a terminal sends for a key
+fn key_bytes(key: KeyEvent, application_cursor: bool) -> Vec<u8> {
+    let arrow = |c: char| {
+        if application_cursor {
+            format!("\x1bO{}", c).into_bytes()
+        } else {
+            format!("\x1b[{}", c).into_bytes()
+        }
+    };
+
+    match key.code {
+        KeyCode::Char(c)
+            if key.modifiers.contains(KeyModifiers::CONTROL) && c.is_ascii_alphabetic() =>
+            vec![c.to_ascii_lowercase() as u8 - b'a' + 1],
+        KeyCode::Char(c) => {
+            let mut bytes = if key.modifiers.contains(KeyModifiers::ALT) {
+                vec![0x1b]
+            } else {
+                vec![]
+            };
+            bytes.extend(c.to_string().as_bytes());
+            bytes
+        },
+        KeyCode::Enter => vec![b'\r'],
+        KeyCode::Tab => vec![b'\t'],
+        KeyCode::BackTab => b"\x1b[Z".to_vec(),
+        KeyCode::Backspace => vec![0x7f],
+        KeyCode::Esc => vec![0x1b],
+        KeyCode::Up => arrow('A'),
+        KeyCode::Down => arrow('B'),
+        KeyCode::Right => arrow('C'),
+        KeyCode::Left => arrow('D'),
+        KeyCode::Home => b"\x1b[H".to_vec(),
+        KeyCode::End => b"\x1b[F".to_vec(),
+        KeyCode::Delete => b"\x1b[3~".to_vec(),
+        _ => vec![],
+    }
+}
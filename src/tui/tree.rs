@@ -0,0 +1,144 @@
+//! The menu as a tree, flattened into the rows that are visible
+
+use crate::{
+    runner::{Action, Options},
+    text,
+};
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use std::{cmp::Reverse, collections::HashSet, sync::Arc};
+
+/// A visible row of the tree
+#[derive(Debug, Clone)]
+pub(crate) struct Row<'a> {
+    /// Keys leading to the action
+    pub(crate) path:     Vec<String>,
+    /// Depth of the row in the tree (always `0` when filtering)
+    pub(crate) depth:    usize,
+    pub(crate) action:   &'a Action,
+    /// Whether the row is a submenu whose children are shown
+    pub(crate) expanded: bool,
+}
+
+impl Row<'_> {
+    /// Whether the row is a submenu
+    pub(crate) fn is_menu(&self) -> bool {
+        matches!(self.action, Action::Select { .. })
+    }
+}
+
+/// The menu, along with the submenus that are expanded
+#[derive(Debug)]
+pub(crate) struct Tree<'a> {
+    options:  &'a Options,
+    expanded: HashSet<Vec<String>>,
+}
+
+/// Options of a menu that aren't hidden, sorted alphabetically by key
+fn sorted(options: &Options) -> Vec<(&Arc<str>, &Action)> {
+    let mut options = options
+        .iter()
+        .filter(|(_, action)| action.meta().is_listed())
+        .collect::<Vec<_>>();
+    options.sort_by(|a, b| text::compare(a.0, b.0));
+    options
+}
+
+impl<'a> Tree<'a> {
+    pub(crate) fn new(options: &'a Options) -> Self {
+        Self {
+            options,
+            expanded: HashSet::new(),
+        }
+    }
+
+    /// Expand or collapse the submenu at `path`
+    pub(crate) fn toggle(&mut self, path: &[String]) {
+        if !self.expanded.remove(path) {
+            self.expanded.insert(path.to_vec());
+        }
+    }
+
+    pub(crate) fn expand(&mut self, path: &[String]) {
+        self.expanded.insert(path.to_vec());
+    }
+
+    pub(crate) fn collapse(&mut self, path: &[String]) {
+        self.expanded.remove(path);
+    }
+
+    /// The visible rows. When `filter` is not empty, only the actions that
+    /// are not submenus and match it are listed, best match first
+    pub(crate) fn rows(&self, filter: &str) -> Vec<Row<'a>> {
+        let mut rows = Vec::new();
+
+        if filter.is_empty() {
+            self.walk(self.options, &[], &mut rows);
+        } else {
+            let mut scored = Vec::new();
+            Self::leaves(
+                &SkimMatcherV2::default(),
+                self.options,
+                &[],
+                filter,
+                &mut scored,
+            );
+            scored.sort_by_key(|(score, _)| Reverse(*score));
+            rows.extend(scored.into_iter().map(|(_, row)| row));
+        }
+
+        rows
+    }
+
+    fn walk(&self, options: &'a Options, parent: &[String], rows: &mut Vec<Row<'a>>) {
+        for (key, action) in sorted(options) {
+            let mut path = parent.to_vec();
+            path.push(key.to_string());
+            let expanded = self.expanded.contains(&path);
+
+            rows.push(Row {
+                path: path.clone(),
+                depth: parent.len(),
+                action,
+                expanded,
+            });
+
+            if let (true, Action::Select { options, .. }) = (expanded, action) {
+                self.walk(options, &path, rows);
+            }
+        }
+    }
+
+    fn leaves(
+        matcher: &SkimMatcherV2,
+        options: &'a Options,
+        parent: &[String],
+        filter: &str,
+        scored: &mut Vec<(i64, Row<'a>)>,
+    ) {
+        for (key, action) in sorted(options) {
+            let mut path = parent.to_vec();
+            path.push(key.to_string());
+
+            if let Action::Select { options, .. } = action {
+                Self::leaves(matcher, options, &path, filter, scored);
+                continue;
+            }
+
+            // Both sides are folded so that matching ignores diacritics
+            let haystack = text::fold(&format!(
+                "{} {} {}",
+                path.join("."),
+                action.description().unwrap_or_default(),
+                action.tags().join(" ")
+            ));
+            if let Some(score) = matcher.fuzzy_match(&haystack, &text::fold(filter)) {
+                scored.push((score, Row {
+                    path,
+                    depth: 0,
+                    action,
+                    expanded: false,
+                }));
+            }
+        }
+    }
+}
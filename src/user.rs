@@ -0,0 +1,48 @@
+//! The user running Jaime, and the groups they belong to, looked up once
+
+use once_cell::sync::Lazy;
+use std::env;
+
+static NAME: Lazy<String> = Lazy::new(|| {
+    #[cfg(unix)]
+    {
+        use nix::unistd::{Uid, User};
+        if let Ok(Some(user)) = User::from_uid(Uid::current()) {
+            return user.name;
+        }
+    }
+
+    env::var("USER")
+        .or_else(|_| env::var("USERNAME"))
+        .unwrap_or_else(|_| String::from("unknown"))
+});
+
+static GROUPS: Lazy<Vec<String>> = Lazy::new(|| {
+    #[cfg(unix)]
+    {
+        use nix::unistd::{getgroups, Gid, Group};
+        let mut gids = getgroups().unwrap_or_default();
+        gids.push(Gid::current());
+        gids.sort_unstable_by_key(|gid| gid.as_raw());
+        gids.dedup();
+
+        gids.into_iter()
+            .filter_map(|gid| Group::from_gid(gid).ok().flatten())
+            .map(|group| group.name)
+            .collect()
+    }
+
+    #[cfg(not(unix))]
+    Vec::new()
+});
+
+/// The name of the user running Jaime
+pub(crate) fn name() -> &'static str {
+    &NAME
+}
+
+/// The names of the groups of the user running Jaime, the primary one included.
+/// There are none outside of unix
+pub(crate) fn groups() -> &'static [String] {
+    &GROUPS
+}
@@ -0,0 +1,421 @@
+//! `jaime serve`: the menu on a small local web page, for tablets or remote
+//! machines where a picker in a terminal is awkward. The widgets are displayed
+//! on the page, and the commands run on the server, their output being shown
+//! on the page as it is printed.
+//!
+//! One action runs at a time. The page polls the events of the action running
+//! (the selections, the input, the output, and how it ended)
+//!
+//! The API requires the token printed in the address of the page, and the
+//! requests must name the address listened on as their host, so that other
+//! pages of the browser can't run the commands
+
+use crate::{
+    app::Handler,
+    frontend::{self, Frontend, Output},
+    metrics, protocol,
+    runner::{Config, Context, Outcome},
+    service, tree,
+};
+use anyhow::{Context as AnyhowContext, Result};
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+/// The page, with its script and style
+const INDEX: &str = include_str!("web/index.html");
+
+/// Header carrying the token of the API
+const TOKEN_HEADER: &str = "x-jaime-token";
+
+/// Something that happened while an action runs, for the page to display
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum Event {
+    Select {
+        items: Vec<String>,
+        multi: bool,
+    },
+    Input {
+        prompt:  String,
+        initial: String,
+    },
+    Output {
+        line: String,
+    },
+    Done {
+        outcome: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output:  Option<String>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// The answer to the last `select` or `input` event, neither being set when
+/// it is cancelled
+#[derive(Deserialize, Debug, Default)]
+struct Answer {
+    #[serde(default)]
+    indices: Option<Vec<usize>>,
+    #[serde(default)]
+    text:    Option<String>,
+}
+
+/// The action running, or the last one that ran
+#[derive(Debug, Default)]
+struct Run {
+    events:  Vec<Event>,
+    running: bool,
+    answers: Option<mpsc::Sender<Answer>>,
+}
+
+/// Who may use the server
+#[derive(Debug)]
+struct Access {
+    /// Required by the API
+    token: String,
+    /// The values the `Host` header may take, any when listening on every
+    /// address
+    hosts: Option<Vec<String>>,
+}
+
+impl Access {
+    fn new(token: String, address: SocketAddr) -> Self {
+        let hosts = if address.ip().is_unspecified() {
+            None
+        } else if address.ip().is_loopback() {
+            Some(vec![
+                address.to_string(),
+                format!("localhost:{}", address.port()),
+            ])
+        } else {
+            Some(vec![address.to_string()])
+        };
+        Self { token, hosts }
+    }
+
+    fn allows_host(&self, host: Option<&str>) -> bool {
+        match (&self.hosts, host) {
+            (None, _) => true,
+            (Some(hosts), Some(host)) => hosts.iter().any(|h| h.eq_ignore_ascii_case(host)),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Compare without returning early, not to tell how much of the token
+    /// was right
+    fn allows_token(&self, token: Option<&str>) -> bool {
+        token.map_or(false, |token| {
+            token.len() == self.token.len()
+                && token
+                    .bytes()
+                    .zip(self.token.bytes())
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Server {
+    context: Context,
+    config:  Config,
+    handler: Handler,
+    access:  Access,
+    run:     Mutex<Run>,
+}
+
+impl Server {
+    fn run(&self) -> MutexGuard<'_, Run> {
+        self.run.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Displays the widgets on the page
+#[derive(Debug)]
+struct Page {
+    server:  Arc<Server>,
+    answers: mpsc::Receiver<Answer>,
+}
+
+impl Page {
+    /// Show `event` on the page and wait for the answer to it
+    fn ask(&self, event: Event) -> Answer {
+        self.server.run().events.push(event);
+        self.answers.recv().unwrap_or_default()
+    }
+}
+
+impl Frontend for Page {
+    fn select(
+        &mut self,
+        items: &[&str],
+        _preview: Option<&str>,
+        multi: bool,
+    ) -> Result<Option<Vec<usize>>> {
+        let items = items.iter().map(|item| (*item).to_owned()).collect();
+        Ok(self.ask(Event::Select { items, multi }).indices)
+    }
+
+    fn read(&mut self, prompt: &str, initial: &str) -> Result<Option<String>> {
+        Ok(self
+            .ask(Event::Input {
+                prompt:  prompt.to_owned(),
+                initial: initial.to_owned(),
+            })
+            .text)
+    }
+
+    fn output(&self) -> Output {
+        Output::Forwarded
+    }
+
+    fn print(&mut self, line: &str) {
+        self.server.run().events.push(Event::Output {
+            line: line.to_owned(),
+        });
+    }
+}
+
+/// Refuse the requests naming another host, and those to the API without the
+/// token
+async fn guard(State(server): State<Arc<Server>>, request: Request, next: Next) -> Response {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok());
+    if !server.access.allows_host(host) {
+        return (StatusCode::FORBIDDEN, "unknown host").into_response();
+    }
+    if request.uri().path().starts_with("/api/") {
+        let token = request
+            .headers()
+            .get(TOKEN_HEADER)
+            .and_then(|token| token.to_str().ok());
+        if !server.access.allows_token(token) {
+            return (StatusCode::UNAUTHORIZED, "missing or invalid token").into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX)
+}
+
+async fn menu(State(server): State<Arc<Server>>) -> Result<Json<Value>, (StatusCode, String)> {
+    serde_json::to_value(tree::nodes(&server.config.options, None))
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Deserialize, Debug)]
+struct RunRequest {
+    #[serde(default)]
+    path: Option<String>,
+}
+
+/// Start running the action at `path`, the root menu if absent
+async fn start(
+    State(server): State<Arc<Server>>,
+    Json(RunRequest { path }): Json<RunRequest>,
+) -> Result<StatusCode, (StatusCode, &'static str)> {
+    let (sender, receiver) = mpsc::channel();
+    {
+        let mut run = server.run();
+        if run.running {
+            return Err((StatusCode::CONFLICT, "an action is already running"));
+        }
+        *run = Run {
+            events:  Vec::new(),
+            running: true,
+            answers: Some(sender),
+        };
+    }
+    frontend::set(Some(Box::new(Page {
+        server:  Arc::clone(&server),
+        answers: receiver,
+    })));
+
+    thread::spawn(move || {
+        let Server {
+            context,
+            config,
+            handler,
+            ..
+        } = &*server;
+        let event = match protocol::run_path(context, config, handler, path.as_deref()) {
+            Ok(Outcome::Cancelled) => Event::Done {
+                outcome: "cancelled",
+                output:  None,
+            },
+            Ok(Outcome::Captured(output)) => Event::Done {
+                outcome: "captured",
+                output:  Some(output),
+            },
+            Ok(Outcome::Done) => Event::Done {
+                outcome: "done",
+                output:  None,
+            },
+            Err(e) => Event::Error {
+                message: format!("{:#}", e),
+            },
+        };
+
+        let mut run = server.run();
+        run.events.push(event);
+        run.running = false;
+        run.answers = None;
+    });
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize, Debug)]
+struct Since {
+    #[serde(default)]
+    since: usize,
+}
+
+#[derive(Serialize, Debug)]
+struct Events {
+    events:  Vec<Event>,
+    running: bool,
+}
+
+/// The events of the action running from the index `since`
+async fn events(
+    State(server): State<Arc<Server>>,
+    Query(Since { since }): Query<Since>,
+) -> Json<Events> {
+    let run = server.run();
+    Json(Events {
+        events:  run.events.get(since..).unwrap_or_default().to_vec(),
+        running: run.running,
+    })
+}
+
+/// The counters of the commands ran, when the metrics are enabled
+async fn counters(State(server): State<Arc<Server>>) -> Result<String, (StatusCode, String)> {
+    if server.config.metrics.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            String::from("the metrics aren't enabled"),
+        ));
+    }
+    metrics::render(&server.context)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))
+}
+
+/// Answer the last `select` or `input` event
+async fn answer(State(server): State<Arc<Server>>, Json(answer): Json<Answer>) -> StatusCode {
+    match server
+        .run()
+        .answers
+        .as_ref()
+        .map(|answers| answers.send(answer))
+    {
+        Some(Ok(())) => StatusCode::NO_CONTENT,
+        _ => StatusCode::CONFLICT,
+    }
+}
+
+/// How `jaime serve` listens
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Listen<'a> {
+    /// The address to listen on, unless `systemd`
+    pub(crate) address:      &'a str,
+    /// Use the socket passed by systemd
+    pub(crate) systemd:      bool,
+    /// Accept listening on an address other machines can reach
+    pub(crate) allow_remote: bool,
+}
+
+/// Serve the menu on the address of `listen`, or on the socket passed by
+/// systemd, until the program is stopped
+pub(crate) fn run(
+    context: Context,
+    config: Config,
+    handler: Handler,
+    listen: Listen<'_>,
+) -> Result<()> {
+    let (activated, token) = if listen.systemd {
+        // The token outlives the service, as the page stays open in between
+        (
+            Some(service::activated()?),
+            service::token(&context.cache_directory)?,
+        )
+    } else {
+        (None, service::new_token())
+    };
+    let address = listen
+        .address
+        .parse::<SocketAddr>()
+        .context(format!("invalid address: {}", listen.address))?;
+    if activated.is_none() {
+        service::check_address(&address, listen.allow_remote)?;
+    }
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(async move {
+            let listener = match activated {
+                Some(listener) => {
+                    listener.set_nonblocking(true)?;
+                    tokio::net::TcpListener::from_std(listener)?
+                },
+                None => tokio::net::TcpListener::bind(address)
+                    .await
+                    .context(format!("unable to listen on {}", address))?,
+            };
+            // The socket passed by systemd may listen anywhere
+            let local = listener.local_addr()?;
+            service::check_address(&local, listen.allow_remote)?;
+
+            let server = Arc::new(Server {
+                context,
+                config,
+                handler,
+                access: Access::new(token, local),
+                run: Mutex::new(Run::default()),
+            });
+            let app = Router::new()
+                .route("/", get(index))
+                .route("/api/menu", get(menu))
+                .route("/api/run", post(start))
+                .route("/api/events", get(events))
+                .route("/api/answer", post(answer))
+                .route("/metrics", get(counters))
+                .layer(middleware::from_fn_with_state(Arc::clone(&server), guard))
+                .with_state(Arc::clone(&server));
+
+            // The page on all addresses is reached through one of them
+            let shown = if local.ip().is_unspecified() {
+                SocketAddr::new(Ipv4Addr::LOCALHOST.into(), local.port())
+            } else {
+                local
+            };
+            eprintln!(
+                "Serving the menu on http://{}/?token={}",
+                shown, server.access.token
+            );
+            axum::serve(listener, app)
+                .await
+                .context("the server stopped")
+        })
+}
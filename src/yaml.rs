@@ -0,0 +1,245 @@
+//! Checks of the YAML configuration that `serde_yaml` doesn't do.
+//!
+//! `serde_yaml` keeps the last of several identical keys of a mapping, so two
+//! options named `git` silently shadow each other. The events of the document
+//! are read beforehand to find those duplicates. Fields that aren't known are
+//! ignored as well, and are found by comparing the document with the parsed
+//! configuration serialized back. Overlays of the configuration are merged over
+//! it as documents, before being parsed
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use yaml_rust::{
+    parser::{Event, MarkedEventReceiver, Parser},
+    scanner::Marker,
+};
+
+/// A line and column of the document, starting from 1
+#[derive(Debug, Clone, Copy)]
+struct Location {
+    line:   usize,
+    column: usize,
+}
+
+impl From<Marker> for Location {
+    fn from(mark: Marker) -> Self {
+        Self {
+            line:   mark.line(),
+            column: mark.col() + 1,
+        }
+    }
+}
+
+/// A key found more than once in the same mapping
+#[derive(Debug)]
+struct Duplicate {
+    /// Dotted keys of the mapping, empty at the top level
+    path:  String,
+    key:   String,
+    first: Location,
+    again: Location,
+}
+
+/// A mapping or sequence being read
+#[derive(Debug)]
+enum Frame {
+    Mapping {
+        keys:      HashMap<String, Location>,
+        /// The key of the value being read, `None` for keys that aren't
+        /// scalars
+        key:       Option<String>,
+        /// Whether the next node is a key rather than a value
+        expecting: bool,
+    },
+    Sequence {
+        index: usize,
+    },
+}
+
+/// Follows the nesting of the events, remembering the keys of each mapping
+#[derive(Debug, Default)]
+struct Receiver {
+    stack:      Vec<Frame>,
+    duplicates: Vec<Duplicate>,
+}
+
+impl Receiver {
+    /// The keys and indices leading to the innermost node, the value being
+    /// read excluded
+    fn path(&self) -> String {
+        let mut path = Vec::new();
+        for frame in &self.stack[..self.stack.len().saturating_sub(1)] {
+            match frame {
+                Frame::Mapping { key, .. } => path.push(key.clone().unwrap_or_else(|| "?".into())),
+                Frame::Sequence { index } => path.push(index.to_string()),
+            }
+        }
+        path.join(".")
+    }
+
+    /// Record the start of a node. Scalars are given with their value
+    fn begin(&mut self, scalar: Option<String>, location: Location) {
+        let path = self.path();
+        if let Some(Frame::Mapping {
+            keys,
+            key,
+            expecting: true,
+        }) = self.stack.last_mut()
+        {
+            if let Some(scalar) = &scalar {
+                if let Some(first) = keys.get(scalar) {
+                    self.duplicates.push(Duplicate {
+                        path,
+                        key: scalar.clone(),
+                        first: *first,
+                        again: location,
+                    });
+                } else {
+                    keys.insert(scalar.clone(), location);
+                }
+            }
+            *key = scalar;
+        }
+    }
+
+    /// Record the end of a node, which is then either a key or a value of the
+    /// enclosing mapping, or an item of the enclosing sequence
+    fn end(&mut self) {
+        match self.stack.last_mut() {
+            Some(Frame::Mapping { expecting, .. }) => *expecting = !*expecting,
+            Some(Frame::Sequence { index }) => *index += 1,
+            None => {},
+        }
+    }
+}
+
+impl MarkedEventReceiver for Receiver {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => {
+                self.begin(Some(value), mark.into());
+                self.end();
+            },
+            Event::Alias(_) => {
+                self.begin(None, mark.into());
+                self.end();
+            },
+            Event::MappingStart(_) => {
+                self.begin(None, mark.into());
+                self.stack.push(Frame::Mapping {
+                    keys:      HashMap::new(),
+                    key:       None,
+                    expecting: true,
+                });
+            },
+            Event::SequenceStart(_) => {
+                self.begin(None, mark.into());
+                self.stack.push(Frame::Sequence { index: 0 });
+            },
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+                self.end();
+            },
+            Event::DocumentStart | Event::DocumentEnd => self.stack.clear(),
+            Event::Nothing | Event::StreamStart | Event::StreamEnd => {},
+        }
+    }
+}
+
+/// Fail if a mapping of `text`, at any level, has the same key more than once,
+/// listing where each duplicate is found. Syntax errors are left to
+/// `serde_yaml`
+pub(crate) fn check_duplicate_keys(text: &str) -> Result<()> {
+    let mut receiver = Receiver::default();
+    if Parser::new(text.chars()).load(&mut receiver, true).is_err() {
+        return Ok(());
+    }
+    if receiver.duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let lines = receiver
+        .duplicates
+        .iter()
+        .map(|d| {
+            format!(
+                "  `{}`{} at line {} column {} (first defined at line {} column {})",
+                d.key,
+                if d.path.is_empty() {
+                    String::new()
+                } else {
+                    format!(" in `{}`", d.path)
+                },
+                d.again.line,
+                d.again.column,
+                d.first.line,
+                d.first.column
+            )
+        })
+        .collect::<Vec<_>>();
+
+    Err(anyhow!("duplicate keys in config:\n{}", lines.join("\n")))
+}
+
+/// Collect the keys of `source` missing from `parsed` into `unknown`, as
+/// dotted paths
+fn missing_keys(source: &Value, parsed: &Value, path: &str, unknown: &mut Vec<String>) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match (source, parsed) {
+        (Value::Mapping(source), Value::Mapping(parsed)) =>
+            for (key, value) in source {
+                let name = key
+                    .as_str()
+                    .map_or_else(|| format!("{:?}", key), ToOwned::to_owned);
+                match parsed.get(key) {
+                    Some(parsed) => missing_keys(value, parsed, &join(&name), unknown),
+                    None => unknown.push(join(&name)),
+                }
+            },
+        (Value::Sequence(source), Value::Sequence(parsed)) =>
+            for (index, (source, parsed)) in source.iter().zip(parsed).enumerate() {
+                missing_keys(source, parsed, &join(&index.to_string()), unknown);
+            },
+        _ => {},
+    }
+}
+
+/// The dotted paths of the fields of `source` that were dropped when it was
+/// parsed into `parsed`, because they aren't part of the configuration (e.g., a
+/// misspelled `comand`)
+pub(crate) fn unknown_fields<T: Serialize>(source: &Value, parsed: &T) -> Vec<String> {
+    let mut unknown = Vec::new();
+    if let Ok(parsed) = serde_yaml::to_value(parsed) {
+        missing_keys(source, &parsed, "", &mut unknown);
+    }
+    unknown
+}
+
+/// Merge `overlay` over `base`. Mappings are merged key by key, a `null` value
+/// removing the key, while anything else replaces what it is merged over
+/// (sequences included). An empty overlay leaves `base` as is
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (_, Value::Null) => {},
+        (Value::Mapping(base), Value::Mapping(overlay)) =>
+            for (key, value) in overlay {
+                if value.is_null() {
+                    base.remove(&key);
+                } else if let Some(existing) = base.get_mut(&key) {
+                    merge(existing, value);
+                } else {
+                    base.insert(key, value);
+                }
+            },
+        (base, overlay) => *base = overlay,
+    }
+}